@@ -0,0 +1,65 @@
+//! The echo example over TCP, in two processes:
+//!
+//! ```text
+//! cargo run --example echo_tcp -- server 127.0.0.1:7777
+//! cargo run --example echo_tcp -- client 127.0.0.1:7777
+//! ```
+
+use std::env;
+use std::thread;
+use std::time::Duration;
+
+use futures::executor::{block_on, ThreadPool};
+use prost_derive::Message;
+
+use labrpc::*;
+
+/// A Hand-written protobuf messages
+#[derive(Clone, PartialEq, Message)]
+pub struct Echo {
+    #[prost(int64, tag = "1")]
+    pub x: i64,
+}
+
+service! {
+    service echo {
+        rpc ping(Echo) returns (Echo);
+    }
+}
+use echo::{add_service, Client, Service};
+
+#[derive(Clone)]
+struct EchoService;
+
+#[async_trait::async_trait]
+impl Service for EchoService {
+    async fn ping(&self, input: Echo) -> Result<Echo> {
+        Ok(input)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let addr = args.get(2).map_or("127.0.0.1:7777", String::as_str);
+    match args.get(1).map(String::as_str) {
+        Some("server") => {
+            let mut builder = ServerBuilder::new("echo_server".to_owned());
+            add_service(EchoService, &mut builder).unwrap();
+            let server = builder.build();
+            let listener = server.listen(addr).unwrap();
+            println!("listening on {}", listener.local_addr());
+            loop {
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+        Some("client") => {
+            let worker = ThreadPool::new().unwrap();
+            let rpc_client = labrpc::Client::dial("client".to_owned(), addr, worker).unwrap();
+            let client = Client::new(rpc_client);
+            let reply = block_on(async { client.ping(&Echo { x: 777 }).await.unwrap() });
+            assert_eq!(reply, Echo { x: 777 });
+            println!("{:?}", reply);
+        }
+        _ => eprintln!("usage: echo_tcp (server|client) [addr]"),
+    }
+}
@@ -3,10 +3,10 @@ use std::sync::{Arc, Mutex};
 
 use futures::channel::mpsc::UnboundedSender;
 use futures::channel::oneshot;
-use futures::executor::ThreadPool;
 use futures::future::{self, FutureExt};
 
 use crate::error::{Error, Result};
+use crate::executor::Executor;
 use crate::server::RpcFuture;
 
 pub struct Rpc {
@@ -45,7 +45,12 @@ pub struct Client {
     pub(crate) sender: UnboundedSender<Rpc>,
     pub(crate) hooks: Arc<Mutex<Option<Arc<dyn RpcHooks>>>>,
 
-    pub worker: ThreadPool,
+    /// Runs the futures spawned with the client.
+    ///
+    /// It is an `Executor` rather than a `ThreadPool`, so that the futures of a
+    /// simulated `Network` run on its deterministic executor. Both have
+    /// `spawn_ok`.
+    pub worker: Executor,
 }
 
 impl Client {
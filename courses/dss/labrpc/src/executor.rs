@@ -0,0 +1,46 @@
+use std::future::Future;
+
+use futures::executor::ThreadPool;
+
+use crate::sim::Sim;
+
+#[derive(Clone)]
+enum Inner {
+    Pool(ThreadPool),
+    Sim(Sim),
+}
+
+/// Runs the futures spawned by a `Network` and its clients.
+///
+/// It is a thread pool, or the deterministic executor of a simulated
+/// `Network`, whose futures only run within `Network::block_on`.
+#[derive(Clone)]
+pub struct Executor {
+    inner: Inner,
+}
+
+impl Executor {
+    pub(crate) fn sim(sim: Sim) -> Executor {
+        Executor {
+            inner: Inner::Sim(sim),
+        }
+    }
+
+    pub fn spawn_ok<F>(&self, f: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match self.inner {
+            Inner::Pool(ref pool) => pool.spawn_ok(f),
+            Inner::Sim(ref sim) => sim.spawn(f),
+        }
+    }
+}
+
+impl From<ThreadPool> for Executor {
+    fn from(pool: ThreadPool) -> Executor {
+        Executor {
+            inner: Inner::Pool(pool),
+        }
+    }
+}
@@ -2,18 +2,26 @@
 
 mod client;
 mod error;
+mod executor;
+mod link;
 #[macro_use]
 mod macros;
 mod network;
 mod server;
+mod sim;
+mod tcp;
 
 pub use self::client::{Client, Rpc, RpcHooks};
 pub use self::error::{Error, Result};
+pub use self::executor::Executor;
+pub use self::link::{Latency, LinkPolicy};
 pub use self::network::Network;
 pub use self::server::{Handler, HandlerFactory, RpcFuture, Server, ServerBuilder};
+pub use self::tcp::Listener;
 
 #[cfg(test)]
 pub mod tests {
+    use std::env;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::{mpsc, Arc, Mutex, Once};
     use std::thread;
@@ -21,6 +29,7 @@ pub mod tests {
 
     use futures::channel::oneshot::Canceled;
     use futures::executor::{block_on, ThreadPool};
+    use futures::future;
     use futures::stream::StreamExt;
     use futures_timer::Delay;
     use prost_derive::Message;
@@ -536,4 +545,349 @@ pub mod tests {
         block_on(async { client.handler2(&JunkArgs { x: i }).await.unwrap() });
         assert_eq!(reply.x, format!("handler2-{}", i));
     }
+
+    // runs unreliable RPCs on a simulated network, returning when each
+    // completed and whether it succeeded.
+    fn simulated_rpcs(net: Network) -> Vec<(i64, Duration, bool)> {
+        let mut builder = ServerBuilder::new("test_server".to_owned());
+        add_service(JunkService::new(), &mut builder).unwrap();
+        net.add_server(builder.build());
+        net.set_reliable(false);
+        net.set_long_reordering(true);
+
+        let results = Arc::new(Mutex::new(vec![]));
+        let calls = (0..50).map(|x| {
+            let client_name = format!("client-{}", x);
+            let client = JunkClient::new(net.create_client(client_name.clone()));
+            net.connect(&client_name, "test_server");
+            // some clients are cut off.
+            net.enable(&client_name, x % 10 != 0);
+            let (net, results) = (net.clone(), results.clone());
+            async move {
+                let ok = client.handler2(&JunkArgs { x }).await.is_ok();
+                results.lock().unwrap().push((x, net.elapsed(), ok));
+            }
+        });
+        net.block_on(future::join_all(calls));
+        let results = results.lock().unwrap().clone();
+        results
+    }
+
+    #[test]
+    fn test_simulated_replay() {
+        init_logger();
+
+        let seed = 4242;
+        let t0 = Instant::now();
+        let results = simulated_rpcs(Network::with_seed(seed));
+        assert_eq!(results.len(), 50);
+        let succeeded = results.iter().filter(|&&(_, _, ok)| ok).count();
+        assert!(
+            succeeded > 0 && succeeded < 50,
+            "{} of 50 RPCs succeeded despite unreliable",
+            succeeded
+        );
+        // the reordering delays are virtual.
+        let (_, last, _) = results[results.len() - 1];
+        assert!(last > Duration::from_millis(200), "finished at {:?}", last);
+        assert!(
+            t0.elapsed() < last,
+            "took {:?} for {:?}",
+            t0.elapsed(),
+            last
+        );
+
+        assert_eq!(results, simulated_rpcs(Network::with_seed(seed)));
+        assert_ne!(results, simulated_rpcs(Network::with_seed(seed + 1)));
+    }
+
+    #[test]
+    fn test_simulated_env_seed() {
+        init_logger();
+
+        // no other test reads LABRPC_SEED.
+        env::set_var("LABRPC_SEED", "4242");
+        let net = Network::new_simulated();
+        let net2 = Network::new_simulated();
+        let real_time = Network::new_seeded();
+        env::set_var("LABRPC_SEED", "not a seed");
+        let malformed = Network::new_simulated();
+        env::remove_var("LABRPC_SEED");
+        assert_eq!(net.seed(), Some(4242));
+        assert_eq!(real_time.seed(), Some(4242));
+        assert!(malformed.seed().is_some());
+        let results = simulated_rpcs(net);
+        assert_eq!(results, simulated_rpcs(net2));
+        assert_eq!(results, simulated_rpcs(Network::with_seed(4242)));
+    }
+
+    #[test]
+    fn test_simulated_sleep() {
+        init_logger();
+
+        let net = Network::with_seed(1);
+        assert_eq!(net.seed(), Some(1));
+        let t0 = Instant::now();
+        let elapsed = net.block_on({
+            let net = net.clone();
+            async move {
+                net.sleep(Duration::from_secs(3600)).await;
+                net.elapsed()
+            }
+        });
+        assert_eq!(elapsed, Duration::from_secs(3600));
+        assert!(t0.elapsed() < Duration::from_secs(1));
+        assert_eq!(Network::new().seed(), None);
+    }
+
+    fn junk_server(net: &Network, name: &str) {
+        let mut builder = ServerBuilder::new(name.to_owned());
+        add_service(JunkService::new(), &mut builder).unwrap();
+        net.add_server(builder.build());
+    }
+
+    fn junk_client(net: &Network, name: &str, host: &str, server: &str) -> JunkClient {
+        let client = JunkClient::new(net.create_client(name.to_owned()));
+        net.connect(name, server);
+        net.enable(name, true);
+        net.place_client(name, host);
+        client
+    }
+
+    #[test]
+    fn test_partition() {
+        init_logger();
+
+        let net = Network::with_seed(7);
+        junk_server(&net, "a");
+        junk_server(&net, "b");
+        junk_server(&net, "c");
+        let a_to_b = junk_client(&net, "a-b", "a", "b");
+        let b_to_a = junk_client(&net, "b-a", "b", "a");
+        let a_to_c = junk_client(&net, "a-c", "a", "c");
+        let call = |client: &JunkClient| net.block_on(client.handler4(&JunkArgs::default()));
+
+        net.partition(&[vec!["a"], vec!["b"]]);
+        assert_eq!(call(&a_to_b), Err(Error::Timeout));
+        assert_eq!(call(&b_to_a), Err(Error::Timeout));
+        // c is in no group.
+        call(&a_to_c).unwrap();
+
+        net.partition(&[vec!["a", "b"], vec!["c"]]);
+        call(&a_to_b).unwrap();
+        call(&b_to_a).unwrap();
+        assert_eq!(call(&a_to_c), Err(Error::Timeout));
+
+        net.heal();
+        call(&a_to_c).unwrap();
+
+        // an asymmetric link.
+        let lossy = LinkPolicy {
+            drop_request: 1.0,
+            ..LinkPolicy::default()
+        };
+        net.set_link_policy("a", "b", lossy.clone());
+        assert_eq!(call(&a_to_b), Err(Error::Timeout));
+        call(&b_to_a).unwrap();
+        // the policy of a client takes precedence over its host's.
+        net.set_link_policy("a-b", "b", LinkPolicy::default());
+        call(&a_to_b).unwrap();
+        net.clear_link_policy("a-b", "b");
+        assert_eq!(call(&a_to_b), Err(Error::Timeout));
+        net.clear_link_policy("a", "b");
+        call(&a_to_b).unwrap();
+    }
+
+    #[test]
+    fn test_link_policy() {
+        init_logger();
+
+        let net = Network::with_seed(7);
+        junk_server(&net, "test_server");
+        let client = junk_client(&net, "test_client", "host", "test_server");
+        let call = |x| {
+            let t0 = net.elapsed();
+            let reply = net.block_on(client.handler2(&JunkArgs { x }));
+            (reply, net.elapsed() - t0)
+        };
+
+        net.set_link_policy(
+            "test_client",
+            "test_server",
+            LinkPolicy {
+                delay: Latency::Fixed(Duration::from_millis(50)),
+                bandwidth: Some(100),
+                ..LinkPolicy::default()
+            },
+        );
+        let (reply, elapsed) = call(1);
+        assert_eq!(reply.unwrap().x, "handler2-1");
+        // 2 bytes of request and 12 bytes of reply, at 100 bytes/s.
+        assert_eq!(elapsed, Duration::from_millis(50 + 20 + 120));
+
+        net.set_link_policy(
+            "test_client",
+            "test_server",
+            LinkPolicy {
+                reorder: 1.0,
+                reorder_delay: Latency::Uniform(Duration::from_secs(1), Duration::from_secs(2)),
+                ..LinkPolicy::default()
+            },
+        );
+        let (reply, elapsed) = call(2);
+        reply.unwrap();
+        assert!(
+            elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(2),
+            "reordered by {:?}",
+            elapsed
+        );
+
+        net.set_link_policy(
+            "test_client",
+            "test_server",
+            LinkPolicy {
+                duplicate: 1.0,
+                ..LinkPolicy::default()
+            },
+        );
+        let n = net.count("test_server");
+        call(3).0.unwrap();
+        assert_eq!(net.count("test_server"), n + 2);
+
+        net.set_link_policy(
+            "test_client",
+            "test_server",
+            LinkPolicy {
+                drop_reply: 1.0,
+                ..LinkPolicy::default()
+            },
+        );
+        let n = net.count("test_server");
+        assert_eq!(call(4).0, Err(Error::Timeout));
+        assert_eq!(net.count("test_server"), n + 1);
+    }
+
+    #[test]
+    fn test_tcp_basic() {
+        init_logger();
+        let (_, server, junk_server) = junk_suit();
+        let listener = server.listen("127.0.0.1:0").unwrap();
+
+        let pool = ThreadPool::new().unwrap();
+        let raw_cli = Client::dial("test_client".to_owned(), listener.local_addr(), pool).unwrap();
+        let hook = Arc::new(Hooks {
+            drop_req: AtomicBool::new(false),
+            drop_resp: AtomicBool::new(false),
+        });
+        raw_cli.set_hooks(hook.clone());
+        let client = JunkClient::new(raw_cli);
+
+        let rsp = block_on(async { client.handler4(&JunkArgs::default()).await.unwrap() });
+        assert_eq!(
+            JunkReply {
+                x: "pointer".to_owned(),
+            },
+            rsp,
+        );
+
+        // many RPCs at once over the same connection.
+        let (tx, rx) = mpsc::channel();
+        for x in 0..20 {
+            let cli = client.clone();
+            let tx = tx.clone();
+            client.spawn(async move {
+                let reply = cli.handler2(&JunkArgs { x }).await.unwrap();
+                tx.send((x, reply.x)).unwrap();
+            });
+        }
+        for _ in 0..20 {
+            let (x, reply) = rx.recv().unwrap();
+            assert_eq!(reply, format!("handler2-{}", x));
+        }
+        assert_eq!(junk_server.inner.lock().unwrap().log2.len(), 20);
+        assert_eq!(server.count(), 21);
+
+        hook.drop_req.store(true, Ordering::Relaxed);
+        assert_eq!(
+            block_on(async { client.handler2(&JunkArgs { x: 1 }).await.unwrap_err() }),
+            Error::Other("reqhook".to_owned())
+        );
+        assert_eq!(server.count(), 21);
+    }
+
+    #[test]
+    fn test_tcp_unimplemented() {
+        init_logger();
+        let server = ServerBuilder::new("empty".to_owned()).build();
+        let listener = server.listen("127.0.0.1:0").unwrap();
+
+        let pool = ThreadPool::new().unwrap();
+        let raw_cli = Client::dial("test_client".to_owned(), listener.local_addr(), pool).unwrap();
+        let client = JunkClient::new(raw_cli);
+        assert_eq!(
+            block_on(async { client.handler4(&JunkArgs::default()).await }),
+            Err(Error::Unimplemented("unknown junk.handler4".to_owned()))
+        );
+    }
+
+    // does an RPC stuck in a server get un-stuck when the server stops
+    // listening?
+    #[test]
+    fn test_tcp_stopped() {
+        init_logger();
+        let (_, server, _) = junk_suit();
+        let listener = server.listen("127.0.0.1:0").unwrap();
+
+        let pool = ThreadPool::new().unwrap();
+        let raw_cli = Client::dial("test_client".to_owned(), listener.local_addr(), pool).unwrap();
+        let client = JunkClient::new(raw_cli);
+        let (tx, rx) = mpsc::channel();
+        let cli = client.clone();
+        client.spawn(async move {
+            let reply = cli.handler3(&JunkArgs { x: 99 }).await;
+            tx.send(reply).unwrap();
+        });
+        thread::sleep(Duration::from_millis(200));
+        rx.recv_timeout(Duration::from_millis(100)).unwrap_err();
+
+        drop(listener);
+        let reply = rx.recv_timeout(Duration::from_millis(100)).unwrap();
+        assert_eq!(reply, Err(Error::Stopped));
+        assert_eq!(
+            block_on(async { client.handler4(&JunkArgs::default()).await }),
+            Err(Error::Stopped)
+        );
+    }
+
+    // does an RPC stuck in a server time out, while the connection stays
+    // usable?
+    #[test]
+    fn test_tcp_timeout() {
+        init_logger();
+        let (_, server, _) = junk_suit();
+        let listener = server.listen("127.0.0.1:0").unwrap();
+
+        let pool = ThreadPool::new().unwrap();
+        let timeout = Duration::from_millis(200);
+        let raw_cli = Client::dial_timeout(
+            "test_client".to_owned(),
+            listener.local_addr(),
+            pool,
+            timeout,
+        )
+        .unwrap();
+        let client = JunkClient::new(raw_cli);
+        let start = Instant::now();
+        assert_eq!(
+            block_on(async { client.handler3(&JunkArgs { x: 99 }).await }),
+            Err(Error::Timeout)
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(
+            block_on(async { client.handler4(&JunkArgs::default()).await }).unwrap(),
+            JunkReply {
+                x: "pointer".to_owned(),
+            }
+        );
+    }
 }
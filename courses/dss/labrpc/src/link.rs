@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// A distribution of delays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+    /// Uniformly distributed in `[min, max)`.
+    Uniform(Duration, Duration),
+}
+
+impl Latency {
+    pub(crate) fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        match *self {
+            Latency::Fixed(delay) => delay,
+            Latency::Uniform(min, max) if max > min => {
+                let nanos = rng.gen_range(0, (max - min).as_nanos() as u64);
+                min + Duration::from_nanos(nanos)
+            }
+            Latency::Uniform(min, _) => min,
+        }
+    }
+}
+
+impl Default for Latency {
+    fn default() -> Latency {
+        Latency::Fixed(Duration::from_millis(0))
+    }
+}
+
+/// How the RPCs of a link, from a client to a server, are delivered.
+///
+/// The default policy delivers every RPC at once.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkPolicy {
+    /// The probability that a request is lost, and the client times out.
+    pub drop_request: f64,
+    /// The probability that a reply is lost after the server handled the
+    /// request, and the client times out.
+    pub drop_reply: f64,
+    /// How long a request takes to reach the server.
+    pub delay: Latency,
+    /// The probability that a request reaches the server twice. The reply to
+    /// the copy is lost.
+    pub duplicate: f64,
+    /// The probability that a reply is held back for `reorder_delay`, so that
+    /// the replies to later requests overtake it.
+    pub reorder: f64,
+    pub reorder_delay: Latency,
+    /// The bytes per second requests and replies are sent at, if limited.
+    pub bandwidth: Option<u64>,
+}
+
+impl LinkPolicy {
+    /// Returns how long sending `len` bytes takes.
+    pub(crate) fn transfer_time(&self, len: usize) -> Duration {
+        match self.bandwidth {
+            Some(bandwidth) if bandwidth > 0 => {
+                Duration::from_nanos((len as u128 * 1_000_000_000 / bandwidth as u128) as u64)
+            }
+            _ => Duration::from_millis(0),
+        }
+    }
+}
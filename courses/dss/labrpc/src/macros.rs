@@ -56,10 +56,11 @@ macro_rules! service {
                     svc: Mutex<S>,
                 }
                 impl<S: Service> $crate::HandlerFactory for Factory<S> {
-                    fn handler(&self, name: &'static str) -> Box<$crate::Handler> {
+                    fn handler(&self, name: &str) -> Box<$crate::Handler> {
                         let s = self.svc.lock().unwrap().clone();
+                        let name = name.to_owned();
                         Box::new(move |req| {
-                            match name {
+                            match name.as_str() {
                                 $(stringify!($method_name) => {
                                     let request = match labcodec::decode(req) {
                                         Ok(req) => req,
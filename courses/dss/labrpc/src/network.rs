@@ -1,27 +1,34 @@
 use std::collections::HashMap;
+use std::env;
 use std::future::Future;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
-use futures::executor::ThreadPool;
+use futures::executor::{self, ThreadPool};
 use futures::future::FutureExt;
 use futures::select;
 use futures::stream::StreamExt;
 use futures_timer::Delay;
 use log::{debug, error};
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
 
 use crate::client::{Client, Rpc};
 use crate::error::{Error, Result};
-use crate::server::Server;
+use crate::executor::Executor;
+use crate::link::LinkPolicy;
+use crate::server::{RpcFuture, Server};
+use crate::sim::Sim;
 
 #[derive(Debug)]
 struct EndInfo {
     enabled: bool,
     reliable: bool,
     long_reordering: bool,
+    policy: Option<LinkPolicy>,
     server: Option<Server>,
 }
 
@@ -32,6 +39,55 @@ struct Endpoints {
     servers: HashMap<String, Option<Server>>,
     // client_name -> server_name
     connections: HashMap<String, Option<String>>,
+    // client_name -> the server hosting it
+    hosts: HashMap<String, String>,
+    // server_name -> its group in the partition
+    groups: HashMap<String, usize>,
+    // (client name or host, server_name) -> policy
+    policies: HashMap<(String, String), LinkPolicy>,
+}
+
+impl Endpoints {
+    fn is_enabled(&self, client_name: &str) -> bool {
+        self.enabled[client_name] && !self.is_partitioned(client_name)
+    }
+
+    // Whether the host of the client and its server are in different groups.
+    fn is_partitioned(&self, client_name: &str) -> bool {
+        let host = self.hosts.get(client_name);
+        let server = self.connections.get(client_name).and_then(Option::as_ref);
+        match (
+            host.and_then(|h| self.groups.get(h)),
+            server.and_then(|s| self.groups.get(s)),
+        ) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        }
+    }
+
+    fn policy(&self, client_name: &str) -> Option<LinkPolicy> {
+        let server = self.connections.get(client_name)?.as_ref()?;
+        self.policies
+            .get(&(client_name.to_owned(), server.clone()))
+            .or_else(|| {
+                let host = self.hosts.get(client_name)?;
+                self.policies.get(&(host.clone(), server.clone()))
+            })
+            .cloned()
+    }
+}
+
+// What happens to an RPC on its way.
+#[derive(Debug, Default)]
+struct Plan {
+    // Lose the request, after waiting this long.
+    drop_request: Option<Duration>,
+    delay: Duration,
+    duplicate: bool,
+    drop_reply: bool,
+    reply_delay: Duration,
+    // Limits the time to send the reply.
+    policy: Option<LinkPolicy>,
 }
 
 struct NetworkCore {
@@ -43,8 +99,14 @@ struct NetworkCore {
     endpoints: Mutex<Endpoints>,
     count: AtomicUsize,
     sender: UnboundedSender<Rpc>,
-    poller: ThreadPool,
-    worker: ThreadPool,
+    poller: Executor,
+    worker: Executor,
+    // The executor and clock of a simulated network.
+    sim: Option<Sim>,
+    seed: Option<u64>,
+    seed_reported: AtomicBool,
+    rng: Mutex<StdRng>,
+    started: Instant,
 }
 
 #[derive(Clone)]
@@ -60,7 +122,60 @@ impl Network {
     }
 
     pub fn create() -> (Network, UnboundedReceiver<Rpc>) {
+        Network::create_with(None, None)
+    }
+
+    /// Creates a network running in real time like `new`, whose faults are drawn
+    /// from a RNG seeded with the `LABRPC_SEED` environment variable if it is
+    /// set, or else with a random seed.
+    ///
+    /// If the thread owning the network panics, the seed is printed. Unlike with
+    /// a simulated network, a seed only draws the same faults again as long as
+    /// the RPCs are sent in the same order, which threads and real timers do not
+    /// ensure.
+    pub fn new_seeded() -> Network {
+        let seed = env_seed();
+        debug!("network with seed {}", seed);
+        let (net, incoming) = Network::create_with(None, Some(seed));
+        net.start(incoming);
+        net
+    }
+
+    /// Creates a simulated network, seeded with the `LABRPC_SEED` environment
+    /// variable if it is set, or else with a random seed.
+    pub fn new_simulated() -> Network {
+        Network::with_seed(env_seed())
+    }
+
+    /// Creates a simulated network, whose faults are drawn from a RNG seeded
+    /// with `seed`.
+    ///
+    /// Its futures, and those spawned by its clients, only run within
+    /// `Network::block_on`, one at a time, on a virtual clock that jumps to the
+    /// next timer once they all wait. So the same seed replays the same RPCs,
+    /// as long as the services wait with `Network::sleep` rather than on other
+    /// threads or real timers. If the thread owning the network panics, the
+    /// seed is printed to replay the run.
+    pub fn with_seed(seed: u64) -> Network {
+        debug!("simulated network with seed {}", seed);
+        let (net, incoming) = Network::create_with(Some(Sim::default()), Some(seed));
+        net.start(incoming);
+        net
+    }
+
+    fn create_with(sim: Option<Sim>, seed: Option<u64>) -> (Network, UnboundedReceiver<Rpc>) {
         let (sender, incoming) = unbounded();
+        let (poller, worker) = match sim {
+            Some(ref sim) => (Executor::sim(sim.clone()), Executor::sim(sim.clone())),
+            None => (
+                ThreadPool::builder().pool_size(2).create().unwrap().into(),
+                ThreadPool::new().unwrap().into(),
+            ),
+        };
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
         let net = Network {
             core: Arc::new(NetworkCore {
                 reliable: AtomicBool::new(true),
@@ -70,11 +185,19 @@ impl Network {
                     enabled: HashMap::new(),
                     servers: HashMap::new(),
                     connections: HashMap::new(),
+                    hosts: HashMap::new(),
+                    groups: HashMap::new(),
+                    policies: HashMap::new(),
                 }),
                 count: AtomicUsize::new(0),
-                poller: ThreadPool::builder().pool_size(2).create().unwrap(),
-                worker: ThreadPool::new().unwrap(),
+                poller,
+                worker,
                 sender,
+                sim,
+                seed,
+                seed_reported: AtomicBool::new(false),
+                rng: Mutex::new(rng),
+                started: Instant::now(),
             }),
         };
 
@@ -141,6 +264,55 @@ impl Network {
         eps.enabled.insert(client_name.to_owned(), enabled);
     }
 
+    /// Places a client on the host of a server, so that the partitions and
+    /// link policies of the server apply to its RPCs.
+    pub fn place_client(&self, client_name: &str, server_name: &str) {
+        let mut eps = self.core.endpoints.lock().unwrap();
+        eps.hosts
+            .insert(client_name.to_owned(), server_name.to_owned());
+    }
+
+    /// Splits the servers into groups, whose clients only reach the servers
+    /// of the same group. It replaces the previous partition.
+    ///
+    /// It only applies to the clients placed with `place_client`, and the
+    /// servers in no group are reached by every client.
+    pub fn partition<G, S>(&self, groups: &[G])
+    where
+        G: AsRef<[S]>,
+        S: AsRef<str>,
+    {
+        let mut eps = self.core.endpoints.lock().unwrap();
+        eps.groups.clear();
+        for (i, group) in groups.iter().enumerate() {
+            for server_name in group.as_ref() {
+                eps.groups.insert(server_name.as_ref().to_owned(), i);
+            }
+        }
+        debug!("partition {:?}", eps.groups);
+    }
+
+    /// Removes the partition.
+    pub fn heal(&self) {
+        self.partition::<&[&str], &str>(&[]);
+    }
+
+    /// Sets how the RPCs from `from` to the server `to` are delivered, in
+    /// place of `set_reliable` and `set_long_reordering`.
+    ///
+    /// `from` is a client, or a server hosting clients. The policy of a client
+    /// takes precedence over the one of its host.
+    pub fn set_link_policy(&self, from: &str, to: &str, policy: LinkPolicy) {
+        let mut eps = self.core.endpoints.lock().unwrap();
+        eps.policies
+            .insert((from.to_owned(), to.to_owned()), policy);
+    }
+
+    pub fn clear_link_policy(&self, from: &str, to: &str) {
+        let mut eps = self.core.endpoints.lock().unwrap();
+        eps.policies.remove(&(from.to_owned(), to.to_owned()));
+    }
+
     pub fn set_reliable(&self, yes: bool) {
         self.core.reliable.store(yes, Ordering::Release);
     }
@@ -162,6 +334,88 @@ impl Network {
         self.core.count.load(Ordering::Relaxed)
     }
 
+    /// Returns the seed of a simulated or seeded network.
+    pub fn seed(&self) -> Option<u64> {
+        self.core.seed
+    }
+
+    /// Runs a future to completion, along with the futures of a simulated
+    /// network.
+    pub fn block_on<F: Future>(&self, f: F) -> F::Output {
+        match self.core.sim {
+            Some(ref sim) => sim.block_on(f),
+            None => executor::block_on(f),
+        }
+    }
+
+    /// Waits for `dur`, on the virtual clock of a simulated network.
+    pub fn sleep(&self, dur: Duration) -> RpcFuture<()> {
+        match self.core.sim {
+            Some(ref sim) => Box::pin(sim.sleep(dur)),
+            None => Box::pin(Delay::new(dur)),
+        }
+    }
+
+    /// Returns the time elapsed since the network was created, on the virtual
+    /// clock of a simulated network.
+    pub fn elapsed(&self) -> Duration {
+        match self.core.sim {
+            Some(ref sim) => sim.now(),
+            None => self.core.started.elapsed(),
+        }
+    }
+
+    fn gen_range(&self, low: u64, high: u64) -> u64 {
+        self.core.rng.lock().unwrap().gen_range(low, high)
+    }
+
+    fn plan(&self, reliable: bool, long_reordering: bool) -> Plan {
+        let mut plan = Plan::default();
+        if !reliable {
+            // short delay
+            let ms = self.gen_range(0, 27);
+            if self.gen_range(0, 1000) < 100 {
+                // drop the request, return as if timeout
+                plan.drop_request = Some(Duration::from_secs(ms));
+                return plan;
+            }
+            plan.delay = Duration::from_millis(ms);
+            plan.drop_reply = self.gen_range(0, 1000) < 100;
+        }
+        if long_reordering && self.gen_range(0, 900) < 600 {
+            // delay the response for a while
+            let upper_bound = 1 + self.gen_range(0, 2000);
+            plan.reply_delay = Duration::from_millis(200 + self.gen_range(0, upper_bound));
+        }
+        plan
+    }
+
+    fn plan_with(&self, policy: LinkPolicy, req_len: usize) -> Plan {
+        let mut rng = self.core.rng.lock().unwrap();
+        let delay = policy.delay.sample(&mut *rng) + policy.transfer_time(req_len);
+        if rng.gen::<f64>() < policy.drop_request {
+            return Plan {
+                drop_request: Some(delay),
+                ..Plan::default()
+            };
+        }
+        let duplicate = rng.gen::<f64>() < policy.duplicate;
+        let drop_reply = rng.gen::<f64>() < policy.drop_reply;
+        let reply_delay = if rng.gen::<f64>() < policy.reorder {
+            policy.reorder_delay.sample(&mut *rng)
+        } else {
+            Duration::from_millis(0)
+        };
+        Plan {
+            drop_request: None,
+            delay,
+            duplicate,
+            drop_reply,
+            reply_delay,
+            policy: Some(policy),
+        }
+    }
+
     fn end_info(&self, client_name: &str) -> EndInfo {
         let eps = self.core.endpoints.lock().unwrap();
         let mut server = None;
@@ -169,19 +423,23 @@ impl Network {
             server = eps.servers[server_name].clone();
         }
         EndInfo {
-            enabled: eps.enabled[client_name],
+            enabled: eps.is_enabled(client_name),
             reliable: self.core.reliable.load(Ordering::Acquire),
             long_reordering: self.core.long_reordering.load(Ordering::Acquire),
+            policy: eps.policy(client_name),
             server,
         }
     }
 
     fn is_server_dead(&self, client_name: &str, server_name: &str, server_id: usize) -> bool {
         let eps = self.core.endpoints.lock().unwrap();
-        !eps.enabled[client_name]
-            || eps.servers.get(server_name).map_or(true, |o| {
-                o.as_ref().map(|s| s.core.id != server_id).unwrap_or(true)
-            })
+        !eps.is_enabled(client_name)
+            || eps
+                .servers
+                .get(server_name)
+                .and_then(Option::as_ref)
+                .map(|s| s.core.id)
+                != Some(server_id)
     }
 
     async fn process_rpc(&self, rpc: Rpc) -> Result<Vec<u8>> {
@@ -193,60 +451,43 @@ impl Network {
             enabled,
             reliable,
             long_reordering,
+            policy,
             server,
         } = end_info;
 
         match (enabled, server) {
             (true, Some(server)) => {
-                let short_delay = if !reliable {
-                    // short delay
-                    let ms = thread_rng().gen::<u64>() % 27;
-                    Some(ms)
-                } else {
-                    None
+                let plan = match policy {
+                    Some(policy) => {
+                        let req_len = rpc.req.as_ref().map_or(0, Vec::len);
+                        self.plan_with(policy, req_len)
+                    }
+                    None => self.plan(reliable, long_reordering),
                 };
+                debug!("{:?} plan {:?}", rpc, plan);
 
-                if !reliable && (thread_rng().gen::<u64>() % 1000) < 100 {
-                    // drop the request, return as if timeout
-                    Delay::new(Duration::from_secs(short_delay.unwrap())).await;
+                if let Some(delay) = plan.drop_request {
+                    self.sleep(delay).await;
                     return Err(Error::Timeout);
                 }
 
-                let drop_reply = !reliable && thread_rng().gen::<u64>() % 1000 < 100;
-                let long_reordering = if long_reordering && thread_rng().gen_range(0, 900) < 600i32
-                {
-                    // delay the response for a while
-                    let upper_bound: u64 = 1 + thread_rng().gen_range(0, 2000);
-                    Some(200 + thread_rng().gen_range(0, upper_bound))
-                } else {
-                    None
-                };
-
                 // Dispatch
-                process_rpc(
-                    short_delay,
-                    drop_reply,
-                    long_reordering,
-                    rpc,
-                    network,
-                    server,
-                )
-                .await
+                process_rpc(plan, rpc, network, server).await
             }
             _ => {
                 // simulate no reply and eventual timeout.
                 let ms = if self.core.long_delays.load(Ordering::Acquire) {
                     // let Raft tests check that leader doesn't send
                     // RPCs synchronously.
-                    thread_rng().gen::<u64>() % 7000
+                    self.gen_range(0, 7000)
                 } else {
                     // many kv tests require the client to try each
                     // server in fairly rapid succession.
-                    thread_rng().gen::<u64>() % 100
+                    self.gen_range(0, 100)
                 };
 
                 debug!("{:?} delay {}ms then timeout", rpc, ms);
-                Delay::new(Duration::from_millis(ms)).await;
+                self.sleep(Duration::from_millis(ms)).await;
                 Err(Error::Timeout)
             }
         }
@@ -269,27 +510,64 @@ impl Network {
     }
 }
 
+impl Drop for Network {
+    fn drop(&mut self) {
+        if let Some(seed) = self.core.seed {
+            if thread::panicking() && !self.core.seed_reported.swap(true, Ordering::Relaxed) {
+                if self.core.sim.is_some() {
+                    eprintln!("labrpc: replay this run with LABRPC_SEED={}", seed);
+                } else {
+                    eprintln!(
+                        "labrpc: the faults of this run were drawn with LABRPC_SEED={}",
+                        seed
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Returns the seed in the `LABRPC_SEED` environment variable, or a random seed
+/// if it is not set or not a `u64`.
+fn env_seed() -> u64 {
+    match env::var("LABRPC_SEED") {
+        Ok(value) => match value.trim().parse() {
+            Ok(seed) => seed,
+            Err(e) => {
+                let seed = thread_rng().gen();
+                eprintln!(
+                    "labrpc: LABRPC_SEED={:?} is not a u64 ({}), using the random seed {}",
+                    value, e, seed
+                );
+                seed
+            }
+        },
+        Err(_) => thread_rng().gen(),
+    }
+}
+
 async fn process_rpc(
-    mut delay: Option<u64>,
-    drop_reply: bool,
-    long_reordering: Option<u64>,
+    plan: Plan,
     mut rpc: Rpc,
     network: Network,
     server: Server,
 ) -> Result<Vec<u8>> {
     // Dispatch ===============================================================
-    if let Some(delay) = delay {
-        Delay::new(Duration::from_millis(delay)).await;
+    if plan.delay > Duration::from_millis(0) {
+        network.sleep(plan.delay).await;
     }
-    // We has finished the delay, take it out to prevent polling
-    // twice.
-    delay.take();
 
     let fq_name = rpc.fq_name;
     let req = rpc.req.take().unwrap();
     if let Some(hooks) = rpc.hooks.lock().unwrap().as_ref() {
         hooks.before_dispatch(fq_name, &req)?;
     }
+    if plan.duplicate {
+        let duplicate = server.dispatch(fq_name, &req);
+        network.spawn_poller(async move {
+            let _ = duplicate.await;
+        });
+    }
 
     // Execute the request (call the RPC handler) in a separate thread so that
     // we can periodically check if the server has been killed and the RPC
@@ -323,19 +601,22 @@ async fn process_rpc(
     if network.is_server_dead(client_name, server_name, server_id) {
         return Err(Error::Stopped);
     }
-    if drop_reply {
+    if plan.drop_reply {
         // drop the reply, return as if timeout.
         return Err(Error::Timeout);
     }
 
     // Reordering =============================================================
-    if let Some(reordering) = long_reordering {
-        debug!("{:?} next long reordering {}ms", rpc, reordering);
-        Delay::new(Duration::from_millis(reordering)).await;
-        Ok(resp)
-    } else {
-        Ok(resp)
+    let transfer_time = plan
+        .policy
+        .as_ref()
+        .map_or(Duration::from_millis(0), |p| p.transfer_time(resp.len()));
+    let delay = plan.reply_delay + transfer_time;
+    if delay > Duration::from_millis(0) {
+        debug!("{:?} next long reordering {:?}", rpc, delay);
+        network.sleep(delay).await;
     }
+    Ok(resp)
 }
 
 /// Checks if the specified server killed.
@@ -349,7 +630,7 @@ async fn server_dead(
     server_id: usize,
 ) {
     loop {
-        Delay::new(interval).await;
+        net.sleep(interval).await;
         if net.is_server_dead(client_name, server_name, server_id) {
             debug!("{:?} is dead", server_name);
             return;
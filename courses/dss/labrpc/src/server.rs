@@ -14,7 +14,11 @@ pub type RpcFuture<T> = BoxFuture<'static, T>;
 pub type Handler = dyn FnOnce(&[u8]) -> RpcFuture<Result<Vec<u8>>>;
 
 pub trait HandlerFactory: Sync + Send + 'static {
-    fn handler(&self, name: &'static str) -> Box<Handler>;
+    /// Returns the handler of the method `name`.
+    ///
+    /// `name` is not `&'static str`, as the RPCs coming over TCP carry it in
+    /// their requests. Factories keeping it must copy it.
+    fn handler(&self, name: &str) -> Box<Handler>;
 }
 
 pub struct ServerBuilder {
@@ -82,7 +86,7 @@ impl Server {
         &self.core.name
     }
 
-    pub(crate) fn dispatch(&self, fq_name: &'static str, req: &[u8]) -> RpcFuture<Result<Vec<u8>>> {
+    pub(crate) fn dispatch(&self, fq_name: &str, req: &[u8]) -> RpcFuture<Result<Vec<u8>>> {
         self.core.count.fetch_add(1, Ordering::Relaxed);
         let mut names = fq_name.split('.');
         let service_name = match names.next() {
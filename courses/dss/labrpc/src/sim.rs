@@ -0,0 +1,215 @@
+//! A deterministic executor with a virtual clock, behind `Network::with_seed`.
+//!
+//! Every task runs on the thread calling `Sim::block_on`, one at a time and in
+//! the order they were woken up. When no task can make progress, the clock
+//! jumps to the next timer instead of waiting for it.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::pin_mut;
+use futures::task::{self, ArcWake};
+
+#[derive(Default)]
+struct Ready {
+    // The future given to `block_on` has been woken up.
+    main: bool,
+    tasks: VecDeque<Arc<Task>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    ready: Mutex<Ready>,
+    wakeup: Condvar,
+}
+
+struct Task {
+    // `None` once it has completed.
+    future: Mutex<Option<BoxFuture<'static, ()>>>,
+    queue: Arc<Queue>,
+}
+
+impl Task {
+    fn run(self: &Arc<Self>) {
+        let mut slot = self.future.lock().unwrap();
+        if let Some(mut future) = slot.take() {
+            let waker = task::waker_ref(self);
+            let mut cx = Context::from_waker(&waker);
+            if future.as_mut().poll(&mut cx).is_pending() {
+                *slot = Some(future);
+            }
+        }
+    }
+}
+
+impl ArcWake for Task {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        let mut ready = arc_self.queue.ready.lock().unwrap();
+        ready.tasks.push_back(arc_self.clone());
+        arc_self.queue.wakeup.notify_one();
+    }
+}
+
+struct MainWaker {
+    queue: Arc<Queue>,
+}
+
+impl ArcWake for MainWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.queue.ready.lock().unwrap().main = true;
+        arc_self.queue.wakeup.notify_one();
+    }
+}
+
+#[derive(Default)]
+struct Clock {
+    now: Duration,
+    // (deadline, timer id) -> the waiting task
+    timers: BTreeMap<(Duration, u64), Waker>,
+    next_timer: u64,
+}
+
+enum Next {
+    Main,
+    Task(Arc<Task>),
+    Idle,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Sim {
+    queue: Arc<Queue>,
+    clock: Arc<Mutex<Clock>>,
+}
+
+impl Sim {
+    pub(crate) fn spawn<F>(&self, f: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(Box::pin(f))),
+            queue: self.queue.clone(),
+        });
+        ArcWake::wake(task);
+    }
+
+    /// Returns the virtual time elapsed since the simulation started.
+    pub(crate) fn now(&self) -> Duration {
+        self.clock.lock().unwrap().now
+    }
+
+    pub(crate) fn sleep(&self, dur: Duration) -> Sleep {
+        Sleep {
+            clock: self.clock.clone(),
+            deadline: self.now() + dur,
+            timer: None,
+        }
+    }
+
+    /// Runs the spawned tasks until `f` completes.
+    pub(crate) fn block_on<F: Future>(&self, f: F) -> F::Output {
+        pin_mut!(f);
+        let waker = task::waker(Arc::new(MainWaker {
+            queue: self.queue.clone(),
+        }));
+        let mut cx = Context::from_waker(&waker);
+        self.queue.ready.lock().unwrap().main = true;
+        loop {
+            let next = {
+                let mut ready = self.queue.ready.lock().unwrap();
+                if ready.main {
+                    ready.main = false;
+                    Next::Main
+                } else if let Some(task) = ready.tasks.pop_front() {
+                    Next::Task(task)
+                } else {
+                    Next::Idle
+                }
+            };
+            match next {
+                Next::Main => {
+                    if let Poll::Ready(output) = f.as_mut().poll(&mut cx) {
+                        return output;
+                    }
+                }
+                Next::Task(task) => task.run(),
+                Next::Idle => {
+                    if !self.advance() {
+                        // Nothing left but what other threads may wake up.
+                        let mut ready = self.queue.ready.lock().unwrap();
+                        while !ready.main && ready.tasks.is_empty() {
+                            ready = self.queue.wakeup.wait(ready).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves the clock to the next deadline and wakes up its timers.
+    fn advance(&self) -> bool {
+        let wakers = {
+            let mut clock = self.clock.lock().unwrap();
+            let deadline = match clock.timers.keys().next() {
+                Some(&(deadline, _)) => deadline,
+                None => return false,
+            };
+            clock.now = clock.now.max(deadline);
+            let later = clock.timers.split_off(&(deadline, u64::MAX));
+            std::mem::replace(&mut clock.timers, later)
+        };
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+        true
+    }
+}
+
+/// Completes once the virtual clock reaches its deadline.
+pub(crate) struct Sleep {
+    clock: Arc<Mutex<Clock>>,
+    deadline: Duration,
+    timer: Option<u64>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let mut clock = this.clock.lock().unwrap();
+        if clock.now >= this.deadline {
+            if let Some(id) = this.timer.take() {
+                clock.timers.remove(&(this.deadline, id));
+            }
+            return Poll::Ready(());
+        }
+        let id = match this.timer {
+            Some(id) => id,
+            None => {
+                clock.next_timer += 1;
+                clock.next_timer
+            }
+        };
+        clock.timers.insert((this.deadline, id), cx.waker().clone());
+        this.timer = Some(id);
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.timer {
+            self.clock
+                .lock()
+                .unwrap()
+                .timers
+                .remove(&(self.deadline, id));
+        }
+    }
+}
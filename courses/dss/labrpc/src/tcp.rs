@@ -0,0 +1,426 @@
+//! A TCP transport, so that services run across processes.
+//!
+//! Every message is a labcodec-encoded `Request` or `Response` behind its
+//! length, as a big-endian `u32`. A connection carries many RPCs at once, the
+//! responses are matched to the requests by their id.
+//!
+//! There is no network to drop or delay messages, so a client gives up on an RPC
+//! with `Error::Timeout` when the response takes longer than its timeout.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use futures::channel::oneshot;
+use futures::executor::{block_on_stream, ThreadPool};
+use futures_timer::Delay;
+use log::{debug, error};
+use prost_derive::Message;
+
+use crate::client::{Client, Rpc, RpcHooks};
+use crate::error::{Error, Result};
+use crate::server::Server;
+
+// Larger frames are refused instead of allocating for them.
+const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+// The timeout of the RPCs of clients connected with `Client::dial`.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const CODE_OK: i32 = 0;
+const CODE_UNIMPLEMENTED: i32 = 1;
+const CODE_TIMEOUT: i32 = 2;
+const CODE_STOPPED: i32 = 3;
+const CODE_OTHER: i32 = 4;
+
+#[derive(Clone, PartialEq, Message)]
+struct Request {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(string, tag = "2")]
+    fq_name: String,
+    #[prost(bytes, tag = "3")]
+    req: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Response {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(int32, tag = "2")]
+    code: i32,
+    #[prost(bytes, tag = "3")]
+    resp: Vec<u8>,
+    #[prost(string, tag = "4")]
+    msg: String,
+}
+
+impl Response {
+    fn new(id: u64, res: Result<Vec<u8>>) -> Response {
+        let (code, resp, msg) = match res {
+            Ok(resp) => (CODE_OK, resp, String::new()),
+            Err(Error::Unimplemented(msg)) => (CODE_UNIMPLEMENTED, vec![], msg),
+            Err(Error::Timeout) => (CODE_TIMEOUT, vec![], String::new()),
+            Err(Error::Stopped) => (CODE_STOPPED, vec![], String::new()),
+            Err(Error::Other(msg)) => (CODE_OTHER, vec![], msg),
+            // Encoding errors can not be rebuilt on the other side.
+            Err(e) => (CODE_OTHER, vec![], e.to_string()),
+        };
+        Response {
+            id,
+            code,
+            resp,
+            msg,
+        }
+    }
+
+    fn into_result(self) -> Result<Vec<u8>> {
+        match self.code {
+            CODE_OK => Ok(self.resp),
+            CODE_UNIMPLEMENTED => Err(Error::Unimplemented(self.msg)),
+            CODE_TIMEOUT => Err(Error::Timeout),
+            CODE_STOPPED => Err(Error::Stopped),
+            CODE_OTHER => Err(Error::Other(self.msg)),
+            code => Err(Error::Other(format!("unknown response code {}", code))),
+        }
+    }
+}
+
+fn write_frame<M: labcodec::Message>(stream: &mut TcpStream, msg: &M) -> io::Result<()> {
+    let mut buf = vec![0; 4];
+    labcodec::encode(msg, &mut buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let len = buf.len() - 4;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is too large", len),
+        ));
+    }
+    buf[..4].copy_from_slice(&(len as u32).to_be_bytes());
+    stream.write_all(&buf)
+}
+
+/// Reads the next message, or `None` if the peer closed the connection.
+fn read_frame<M: labcodec::Message>(stream: &mut TcpStream) -> io::Result<Option<M>> {
+    let mut len = [0; 4];
+    match stream.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is too large", len),
+        ));
+    }
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf)?;
+    labcodec::decode(&buf)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+struct Call {
+    fq_name: &'static str,
+    hooks: Option<Arc<dyn RpcHooks>>,
+    resp: oneshot::Sender<Result<Vec<u8>>>,
+}
+
+impl Call {
+    fn finish(self, res: Result<Vec<u8>>) {
+        let res = match self.hooks {
+            Some(hooks) => hooks.after_dispatch(self.fq_name, res),
+            None => res,
+        };
+        let _ = self.resp.send(res);
+    }
+}
+
+// The calls waiting for their responses, by request id.
+// It is `None` once the connection is closed.
+type Pending = Mutex<Option<HashMap<u64, Call>>>;
+
+impl Client {
+    /// Connects to a server listening at `addr`, see `Server::listen`.
+    ///
+    /// Unlike a client of a `Network`, it is connected and enabled at once.
+    /// The RPC hooks run in this process, around the round trip to the server.
+    /// Once the connection is lost, every RPC fails with `Error::Stopped`.
+    ///
+    /// RPCs fail with `Error::Timeout` after 10 seconds without a response, see
+    /// `dial_timeout`.
+    pub fn dial<A: ToSocketAddrs>(name: String, addr: A, worker: ThreadPool) -> io::Result<Client> {
+        Client::dial_timeout(name, addr, worker, DEFAULT_TIMEOUT)
+    }
+
+    /// Connects to a server like `dial`, with RPCs failing with `Error::Timeout`
+    /// after `timeout` without a response.
+    pub fn dial_timeout<A: ToSocketAddrs>(
+        name: String,
+        addr: A,
+        worker: ThreadPool,
+        timeout: Duration,
+    ) -> io::Result<Client> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+        let (sender, incoming) = unbounded();
+        let pending = Arc::new(Mutex::new(Some(HashMap::new())));
+
+        let (p, timers) = (pending.clone(), worker.clone());
+        thread::Builder::new()
+            .name(format!("{}-sender", name))
+            .spawn(move || send_requests(stream, incoming, p, timers, timeout))?;
+        let p = pending;
+        thread::Builder::new()
+            .name(format!("{}-receiver", name))
+            .spawn(move || receive_responses(reader, &p))?;
+
+        Ok(Client {
+            name,
+            sender,
+            worker: worker.into(),
+            hooks: Arc::new(Mutex::new(None)),
+        })
+    }
+}
+
+// Runs until every clone of the client is dropped.
+fn send_requests(
+    mut stream: TcpStream,
+    incoming: UnboundedReceiver<Rpc>,
+    pending: Arc<Pending>,
+    timers: ThreadPool,
+    timeout: Duration,
+) {
+    let mut next_id = 0;
+    for mut rpc in block_on_stream(incoming) {
+        let resp = rpc.take_resp_sender().unwrap();
+        let req = rpc.req.take().unwrap();
+        let hooks = rpc.hooks.lock().unwrap().clone();
+        if let Some(hooks) = hooks.as_ref() {
+            if let Err(e) = hooks.before_dispatch(rpc.fq_name, &req) {
+                let _ = resp.send(Err(e));
+                continue;
+            }
+        }
+
+        let id = next_id;
+        next_id += 1;
+        let call = Call {
+            fq_name: rpc.fq_name,
+            hooks,
+            resp,
+        };
+        match pending.lock().unwrap().as_mut() {
+            Some(calls) => calls.insert(id, call),
+            None => {
+                let _ = call.resp.send(Err(Error::Stopped));
+                continue;
+            }
+        };
+        let p = pending.clone();
+        timers.spawn_ok(async move {
+            Delay::new(timeout).await;
+            // The call is still pending if the response has not arrived.
+            let call = p
+                .lock()
+                .unwrap()
+                .as_mut()
+                .and_then(|calls| calls.remove(&id));
+            if let Some(call) = call {
+                debug!("call {} to {} timed out", id, call.fq_name);
+                call.finish(Err(Error::Timeout));
+            }
+        });
+
+        let request = Request {
+            id,
+            fq_name: rpc.fq_name.to_owned(),
+            req,
+        };
+        if let Err(e) = write_frame(&mut stream, &request) {
+            debug!("{:?} fails to send: {}", rpc, e);
+            // The receiver fails the pending calls once the connection is closed.
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+fn receive_responses(mut stream: TcpStream, pending: &Pending) {
+    loop {
+        let rsp: Response = match read_frame(&mut stream) {
+            Ok(Some(rsp)) => rsp,
+            Ok(None) => break,
+            Err(e) => {
+                debug!("fail to receive response: {}", e);
+                break;
+            }
+        };
+        let call = pending
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|calls| calls.remove(&rsp.id));
+        if let Some(call) = call {
+            call.finish(rsp.into_result());
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+    let calls = pending.lock().unwrap().take();
+    for (_, call) in calls.into_iter().flatten() {
+        let _ = call.resp.send(Err(Error::Stopped));
+    }
+}
+
+struct ListenerCore {
+    stopped: AtomicBool,
+    // The open connections, to close them when the listener stops.
+    conns: Mutex<HashMap<usize, TcpStream>>,
+    next_conn: AtomicUsize,
+}
+
+/// Serves the RPCs coming over TCP to a `Server`, until it is dropped.
+///
+/// Dropping it also closes the open connections, so their clients see the
+/// server stopped like after `Network::delete_server`.
+pub struct Listener {
+    addr: SocketAddr,
+    core: Arc<ListenerCore>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl Listener {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.core.stopped.store(true, Ordering::Release);
+        // Wakes up the acceptor, which then sees it is stopped.
+        let mut addr = self.addr;
+        if addr.ip().is_unspecified() {
+            match addr {
+                SocketAddr::V4(_) => addr.set_ip([127, 0, 0, 1].into()),
+                SocketAddr::V6(_) => addr.set_ip([0, 0, 0, 0, 0, 0, 0, 1].into()),
+            }
+        }
+        let _ = TcpStream::connect(addr);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        for (_, conn) in self.core.conns.lock().unwrap().drain() {
+            let _ = conn.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Server {
+    /// Serves the clients dialing `addr` with `Client::dial`.
+    ///
+    /// The same server can be added to a `Network` as well.
+    pub fn listen<A: ToSocketAddrs>(&self, addr: A) -> io::Result<Listener> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let worker = ThreadPool::new()?;
+        let core = Arc::new(ListenerCore {
+            stopped: AtomicBool::new(false),
+            conns: Mutex::new(HashMap::new()),
+            next_conn: AtomicUsize::new(0),
+        });
+
+        let server = self.clone();
+        let c = core.clone();
+        let acceptor = thread::Builder::new()
+            .name(format!("{}-listener", self.name()))
+            .spawn(move || accept(listener, server, worker, c))?;
+        Ok(Listener {
+            addr,
+            core,
+            acceptor: Some(acceptor),
+        })
+    }
+}
+
+fn accept(listener: TcpListener, server: Server, worker: ThreadPool, core: Arc<ListenerCore>) {
+    for stream in listener.incoming() {
+        if core.stopped.load(Ordering::Acquire) {
+            break;
+        }
+        let stream = match stream.and_then(|s| s.set_nodelay(true).map(|_| s)) {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("{:?} fails to accept: {}", server, e);
+                continue;
+            }
+        };
+        let (server, worker, core) = (server.clone(), worker.clone(), core.clone());
+        let res = thread::Builder::new()
+            .name(format!("{}-conn", server.name()))
+            .spawn(move || serve(stream, server, worker, core));
+        if let Err(e) = res {
+            error!("fail to spawn connection thread: {}", e);
+        }
+    }
+}
+
+fn serve(mut stream: TcpStream, server: Server, worker: ThreadPool, core: Arc<ListenerCore>) {
+    let conn_id = core.next_conn.fetch_add(1, Ordering::Relaxed);
+    let writer = match (stream.try_clone(), stream.try_clone()) {
+        (Ok(writer), Ok(conn)) => {
+            core.conns.lock().unwrap().insert(conn_id, conn);
+            writer
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            error!("{:?} fails to serve connection: {}", server, e);
+            return;
+        }
+    };
+    // Closes the connection if the listener stopped while registering it.
+    if core.stopped.load(Ordering::Acquire) {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let responder = thread::spawn(move || send_responses(writer, rx));
+    loop {
+        let request: Request = match read_frame(&mut stream) {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(e) => {
+                debug!("{:?} fails to receive request: {}", server, e);
+                break;
+            }
+        };
+        let Request { id, fq_name, req } = request;
+        let resp = server.dispatch(&fq_name, &req);
+        let tx = tx.clone();
+        worker.spawn_ok(async move {
+            let _ = tx.send(Response::new(id, resp.await));
+        });
+    }
+    // The responder sends the responses still running, then exits.
+    drop(tx);
+    let _ = responder.join();
+    let _ = stream.shutdown(Shutdown::Both);
+    core.conns.lock().unwrap().remove(&conn_id);
+}
+
+fn send_responses(mut stream: TcpStream, responses: mpsc::Receiver<Response>) {
+    for rsp in responses {
+        if let Err(e) = write_frame(&mut stream, &rsp) {
+            debug!("fail to send response: {}", e);
+            return;
+        }
+    }
+}
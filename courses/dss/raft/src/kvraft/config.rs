@@ -61,7 +61,8 @@ impl Config {
         };
         let cfg = Config {
             n,
-            net: labrpc::Network::new(),
+            // Real time and seeded, as in `raft::config`.
+            net: labrpc::Network::new_seeded(),
             servers: Mutex::new(servers),
             clerks: Mutex::new(HashMap::new()),
             // client ids start 1000 above the highest serverid,
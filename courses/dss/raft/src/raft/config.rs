@@ -96,7 +96,11 @@ impl Config {
     pub fn new_with(n: usize, unreliable: bool, snapshot: bool) -> Config {
         init_logger();
 
-        let net = labrpc::Network::new();
+        // Not `Network::new_simulated()`: Raft runs on its own threads and timers,
+        // and the tests wait with `thread::sleep`, while a simulated network only
+        // runs its futures within `Network::block_on`. The faults are still drawn
+        // from `LABRPC_SEED`, which a failing test prints.
+        let net = labrpc::Network::new_seeded();
         net.set_reliable(!unreliable);
         net.set_long_delays(true);
         let storage = Storage {
@@ -393,6 +397,9 @@ impl Config {
             let client = RaftClient::new(cli);
             clients.push(client);
             self.net.connect(name, &format!("{}", j));
+            // the partition decides whether it reaches server j.
+            self.net.place_client(name, &format!("{}", i));
+            self.net.enable(name, true);
         }
 
         let (tx, apply_ch) = unbounded();
@@ -487,17 +494,7 @@ impl Config {
         debug!("disconnect({})", i);
 
         self.connected[i] = false;
-
-        // outgoing ClientEnds
-        for endname in &*self.endnames[i] {
-            self.net.enable(endname, false);
-        }
-
-        // incoming ClientEnds
-        for names in &*self.endnames {
-            let endname = &names[i];
-            self.net.enable(endname, false);
-        }
+        self.partition();
     }
 
     /// attach server i to the net.
@@ -505,22 +502,21 @@ impl Config {
         debug!("connect({})", i);
 
         self.connected[i] = true;
+        self.partition();
+    }
 
-        // outgoing ClientEnds
-        for (j, connected) in self.connected.iter().enumerate() {
-            if *connected {
-                let endname = &*self.endnames[i][j];
-                self.net.enable(endname, true);
-            }
-        }
-
-        // incoming ClientEnds
-        for (j, connected) in self.connected.iter().enumerate() {
+    /// the connected servers reach each other, and each of the
+    /// others is on its own.
+    fn partition(&self) {
+        let mut groups = vec![vec![]];
+        for (i, connected) in self.connected.iter().enumerate() {
             if *connected {
-                let endname = &*self.endnames[j][i];
-                self.net.enable(endname, true);
+                groups[0].push(format!("{}", i));
+            } else {
+                groups.push(vec![format!("{}", i)]);
             }
         }
+        self.net.partition(&groups);
     }
 }
 
@@ -7,8 +7,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut client =
         Client::new(format!("{}{}", "127.0.0.1:", port)).expect("Failed to initialize the client!");
     trace!("client was initialised: {:?}", &client);
-    let buf = [0; 7];
-    let parsed_string = client.send::<7>(b"PING", buf)?;
-    info!("Parsed str: {}", &parsed_string);
+    let reply = client.ping()?;
+    info!("Parsed str: {}", &reply);
     Ok(())
 }
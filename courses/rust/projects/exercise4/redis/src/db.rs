@@ -0,0 +1,229 @@
+//! The keyspace shared by the threads serving clients, and its persistence.
+
+use crate::persistence::{self, Aof, Config, FsyncPolicy};
+use bytes::{Bytes, BytesMut};
+use log::{error, info};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub(crate) struct Entry {
+    pub(crate) value: Bytes,
+    pub(crate) expires_at: Option<Instant>,
+}
+
+/// The keys and values, where expired keys are removed when they are accessed.
+#[derive(Debug, Default)]
+pub(crate) struct Keyspace {
+    pub(crate) entries: HashMap<Bytes, Entry>,
+    aof: Option<Aof>,
+    /// The writes since the last snapshot.
+    changes: u64,
+}
+
+impl Keyspace {
+    pub(crate) fn get(&mut self, key: &Bytes) -> Option<&Entry> {
+        self.evict_expired(key);
+        self.entries.get(key)
+    }
+
+    /// Removes `key` and returns whether it existed.
+    pub(crate) fn remove(&mut self, key: &Bytes) -> bool {
+        self.evict_expired(key);
+        self.entries.remove(key).is_some()
+    }
+
+    fn evict_expired(&mut self, key: &Bytes) {
+        let expired = matches!(
+            self.entries.get(key),
+            Some(Entry { expires_at: Some(at), .. }) if *at <= Instant::now()
+        );
+        if expired {
+            self.entries.remove(key);
+        }
+    }
+
+    pub(crate) fn purge_expired(&mut self) {
+        let now = Instant::now();
+        self.entries
+            .retain(|_, entry| !matches!(entry.expires_at, Some(at) if at <= now));
+    }
+
+    /// Records a write command that changed `keys`, logging their new state to the
+    /// append-only file.
+    ///
+    /// The keyspace keeps the write if it cannot be logged, so the error tells the
+    /// client that it may be lost on restart.
+    pub(crate) fn record_write(&mut self, keys: &[Bytes]) -> io::Result<()> {
+        self.changes += 1;
+        if let Some(aof) = &mut self.aof {
+            let mut commands = BytesMut::new();
+            for key in keys {
+                persistence::write_key(key, self.entries.get(key), &mut commands);
+            }
+            if let Err(e) = aof.append(&commands) {
+                error!("Failed to append to the AOF: {}", e);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The keyspace shared by the threads serving clients.
+#[derive(Debug, Clone)]
+pub(crate) struct Db {
+    keyspace: Arc<Mutex<Keyspace>>,
+    config: Arc<Config>,
+    saving: Arc<AtomicBool>,
+}
+
+impl Db {
+    /// Loads the keyspace persisted in the data directory and starts the threads
+    /// flushing the append-only file and writing the periodic snapshots.
+    ///
+    /// The append-only file is preferred when it is enabled, since it is more up to
+    /// date than the snapshot.
+    pub(crate) fn open(config: Config) -> io::Result<Db> {
+        fs::create_dir_all(&config.dir)?;
+        let mut keyspace = Keyspace::default();
+        let aof_path = config.aof_path();
+        let loaded_aof = if config.appendonly {
+            persistence::load_aof(&aof_path)?
+        } else {
+            None
+        };
+        match loaded_aof {
+            Some(entries) => {
+                info!("Loaded {} keys from {}", entries.len(), aof_path.display());
+                keyspace.entries = entries;
+            }
+            None => {
+                let snapshot_path = config.snapshot_path();
+                if let Some(entries) = persistence::load_snapshot(&snapshot_path)? {
+                    info!(
+                        "Loaded {} keys from {}",
+                        entries.len(),
+                        snapshot_path.display()
+                    );
+                    keyspace.entries = entries;
+                }
+                if config.appendonly {
+                    // The new file starts from the snapshot instead of an empty keyspace.
+                    persistence::write_atomically(
+                        &aof_path,
+                        &persistence::dump(&keyspace.entries),
+                    )?;
+                }
+            }
+        }
+        if config.appendonly {
+            keyspace.aof = Some(Aof::open(aof_path, config.appendfsync)?);
+        }
+
+        let db = Db {
+            keyspace: Arc::new(Mutex::new(keyspace)),
+            config: Arc::new(config),
+            saving: Arc::new(AtomicBool::new(false)),
+        };
+        if db.config.appendonly && db.config.appendfsync == FsyncPolicy::EverySec {
+            let db = db.clone();
+            thread::spawn(move || loop {
+                thread::sleep(Duration::from_secs(1));
+                if let Some(aof) = &mut db.lock().aof {
+                    if let Err(e) = aof.sync() {
+                        error!("Failed to fsync the AOF: {}", e);
+                    }
+                }
+            });
+        }
+        if let Some(interval) = db.config.save_interval {
+            let db = db.clone();
+            thread::spawn(move || loop {
+                thread::sleep(interval);
+                if db.lock().changes > 0 {
+                    db.bgsave();
+                }
+            });
+        }
+        Ok(db)
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Keyspace> {
+        // A thread panicking while holding the lock leaves the keyspace usable.
+        self.keyspace
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Writes a snapshot of the keyspace, blocking the other clients meanwhile.
+    pub(crate) fn save(&self) -> io::Result<()> {
+        let mut keyspace = self.lock();
+        let contents = persistence::dump(&keyspace.entries);
+        persistence::write_atomically(&self.config.snapshot_path(), &contents)?;
+        keyspace.changes = 0;
+        Ok(())
+    }
+
+    /// Writes a snapshot of the keyspace from another thread, or returns false if a
+    /// background save is already running.
+    pub(crate) fn bgsave(&self) -> bool {
+        if self.saving.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        let (contents, changes) = {
+            let keyspace = self.lock();
+            (persistence::dump(&keyspace.entries), keyspace.changes)
+        };
+        let path = self.config.snapshot_path();
+        let db = self.clone();
+        thread::spawn(move || {
+            match persistence::write_atomically(&path, &contents) {
+                Ok(()) => {
+                    // Changes made during the write are not in the snapshot yet.
+                    let mut keyspace = db.lock();
+                    keyspace.changes = keyspace.changes.saturating_sub(changes);
+                    info!("Background save done");
+                }
+                Err(e) => error!("Background save failed: {}", e),
+            }
+            db.saving.store(false, Ordering::SeqCst);
+        });
+        true
+    }
+
+    /// Rewrites the append-only file from the keyspace in another thread, replacing
+    /// the history of every key with its current state.
+    pub(crate) fn bgrewriteaof(&self) -> Result<(), &'static str> {
+        let contents = {
+            let mut keyspace = self.lock();
+            let keyspace = &mut *keyspace;
+            let aof = keyspace.aof.as_mut().ok_or("ERR AOF is disabled")?;
+            if !aof.start_rewrite() {
+                return Err("ERR Background append only file rewriting already in progress");
+            }
+            persistence::dump(&keyspace.entries)
+        };
+        let path = self.config.aof_path().with_extension("rewrite");
+        let db = self.clone();
+        thread::spawn(move || {
+            let written = persistence::write_file(&path, &contents);
+            let mut keyspace = db.lock();
+            if let Some(aof) = &mut keyspace.aof {
+                match written.and_then(|()| aof.finish_rewrite(&path)) {
+                    Ok(()) => info!("Background AOF rewrite done"),
+                    Err(e) => {
+                        aof.abort_rewrite();
+                        error!("Background AOF rewrite failed: {}", e);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
-use log::{info, trace};
-use std::error::Error;
-use std::io::Read;
+use crate::error::{Error, Result};
+use crate::protocol::{Decoder, Frame, Version};
+use bytes::{Bytes, BytesMut};
+use log::trace;
 use std::io::Write;
 use std::net::{TcpStream, ToSocketAddrs};
 use std::time::Duration;
@@ -8,62 +9,167 @@ use std::time::Duration;
 #[derive(Debug)]
 pub struct Client {
     stream: TcpStream,
+    decoder: Decoder,
+    version: Version,
 }
 
 impl Client {
-    pub fn new<T: ToSocketAddrs>(to_socker_addrs: T) -> Result<Client, Box<dyn Error>> {
-        let tcpstream = TcpStream::connect(to_socker_addrs)?;
-        tcpstream.set_nonblocking(true)?;
-        let client = Self { stream: tcpstream };
-        client.set_read_timeout(Some(Duration::from_millis(200)))?;
-        Ok(client)
+    /// Connects to a server, speaking RESP2 until `hello` is called.
+    pub fn new<T: ToSocketAddrs>(to_socket_addrs: T) -> Result<Client> {
+        let stream = TcpStream::connect(to_socket_addrs)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            decoder: Decoder::new(),
+            version: Version::Resp2,
+        })
     }
 
     pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
         self.stream.set_read_timeout(timeout)
     }
 
-    pub fn send<const M: usize>(
-        &mut self,
-        bytes: &[u8],
-        mut buf: [u8; M],
-    ) -> Result<String, Box<dyn Error>> {
-        let crlf = [b'\r', b'\n'];
-        let bytes_trimmed = std::str::from_utf8(&bytes)
-            .unwrap()
-            .trim_end_matches(&['\r', '\n']);
-        trace!("About to send: {}", bytes_trimmed);
-        let bytes_appended = [bytes_trimmed.as_bytes(), &crlf].concat();
-        self.write(&bytes_appended).expect("Failed to write.");
-        self.flush().expect("Failed to flush the client!");
-        loop {
-            if let Err(e) = self.read(&mut buf) {
-                trace!("Error reading {:?}", e);
-            }
+    /// Returns the protocol version of the connection.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Switches the connection to `version` with the `HELLO` command.
+    ///
+    /// Returns the reply of the server, which describes the server and the connection.
+    pub fn hello(&mut self, version: Version) -> Result<Frame> {
+        let protover = match version {
+            Version::Resp2 => "2",
+            Version::Resp3 => "3",
+        };
+        let reply = self.request(&["HELLO", protover])?;
+        self.version = version;
+        Ok(reply)
+    }
 
-            if let Ok(()) = self.read_exact(&mut buf) {
-                info!("Read bytes!");
-                break;
+    pub fn ping(&mut self) -> Result<String> {
+        match self.request(&["PING"])? {
+            Frame::SimpleString(s) | Frame::BulkString(s) => {
+                Ok(String::from_utf8_lossy(&s).into_owned())
             }
+            frame => Err(Error::UnexpectedResponse(frame)),
         }
-        let parsed_str = std::str::from_utf8(&buf)?;
-        trace!("Parsed str: {parsed_str}");
-        Ok(parsed_str.to_string())
     }
-}
 
-impl Write for Client {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.stream.write(buf)
+    /// Returns the value of `key`, or `None` if it does not exist.
+    pub fn get(&mut self, key: impl AsRef<[u8]>) -> Result<Option<Bytes>> {
+        match self.request(&[&b"GET"[..], key.as_ref()])? {
+            Frame::BulkString(value) => Ok(Some(value)),
+            Frame::Null => Ok(None),
+            frame => Err(Error::UnexpectedResponse(frame)),
+        }
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.stream.flush()
+    pub fn set(&mut self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<()> {
+        match self.request(&[&b"SET"[..], key.as_ref(), value.as_ref()])? {
+            Frame::SimpleString(s) if s == "OK" => Ok(()),
+            frame => Err(Error::UnexpectedResponse(frame)),
+        }
+    }
+
+    /// Removes `keys` and returns how many of them existed.
+    pub fn del<K: AsRef<[u8]>>(&mut self, keys: &[K]) -> Result<i64> {
+        let mut args: Vec<&[u8]> = vec![&b"DEL"[..]];
+        args.extend(keys.iter().map(AsRef::as_ref));
+        self.integer(&args)
+    }
+
+    /// Returns how many of `keys` exist, counting repeated keys again.
+    pub fn exists<K: AsRef<[u8]>>(&mut self, keys: &[K]) -> Result<i64> {
+        let mut args: Vec<&[u8]> = vec![&b"EXISTS"[..]];
+        args.extend(keys.iter().map(AsRef::as_ref));
+        self.integer(&args)
+    }
+
+    /// Increments the integer value of `key` by one and returns the new value.
+    pub fn incr(&mut self, key: impl AsRef<[u8]>) -> Result<i64> {
+        self.integer(&[&b"INCR"[..], key.as_ref()])
+    }
+
+    /// Sets `key` to expire after `seconds`.
+    ///
+    /// Returns `false` if the key does not exist.
+    pub fn expire(&mut self, key: impl AsRef<[u8]>, seconds: u64) -> Result<bool> {
+        let seconds = seconds.to_string();
+        match self.request(&[&b"EXPIRE"[..], key.as_ref(), seconds.as_bytes()])? {
+            Frame::Integer(n) => Ok(n == 1),
+            Frame::Boolean(b) => Ok(b),
+            frame => Err(Error::UnexpectedResponse(frame)),
+        }
+    }
+
+    /// Returns the seconds until `key` expires, -1 if it does not expire or -2 if it
+    /// does not exist.
+    pub fn ttl(&mut self, key: impl AsRef<[u8]>) -> Result<i64> {
+        self.integer(&[&b"TTL"[..], key.as_ref()])
     }
-}
 
-impl Read for Client {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.stream.read(buf)
+    /// Returns the keys matching the glob-style `pattern`.
+    pub fn keys(&mut self, pattern: impl AsRef<[u8]>) -> Result<Vec<Bytes>> {
+        let items = match self.request(&[&b"KEYS"[..], pattern.as_ref()])? {
+            Frame::Array(items) | Frame::Set(items) => items,
+            frame => return Err(Error::UnexpectedResponse(frame)),
+        };
+        items
+            .into_iter()
+            .map(|item| match item {
+                Frame::BulkString(key) => Ok(key),
+                frame => Err(Error::UnexpectedResponse(frame)),
+            })
+            .collect()
+    }
+
+    /// Sends a command and waits for its reply.
+    ///
+    /// Error replies are returned as `Error::Server`.
+    pub fn request<A: AsRef<[u8]>>(&mut self, args: &[A]) -> Result<Frame> {
+        self.send(&Frame::command(args))?;
+        match self.read_reply()? {
+            Frame::Error(msg) | Frame::BulkError(msg) => Err(Error::Server(msg.to_string())),
+            frame => Ok(frame),
+        }
+    }
+
+    fn integer<A: AsRef<[u8]>>(&mut self, args: &[A]) -> Result<i64> {
+        match self.request(args)? {
+            Frame::Integer(n) => Ok(n),
+            frame => Err(Error::UnexpectedResponse(frame)),
+        }
+    }
+
+    fn send(&mut self, frame: &Frame) -> Result<()> {
+        trace!("About to send: {:?}", frame);
+        let mut buf = BytesMut::new();
+        frame.encode(self.version, &mut buf);
+        self.stream.write_all(&buf)?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    /// Reads the next reply, skipping the out-of-band push frames of RESP3.
+    fn read_reply(&mut self) -> Result<Frame> {
+        loop {
+            match self.read_frame()? {
+                Frame::Push(items) => trace!("Skipped push frame: {:?}", items),
+                frame => return Ok(frame),
+            }
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<Frame> {
+        loop {
+            if let Some(frame) = self.decoder.decode_frame()? {
+                trace!("Received: {:?}", frame);
+                return Ok(frame);
+            }
+            if self.decoder.read_from(&mut self.stream)? == 0 {
+                return Err(Error::ConnectionClosed);
+            }
+        }
     }
 }
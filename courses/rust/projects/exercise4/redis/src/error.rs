@@ -0,0 +1,54 @@
+use crate::protocol::{Frame, ProtocolError};
+use std::fmt;
+use std::io;
+
+/// Error returned by `Client`.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from or writing to the connection failed
+    Io(io::Error),
+    /// The server sent a malformed frame
+    Protocol(ProtocolError),
+    /// The server replied with an error
+    Server(String),
+    /// The server replied with a frame of an unexpected type
+    UnexpectedResponse(Frame),
+    /// The server closed the connection
+    ConnectionClosed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Protocol(e) => write!(f, "{}", e),
+            Error::Server(msg) => write!(f, "{}", msg),
+            Error::UnexpectedResponse(frame) => write!(f, "Unexpected response: {:?}", frame),
+            Error::ConnectionClosed => write!(f, "Connection closed by the server"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Protocol(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<ProtocolError> for Error {
+    fn from(err: ProtocolError) -> Self {
+        Error::Protocol(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,5 +1,13 @@
+mod db;
 mod domain;
-mod protocol;
+mod error;
+mod persistence;
+pub mod protocol;
+mod server;
 mod util;
 pub use crate::domain::Client;
+pub use crate::error::{Error, Result};
+pub use crate::persistence::{Config, FsyncPolicy};
+pub use crate::protocol::ProtocolError;
+pub use crate::server::serve;
 pub use crate::util::init_logger;
@@ -0,0 +1,403 @@
+//! Persistence of the keyspace in an append-only file and in snapshots.
+//!
+//! Both files are made of RESP commands: `SET key value`, `PEXPIREAT key
+//! unix-time-milliseconds` and `DEL key...`. A snapshot holds the commands
+//! recreating the keyspace at one point in time, and the append-only file logs the
+//! new state of the keys after every write, so replaying either rebuilds the
+//! keyspace. Expiry times are stored as wall-clock times to survive restarts.
+
+use crate::db::Entry;
+use crate::protocol::{self, Frame, Version};
+use bytes::{Bytes, BytesMut};
+use log::warn;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const AOF_FILE_NAME: &str = "appendonly.aof";
+const SNAPSHOT_FILE_NAME: &str = "dump.resp";
+
+/// When the appended commands are flushed to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FsyncPolicy {
+    /// After every write command, so no acknowledged write is lost.
+    Always,
+    /// Once per second, from a background thread.
+    #[default]
+    EverySec,
+    /// Never explicitly, leaving it to the operating system.
+    No,
+}
+
+impl FromStr for FsyncPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "always" => Ok(FsyncPolicy::Always),
+            "everysec" => Ok(FsyncPolicy::EverySec),
+            "no" => Ok(FsyncPolicy::No),
+            _ => Err(format!(
+                "invalid fsync policy '{}', expected always, everysec or no",
+                s
+            )),
+        }
+    }
+}
+
+/// Where and how the server persists its keyspace.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The directory holding the append-only file and the snapshot.
+    pub dir: PathBuf,
+    /// Whether the write commands are logged to the append-only file.
+    pub appendonly: bool,
+    pub appendfsync: FsyncPolicy,
+    /// How often a snapshot is written while the keyspace changes, if at all.
+    pub save_interval: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            dir: PathBuf::from("."),
+            appendonly: false,
+            appendfsync: FsyncPolicy::default(),
+            save_interval: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration from the environment:
+    ///
+    /// - `REDIS_DIR`: the data directory, the current one by default
+    /// - `REDIS_APPENDONLY`: `yes` to enable the append-only file
+    /// - `REDIS_APPENDFSYNC`: `always`, `everysec` (the default) or `no`
+    /// - `REDIS_SAVE`: the seconds between snapshots, `0` (the default) to disable them
+    pub fn from_env() -> io::Result<Config> {
+        let mut config = Config::default();
+        if let Ok(dir) = env::var("REDIS_DIR") {
+            config.dir = PathBuf::from(dir);
+        }
+        if let Ok(appendonly) = env::var("REDIS_APPENDONLY") {
+            config.appendonly = match appendonly.to_ascii_lowercase().as_str() {
+                "yes" => true,
+                "no" => false,
+                _ => {
+                    return Err(invalid_input(format!(
+                        "invalid REDIS_APPENDONLY '{}', expected yes or no",
+                        appendonly
+                    )))
+                }
+            };
+        }
+        if let Ok(policy) = env::var("REDIS_APPENDFSYNC") {
+            config.appendfsync = policy.parse().map_err(invalid_input)?;
+        }
+        if let Ok(seconds) = env::var("REDIS_SAVE") {
+            let seconds: u64 = seconds
+                .parse()
+                .map_err(|_| invalid_input(format!("invalid REDIS_SAVE '{}'", seconds)))?;
+            config.save_interval = Some(Duration::from_secs(seconds)).filter(|i| !i.is_zero());
+        }
+        Ok(config)
+    }
+
+    pub fn aof_path(&self) -> PathBuf {
+        self.dir.join(AOF_FILE_NAME)
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.dir.join(SNAPSHOT_FILE_NAME)
+    }
+}
+
+/// The append-only file.
+#[derive(Debug)]
+pub(crate) struct Aof {
+    file: File,
+    path: PathBuf,
+    policy: FsyncPolicy,
+    unsynced: bool,
+    /// The length of the complete commands in the file.
+    len: u64,
+    /// Whether a failed append could not remove its partial command from the file.
+    torn: bool,
+    /// The commands appended since the running rewrite took its snapshot.
+    rewrite_buffer: Option<BytesMut>,
+}
+
+impl Aof {
+    pub(crate) fn open(path: PathBuf, policy: FsyncPolicy) -> io::Result<Aof> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Aof {
+            file,
+            path,
+            policy,
+            unsynced: false,
+            len,
+            torn: false,
+            rewrite_buffer: None,
+        })
+    }
+
+    /// Appends `commands` to the file.
+    ///
+    /// If writing fails, the file is cut back to the commands before them, since a
+    /// partial command followed by later ones would stop the file from loading.
+    pub(crate) fn append(&mut self, commands: &[u8]) -> io::Result<()> {
+        if self.torn {
+            self.file.set_len(self.len)?;
+            self.torn = false;
+        }
+        if let Err(e) = self.file.write_all(commands) {
+            self.torn = self.file.set_len(self.len).is_err();
+            return Err(e);
+        }
+        self.len += commands.len() as u64;
+        if let Some(buffer) = &mut self.rewrite_buffer {
+            buffer.extend_from_slice(commands);
+        }
+        match self.policy {
+            FsyncPolicy::Always => self.file.sync_data(),
+            FsyncPolicy::EverySec | FsyncPolicy::No => {
+                self.unsynced = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Flushes the commands appended since the last call to disk.
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        if self.unsynced {
+            self.file.sync_data()?;
+            self.unsynced = false;
+        }
+        Ok(())
+    }
+
+    /// Starts buffering the appended commands for a rewrite, or returns false if a
+    /// rewrite is already running.
+    pub(crate) fn start_rewrite(&mut self) -> bool {
+        if self.rewrite_buffer.is_some() {
+            return false;
+        }
+        self.rewrite_buffer = Some(BytesMut::new());
+        true
+    }
+
+    /// Replaces the file with `rewritten`, after appending to it the commands
+    /// buffered since the rewrite started.
+    pub(crate) fn finish_rewrite(&mut self, rewritten: &Path) -> io::Result<()> {
+        let buffer = self.rewrite_buffer.take().unwrap_or_default();
+        let mut file = OpenOptions::new().append(true).open(rewritten)?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+        fs::rename(rewritten, &self.path)?;
+        self.len = file.metadata()?.len();
+        self.file = file;
+        self.unsynced = false;
+        self.torn = false;
+        Ok(())
+    }
+
+    pub(crate) fn abort_rewrite(&mut self) {
+        self.rewrite_buffer = None;
+    }
+}
+
+/// Appends the commands setting `key` to `entry`, or deleting it if there is none.
+pub(crate) fn write_key(key: &Bytes, entry: Option<&Entry>, dst: &mut BytesMut) {
+    let Some(entry) = entry else {
+        Frame::command(&[&b"DEL"[..], &key[..]]).encode(Version::Resp2, dst);
+        return;
+    };
+    Frame::command(&[&b"SET"[..], &key[..], &entry.value[..]]).encode(Version::Resp2, dst);
+    if let Some(at) = entry.expires_at {
+        let millis = unix_millis(at).to_string();
+        Frame::command(&[&b"PEXPIREAT"[..], &key[..], millis.as_bytes()])
+            .encode(Version::Resp2, dst);
+    }
+}
+
+/// Returns the commands recreating `entries`, leaving out the expired ones.
+pub(crate) fn dump(entries: &HashMap<Bytes, Entry>) -> BytesMut {
+    let now = Instant::now();
+    let mut dst = BytesMut::new();
+    for (key, entry) in entries {
+        if !matches!(entry.expires_at, Some(at) if at <= now) {
+            write_key(key, Some(entry), &mut dst);
+        }
+    }
+    dst
+}
+
+/// Writes `contents` to `path` through a temporary file renamed over it, so that a
+/// crash never leaves a partially written file behind.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    write_file(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+/// Creates `path` with `contents` and flushes it to disk.
+pub(crate) fn write_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Reads the keyspace saved in the snapshot at `path`, if it exists.
+pub(crate) fn load_snapshot(path: &Path) -> io::Result<Option<HashMap<Bytes, Entry>>> {
+    let data = match read_existing(path)? {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+    let (entries, len) = replay(path, &data)?;
+    if len < data.len() {
+        return Err(invalid_data(format!("{} is truncated", path.display())));
+    }
+    Ok(Some(entries))
+}
+
+/// Reads the keyspace logged in the append-only file at `path`, if it exists.
+///
+/// A command cut short by a crash while it was appended is removed from the file.
+pub(crate) fn load_aof(path: &Path) -> io::Result<Option<HashMap<Bytes, Entry>>> {
+    let data = match read_existing(path)? {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+    let (entries, len) = replay(path, &data)?;
+    if len < data.len() {
+        warn!(
+            "Removing {} bytes of an incomplete command at the end of {}",
+            data.len() - len,
+            path.display()
+        );
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(len as u64)?;
+    }
+    Ok(Some(entries))
+}
+
+fn read_existing(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Applies the commands of `data` to an empty keyspace, and returns it with the
+/// length of the complete commands.
+fn replay(path: &Path, data: &[u8]) -> io::Result<(HashMap<Bytes, Entry>, usize)> {
+    let mut entries = HashMap::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let invalid =
+            |msg: String| invalid_data(format!("{} at byte {}: {}", path.display(), pos, msg));
+        let (frame, len) = match protocol::decode(&data[pos..]) {
+            Ok(Some(decoded)) => decoded,
+            Ok(None) => break,
+            Err(e) => return Err(invalid(e.to_string())),
+        };
+        apply(&mut entries, frame).map_err(invalid)?;
+        pos += len;
+    }
+    Ok((entries, pos))
+}
+
+fn apply(entries: &mut HashMap<Bytes, Entry>, frame: Frame) -> Result<(), String> {
+    let args = match frame {
+        Frame::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Frame::BulkString(arg) => Ok(arg),
+                item => Err(format!("unexpected {:?} argument", item.kind())),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        frame => {
+            return Err(format!(
+                "unexpected {:?} instead of a command",
+                frame.kind()
+            ))
+        }
+    };
+    let name = args.first().map(|name| name.to_ascii_uppercase());
+    match (name.as_deref(), args.len()) {
+        (Some(b"SET"), 3) => {
+            let entry = Entry {
+                value: args[2].clone(),
+                expires_at: None,
+            };
+            entries.insert(args[1].clone(), entry);
+        }
+        (Some(b"PEXPIREAT"), 3) => {
+            let millis = std::str::from_utf8(&args[2])
+                .ok()
+                .and_then(|millis| millis.parse().ok())
+                .ok_or("invalid expiry time")?;
+            match instant_at(millis) {
+                Some(at) => {
+                    if let Some(entry) = entries.get_mut(&args[1]) {
+                        entry.expires_at = Some(at);
+                    }
+                }
+                None => {
+                    entries.remove(&args[1]);
+                }
+            }
+        }
+        (Some(b"DEL"), len) if len >= 2 => {
+            for key in &args[1..] {
+                entries.remove(key);
+            }
+        }
+        _ => {
+            let command: Vec<_> = args
+                .iter()
+                .map(|arg| String::from_utf8_lossy(arg))
+                .collect();
+            return Err(format!("unexpected command {:?}", command));
+        }
+    }
+    Ok(())
+}
+
+/// Converts `at` to milliseconds since the Unix epoch.
+pub(crate) fn unix_millis(at: Instant) -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    (now + at.saturating_duration_since(Instant::now())).as_millis() as i64
+}
+
+/// Converts milliseconds since the Unix epoch to an instant, or `None` if that
+/// time has passed.
+pub(crate) fn instant_at(millis: i64) -> Option<Instant> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let remaining = Duration::from_millis(u64::try_from(millis).ok()?).checked_sub(now)?;
+    if remaining.is_zero() {
+        return None;
+    }
+    Some(Instant::now() + remaining)
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
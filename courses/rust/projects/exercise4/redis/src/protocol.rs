@@ -1,24 +1,16 @@
-mod types {
-    use bytes::Bytes;
-    use bytes_utils::Str;
+//! Encoding and decoding of RESP2 and RESP3 frames.
+//!
+//! The frames and their parser are the ones of `redis_protocol`, which the server
+//! shares with the exercise 5 server. `decode` and `Decoder::decode_frame` return
+//! `None` until the buffer holds a complete frame, so data can be fed to a `Decoder`
+//! as it arrives from a socket.
 
-    #[derive(Debug)]
-    pub(crate) enum FrameKind {
-        SimpleString,
-        Error,
-        Integer,
-        BulkString,
-        Array,
-        Null,
-    }
+pub use redis_protocol::{Decoder, Frame, FrameKind, Limits, ProtocolError, Version};
 
-    #[derive(Debug)]
-    pub(crate) enum Frame {
-        SimpleString(Bytes),
-        Error(Str),
-        Integer(i64),
-        BulkString(Bytes),
-        Array(Vec<Frame>),
-        Null,
-    }
+/// Decodes the frame at the beginning of `src` with the default limits.
+///
+/// Returns the frame and the number of bytes it takes, or `None` if `src` does not
+/// hold a complete frame yet.
+pub fn decode(src: &[u8]) -> Result<Option<(Frame, usize)>, ProtocolError> {
+    redis_protocol::decode_frame(src, &Limits::default())
 }
@@ -0,0 +1,319 @@
+//! An in-memory server speaking the redis protocol.
+//!
+//! Every client is served on its own thread, and all of them share one keyspace.
+
+use crate::db::{Db, Entry};
+use crate::persistence::{self, Config};
+use crate::protocol::{Decoder, Frame, Version};
+use bytes::{Bytes, BytesMut};
+use log::{info, trace, warn};
+use redis_protocol::glob_match;
+use std::io::{self, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Loads the keyspace persisted as `config` describes, then accepts clients on
+/// `listener` until accepting fails.
+pub fn serve(listener: TcpListener, config: Config) -> io::Result<()> {
+    let db = Db::open(config)?;
+    info!("Listening on {}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let db = db.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_client(stream, &db) {
+                warn!("Connection failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle_client(mut stream: TcpStream, db: &Db) -> io::Result<()> {
+    trace!("Recieved client connection from stream: {:?}", stream);
+    stream.set_nodelay(true)?;
+    let mut decoder = Decoder::new();
+    let mut version = Version::Resp2;
+    let mut out = BytesMut::new();
+    loop {
+        let frame = match decoder.decode_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                // Pipelined commands are answered with a single write once the
+                // received bytes are used up.
+                if !out.is_empty() {
+                    stream.write_all(&out)?;
+                    out.clear();
+                }
+                if decoder.read_from(&mut stream)? == 0 {
+                    trace!("Client closed the connection");
+                    return Ok(());
+                }
+                continue;
+            }
+            Err(e) => {
+                // The rest of the buffer cannot be split into frames anymore.
+                warn!("{}", e);
+                error(format!("ERR {}", e)).encode(version, &mut out);
+                return stream.write_all(&out);
+            }
+        };
+        trace!("Received: {:?}", frame);
+        let reply = match parse_command(frame) {
+            Ok(args) => execute(db, &mut version, &args),
+            Err(reply) => reply,
+        };
+        reply.encode(version, &mut out);
+    }
+}
+
+/// Splits a command into its arguments, or returns the error reply.
+fn parse_command(frame: Frame) -> Result<Vec<Bytes>, Frame> {
+    let items = match frame {
+        Frame::Array(items) if !items.is_empty() => items,
+        _ => {
+            return Err(error(
+                "ERR Protocol error: expected an array of bulk strings",
+            ))
+        }
+    };
+    items
+        .into_iter()
+        .map(|item| match item {
+            Frame::BulkString(arg) => Ok(arg),
+            _ => Err(error(
+                "ERR Protocol error: expected an array of bulk strings",
+            )),
+        })
+        .collect()
+}
+
+fn execute(db: &Db, version: &mut Version, args: &[Bytes]) -> Frame {
+    let name = String::from_utf8_lossy(&args[0]).to_lowercase();
+    let arity_ok = match name.as_str() {
+        "ping" | "hello" => args.len() <= 2,
+        "get" | "incr" | "ttl" | "keys" => args.len() == 2,
+        "set" => args.len() >= 3,
+        "expire" | "pexpireat" => args.len() == 3,
+        "del" | "exists" => args.len() >= 2,
+        "save" | "bgsave" | "bgrewriteaof" => args.len() == 1,
+        _ => return error(format!("ERR unknown command '{}'", name)),
+    };
+    if !arity_ok {
+        return error(format!(
+            "ERR wrong number of arguments for '{}' command",
+            name
+        ));
+    }
+
+    match name.as_str() {
+        "ping" => match args.get(1) {
+            Some(message) => Frame::BulkString(message.clone()),
+            None => Frame::SimpleString(Bytes::from_static(b"PONG")),
+        },
+        "hello" => {
+            let protover = match args.get(1).map(|arg| &arg[..]) {
+                None => *version,
+                Some(b"2") => Version::Resp2,
+                Some(b"3") => Version::Resp3,
+                Some(_) => return error("NOPROTO unsupported protocol version"),
+            };
+            *version = protover;
+            hello(protover)
+        }
+        "get" => match db.lock().get(&args[1]) {
+            Some(entry) => Frame::BulkString(entry.value.clone()),
+            None => Frame::Null,
+        },
+        "set" => set(db, args),
+        "del" => {
+            let mut keyspace = db.lock();
+            let removed: Vec<Bytes> = args[1..]
+                .iter()
+                .filter(|key| keyspace.remove(key))
+                .cloned()
+                .collect();
+            let written = if removed.is_empty() {
+                Ok(())
+            } else {
+                keyspace.record_write(&removed)
+            };
+            logged(written, Frame::Integer(removed.len() as i64))
+        }
+        "exists" => {
+            let mut keyspace = db.lock();
+            let found = args[1..]
+                .iter()
+                .filter(|key| keyspace.get(key).is_some())
+                .count();
+            Frame::Integer(found as i64)
+        }
+        "incr" => {
+            let mut keyspace = db.lock();
+            let (current, expires_at) = match keyspace.get(&args[1]) {
+                Some(entry) => match parse_int(&entry.value) {
+                    Some(n) => (n, entry.expires_at),
+                    None => return error("ERR value is not an integer or out of range"),
+                },
+                None => (0, None),
+            };
+            let next = match current.checked_add(1) {
+                Some(next) => next,
+                None => return error("ERR increment or decrement would overflow"),
+            };
+            keyspace.entries.insert(
+                args[1].clone(),
+                Entry {
+                    value: Bytes::from(next.to_string()),
+                    expires_at,
+                },
+            );
+            logged(keyspace.record_write(&args[1..2]), Frame::Integer(next))
+        }
+        "expire" => match parse_int(&args[2]) {
+            Some(seconds) if seconds <= 0 => expire(db, &args[1], None),
+            Some(seconds) => {
+                match Instant::now().checked_add(Duration::from_secs(seconds as u64)) {
+                    Some(at) => expire(db, &args[1], Some(at)),
+                    None => error("ERR invalid expire time in 'expire' command"),
+                }
+            }
+            None => error("ERR value is not an integer or out of range"),
+        },
+        "pexpireat" => match parse_int(&args[2]) {
+            Some(millis) => expire(db, &args[1], persistence::instant_at(millis)),
+            None => error("ERR value is not an integer or out of range"),
+        },
+        "ttl" => match db.lock().get(&args[1]) {
+            None => Frame::Integer(-2),
+            Some(Entry {
+                expires_at: None, ..
+            }) => Frame::Integer(-1),
+            Some(Entry {
+                expires_at: Some(at),
+                ..
+            }) => {
+                // Rounded like redis, so a key set to expire in 10 seconds reports 10.
+                let millis = at.saturating_duration_since(Instant::now()).as_millis();
+                Frame::Integer(((millis + 500) / 1000) as i64)
+            }
+        },
+        "keys" => {
+            let mut keyspace = db.lock();
+            keyspace.purge_expired();
+            let keys = keyspace
+                .entries
+                .keys()
+                .filter(|key| glob_match(&args[1], key))
+                .map(|key| Frame::BulkString(key.clone()))
+                .collect();
+            Frame::Array(keys)
+        }
+        "save" => match db.save() {
+            Ok(()) => Frame::SimpleString(Bytes::from_static(b"OK")),
+            Err(e) => error(format!("ERR {}", e)),
+        },
+        "bgsave" => {
+            if db.bgsave() {
+                Frame::SimpleString(Bytes::from_static(b"Background saving started"))
+            } else {
+                error("ERR Background save already in progress")
+            }
+        }
+        "bgrewriteaof" => match db.bgrewriteaof() {
+            Ok(()) => Frame::SimpleString(Bytes::from_static(
+                b"Background append only file rewriting started",
+            )),
+            Err(msg) => error(msg),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// `SET key value [EX seconds|PX milliseconds]`
+fn set(db: &Db, args: &[Bytes]) -> Frame {
+    let mut ttl = None;
+    let mut options = args[3..].iter();
+    while let Some(option) = options.next() {
+        let unit = match option.to_ascii_uppercase().as_slice() {
+            b"EX" if ttl.is_none() => Duration::from_secs(1),
+            b"PX" if ttl.is_none() => Duration::from_millis(1),
+            _ => return error("ERR syntax error"),
+        };
+        let amount = match options.next().map(|amount| parse_int(amount)) {
+            Some(Some(amount)) if amount > 0 => amount,
+            Some(Some(_)) => return error("ERR invalid expire time in 'set' command"),
+            Some(None) => return error("ERR value is not an integer or out of range"),
+            None => return error("ERR syntax error"),
+        };
+        ttl = Some(unit * amount.min(u32::MAX as i64) as u32);
+    }
+    let mut keyspace = db.lock();
+    keyspace.entries.insert(
+        args[1].clone(),
+        Entry {
+            value: args[2].clone(),
+            expires_at: ttl.map(|ttl| Instant::now() + ttl),
+        },
+    );
+    logged(
+        keyspace.record_write(&args[1..2]),
+        Frame::SimpleString(Bytes::from_static(b"OK")),
+    )
+}
+
+/// Makes `key` expire at `at`, or deletes it right away if `at` is `None`.
+fn expire(db: &Db, key: &Bytes, at: Option<Instant>) -> Frame {
+    let mut keyspace = db.lock();
+    if keyspace.get(key).is_none() {
+        return Frame::Integer(0);
+    }
+    match at {
+        None => {
+            keyspace.remove(key);
+        }
+        Some(at) => {
+            if let Some(entry) = keyspace.entries.get_mut(key) {
+                entry.expires_at = Some(at);
+            }
+        }
+    }
+    logged(
+        keyspace.record_write(std::slice::from_ref(key)),
+        Frame::Integer(1),
+    )
+}
+
+/// Returns `reply`, or the error of redis if the write could not be logged to the
+/// append-only file.
+fn logged(written: io::Result<()>, reply: Frame) -> Frame {
+    match written {
+        Ok(()) => reply,
+        Err(e) => error(format!("MISCONF Errors writing to the AOF file: {}", e)),
+    }
+}
+
+fn hello(version: Version) -> Frame {
+    let proto = match version {
+        Version::Resp2 => 2,
+        Version::Resp3 => 3,
+    };
+    let field = |name: &'static str| Frame::BulkString(Bytes::from_static(name.as_bytes()));
+    Frame::Map(vec![
+        (field("server"), field("redis-simple")),
+        (field("version"), field(env!("CARGO_PKG_VERSION"))),
+        (field("proto"), Frame::Integer(proto)),
+        (field("mode"), field("standalone")),
+        (field("role"), field("master")),
+        (field("modules"), Frame::Array(Vec::new())),
+    ])
+}
+
+fn error(msg: impl Into<String>) -> Frame {
+    Frame::Error(msg.into().into())
+}
+
+fn parse_int(value: &[u8]) -> Option<i64> {
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
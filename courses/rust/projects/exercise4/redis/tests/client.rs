@@ -0,0 +1,146 @@
+use bytes::{Bytes, BytesMut};
+use simple_redis::protocol::{Decoder, Frame, Version};
+use simple_redis::{Client, Error};
+use std::io::Write;
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+mod common;
+
+use common::start_server;
+
+// Should run the typed commands against the server of the exercise
+#[test]
+fn typed_commands() {
+    let (_server, mut client) = start_server(None, &[]);
+
+    assert_eq!(client.ping().unwrap(), "PONG");
+    assert_eq!(client.get("key").unwrap(), None);
+    client.set("key", "value").unwrap();
+    assert_eq!(
+        client.get("key").unwrap(),
+        Some(Bytes::from_static(b"value"))
+    );
+
+    assert_eq!(client.incr("counter").unwrap(), 1);
+    assert_eq!(client.incr("counter").unwrap(), 2);
+    assert!(matches!(client.incr("key"), Err(Error::Server(_))));
+
+    assert!(client.expire("counter", 100).unwrap());
+    assert!(!client.expire("missing", 100).unwrap());
+    assert_eq!(client.del(&["key", "counter", "missing"]).unwrap(), 2);
+    assert_eq!(client.get("counter").unwrap(), None);
+
+    assert!(matches!(
+        client.request(&["NOSUCHCOMMAND"]),
+        Err(Error::Server(_))
+    ));
+}
+
+// Should switch the connection to RESP3 with HELLO
+#[test]
+fn hello_resp3() {
+    let (_server, mut client) = start_server(None, &[]);
+
+    let reply = client.hello(Version::Resp3).unwrap();
+    assert!(matches!(reply, Frame::Map(_)));
+    assert_eq!(client.version(), Version::Resp3);
+    assert_eq!(client.get("key").unwrap(), None);
+    assert!(!client.expire("key", 1).unwrap());
+    client.set("key", "value").unwrap();
+    assert_eq!(
+        client.get("key").unwrap(),
+        Some(Bytes::from_static(b"value"))
+    );
+}
+
+// Should report existence, expiry and matching keys
+#[test]
+fn keyspace_commands() {
+    let (_server, mut client) = start_server(None, &[]);
+
+    client.set("user:1", "a").unwrap();
+    client.set("user:2", "b").unwrap();
+    client.set("session", "c").unwrap();
+    assert_eq!(client.exists(&["user:1", "user:1", "missing"]).unwrap(), 2);
+
+    assert_eq!(client.ttl("missing").unwrap(), -2);
+    assert_eq!(client.ttl("session").unwrap(), -1);
+    assert!(client.expire("session", 100).unwrap());
+    assert_eq!(client.ttl("session").unwrap(), 100);
+    assert!(matches!(
+        client.expire("session", i64::MAX as u64),
+        Err(Error::Server(msg)) if msg == "ERR invalid expire time in 'expire' command"
+    ));
+    assert_eq!(client.ttl("session").unwrap(), 100);
+    client.request(&["SET", "short", "d", "PX", "50"]).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(client.get("short").unwrap(), None);
+
+    let mut keys = client.keys("user:*").unwrap();
+    keys.sort();
+    assert_eq!(keys, vec![Bytes::from("user:1"), Bytes::from("user:2")]);
+    assert_eq!(
+        client.keys("user:[^1]").unwrap(),
+        vec![Bytes::from("user:2")]
+    );
+    assert_eq!(client.keys("s?ss*").unwrap(), vec![Bytes::from("session")]);
+    assert_eq!(client.keys("*").unwrap().len(), 3);
+}
+
+// Should answer pipelined commands in order
+#[test]
+fn pipelining() {
+    let (server, _client) = start_server(None, &[]);
+
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+    let mut requests = BytesMut::new();
+    let commands: [&[&str]; 4] = [&["SET", "k", "1"], &["INCR", "k"], &["GET", "k"], &["PING"]];
+    for args in commands {
+        Frame::command(args).encode(Version::Resp2, &mut requests);
+    }
+    stream.write_all(&requests).unwrap();
+
+    let mut decoder = Decoder::new();
+    let mut replies = Vec::new();
+    while replies.len() < 4 {
+        match decoder.decode_frame().unwrap() {
+            Some(frame) => replies.push(frame),
+            None => assert_ne!(decoder.read_from(&mut stream).unwrap(), 0),
+        }
+    }
+    assert_eq!(
+        replies,
+        vec![
+            Frame::SimpleString(Bytes::from("OK")),
+            Frame::Integer(2),
+            Frame::BulkString(Bytes::from("2")),
+            Frame::SimpleString(Bytes::from("PONG")),
+        ]
+    );
+}
+
+// Should serve clients concurrently on one keyspace
+#[test]
+fn concurrent_clients() {
+    let (server, mut idle) = start_server(None, &[]);
+    // A connected client that sends nothing must not block the others.
+    idle.set("idle", "yes").unwrap();
+
+    let port = server.port();
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            thread::spawn(move || {
+                let mut client = Client::new(("127.0.0.1", port)).unwrap();
+                for _ in 0..100 {
+                    client.incr("counter").unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(idle.get("counter").unwrap(), Some(Bytes::from("800")));
+}
@@ -0,0 +1,59 @@
+use simple_redis::Client;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Child, Command};
+use std::thread;
+use std::time::Duration;
+
+// Kills the server when a test finishes, even if it panics.
+pub struct Server {
+    child: Child,
+    port: u16,
+}
+
+impl Server {
+    // Not every test file connects more clients.
+    #[allow(dead_code)]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Starts a server keeping its files in `dir`, if any, with the extra variables of
+// `env`, and connects a client to it.
+pub fn start_server(dir: Option<&Path>, env: &[(&str, &str)]) -> (Server, Client) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_redis-simple-server"));
+    command.env("REDIS_PORT", port.to_string());
+    if let Some(dir) = dir {
+        command.env("REDIS_DIR", dir);
+    }
+    let child = command
+        .envs(env.iter().copied())
+        // The server writes its log to tmp/ in its current directory.
+        .current_dir(std::env::temp_dir())
+        .spawn()
+        .unwrap();
+    let server = Server { child, port };
+    for _ in 0..100 {
+        if let Ok(client) = Client::new(("127.0.0.1", port)) {
+            client
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            return (server, client);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("Failed to connect to the server");
+}
@@ -0,0 +1,162 @@
+use bytes::Bytes;
+use simple_redis::protocol::Frame;
+use simple_redis::Config;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod common;
+
+use common::start_server;
+
+// Returns an empty data directory for the servers of one test.
+fn data_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("redis-simple-{}-{}", test, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn wait_until(mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "Timed out");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+const APPENDONLY: [(&str, &str); 2] =
+    [("REDIS_APPENDONLY", "yes"), ("REDIS_APPENDFSYNC", "always")];
+
+// Should replay the append-only file when the server restarts
+#[test]
+fn aof_restart() {
+    let dir = data_dir("aof_restart");
+    {
+        let (_server, mut client) = start_server(Some(&dir), &APPENDONLY);
+        client.set("key", "value").unwrap();
+        for _ in 0..3 {
+            client.incr("counter").unwrap();
+        }
+        assert!(client.expire("counter", 100).unwrap());
+        client.request(&["SET", "short", "v", "PX", "50"]).unwrap();
+        client.set("deleted", "v").unwrap();
+        client.del(&["deleted"]).unwrap();
+    }
+    thread::sleep(Duration::from_millis(100));
+
+    let (_server, mut client) = start_server(Some(&dir), &APPENDONLY);
+    assert_eq!(client.get("key").unwrap(), Some(Bytes::from("value")));
+    assert_eq!(client.get("counter").unwrap(), Some(Bytes::from("3")));
+    assert!((99..=100).contains(&client.ttl("counter").unwrap()));
+    assert_eq!(client.get("short").unwrap(), None);
+    assert_eq!(client.get("deleted").unwrap(), None);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Should drop a command cut short at the end of the append-only file
+#[test]
+fn aof_truncated() {
+    let dir = data_dir("aof_truncated");
+    let aof_path = Config {
+        dir: dir.clone(),
+        ..Config::default()
+    }
+    .aof_path();
+    {
+        let (_server, mut client) = start_server(Some(&dir), &APPENDONLY);
+        client.set("key", "value").unwrap();
+    }
+    let mut aof = OpenOptions::new().append(true).open(&aof_path).unwrap();
+    aof.write_all(b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nva")
+        .unwrap();
+
+    let (server, mut client) = start_server(Some(&dir), &APPENDONLY);
+    assert_eq!(client.get("key").unwrap(), Some(Bytes::from("value")));
+    client.set("other", "value").unwrap();
+    assert_eq!(client.keys("*").unwrap().len(), 2);
+    drop(server);
+
+    let (_server, mut client) = start_server(Some(&dir), &APPENDONLY);
+    assert_eq!(client.get("other").unwrap(), Some(Bytes::from("value")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Should compact the append-only file without losing writes
+#[test]
+fn aof_rewrite() {
+    let dir = data_dir("aof_rewrite");
+    let aof_path = Config {
+        dir: dir.clone(),
+        ..Config::default()
+    }
+    .aof_path();
+    {
+        let (_server, mut client) = start_server(Some(&dir), &APPENDONLY);
+        for _ in 0..100 {
+            client.incr("counter").unwrap();
+        }
+        let size = fs::metadata(&aof_path).unwrap().len();
+        assert_eq!(
+            client.request(&["BGREWRITEAOF"]).unwrap(),
+            Frame::SimpleString(Bytes::from("Background append only file rewriting started"))
+        );
+        client.set("after", "rewrite").unwrap();
+        wait_until(|| fs::metadata(&aof_path).unwrap().len() < size / 10);
+    }
+
+    let (_server, mut client) = start_server(Some(&dir), &APPENDONLY);
+    assert_eq!(client.get("counter").unwrap(), Some(Bytes::from("100")));
+    assert_eq!(client.get("after").unwrap(), Some(Bytes::from("rewrite")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Should restore the last snapshot when the server restarts
+#[test]
+fn snapshot_restart() {
+    let dir = data_dir("snapshot_restart");
+    let snapshot_path = Config {
+        dir: dir.clone(),
+        ..Config::default()
+    }
+    .snapshot_path();
+    {
+        let (_server, mut client) = start_server(Some(&dir), &[]);
+        client.set("saved", "value").unwrap();
+        client
+            .request(&["SET", "ttl", "value", "EX", "100"])
+            .unwrap();
+        client.request(&["SAVE"]).unwrap();
+        client.set("unsaved", "value").unwrap();
+        assert!(matches!(
+            client.request(&["BGREWRITEAOF"]),
+            Err(simple_redis::Error::Server(_))
+        ));
+    }
+
+    let (server, mut client) = start_server(Some(&dir), &[]);
+    assert_eq!(client.get("saved").unwrap(), Some(Bytes::from("value")));
+    assert!((99..=100).contains(&client.ttl("ttl").unwrap()));
+    assert_eq!(client.get("unsaved").unwrap(), None);
+
+    fs::remove_file(&snapshot_path).unwrap();
+    client.set("background", "value").unwrap();
+    client.request(&["BGSAVE"]).unwrap();
+    wait_until(|| snapshot_path.exists());
+    drop(server);
+
+    // Enabling the append-only file starts it from the snapshot.
+    let (server, mut client) = start_server(Some(&dir), &APPENDONLY);
+    assert_eq!(
+        client.get("background").unwrap(),
+        Some(Bytes::from("value"))
+    );
+    drop(server);
+    fs::remove_file(&snapshot_path).unwrap();
+    let (_server, mut client) = start_server(Some(&dir), &APPENDONLY);
+    assert_eq!(client.get("saved").unwrap(), Some(Bytes::from("value")));
+    fs::remove_dir_all(&dir).unwrap();
+}
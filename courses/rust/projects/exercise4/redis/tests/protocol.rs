@@ -0,0 +1,114 @@
+use bytes::{Bytes, BytesMut};
+use simple_redis::protocol::{decode, Decoder, Frame, Version};
+
+fn encoded(frame: &Frame, version: Version) -> Vec<u8> {
+    let mut buf = BytesMut::new();
+    frame.encode(version, &mut buf);
+    buf.to_vec()
+}
+
+fn sample() -> Frame {
+    Frame::Array(vec![
+        Frame::SimpleString(Bytes::from_static(b"OK")),
+        Frame::Error("ERR oops".into()),
+        Frame::Integer(-42),
+        Frame::BulkString(Bytes::from_static(b"with\r\nnewline")),
+        Frame::Null,
+        Frame::Boolean(true),
+        Frame::Double(1.5),
+        Frame::BigNumber("3492890328409238509324850943850943825024385".into()),
+        Frame::BulkError("SYNTAX invalid".into()),
+        Frame::VerbatimString {
+            format: "txt".into(),
+            text: Bytes::from_static(b"Some string"),
+        },
+        Frame::Map(vec![(
+            Frame::SimpleString(Bytes::from_static(b"key")),
+            Frame::Set(vec![Frame::Integer(1), Frame::Integer(2)]),
+        )]),
+        Frame::Push(vec![Frame::BulkString(Bytes::from_static(b"message"))]),
+    ])
+}
+
+// Should round-trip every frame type with RESP3
+#[test]
+fn resp3_round_trip() {
+    let frame = sample();
+    let bytes = encoded(&frame, Version::Resp3);
+    assert_eq!(decode(&bytes).unwrap(), Some((frame, bytes.len())));
+}
+
+// Should write the RESP3 types as RESP2 types
+#[test]
+fn resp2_downgrade() {
+    let frame = Frame::Map(vec![(
+        Frame::BulkString(Bytes::from_static(b"flag")),
+        Frame::Boolean(false),
+    )]);
+    assert_eq!(
+        encoded(&frame, Version::Resp2),
+        b"*2\r\n$4\r\nflag\r\n:0\r\n"
+    );
+    assert_eq!(encoded(&Frame::Null, Version::Resp2), b"$-1\r\n");
+    assert_eq!(encoded(&Frame::Null, Version::Resp3), b"_\r\n");
+    assert_eq!(decode(b"*-1\r\n").unwrap(), Some((Frame::Null, 5)));
+}
+
+// Should wait for more bytes at every cut of a frame
+#[test]
+fn partial_frames() {
+    let bytes = encoded(&sample(), Version::Resp3);
+    for cut in 0..bytes.len() {
+        assert_eq!(decode(&bytes[..cut]).unwrap(), None, "cut at {}", cut);
+    }
+
+    let mut decoder = Decoder::new();
+    for byte in &bytes {
+        assert_eq!(decoder.decode_frame().unwrap(), None);
+        decoder.extend(&[*byte]);
+    }
+    assert_eq!(decoder.decode_frame().unwrap(), Some(sample()));
+    assert!(decoder.is_empty());
+}
+
+// Should split pipelined frames
+#[test]
+fn pipelined_frames() {
+    let mut decoder = Decoder::new();
+    decoder.extend(b"+PONG\r\n:1\r\n$3\r\nfoo\r\n$2\r");
+    assert_eq!(
+        decoder.decode_frame().unwrap(),
+        Some(Frame::SimpleString(Bytes::from_static(b"PONG")))
+    );
+    assert_eq!(decoder.decode_frame().unwrap(), Some(Frame::Integer(1)));
+    assert_eq!(
+        decoder.decode_frame().unwrap(),
+        Some(Frame::BulkString(Bytes::from_static(b"foo")))
+    );
+    assert_eq!(decoder.decode_frame().unwrap(), None);
+    decoder.extend(b"\nhi\r\n");
+    assert_eq!(
+        decoder.decode_frame().unwrap(),
+        Some(Frame::BulkString(Bytes::from_static(b"hi")))
+    );
+}
+
+// Should reject malformed frames
+#[test]
+fn malformed_frames() {
+    assert!(decode(b"?foo\r\n").is_err());
+    assert!(decode(b":12a\r\n").is_err());
+    assert!(decode(b"$3\r\nfoobar\r\n").is_err());
+    assert!(decode(b"$-2\r\n").is_err());
+    assert!(decode(b"#x\r\n").is_err());
+    assert!(decode(b"$1000000000\r\n").is_err());
+    assert!(decode("*1\r\n".repeat(200).as_bytes()).is_err());
+
+    // before the rest of the frame arrives
+    let mut decoder = Decoder::new();
+    decoder.extend("*1\r\n".repeat(200).as_bytes());
+    assert!(decoder.decode_frame().is_err());
+    let mut decoder = Decoder::new();
+    decoder.extend(b"*2\r\n$1000000000\r\n");
+    assert!(decoder.decode_frame().is_err());
+}
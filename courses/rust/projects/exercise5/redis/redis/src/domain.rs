@@ -1,6 +1,7 @@
-use log::{info, trace};
-use std::error::Error;
-use std::io::Read;
+use crate::error::{Error, Result};
+use crate::subscriber::Subscriber;
+use log::trace;
+use redis_protocol::{Decoder, RespValue};
 use std::io::Write;
 use std::net::{TcpStream, ToSocketAddrs};
 use std::time::Duration;
@@ -8,62 +9,103 @@ use std::time::Duration;
 #[derive(Debug)]
 pub struct Client {
     stream: TcpStream,
+    decoder: Decoder,
 }
 
 impl Client {
-    pub fn new<T: ToSocketAddrs>(to_socker_addrs: T) -> Result<Client, Box<dyn Error>> {
-        let tcpstream = TcpStream::connect(to_socker_addrs)?;
-        tcpstream.set_nonblocking(true)?;
-        let client = Self { stream: tcpstream };
-        client.set_read_timeout(Some(Duration::from_millis(200)))?;
-        Ok(client)
+    pub fn new<T: ToSocketAddrs>(to_socket_addrs: T) -> Result<Client> {
+        let stream = TcpStream::connect(to_socket_addrs)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            decoder: Decoder::new(),
+        })
     }
 
     pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
         self.stream.set_read_timeout(timeout)
     }
 
-    pub fn send<const M: usize>(
-        &mut self,
-        bytes: &[u8],
-        mut buf: [u8; M],
-    ) -> Result<String, Box<dyn Error>> {
-        let crlf = [b'\r', b'\n'];
-        let bytes_trimmed = std::str::from_utf8(&bytes)
-            .unwrap()
-            .trim_end_matches(&['\r', '\n']);
-        trace!("About to send: {}", bytes_trimmed);
-        let bytes_appended = [bytes_trimmed.as_bytes(), &crlf].concat();
-        self.write(&bytes_appended).expect("Failed to write.");
-        self.flush().expect("Failed to flush the client!");
-        loop {
-            if let Err(e) = self.read(&mut buf) {
-                trace!("Error reading {:?}", e);
-            }
+    pub fn ping(&mut self) -> Result<String> {
+        match self.request(&["PING"])? {
+            RespValue::SimpleString(s) => Ok(s),
+            value => Err(Error::UnexpectedResponse(value)),
+        }
+    }
 
-            if let Ok(()) = self.read_exact(&mut buf) {
-                info!("Read bytes!");
-                break;
-            }
+    /// Returns the value of `key`, or `None` if it does not exist.
+    pub fn get(&mut self, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>> {
+        match self.request(&[&b"GET"[..], key.as_ref()])? {
+            RespValue::BulkString(value) => Ok(Some(value)),
+            RespValue::Null => Ok(None),
+            value => Err(Error::UnexpectedResponse(value)),
         }
-        let parsed_str = std::str::from_utf8(&buf)?;
-        trace!("Parsed str: {parsed_str}");
-        Ok(parsed_str.to_string())
     }
-}
 
-impl Write for Client {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.stream.write(buf)
+    pub fn set(&mut self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<()> {
+        match self.request(&[&b"SET"[..], key.as_ref(), value.as_ref()])? {
+            RespValue::SimpleString(s) if s == "OK" => Ok(()),
+            value => Err(Error::UnexpectedResponse(value)),
+        }
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.stream.flush()
+    /// Removes `keys` and returns how many of them existed.
+    pub fn del<K: AsRef<[u8]>>(&mut self, keys: &[K]) -> Result<i64> {
+        let mut args: Vec<&[u8]> = vec![&b"DEL"[..]];
+        args.extend(keys.iter().map(AsRef::as_ref));
+        match self.request(&args)? {
+            RespValue::Integer(n) => Ok(n),
+            value => Err(Error::UnexpectedResponse(value)),
+        }
+    }
+
+    /// Publishes `message` on `channel` and returns how many subscribers received it.
+    pub fn publish(&mut self, channel: impl AsRef<[u8]>, message: impl AsRef<[u8]>) -> Result<i64> {
+        match self.request(&[&b"PUBLISH"[..], channel.as_ref(), message.as_ref()])? {
+            RespValue::Integer(n) => Ok(n),
+            value => Err(Error::UnexpectedResponse(value)),
+        }
     }
-}
 
-impl Read for Client {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        self.stream.read(buf)
+    /// Subscribes to `channels` and turns the connection into a `Subscriber`.
+    pub fn subscribe<C: AsRef<[u8]>>(self, channels: &[C]) -> Result<Subscriber> {
+        let mut subscriber = Subscriber::new(self);
+        subscriber.subscribe(channels)?;
+        Ok(subscriber)
+    }
+
+    /// Subscribes to the channels matching `patterns` and turns the connection into a
+    /// `Subscriber`.
+    pub fn psubscribe<P: AsRef<[u8]>>(self, patterns: &[P]) -> Result<Subscriber> {
+        let mut subscriber = Subscriber::new(self);
+        subscriber.psubscribe(patterns)?;
+        Ok(subscriber)
+    }
+
+    /// Sends a command and waits for its reply.
+    ///
+    /// Error replies are returned as `Error::Server`.
+    pub fn request<A: AsRef<[u8]>>(&mut self, args: &[A]) -> Result<RespValue> {
+        self.send(args)?;
+        self.read_value()
+    }
+
+    pub(crate) fn send<A: AsRef<[u8]>>(&mut self, args: &[A]) -> Result<()> {
+        let command = RespValue::command(args);
+        trace!("About to send: {:?}", command);
+        self.stream.write_all(&command.to_bytes())?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    pub(crate) fn read_value(&mut self) -> Result<RespValue> {
+        match self.decoder.read_value(&mut self.stream)? {
+            Some(RespValue::Error(msg)) => Err(Error::Server(msg)),
+            Some(value) => {
+                trace!("Received: {:?}", value);
+                Ok(value)
+            }
+            None => Err(Error::ConnectionClosed),
+        }
     }
 }
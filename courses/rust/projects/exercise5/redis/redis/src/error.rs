@@ -0,0 +1,50 @@
+use redis_protocol::{ProtocolError, RespValue};
+use std::fmt;
+use std::io;
+
+/// Error returned by `Client`.
+#[derive(Debug)]
+pub enum Error {
+    /// The connection failed or the server sent malformed RESP
+    Protocol(ProtocolError),
+    /// The server replied with an error
+    Server(String),
+    /// The server replied with a value of an unexpected type
+    UnexpectedResponse(RespValue),
+    /// The server closed the connection
+    ConnectionClosed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Protocol(e) => write!(f, "{}", e),
+            Error::Server(msg) => write!(f, "{}", msg),
+            Error::UnexpectedResponse(value) => write!(f, "Unexpected response: {:?}", value),
+            Error::ConnectionClosed => write!(f, "Connection closed by the server"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Protocol(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProtocolError> for Error {
+    fn from(err: ProtocolError) -> Self {
+        Error::Protocol(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Protocol(err.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
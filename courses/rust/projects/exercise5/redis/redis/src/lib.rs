@@ -1,4 +1,11 @@
 mod domain;
+mod error;
+mod pubsub;
+mod server;
+mod subscriber;
 mod util;
 pub use crate::domain::Client;
+pub use crate::error::{Error, Result};
+pub use crate::server::serve;
+pub use crate::subscriber::{Message, Subscriber};
 pub use crate::util::init_logger;
@@ -0,0 +1,251 @@
+//! Publish/subscribe between the connections of the server.
+//!
+//! Every connection owns a `Session` which registers its channels and patterns in
+//! the shared `Broker`. Published messages are pushed to the sessions through
+//! bounded channels and written out by the connection tasks. A connection whose
+//! channel is full is closed, like with the client-output-buffer-limit of redis.
+
+use redis_protocol::{glob_match, RespValue};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Notify;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Channel,
+    Pattern,
+}
+
+/// The queue of messages pushed to one connection.
+#[derive(Debug, Clone)]
+pub(crate) struct Subscriber {
+    tx: Sender<RespValue>,
+    // notified when the queue is full, so the connection is closed
+    overflow: Arc<Notify>,
+}
+
+impl Subscriber {
+    pub(crate) fn new(tx: Sender<RespValue>, overflow: Arc<Notify>) -> Subscriber {
+        Subscriber { tx, overflow }
+    }
+
+    /// Queues `message`, or asks the connection to close if it is too far behind.
+    ///
+    /// Returns whether the message is queued.
+    fn push(&self, message: RespValue) -> bool {
+        match self.tx.try_send(message) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.overflow.notify_one();
+                false
+            }
+            // The connection may be closing, which is not an error of the publisher.
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    next_id: u64,
+    channels: HashMap<Vec<u8>, HashMap<u64, Subscriber>>,
+    patterns: HashMap<Vec<u8>, HashMap<u64, Subscriber>>,
+}
+
+impl Registry {
+    fn subscribers(&mut self, kind: Kind) -> &mut HashMap<Vec<u8>, HashMap<u64, Subscriber>> {
+        match kind {
+            Kind::Channel => &mut self.channels,
+            Kind::Pattern => &mut self.patterns,
+        }
+    }
+}
+
+/// The subscriptions of all connections.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Broker {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl Broker {
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.registry
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sends `payload` to the subscribers of `channel` and of the patterns matching it.
+    ///
+    /// Returns the number of deliveries, so a connection subscribed to the channel
+    /// and to a matching pattern counts twice, like in redis.
+    fn publish(&self, channel: &[u8], payload: &[u8]) -> usize {
+        let registry = self.lock();
+        let mut receivers = 0;
+        if let Some(subscribers) = registry.channels.get(channel) {
+            let message = RespValue::Array(vec![
+                RespValue::bulk("message"),
+                RespValue::bulk(channel),
+                RespValue::bulk(payload),
+            ]);
+            for subscriber in subscribers.values() {
+                if subscriber.push(message.clone()) {
+                    receivers += 1;
+                }
+            }
+        }
+        for (pattern, subscribers) in &registry.patterns {
+            if !glob_match(pattern, channel) {
+                continue;
+            }
+            let message = RespValue::Array(vec![
+                RespValue::bulk("pmessage"),
+                RespValue::bulk(pattern.clone()),
+                RespValue::bulk(channel),
+                RespValue::bulk(payload),
+            ]);
+            for subscriber in subscribers.values() {
+                if subscriber.push(message.clone()) {
+                    receivers += 1;
+                }
+            }
+        }
+        receivers
+    }
+}
+
+/// The subscriptions of one connection, removed from the broker when dropped.
+#[derive(Debug)]
+pub(crate) struct Session {
+    id: u64,
+    broker: Broker,
+    subscriber: Subscriber,
+    channels: BTreeSet<Vec<u8>>,
+    patterns: BTreeSet<Vec<u8>>,
+}
+
+impl Session {
+    /// Creates a session whose messages are pushed to `subscriber`.
+    pub(crate) fn new(broker: Broker, subscriber: Subscriber) -> Session {
+        let id = {
+            let mut registry = broker.lock();
+            registry.next_id += 1;
+            registry.next_id
+        };
+        Session {
+            id,
+            broker,
+            subscriber,
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+        }
+    }
+
+    /// Returns whether the connection is in subscribed mode, where only the pub/sub
+    /// commands are allowed.
+    pub(crate) fn is_subscribed(&self) -> bool {
+        !self.channels.is_empty() || !self.patterns.is_empty()
+    }
+
+    pub(crate) fn subscribe(&mut self, channels: &[Vec<u8>]) -> Vec<RespValue> {
+        self.add(Kind::Channel, channels)
+    }
+
+    pub(crate) fn psubscribe(&mut self, patterns: &[Vec<u8>]) -> Vec<RespValue> {
+        self.add(Kind::Pattern, patterns)
+    }
+
+    pub(crate) fn publish(&self, channel: &[u8], payload: &[u8]) -> usize {
+        self.broker.publish(channel, payload)
+    }
+
+    /// Removes `channels`, or every channel if it is empty.
+    pub(crate) fn unsubscribe(&mut self, channels: &[Vec<u8>]) -> Vec<RespValue> {
+        self.remove(Kind::Channel, channels)
+    }
+
+    /// Removes `patterns`, or every pattern if it is empty.
+    pub(crate) fn punsubscribe(&mut self, patterns: &[Vec<u8>]) -> Vec<RespValue> {
+        self.remove(Kind::Pattern, patterns)
+    }
+
+    fn names(&mut self, kind: Kind) -> &mut BTreeSet<Vec<u8>> {
+        match kind {
+            Kind::Channel => &mut self.channels,
+            Kind::Pattern => &mut self.patterns,
+        }
+    }
+
+    fn confirmation(&self, kind: Kind, added: bool, name: Option<&[u8]>) -> RespValue {
+        let command = match (kind, added) {
+            (Kind::Channel, true) => "subscribe",
+            (Kind::Channel, false) => "unsubscribe",
+            (Kind::Pattern, true) => "psubscribe",
+            (Kind::Pattern, false) => "punsubscribe",
+        };
+        let count = self.channels.len() + self.patterns.len();
+        RespValue::Array(vec![
+            RespValue::bulk(command),
+            name.map_or(RespValue::Null, RespValue::bulk),
+            RespValue::Integer(count as i64),
+        ])
+    }
+
+    fn add(&mut self, kind: Kind, names: &[Vec<u8>]) -> Vec<RespValue> {
+        let mut replies = Vec::with_capacity(names.len());
+        for name in names {
+            if self.names(kind).insert(name.clone()) {
+                self.broker
+                    .lock()
+                    .subscribers(kind)
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(self.id, self.subscriber.clone());
+            }
+            replies.push(self.confirmation(kind, true, Some(name.as_slice())));
+        }
+        replies
+    }
+
+    fn remove(&mut self, kind: Kind, names: &[Vec<u8>]) -> Vec<RespValue> {
+        let names: Vec<Vec<u8>> = if names.is_empty() {
+            self.names(kind).iter().cloned().collect()
+        } else {
+            names.to_vec()
+        };
+        if names.is_empty() {
+            return vec![self.confirmation(kind, false, None)];
+        }
+        let mut replies = Vec::with_capacity(names.len());
+        for name in names {
+            if self.names(kind).remove(&name) {
+                self.deregister(kind, &name);
+            }
+            replies.push(self.confirmation(kind, false, Some(name.as_slice())));
+        }
+        replies
+    }
+
+    fn deregister(&self, kind: Kind, name: &[u8]) {
+        let mut registry = self.broker.lock();
+        let subscribers = registry.subscribers(kind);
+        if let Some(ids) = subscribers.get_mut(name) {
+            ids.remove(&self.id);
+            if ids.is_empty() {
+                subscribers.remove(name);
+            }
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for channel in &self.channels {
+            self.deregister(Kind::Channel, channel);
+        }
+        for pattern in &self.patterns {
+            self.deregister(Kind::Pattern, pattern);
+        }
+    }
+}
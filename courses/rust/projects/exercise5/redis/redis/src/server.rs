@@ -0,0 +1,187 @@
+//! An in-memory server speaking RESP, serving every client on its own task.
+
+use crate::pubsub::{Broker, Session, Subscriber};
+use futures::{SinkExt, StreamExt};
+use log::{info, trace, warn};
+use redis_protocol::{ProtocolError, RespCodec, RespValue};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Notify};
+use tokio_util::codec::Framed;
+
+// The most messages queued for a subscriber before its connection is closed.
+const MAX_PENDING_MESSAGES: usize = 1024;
+
+/// Accepts clients on `listener` until accepting fails.
+pub async fn serve(listener: TcpListener) -> io::Result<()> {
+    info!("Listening on {}", listener.local_addr()?);
+    let db = Db::default();
+    let broker = Broker::default();
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        trace!("Accepted a connection from {}", addr);
+        let db = db.clone();
+        let broker = broker.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, &db, broker).await {
+                warn!("Connection from {} failed: {}", addr, e);
+            }
+        });
+    }
+}
+
+async fn handle_client(stream: TcpStream, db: &Db, broker: Broker) -> Result<(), ProtocolError> {
+    stream.set_nodelay(true)?;
+    let mut frames = Framed::new(stream, RespCodec::new());
+    let (tx, mut messages) = mpsc::channel(MAX_PENDING_MESSAGES);
+    let overflow = Arc::new(Notify::new());
+    let mut session = Session::new(broker, Subscriber::new(tx, overflow.clone()));
+    loop {
+        tokio::select! {
+            value = frames.next() => {
+                let value = match value {
+                    Some(Ok(value)) => value,
+                    Some(Err(e @ ProtocolError::Io(_))) => return Err(e),
+                    Some(Err(e)) => {
+                        // The rest of the buffer cannot be split into values anymore.
+                        frames.send(RespValue::Error(format!("ERR {}", e))).await?;
+                        return Err(e);
+                    }
+                    None => break,
+                };
+                trace!("Received: {:?}", value);
+                let replies = match parse_command(value) {
+                    Ok(args) => execute(db, &mut session, &args),
+                    Err(reply) => vec![reply],
+                };
+                // Pipelined commands are answered with a single write once the
+                // received bytes are used up.
+                for reply in replies {
+                    frames.feed(reply).await?;
+                }
+                if frames.read_buffer().is_empty() {
+                    frames.flush().await?;
+                }
+            }
+            // The session holds a sender, so the channel is never closed.
+            Some(message) = messages.recv() => {
+                // A client that stopped reading blocks the write, so it is closed
+                // there as well.
+                tokio::select! {
+                    sent = frames.send(message) => sent?,
+                    _ = overflow.notified() => return Err(lagging()),
+                }
+            }
+            _ = overflow.notified() => return Err(lagging()),
+        }
+    }
+    trace!("Client closed the connection");
+    Ok(())
+}
+
+fn lagging() -> ProtocolError {
+    ProtocolError::Io(io::Error::other(
+        "subscriber is too far behind the published messages",
+    ))
+}
+
+/// Splits a command into its arguments, or returns the error reply.
+fn parse_command(value: RespValue) -> Result<Vec<Vec<u8>>, RespValue> {
+    let invalid = || error("ERR Protocol error: expected an array of bulk strings");
+    let items = match value {
+        RespValue::Array(items) if !items.is_empty() => items,
+        _ => return Err(invalid()),
+    };
+    items
+        .into_iter()
+        .map(|item| match item {
+            RespValue::BulkString(arg) => Ok(arg),
+            _ => Err(invalid()),
+        })
+        .collect()
+}
+
+fn execute(db: &Db, session: &mut Session, args: &[Vec<u8>]) -> Vec<RespValue> {
+    let name = String::from_utf8_lossy(&args[0]).to_lowercase();
+    let arity_ok = match name.as_str() {
+        "ping" => args.len() <= 2,
+        "echo" | "get" => args.len() == 2,
+        "set" | "publish" => args.len() == 3,
+        "del" | "subscribe" | "psubscribe" => args.len() >= 2,
+        "unsubscribe" | "punsubscribe" => true,
+        _ => return vec![error(format!("ERR unknown command '{}'", name))],
+    };
+    if !arity_ok {
+        return vec![error(format!(
+            "ERR wrong number of arguments for '{}' command",
+            name
+        ))];
+    }
+
+    match name.as_str() {
+        "subscribe" => session.subscribe(&args[1..]),
+        "psubscribe" => session.psubscribe(&args[1..]),
+        "unsubscribe" => session.unsubscribe(&args[1..]),
+        "punsubscribe" => session.punsubscribe(&args[1..]),
+        // A subscribed connection receives pushed messages, so its replies are arrays
+        // that can be told apart from them.
+        "ping" if session.is_subscribed() => vec![RespValue::Array(vec![
+            RespValue::bulk("pong"),
+            RespValue::bulk(args.get(1).cloned().unwrap_or_default()),
+        ])],
+        _ if session.is_subscribed() => vec![error(format!(
+            "ERR Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING are allowed in this context",
+            name
+        ))],
+        _ => vec![execute_data(db, session, &name, args)],
+    }
+}
+
+fn execute_data(db: &Db, session: &Session, name: &str, args: &[Vec<u8>]) -> RespValue {
+    match name {
+        "ping" => match args.get(1) {
+            Some(message) => RespValue::bulk(message.clone()),
+            None => RespValue::SimpleString("PONG".to_owned()),
+        },
+        "echo" => RespValue::bulk(args[1].clone()),
+        "get" => match db.lock().get(&args[1]) {
+            Some(value) => RespValue::bulk(value.clone()),
+            None => RespValue::Null,
+        },
+        "set" => {
+            db.lock().insert(args[1].clone(), args[2].clone());
+            RespValue::SimpleString("OK".to_owned())
+        }
+        "del" => {
+            let mut entries = db.lock();
+            let removed = args[1..]
+                .iter()
+                .filter(|key| entries.remove(*key).is_some())
+                .count();
+            RespValue::Integer(removed as i64)
+        }
+        "publish" => RespValue::Integer(session.publish(&args[1], &args[2]) as i64),
+        _ => unreachable!(),
+    }
+}
+
+fn error(msg: impl Into<String>) -> RespValue {
+    RespValue::Error(msg.into())
+}
+
+/// The keyspace shared by the tasks serving clients.
+#[derive(Debug, Clone, Default)]
+struct Db {
+    entries: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl Db {
+    fn lock(&self) -> MutexGuard<'_, HashMap<Vec<u8>, Vec<u8>>> {
+        // A task panicking while holding the lock leaves the keyspace usable.
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
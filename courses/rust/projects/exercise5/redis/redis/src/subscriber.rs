@@ -0,0 +1,187 @@
+use crate::domain::Client;
+use crate::error::{Error, Result};
+use redis_protocol::RespValue;
+use std::collections::{BTreeSet, VecDeque};
+
+/// A message published on a channel the subscriber listens to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub channel: Vec<u8>,
+    /// The pattern the channel matched, if the message came through `psubscribe`
+    pub pattern: Option<Vec<u8>>,
+    pub payload: Vec<u8>,
+}
+
+/// A connection in subscribed mode, which receives the messages published on its
+/// channels.
+///
+/// Iterating blocks until the next message arrives, and ends when the server closes
+/// the connection.
+#[derive(Debug)]
+pub struct Subscriber {
+    client: Client,
+    channels: BTreeSet<Vec<u8>>,
+    patterns: BTreeSet<Vec<u8>>,
+    // Messages received while waiting for the confirmation of a command.
+    pending: VecDeque<Message>,
+}
+
+enum Push {
+    Message(Message),
+    Confirmation(String),
+}
+
+impl Subscriber {
+    pub(crate) fn new(client: Client) -> Subscriber {
+        Subscriber {
+            client,
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Returns the channels subscribed to.
+    pub fn channels(&self) -> impl Iterator<Item = &[u8]> {
+        self.channels.iter().map(Vec::as_slice)
+    }
+
+    /// Returns the patterns subscribed to.
+    pub fn patterns(&self) -> impl Iterator<Item = &[u8]> {
+        self.patterns.iter().map(Vec::as_slice)
+    }
+
+    /// Subscribes to `channels`, returning once the server confirmed them.
+    pub fn subscribe<C: AsRef<[u8]>>(&mut self, channels: &[C]) -> Result<()> {
+        self.command("subscribe", channels, channels.len())?;
+        self.channels
+            .extend(channels.iter().map(|c| c.as_ref().to_vec()));
+        Ok(())
+    }
+
+    /// Subscribes to the channels matching `patterns`, returning once the server
+    /// confirmed them.
+    pub fn psubscribe<P: AsRef<[u8]>>(&mut self, patterns: &[P]) -> Result<()> {
+        self.command("psubscribe", patterns, patterns.len())?;
+        self.patterns
+            .extend(patterns.iter().map(|p| p.as_ref().to_vec()));
+        Ok(())
+    }
+
+    /// Unsubscribes from `channels`, or from every channel if it is empty.
+    pub fn unsubscribe<C: AsRef<[u8]>>(&mut self, channels: &[C]) -> Result<()> {
+        // The server confirms each channel, or once if there is none.
+        let confirmations = match channels.len() {
+            0 => self.channels.len().max(1),
+            len => len,
+        };
+        self.command("unsubscribe", channels, confirmations)?;
+        if channels.is_empty() {
+            self.channels.clear();
+        }
+        for channel in channels {
+            self.channels.remove(channel.as_ref());
+        }
+        Ok(())
+    }
+
+    /// Unsubscribes from `patterns`, or from every pattern if it is empty.
+    pub fn punsubscribe<P: AsRef<[u8]>>(&mut self, patterns: &[P]) -> Result<()> {
+        let confirmations = match patterns.len() {
+            0 => self.patterns.len().max(1),
+            len => len,
+        };
+        self.command("punsubscribe", patterns, confirmations)?;
+        if patterns.is_empty() {
+            self.patterns.clear();
+        }
+        for pattern in patterns {
+            self.patterns.remove(pattern.as_ref());
+        }
+        Ok(())
+    }
+
+    /// Blocks until the next message arrives.
+    pub fn next_message(&mut self) -> Result<Message> {
+        if let Some(message) = self.pending.pop_front() {
+            return Ok(message);
+        }
+        loop {
+            if let Push::Message(message) = self.read_push()? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Sends a command and waits for `confirmations` replies to it, keeping the
+    /// messages received meanwhile.
+    fn command<A: AsRef<[u8]>>(
+        &mut self,
+        command: &str,
+        args: &[A],
+        confirmations: usize,
+    ) -> Result<()> {
+        let mut command_args = vec![command.as_bytes()];
+        command_args.extend(args.iter().map(AsRef::as_ref));
+        self.client.send(&command_args)?;
+        let mut confirmed = 0;
+        while confirmed < confirmations {
+            match self.read_push()? {
+                Push::Message(message) => self.pending.push_back(message),
+                Push::Confirmation(kind) if kind == command => confirmed += 1,
+                Push::Confirmation(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn read_push(&mut self) -> Result<Push> {
+        let items = match self.client.read_value()? {
+            RespValue::Array(items) => items,
+            value => return Err(Error::UnexpectedResponse(value)),
+        };
+        let mut fields = items.iter().map(|item| match item {
+            RespValue::BulkString(data) => Some(data.clone()),
+            _ => None,
+        });
+        let kind = match fields.next().flatten() {
+            Some(kind) => String::from_utf8_lossy(&kind).to_lowercase(),
+            None => return Err(Error::UnexpectedResponse(RespValue::Array(items))),
+        };
+        let push = match (kind.as_str(), items.len()) {
+            ("message", 3) => match (fields.next().flatten(), fields.next().flatten()) {
+                (Some(channel), Some(payload)) => Push::Message(Message {
+                    channel,
+                    pattern: None,
+                    payload,
+                }),
+                _ => return Err(Error::UnexpectedResponse(RespValue::Array(items))),
+            },
+            ("pmessage", 4) => match (
+                fields.next().flatten(),
+                fields.next().flatten(),
+                fields.next().flatten(),
+            ) {
+                (Some(pattern), Some(channel), Some(payload)) => Push::Message(Message {
+                    channel,
+                    pattern: Some(pattern),
+                    payload,
+                }),
+                _ => return Err(Error::UnexpectedResponse(RespValue::Array(items))),
+            },
+            _ => Push::Confirmation(kind),
+        };
+        Ok(push)
+    }
+}
+
+impl Iterator for Subscriber {
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Result<Message>> {
+        match self.next_message() {
+            Err(Error::ConnectionClosed) => None,
+            result => Some(result),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use redis_protocol::{Decoder, RespValue};
+use simple_redis::{Client, Error, Message};
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::Duration;
+
+// Kills the server when a test finishes, even if it panics.
+struct Server {
+    child: Child,
+    port: u16,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn start_server() -> (Server, Client) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let child = Command::new(env!("CARGO_BIN_EXE_redis-simple-server"))
+        .env("REDIS_PORT", port.to_string())
+        // The server writes its log to tmp/ in its current directory.
+        .current_dir(std::env::temp_dir())
+        .spawn()
+        .unwrap();
+    let server = Server { child, port };
+    for _ in 0..100 {
+        if let Ok(client) = Client::new(("127.0.0.1", port)) {
+            client
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            return (server, client);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    panic!("Failed to connect to the server");
+}
+
+// Should run commands against the server of the exercise
+#[test]
+fn commands() {
+    let (_server, mut client) = start_server();
+
+    assert_eq!(client.ping().unwrap(), "PONG");
+    assert_eq!(client.get("key").unwrap(), None);
+    client.set("key", "value").unwrap();
+    assert_eq!(client.get("key").unwrap(), Some(b"value".to_vec()));
+    assert_eq!(client.del(&["key", "missing"]).unwrap(), 1);
+    assert_eq!(client.get("key").unwrap(), None);
+    assert_eq!(
+        client.request(&["ECHO", "hi"]).unwrap(),
+        RespValue::bulk("hi")
+    );
+    assert!(matches!(
+        client.request(&["NOSUCHCOMMAND"]),
+        Err(Error::Server(_))
+    ));
+}
+
+// Should answer pipelined commands in order
+#[test]
+fn pipelining() {
+    let (server, _client) = start_server();
+
+    let mut stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+    let mut requests = Vec::new();
+    let commands: [&[&str]; 3] = [&["SET", "k", "v"], &["GET", "k"], &["PING"]];
+    for args in commands {
+        RespValue::command(args).encode(&mut requests);
+    }
+    stream.write_all(&requests).unwrap();
+
+    let mut decoder = Decoder::new();
+    let mut replies = Vec::new();
+    for _ in 0..3 {
+        replies.push(decoder.read_value(&mut stream).unwrap().unwrap());
+    }
+    assert_eq!(
+        replies,
+        vec![
+            RespValue::SimpleString("OK".to_owned()),
+            RespValue::bulk("v"),
+            RespValue::SimpleString("PONG".to_owned()),
+        ]
+    );
+}
+
+// Should serve clients concurrently on one keyspace
+#[test]
+fn concurrent_clients() {
+    let (server, mut idle) = start_server();
+
+    let port = server.port;
+    let handles: Vec<_> = (0..8)
+        .map(|i| {
+            thread::spawn(move || {
+                let mut client = Client::new(("127.0.0.1", port)).unwrap();
+                for j in 0..50 {
+                    client.set(format!("{}:{}", i, j), "x").unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let keys: Vec<String> = (0..8).map(|i| format!("{}:49", i)).collect();
+    assert_eq!(idle.del(&keys).unwrap(), 8);
+}
+
+fn connect(server: &Server) -> Client {
+    let client = Client::new(("127.0.0.1", server.port)).unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    client
+}
+
+// Should deliver published messages to the subscribers of channels and patterns
+#[test]
+fn publish_subscribe() {
+    let (server, mut publisher) = start_server();
+    let mut news = connect(&server).subscribe(&["news", "weather"]).unwrap();
+    let mut all_news = connect(&server).psubscribe(&["news.*"]).unwrap();
+    let mut other_news = connect(&server).subscribe(&["news"]).unwrap();
+
+    assert_eq!(publisher.publish("news", "hello").unwrap(), 2);
+    assert_eq!(publisher.publish("news.sport", "goal").unwrap(), 1);
+    assert_eq!(publisher.publish("nobody", "listens").unwrap(), 0);
+    assert_eq!(publisher.publish("weather", "rain").unwrap(), 1);
+
+    let message = |channel: &str, pattern: Option<&str>, payload: &str| Message {
+        channel: channel.into(),
+        pattern: pattern.map(Into::into),
+        payload: payload.into(),
+    };
+    assert_eq!(
+        news.next().unwrap().unwrap(),
+        message("news", None, "hello")
+    );
+    assert_eq!(
+        news.next().unwrap().unwrap(),
+        message("weather", None, "rain")
+    );
+    assert_eq!(
+        other_news.next().unwrap().unwrap(),
+        message("news", None, "hello")
+    );
+    assert_eq!(
+        all_news.next().unwrap().unwrap(),
+        message("news.sport", Some("news.*"), "goal")
+    );
+
+    news.unsubscribe(&["news"]).unwrap();
+    assert_eq!(news.channels().collect::<Vec<_>>(), vec![&b"weather"[..]]);
+    assert_eq!(publisher.publish("news", "again").unwrap(), 1);
+    all_news.punsubscribe::<&str>(&[]).unwrap();
+    assert_eq!(publisher.publish("news.sport", "again").unwrap(), 0);
+
+    // Closed connections are removed from their channels.
+    drop(other_news);
+    for _ in 0..100 {
+        if publisher.publish("news", "bye").unwrap() == 0 {
+            return;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    panic!("The closed subscriber still receives messages");
+}
+
+// Should close a subscriber that does not read its messages
+#[test]
+fn slow_subscriber() {
+    let (server, mut publisher) = start_server();
+    let mut subscriber = connect(&server).subscribe(&["news"]).unwrap();
+
+    let payload = vec![b'x'; 4 * 1024];
+    let mut published = 0;
+    while publisher.publish("news", &payload).unwrap() == 1 {
+        published += 1;
+        assert!(published < 10_000, "The subscriber is never closed");
+    }
+
+    let mut received = 0;
+    while subscriber.next_message().is_ok() {
+        received += 1;
+    }
+    assert!(received <= published);
+    assert_eq!(publisher.publish("news", "bye").unwrap(), 0);
+}
+
+// Should only allow pub/sub commands on a subscribed connection
+#[test]
+fn subscribed_mode() {
+    let (_server, mut client) = start_server();
+
+    let reply = client.request(&["SUBSCRIBE", "channel"]).unwrap();
+    assert_eq!(
+        reply,
+        RespValue::Array(vec![
+            RespValue::bulk("subscribe"),
+            RespValue::bulk("channel"),
+            RespValue::Integer(1),
+        ])
+    );
+    assert!(matches!(
+        client.request(&["GET", "key"]),
+        Err(Error::Server(_))
+    ));
+    assert_eq!(
+        client.request(&["PING"]).unwrap(),
+        RespValue::Array(vec![RespValue::bulk("pong"), RespValue::bulk("")])
+    );
+    client.request(&["UNSUBSCRIBE"]).unwrap();
+    assert_eq!(client.get("key").unwrap(), None);
+}
@@ -0,0 +1,43 @@
+//! A codec to read and write RESP values on tokio streams with `Framed`.
+
+use crate::decode::{Limits, Scanner};
+use crate::error::ProtocolError;
+use crate::types::RespValue;
+use bytes::BytesMut;
+
+#[derive(Debug, Clone, Default)]
+pub struct RespCodec {
+    limits: Limits,
+    scanner: Scanner,
+}
+
+impl RespCodec {
+    pub fn new() -> Self {
+        RespCodec::default()
+    }
+
+    pub fn with_limits(limits: Limits) -> Self {
+        RespCodec {
+            limits,
+            scanner: Scanner::default(),
+        }
+    }
+}
+
+impl tokio_util::codec::Decoder for RespCodec {
+    type Item = RespValue;
+    type Error = ProtocolError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<RespValue>, ProtocolError> {
+        self.scanner.decode(src, &self.limits)
+    }
+}
+
+impl tokio_util::codec::Encoder<RespValue> for RespCodec {
+    type Error = ProtocolError;
+
+    fn encode(&mut self, item: RespValue, dst: &mut BytesMut) -> Result<(), ProtocolError> {
+        item.encode(dst);
+        Ok(())
+    }
+}
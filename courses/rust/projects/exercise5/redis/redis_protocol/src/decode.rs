@@ -0,0 +1,477 @@
+//! Incremental decoding of RESP values and frames.
+//!
+//! `decode` returns `None` until the input holds a complete value, so bytes can be
+//! buffered as they arrive and decoded again. `decode_frame` does the same for frames,
+//! which also have the types of RESP3, with the same parser. `Decoder` and `RespCodec` remember how
+//! far they have scanned the buffered value, so it is only decoded once it is
+//! complete.
+
+use crate::error::{ProtocolError, Result};
+use crate::types::*;
+use bytes::{Buf, Bytes, BytesMut};
+use bytes_utils::Str;
+use std::io::{self, Read};
+
+/// Bounds on the values a decoder accepts, so a peer cannot make it buffer or
+/// allocate without limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The longest bulk string
+    pub max_bulk_len: usize,
+    /// The most elements of an array, set or push, or pairs of a map
+    pub max_array_len: usize,
+    /// The longest simple string, error, integer or length line
+    pub max_line_len: usize,
+    /// How deep aggregates can be nested
+    pub max_depth: usize,
+}
+
+impl Default for Limits {
+    /// The limits of redis.
+    fn default() -> Self {
+        Limits {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_array_len: 1024 * 1024,
+            max_line_len: 64 * 1024,
+            max_depth: 128,
+        }
+    }
+}
+
+// Aggregates are allocated at most this many elements before they are read, since the
+// length is sent by the peer.
+const MAX_PREALLOC: usize = 1024;
+
+/// Decodes the RESP2 value at the beginning of `src`.
+///
+/// Returns the value and the number of bytes it takes, or `None` if `src` does not
+/// hold a complete value yet.
+pub fn decode(src: &[u8], limits: &Limits) -> Result<Option<(RespValue, usize)>> {
+    let mut parser = Parser::new(src, limits, Version::Resp2);
+    match parser.frame(0)? {
+        Some(frame) => Ok(Some((into_value(frame)?, parser.pos))),
+        None => Ok(None),
+    }
+}
+
+/// Decodes the frame at the beginning of `src`, which may use the types of RESP3.
+///
+/// Returns the frame and the number of bytes it takes, or `None` if `src` does not
+/// hold a complete frame yet.
+pub fn decode_frame(src: &[u8], limits: &Limits) -> Result<Option<(Frame, usize)>> {
+    let mut parser = Parser::new(src, limits, Version::Resp3);
+    Ok(parser.frame(0)?.map(|frame| (frame, parser.pos)))
+}
+
+// Returns `Ok(None)` from the enclosing function if the value is incomplete.
+macro_rules! complete {
+    ($e:expr) => {
+        match $e {
+            Some(value) => value,
+            None => return Ok(None),
+        }
+    };
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    limits: &'a Limits,
+    // the types accepted, as RESP2 peers must not send the types of RESP3
+    version: Version,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a [u8], limits: &'a Limits, version: Version) -> Self {
+        Parser {
+            src,
+            pos: 0,
+            limits,
+            version,
+        }
+    }
+
+    fn frame(&mut self, depth: usize) -> Result<Option<Frame>> {
+        if depth > self.limits.max_depth {
+            return Err(ProtocolError::TooLarge("nesting".to_owned()));
+        }
+        let kind = complete!(self.kind()?);
+        let line = complete!(self.line()?);
+
+        let frame = match kind {
+            FrameKind::SimpleString => Frame::SimpleString(Bytes::copy_from_slice(line)),
+            FrameKind::Error => Frame::Error(to_str(line)?),
+            FrameKind::Integer => Frame::Integer(parse_int(line)?),
+            FrameKind::Null if line.is_empty() => Frame::Null,
+            FrameKind::Null => return Err(ProtocolError::Invalid("null has a payload".to_owned())),
+            FrameKind::Boolean => match line {
+                b"t" => Frame::Boolean(true),
+                b"f" => Frame::Boolean(false),
+                _ => return Err(ProtocolError::Invalid("invalid boolean".to_owned())),
+            },
+            FrameKind::Double => Frame::Double(parse_double(line)?),
+            FrameKind::BigNumber => {
+                let digits = line.strip_prefix(b"-").unwrap_or(line);
+                if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+                    return Err(ProtocolError::Invalid("invalid big number".to_owned()));
+                }
+                Frame::BigNumber(to_str(line)?)
+            }
+            FrameKind::BulkString | FrameKind::BulkError | FrameKind::VerbatimString => {
+                let len = match self.len(kind, line)? {
+                    Some(len) => len,
+                    None => return Ok(Some(Frame::Null)),
+                };
+                let data = complete!(self.bulk(len)?);
+                match kind {
+                    FrameKind::BulkString => Frame::BulkString(Bytes::copy_from_slice(data)),
+                    FrameKind::BulkError => Frame::BulkError(to_str(data)?),
+                    _ => {
+                        if data.len() < 4 || data[3] != b':' {
+                            return Err(ProtocolError::Invalid(
+                                "verbatim string has no format".to_owned(),
+                            ));
+                        }
+                        Frame::VerbatimString {
+                            format: to_str(&data[..3])?,
+                            text: Bytes::copy_from_slice(&data[4..]),
+                        }
+                    }
+                }
+            }
+            FrameKind::Map => {
+                let len = self.len(kind, line)?.unwrap_or_default();
+                let mut pairs = Vec::with_capacity(len.min(MAX_PREALLOC));
+                for _ in 0..len {
+                    let key = complete!(self.frame(depth + 1)?);
+                    let value = complete!(self.frame(depth + 1)?);
+                    pairs.push((key, value));
+                }
+                Frame::Map(pairs)
+            }
+            FrameKind::Array | FrameKind::Set | FrameKind::Push => {
+                let len = match self.len(kind, line)? {
+                    Some(len) => len,
+                    None => return Ok(Some(Frame::Null)),
+                };
+                let mut items = Vec::with_capacity(len.min(MAX_PREALLOC));
+                for _ in 0..len {
+                    items.push(complete!(self.frame(depth + 1)?));
+                }
+                match kind {
+                    FrameKind::Array => Frame::Array(items),
+                    FrameKind::Set => Frame::Set(items),
+                    _ => Frame::Push(items),
+                }
+            }
+        };
+        Ok(Some(frame))
+    }
+
+    /// Skips the next frame, except for the elements of an aggregate.
+    ///
+    /// Returns the number of frames that follow, which is 0 for other frames.
+    fn skip(&mut self) -> Result<Option<usize>> {
+        let kind = complete!(self.kind()?);
+        let line = complete!(self.line()?);
+        let elements = match kind {
+            FrameKind::BulkString | FrameKind::BulkError | FrameKind::VerbatimString => {
+                if let Some(len) = self.len(kind, line)? {
+                    complete!(self.bulk(len)?);
+                }
+                0
+            }
+            FrameKind::Map => self.len(kind, line)?.unwrap_or_default() * 2,
+            FrameKind::Array | FrameKind::Set | FrameKind::Push => {
+                self.len(kind, line)?.unwrap_or_default()
+            }
+            _ => 0,
+        };
+        Ok(Some(elements))
+    }
+
+    /// Reads the byte giving the type of the next frame.
+    fn kind(&mut self) -> Result<Option<FrameKind>> {
+        let prefix = complete!(self.src.get(self.pos).copied());
+        let kind = FrameKind::from_prefix(prefix)
+            .filter(|kind| self.version == Version::Resp3 || kind.is_resp2())
+            .ok_or_else(|| ProtocolError::Invalid(format!("unknown type {:?}", prefix as char)))?;
+        self.pos += 1;
+        Ok(Some(kind))
+    }
+
+    /// Reads the `len` bytes of a bulk frame and the CRLF after them.
+    fn bulk(&mut self, len: usize) -> Result<Option<&'a [u8]>> {
+        let rest = &self.src[self.pos..];
+        if rest.len() < len + 2 {
+            return Ok(None);
+        }
+        if &rest[len..len + 2] != b"\r\n" {
+            return Err(ProtocolError::Invalid(
+                "bulk string is not terminated by CRLF".to_owned(),
+            ));
+        }
+        self.pos += len + 2;
+        Ok(Some(&rest[..len]))
+    }
+
+    /// Reads until the next CRLF.
+    fn line(&mut self) -> Result<Option<&'a [u8]>> {
+        let rest = &self.src[self.pos..];
+        match rest.windows(2).position(|w| w == b"\r\n") {
+            Some(end) if end <= self.limits.max_line_len => {
+                self.pos += end + 2;
+                Ok(Some(&rest[..end]))
+            }
+            None if rest.len() <= self.limits.max_line_len => Ok(None),
+            _ => Err(ProtocolError::TooLarge("line".to_owned())),
+        }
+    }
+
+    /// Parses the length of a bulk or aggregate frame of `kind`.
+    ///
+    /// Returns `None` for the length `-1` of the null bulk string and null array.
+    fn len(&self, kind: FrameKind, line: &[u8]) -> Result<Option<usize>> {
+        let max = match kind {
+            FrameKind::BulkString | FrameKind::BulkError | FrameKind::VerbatimString => {
+                self.limits.max_bulk_len
+            }
+            _ => self.limits.max_array_len,
+        };
+        let what = describe(kind);
+        match parse_int(line)? {
+            -1 if matches!(kind, FrameKind::BulkString | FrameKind::Array) => Ok(None),
+            len if len < 0 => Err(ProtocolError::Invalid(format!("negative {} length", what))),
+            len if len as u64 > max as u64 => Err(ProtocolError::TooLarge(what.to_owned())),
+            len => Ok(Some(len as usize)),
+        }
+    }
+}
+
+/// Converts a frame of the RESP2 types into a value.
+fn into_value(frame: Frame) -> Result<RespValue> {
+    let value = match frame {
+        Frame::SimpleString(s) => RespValue::SimpleString(to_string(&s)?),
+        Frame::Error(s) => RespValue::Error(s.to_string()),
+        Frame::Integer(n) => RespValue::Integer(n),
+        Frame::BulkString(data) => RespValue::BulkString(data.into()),
+        Frame::Array(items) => {
+            RespValue::Array(items.into_iter().map(into_value).collect::<Result<_>>()?)
+        }
+        Frame::Null => RespValue::Null,
+        frame => {
+            return Err(ProtocolError::Invalid(format!(
+                "unknown type {:?}",
+                frame.kind().prefix() as char
+            )))
+        }
+    };
+    Ok(value)
+}
+
+/// Names the frames of `kind` in errors.
+fn describe(kind: FrameKind) -> &'static str {
+    match kind {
+        FrameKind::BulkString => "bulk string",
+        FrameKind::BulkError => "bulk error",
+        FrameKind::VerbatimString => "verbatim string",
+        FrameKind::Map => "map",
+        FrameKind::Set => "set",
+        FrameKind::Push => "push",
+        FrameKind::Array => "array",
+        _ => unreachable!("{:?} frames have no length", kind),
+    }
+}
+
+fn to_string(line: &[u8]) -> Result<String> {
+    String::from_utf8(line.to_vec())
+        .map_err(|_| ProtocolError::Invalid("invalid UTF-8 string".to_owned()))
+}
+
+fn to_str(data: &[u8]) -> Result<Str> {
+    Str::from_inner(Bytes::copy_from_slice(data))
+        .map_err(|_| ProtocolError::Invalid("invalid UTF-8 string".to_owned()))
+}
+
+fn parse_int(line: &[u8]) -> Result<i64> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| ProtocolError::Invalid("invalid integer".to_owned()))
+}
+
+fn parse_double(line: &[u8]) -> Result<f64> {
+    match line {
+        b"inf" => Ok(f64::INFINITY),
+        b"-inf" => Ok(f64::NEG_INFINITY),
+        b"nan" => Ok(f64::NAN),
+        _ => std::str::from_utf8(line)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| ProtocolError::Invalid("invalid double".to_owned())),
+    }
+}
+
+/// Finds the end of the value at the beginning of a buffer that grows between calls.
+///
+/// It keeps the position and the open arrays it has read so far, so every byte is
+/// scanned once however many reads the value takes to arrive.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Scanner {
+    // where the next element starts
+    pos: usize,
+    // the elements still missing from each open aggregate, innermost last
+    pending: Vec<usize>,
+}
+
+impl Scanner {
+    /// Removes the first RESP2 value from `buf` once it is complete.
+    ///
+    /// `buf` must start with the bytes given to the previous calls.
+    pub(crate) fn decode(
+        &mut self,
+        buf: &mut BytesMut,
+        limits: &Limits,
+    ) -> Result<Option<RespValue>> {
+        let len = complete!(self.scan(buf, limits, Version::Resp2)?);
+        let (value, _) = decode(&buf[..len], limits)?.expect("the value is complete");
+        buf.advance(len);
+        Ok(Some(value))
+    }
+
+    /// Removes the first frame from `buf` once it is complete, like `decode` but
+    /// accepting the types of RESP3.
+    pub(crate) fn decode_frame(
+        &mut self,
+        buf: &mut BytesMut,
+        limits: &Limits,
+    ) -> Result<Option<Frame>> {
+        let len = complete!(self.scan(buf, limits, Version::Resp3)?);
+        let (frame, _) = decode_frame(&buf[..len], limits)?.expect("the frame is complete");
+        buf.advance(len);
+        Ok(Some(frame))
+    }
+
+    /// Returns the length of the first frame in `src`, or `None` if it is incomplete.
+    fn scan(&mut self, src: &[u8], limits: &Limits, version: Version) -> Result<Option<usize>> {
+        loop {
+            if self.pending.len() > limits.max_depth {
+                return Err(ProtocolError::TooLarge("nesting".to_owned()));
+            }
+            let mut parser = Parser::new(src, limits, version);
+            parser.pos = self.pos;
+            let elements = complete!(parser.skip()?);
+            self.pos = parser.pos;
+            if elements > 0 {
+                self.pending.push(elements);
+                continue;
+            }
+            // An element is complete, and so are the aggregates it is the last one of.
+            loop {
+                match self.pending.last_mut() {
+                    None => {
+                        let len = self.pos;
+                        self.pos = 0;
+                        return Ok(Some(len));
+                    }
+                    Some(missing) if *missing > 1 => {
+                        *missing -= 1;
+                        break;
+                    }
+                    Some(_) => {
+                        self.pending.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Buffers bytes read from a connection and splits them into values.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buf: BytesMut,
+    limits: Limits,
+    scanner: Scanner,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Decoder::default()
+    }
+
+    pub fn with_limits(limits: Limits) -> Self {
+        Decoder {
+            buf: BytesMut::new(),
+            limits,
+            scanner: Scanner::default(),
+        }
+    }
+
+    /// Appends received bytes to the buffer.
+    pub fn extend(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Reads once from `reader` into the buffer.
+    ///
+    /// Returns the number of bytes read, which is 0 at the end of the stream.
+    pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let mut chunk = [0; 4096];
+        let len = reader.read(&mut chunk)?;
+        self.buf.extend_from_slice(&chunk[..len]);
+        Ok(len)
+    }
+
+    /// Removes the first complete value from the buffer.
+    ///
+    /// Returns `None` if the buffer does not hold a complete value yet.
+    pub fn decode(&mut self) -> Result<Option<RespValue>> {
+        self.scanner.decode(&mut self.buf, &self.limits)
+    }
+
+    /// Removes the first complete frame from the buffer, which may use the types of
+    /// RESP3.
+    ///
+    /// Returns `None` if the buffer does not hold a complete frame yet.
+    pub fn decode_frame(&mut self) -> Result<Option<Frame>> {
+        self.scanner.decode_frame(&mut self.buf, &self.limits)
+    }
+
+    /// Reads from `reader` until a complete value is buffered and returns it.
+    ///
+    /// Returns `None` if the stream ends between two values.
+    pub fn read_value<R: Read>(&mut self, reader: &mut R) -> Result<Option<RespValue>> {
+        self.read_with(reader, Decoder::decode)
+    }
+
+    /// Reads from `reader` until a complete frame is buffered and returns it, like
+    /// `read_value` but accepting the types of RESP3.
+    pub fn read_frame<R: Read>(&mut self, reader: &mut R) -> Result<Option<Frame>> {
+        self.read_with(reader, Decoder::decode_frame)
+    }
+
+    fn read_with<R: Read, T>(
+        &mut self,
+        reader: &mut R,
+        decode: fn(&mut Self) -> Result<Option<T>>,
+    ) -> Result<Option<T>> {
+        loop {
+            if let Some(decoded) = decode(self)? {
+                return Ok(Some(decoded));
+            }
+            if self.read_from(reader)? == 0 {
+                return if self.buf.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+                };
+            }
+        }
+    }
+
+    /// Returns whether there are buffered bytes that are not decoded.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
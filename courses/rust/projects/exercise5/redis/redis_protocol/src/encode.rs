@@ -0,0 +1,118 @@
+//! Encoding of RESP values and frames.
+
+use crate::types::*;
+use bytes::BufMut;
+
+impl RespValue {
+    /// Appends the encoded value to `dst`.
+    pub fn encode<B: BufMut>(&self, dst: &mut B) {
+        match self {
+            RespValue::SimpleString(s) => put_line(dst, SIMPLESTRING_BYTE, s.as_bytes()),
+            RespValue::Error(s) => put_line(dst, ERROR_BYTE, s.as_bytes()),
+            RespValue::Integer(n) => put_line(dst, INTEGER_BYTE, n.to_string().as_bytes()),
+            RespValue::BulkString(data) => put_bulk(dst, BULKSTRING_BYTE, data),
+            RespValue::Array(items) => {
+                put_line(dst, ARRAY_BYTE, items.len().to_string().as_bytes());
+                for item in items {
+                    item.encode(dst);
+                }
+            }
+            RespValue::Null => dst.put_slice(NULL.as_bytes()),
+        }
+    }
+
+    /// Returns the encoded value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+impl Frame {
+    /// Appends the encoded frame to `dst`.
+    ///
+    /// With `Version::Resp2`, the frame types added in RESP3 are written as the RESP2
+    /// types redis replies with: maps, sets and pushes as flat arrays, booleans as
+    /// integers, errors as simple errors and the others as bulk strings.
+    pub fn encode<B: BufMut>(&self, version: Version, dst: &mut B) {
+        let resp3 = version == Version::Resp3;
+        match self {
+            Frame::SimpleString(s) => put_line(dst, SIMPLESTRING_BYTE, s),
+            Frame::Error(s) => put_line(dst, ERROR_BYTE, s.as_bytes()),
+            Frame::Integer(n) => put_line(dst, INTEGER_BYTE, n.to_string().as_bytes()),
+            Frame::BulkString(data) => put_bulk(dst, BULKSTRING_BYTE, data),
+            Frame::Array(items) => put_aggregate(dst, ARRAY_BYTE, items, version),
+            Frame::Null if resp3 => put_line(dst, NULL_BYTE, b""),
+            Frame::Null => dst.put_slice(NULL.as_bytes()),
+            Frame::Boolean(b) if resp3 => put_line(dst, BOOLEAN_BYTE, if *b { b"t" } else { b"f" }),
+            Frame::Boolean(b) => put_line(dst, INTEGER_BYTE, if *b { b"1" } else { b"0" }),
+            Frame::Double(d) if resp3 => put_line(dst, DOUBLE_BYTE, format_double(*d).as_bytes()),
+            Frame::Double(d) => put_bulk(dst, BULKSTRING_BYTE, format_double(*d).as_bytes()),
+            Frame::BigNumber(s) if resp3 => put_line(dst, BIGNUMBER_BYTE, s.as_bytes()),
+            Frame::BigNumber(s) => put_bulk(dst, BULKSTRING_BYTE, s.as_bytes()),
+            Frame::BulkError(s) if resp3 => put_bulk(dst, BULKERROR_BYTE, s.as_bytes()),
+            Frame::BulkError(s) => put_line(dst, ERROR_BYTE, s.replace("\r\n", " ").as_bytes()),
+            Frame::VerbatimString { format, text } if resp3 => {
+                let data = [format.as_bytes(), &b":"[..], &text[..]].concat();
+                put_bulk(dst, VERBATIMSTRING_BYTE, &data)
+            }
+            Frame::VerbatimString { text, .. } => put_bulk(dst, BULKSTRING_BYTE, text),
+            Frame::Map(pairs) => {
+                let (prefix, len) = if resp3 {
+                    (MAP_BYTE, pairs.len())
+                } else {
+                    (ARRAY_BYTE, pairs.len() * 2)
+                };
+                put_line(dst, prefix, len.to_string().as_bytes());
+                for (key, value) in pairs {
+                    key.encode(version, dst);
+                    value.encode(version, dst);
+                }
+            }
+            Frame::Set(items) => put_aggregate(
+                dst,
+                if resp3 { SET_BYTE } else { ARRAY_BYTE },
+                items,
+                version,
+            ),
+            Frame::Push(items) => put_aggregate(
+                dst,
+                if resp3 { PUSH_BYTE } else { ARRAY_BYTE },
+                items,
+                version,
+            ),
+        }
+    }
+}
+
+fn put_line<B: BufMut>(dst: &mut B, prefix: u8, line: &[u8]) {
+    dst.put_u8(prefix);
+    dst.put_slice(line);
+    dst.put_slice(b"\r\n");
+}
+
+fn put_bulk<B: BufMut>(dst: &mut B, prefix: u8, data: &[u8]) {
+    put_line(dst, prefix, data.len().to_string().as_bytes());
+    dst.put_slice(data);
+    dst.put_slice(b"\r\n");
+}
+
+fn put_aggregate<B: BufMut>(dst: &mut B, prefix: u8, items: &[Frame], version: Version) {
+    put_line(dst, prefix, items.len().to_string().as_bytes());
+    for item in items {
+        item.encode(version, dst);
+    }
+}
+
+fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        "nan".to_owned()
+    } else if d == f64::INFINITY {
+        "inf".to_owned()
+    } else if d == f64::NEG_INFINITY {
+        "-inf".to_owned()
+    } else {
+        d.to_string()
+    }
+}
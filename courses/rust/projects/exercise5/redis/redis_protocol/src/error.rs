@@ -0,0 +1,40 @@
+use std::fmt;
+use std::io;
+
+/// Error of decoding RESP.
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// The input is not valid RESP
+    Invalid(String),
+    /// A value is larger than the limits of the decoder
+    TooLarge(String),
+    /// Reading or writing the connection failed
+    Io(io::Error),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::Invalid(msg) => write!(f, "Protocol error: {}", msg),
+            ProtocolError::TooLarge(msg) => write!(f, "Protocol error: {} is too large", msg),
+            ProtocolError::Io(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProtocolError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ProtocolError {
+    fn from(err: io::Error) -> Self {
+        ProtocolError::Io(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ProtocolError>;
@@ -0,0 +1,73 @@
+/// Matches `string` against a glob-style `pattern` as `KEYS` and `PSUBSCRIBE` do.
+///
+/// `*` matches any bytes, `?` matches one byte, `[abc]`, `[^abc]` and `[a-z]` match
+/// one byte of a set, and `\` escapes the next byte.
+///
+/// It runs in O(pattern × string) time without recursion: when a byte does not
+/// match, only the last `*` is retried one byte further, since the earlier stars can
+/// match whatever the last one would have to skip.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // the pattern after the last `*` and where the string is matched against it
+    let mut star = None;
+    while s < string.len() {
+        if pattern.get(p) == Some(&b'*') {
+            while pattern.get(p) == Some(&b'*') {
+                p += 1;
+            }
+            star = Some((p, s));
+            continue;
+        }
+        if let Some(len) = match_byte(&pattern[p..], string[s]) {
+            p += len;
+            s += 1;
+            continue;
+        }
+        match star {
+            Some((star_p, star_s)) => {
+                star = Some((star_p, star_s + 1));
+                p = star_p;
+                s = star_s + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Matches `byte` against the element at the beginning of `pattern`, which is not
+/// `*`.
+///
+/// Returns the length of the element if it matches.
+fn match_byte(pattern: &[u8], byte: u8) -> Option<usize> {
+    let (matched, len) = match pattern.split_first()? {
+        (b'?', _) => (true, 1),
+        (b'[', rest) => {
+            let (negated, set) = match rest.split_first() {
+                Some((b'^', set)) => (true, set),
+                _ => (false, rest),
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < set.len() && set[i] != b']' {
+                if set[i] == b'\\' && i + 1 < set.len() {
+                    matched |= set[i + 1] == byte;
+                    i += 2;
+                } else if i + 2 < set.len() && set[i + 1] == b'-' && set[i + 2] != b']' {
+                    let (low, high) = (set[i].min(set[i + 2]), set[i].max(set[i + 2]));
+                    matched |= (low..=high).contains(&byte);
+                    i += 3;
+                } else {
+                    matched |= set[i] == byte;
+                    i += 1;
+                }
+            }
+            // An unclosed set extends to the end of the pattern.
+            let len = (pattern.len() - set.len() + i + 1).min(pattern.len());
+            (matched != negated, len)
+        }
+        (b'\\', rest) if !rest.is_empty() => (rest[0] == byte, 2),
+        (&c, _) => (c == byte, 1),
+    };
+    matched.then_some(len)
+}
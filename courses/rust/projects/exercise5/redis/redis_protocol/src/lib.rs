@@ -1,3 +1,12 @@
+mod codec;
+mod decode;
+mod encode;
+mod error;
+mod glob;
 mod types;
 
-pub use self::types::*;
\ No newline at end of file
+pub use self::codec::RespCodec;
+pub use self::decode::{decode, decode_frame, Decoder, Limits};
+pub use self::error::{ProtocolError, Result};
+pub use self::glob::glob_match;
+pub use self::types::*;
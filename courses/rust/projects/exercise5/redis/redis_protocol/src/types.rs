@@ -1,3 +1,6 @@
+use bytes::Bytes;
+use bytes_utils::Str;
+
 // In RESP, the first byte determines the data type:
 
 // For Simple Strings, the first byte of the reply is "+"
@@ -6,6 +9,8 @@
 // For Bulk Strings, the first byte of the reply is "$"
 // For Arrays, the first byte of the reply is "*"
 // RESP can represent a Null value using a special variation of Bulk Strings or Array as specified later.
+//
+// RESP3 adds the types of the `*_BYTE` constants after `ARRAY_BYTE`.
 
 // In RESP, different parts of the protocol are always terminated with "\r\n" (CRLF).
 
@@ -19,14 +24,34 @@ pub const INTEGER_BYTE: u8 = b':';
 pub const BULKSTRING_BYTE: u8 = b'$';
 /// Byte prefix before an array type.
 pub const ARRAY_BYTE: u8 = b'*';
+/// Byte prefix before the RESP3 null type.
+pub const NULL_BYTE: u8 = b'_';
+/// Byte prefix before a boolean type.
+pub const BOOLEAN_BYTE: u8 = b'#';
+/// Byte prefix before a double type.
+pub const DOUBLE_BYTE: u8 = b',';
+/// Byte prefix before a big number type.
+pub const BIGNUMBER_BYTE: u8 = b'(';
+/// Byte prefix before a bulk error type.
+pub const BULKERROR_BYTE: u8 = b'!';
+/// Byte prefix before a verbatim string type.
+pub const VERBATIMSTRING_BYTE: u8 = b'=';
+/// Byte prefix before a map type.
+pub const MAP_BYTE: u8 = b'%';
+/// Byte prefix before a set type.
+pub const SET_BYTE: u8 = b'~';
+/// Byte prefix before a push type.
+pub const PUSH_BYTE: u8 = b'>';
 
 /// The binary representation of NULL in RESP2.
-pub const NULL: &'static str = "$-1\r\n";
-
-
+pub const NULL: &str = "$-1\r\n";
 
-#[derive(Debug)]
-pub(crate) enum RespValue {
+/// A RESP2 value.
+///
+/// The null bulk string and the null array are both decoded as `Null`, which is
+/// encoded as `NULL`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RespValue {
     SimpleString(String),
     Error(String),
     Integer(i64),
@@ -35,3 +60,156 @@ pub(crate) enum RespValue {
     Null,
 }
 
+impl RespValue {
+    /// Builds a command, which is an array of bulk strings.
+    pub fn command<A: AsRef<[u8]>>(args: &[A]) -> RespValue {
+        RespValue::Array(
+            args.iter()
+                .map(|arg| RespValue::BulkString(arg.as_ref().to_vec()))
+                .collect(),
+        )
+    }
+
+    /// Builds a bulk string.
+    pub fn bulk(data: impl Into<Vec<u8>>) -> RespValue {
+        RespValue::BulkString(data.into())
+    }
+}
+
+/// The type of a frame, given by its first byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    SimpleString,
+    Error,
+    Integer,
+    BulkString,
+    Array,
+    Null,
+    Boolean,
+    Double,
+    BigNumber,
+    BulkError,
+    VerbatimString,
+    Map,
+    Set,
+    Push,
+}
+
+impl FrameKind {
+    /// Returns the kind of the frame starting with `prefix`.
+    pub fn from_prefix(prefix: u8) -> Option<FrameKind> {
+        let kind = match prefix {
+            SIMPLESTRING_BYTE => FrameKind::SimpleString,
+            ERROR_BYTE => FrameKind::Error,
+            INTEGER_BYTE => FrameKind::Integer,
+            BULKSTRING_BYTE => FrameKind::BulkString,
+            ARRAY_BYTE => FrameKind::Array,
+            NULL_BYTE => FrameKind::Null,
+            BOOLEAN_BYTE => FrameKind::Boolean,
+            DOUBLE_BYTE => FrameKind::Double,
+            BIGNUMBER_BYTE => FrameKind::BigNumber,
+            BULKERROR_BYTE => FrameKind::BulkError,
+            VERBATIMSTRING_BYTE => FrameKind::VerbatimString,
+            MAP_BYTE => FrameKind::Map,
+            SET_BYTE => FrameKind::Set,
+            PUSH_BYTE => FrameKind::Push,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Returns the first byte of frames of this kind.
+    pub fn prefix(self) -> u8 {
+        match self {
+            FrameKind::SimpleString => SIMPLESTRING_BYTE,
+            FrameKind::Error => ERROR_BYTE,
+            FrameKind::Integer => INTEGER_BYTE,
+            FrameKind::BulkString => BULKSTRING_BYTE,
+            FrameKind::Array => ARRAY_BYTE,
+            FrameKind::Null => NULL_BYTE,
+            FrameKind::Boolean => BOOLEAN_BYTE,
+            FrameKind::Double => DOUBLE_BYTE,
+            FrameKind::BigNumber => BIGNUMBER_BYTE,
+            FrameKind::BulkError => BULKERROR_BYTE,
+            FrameKind::VerbatimString => VERBATIMSTRING_BYTE,
+            FrameKind::Map => MAP_BYTE,
+            FrameKind::Set => SET_BYTE,
+            FrameKind::Push => PUSH_BYTE,
+        }
+    }
+
+    /// Returns whether the kind is one of the types of RESP2.
+    pub(crate) fn is_resp2(self) -> bool {
+        matches!(
+            self,
+            FrameKind::SimpleString
+                | FrameKind::Error
+                | FrameKind::Integer
+                | FrameKind::BulkString
+                | FrameKind::Array
+        )
+    }
+}
+
+/// A RESP2 or RESP3 frame.
+///
+/// The null bulk string and the null array of RESP2 are both decoded as `Null`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    SimpleString(Bytes),
+    Error(Str),
+    Integer(i64),
+    BulkString(Bytes),
+    Array(Vec<Frame>),
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber(Str),
+    BulkError(Str),
+    /// A string with a three-character format such as `txt` or `mkd`
+    VerbatimString {
+        format: Str,
+        text: Bytes,
+    },
+    Map(Vec<(Frame, Frame)>),
+    Set(Vec<Frame>),
+    Push(Vec<Frame>),
+}
+
+impl Frame {
+    /// Builds a command, which is an array of bulk strings.
+    pub fn command<A: AsRef<[u8]>>(args: &[A]) -> Frame {
+        Frame::Array(
+            args.iter()
+                .map(|arg| Frame::BulkString(Bytes::copy_from_slice(arg.as_ref())))
+                .collect(),
+        )
+    }
+
+    pub fn kind(&self) -> FrameKind {
+        match self {
+            Frame::SimpleString(_) => FrameKind::SimpleString,
+            Frame::Error(_) => FrameKind::Error,
+            Frame::Integer(_) => FrameKind::Integer,
+            Frame::BulkString(_) => FrameKind::BulkString,
+            Frame::Array(_) => FrameKind::Array,
+            Frame::Null => FrameKind::Null,
+            Frame::Boolean(_) => FrameKind::Boolean,
+            Frame::Double(_) => FrameKind::Double,
+            Frame::BigNumber(_) => FrameKind::BigNumber,
+            Frame::BulkError(_) => FrameKind::BulkError,
+            Frame::VerbatimString { .. } => FrameKind::VerbatimString,
+            Frame::Map(_) => FrameKind::Map,
+            Frame::Set(_) => FrameKind::Set,
+            Frame::Push(_) => FrameKind::Push,
+        }
+    }
+}
+
+/// The version of the protocol spoken on a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Version {
+    #[default]
+    Resp2,
+    Resp3,
+}
@@ -0,0 +1,175 @@
+use bytes::BytesMut;
+use redis_protocol::{
+    decode, decode_frame, Decoder, Frame, Limits, ProtocolError, RespCodec, RespValue, Version,
+};
+
+fn sample() -> RespValue {
+    RespValue::Array(vec![
+        RespValue::SimpleString("OK".to_owned()),
+        RespValue::Error("ERR oops".to_owned()),
+        RespValue::Integer(-7),
+        RespValue::bulk("with\r\nnewline"),
+        RespValue::Null,
+        RespValue::Array(vec![RespValue::bulk(""), RespValue::Array(Vec::new())]),
+    ])
+}
+
+// Should round-trip every type
+#[test]
+fn round_trip() {
+    let bytes = sample().to_bytes();
+    assert_eq!(
+        decode(&bytes, &Limits::default()).unwrap(),
+        Some((sample(), bytes.len()))
+    );
+    assert_eq!(RespValue::Null.to_bytes(), b"$-1\r\n");
+    assert_eq!(
+        decode(b"*-1\r\n", &Limits::default()).unwrap(),
+        Some((RespValue::Null, 5))
+    );
+}
+
+// Should wait for more bytes at every cut of a value
+#[test]
+fn partial_values() {
+    let bytes = sample().to_bytes();
+    for cut in 0..bytes.len() {
+        assert_eq!(
+            decode(&bytes[..cut], &Limits::default()).unwrap(),
+            None,
+            "cut at {}",
+            cut
+        );
+    }
+
+    let mut decoder = Decoder::new();
+    for byte in &bytes {
+        assert_eq!(decoder.decode().unwrap(), None);
+        decoder.extend(&[*byte]);
+    }
+    assert_eq!(decoder.decode().unwrap(), Some(sample()));
+    assert!(decoder.is_empty());
+}
+
+// Should read values from a reader, leaving pipelined ones buffered
+#[test]
+fn read_values() {
+    let mut bytes = RespValue::command(&["GET", "key"]).to_bytes();
+    bytes.extend_from_slice(b":1\r\n");
+    let mut reader = bytes.as_slice();
+
+    let mut decoder = Decoder::new();
+    assert_eq!(
+        decoder.read_value(&mut reader).unwrap(),
+        Some(RespValue::command(&["GET", "key"]))
+    );
+    assert_eq!(
+        decoder.read_value(&mut reader).unwrap(),
+        Some(RespValue::Integer(1))
+    );
+    assert_eq!(decoder.read_value(&mut reader).unwrap(), None);
+
+    let mut truncated = &b"$3\r\nfo"[..];
+    assert!(matches!(
+        Decoder::new().read_value(&mut truncated),
+        Err(ProtocolError::Io(_))
+    ));
+}
+
+// Should reject malformed values and values above the limits
+#[test]
+fn invalid_values() {
+    let limits = Limits {
+        max_bulk_len: 4,
+        max_array_len: 2,
+        max_line_len: 8,
+        max_depth: 2,
+    };
+    let error = |src: &[u8]| decode(src, &limits).unwrap_err();
+
+    assert!(matches!(error(b"?x\r\n"), ProtocolError::Invalid(_)));
+    assert!(matches!(error(b":1x\r\n"), ProtocolError::Invalid(_)));
+    assert!(matches!(error(b"$2\r\nabc\r\n"), ProtocolError::Invalid(_)));
+    assert!(matches!(error(b"$-2\r\n"), ProtocolError::Invalid(_)));
+    assert!(matches!(error(b"$5\r\n"), ProtocolError::TooLarge(_)));
+    assert!(matches!(error(b"*3\r\n"), ProtocolError::TooLarge(_)));
+    assert!(matches!(error(b"+123456789"), ProtocolError::TooLarge(_)));
+    assert!(matches!(
+        error(b"*1\r\n*1\r\n*1\r\n*1\r\n"),
+        ProtocolError::TooLarge(_)
+    ));
+}
+
+// Should reject values above the limits while they are still arriving
+#[test]
+fn invalid_partial_values() {
+    let limits = Limits {
+        max_bulk_len: 4,
+        max_array_len: 2,
+        max_line_len: 8,
+        max_depth: 2,
+    };
+    let error = |src: &[u8]| {
+        let mut decoder = Decoder::with_limits(limits);
+        for byte in src {
+            decoder.extend(&[*byte]);
+            if let Err(e) = decoder.decode() {
+                return e;
+            }
+        }
+        panic!("{:?} is accepted", String::from_utf8_lossy(src));
+    };
+
+    assert!(matches!(error(b"*2\r\n:1\r\n?"), ProtocolError::Invalid(_)));
+    assert!(matches!(error(b"*2\r\n$5\r\n"), ProtocolError::TooLarge(_)));
+    assert!(matches!(error(b"*1\r\n*3\r\n"), ProtocolError::TooLarge(_)));
+    assert!(matches!(
+        error(b"*2\r\n:1\r\n*1\r\n*1\r\n*1\r\n"),
+        ProtocolError::TooLarge(_)
+    ));
+}
+
+// Should only accept the types of RESP3 when decoding frames
+#[test]
+fn resp3_frames() {
+    let frame = Frame::Map(vec![(
+        Frame::BulkString("flag".into()),
+        Frame::Boolean(true),
+    )]);
+    let mut bytes = Vec::new();
+    frame.encode(Version::Resp3, &mut bytes);
+    assert_eq!(bytes, b"%1\r\n$4\r\nflag\r\n#t\r\n");
+
+    assert_eq!(
+        decode_frame(&bytes, &Limits::default()).unwrap(),
+        Some((frame.clone(), bytes.len()))
+    );
+    assert!(matches!(
+        decode(&bytes, &Limits::default()),
+        Err(ProtocolError::Invalid(_))
+    ));
+
+    let mut decoder = Decoder::new();
+    decoder.extend(&bytes);
+    assert_eq!(decoder.decode_frame().unwrap(), Some(frame));
+    decoder.extend(b"#t\r\n");
+    assert!(decoder.decode().is_err());
+}
+
+// Should decode and encode values on a tokio stream
+#[test]
+fn tokio_codec() {
+    use tokio_util::codec::{Decoder as _, Encoder as _};
+
+    let mut codec = RespCodec::new();
+    let mut buf = BytesMut::new();
+    codec.encode(sample(), &mut buf).unwrap();
+    codec.encode(RespValue::Integer(2), &mut buf).unwrap();
+    let tail = buf.split_off(buf.len() - 2);
+
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(sample()));
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    buf.extend_from_slice(&tail);
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(RespValue::Integer(2)));
+    assert!(buf.is_empty());
+}
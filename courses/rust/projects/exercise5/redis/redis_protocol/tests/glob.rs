@@ -0,0 +1,42 @@
+use redis_protocol::glob_match;
+
+// Should match the wildcards, sets and escapes of redis patterns
+#[test]
+fn patterns() {
+    let cases: &[(&str, &str, bool)] = &[
+        ("*", "", true),
+        ("news.*", "news.sport", true),
+        ("news.*", "new", false),
+        ("h?llo", "hallo", true),
+        ("h?llo", "hllo", false),
+        ("h*llo", "heeeello", true),
+        ("h*l*o", "hello world", false),
+        ("a**b***", "axxb", true),
+        ("h[ae]llo", "hello", true),
+        ("h[^e]llo", "hello", false),
+        ("h[a-b]llo", "hbllo", true),
+        ("h[b-a]llo", "hallo", true),
+        ("h[ab", "ha", true),
+        ("h\\*llo", "h*llo", true),
+        ("h\\*llo", "hello", false),
+        ("*\\", "a\\", true),
+    ];
+    for &(pattern, string, expected) in cases {
+        assert_eq!(
+            glob_match(pattern.as_bytes(), string.as_bytes()),
+            expected,
+            "{:?} against {:?}",
+            pattern,
+            string
+        );
+    }
+}
+
+// Should match long strings against many stars without backtracking exponentially
+#[test]
+fn many_stars() {
+    let string = "a".repeat(100_000);
+    let pattern = format!("{}b", "a*".repeat(50));
+    assert!(!glob_match(pattern.as_bytes(), string.as_bytes()));
+    assert!(glob_match(b"**********a", string.as_bytes()));
+}
@@ -0,0 +1,462 @@
+//! Deserialization of Rust values from RESP, the inverse of the mapping in `ser`.
+//!
+//! Numbers are also read from bulk strings, since redis stores them as strings.
+
+use crate::error::{Error, Result};
+use redis_protocol::{
+    Limits, ARRAY_BYTE, BULKSTRING_BYTE, ERROR_BYTE, INTEGER_BYTE, SIMPLESTRING_BYTE,
+};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::Deserialize;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Deserializes a value from RESP in `bytes`, which must hold nothing else.
+pub fn from_bytes<'a, T>(bytes: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_bytes(bytes);
+    let value = T::deserialize(&mut deserializer)?;
+    if deserializer.input.is_empty() {
+        Ok(value)
+    } else {
+        Err(Error::TrailingBytes)
+    }
+}
+
+/// Deserializes a value from RESP read from `reader` until its end.
+pub fn from_reader<R, T>(mut reader: R) -> Result<T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    from_bytes(&bytes)
+}
+
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+    // the number of arrays being read, bounded like in `redis_protocol`
+    depth: usize,
+    max_depth: usize,
+}
+
+/// A RESP value whose content is not decoded yet.
+enum Token<'de> {
+    SimpleString(&'de [u8]),
+    Integer(&'de [u8]),
+    BulkString(&'de [u8]),
+    Array(usize),
+    Null,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn from_bytes(input: &'de [u8]) -> Self {
+        Deserializer {
+            input,
+            depth: 0,
+            max_depth: Limits::default().max_depth,
+        }
+    }
+
+    fn peek_byte(&self) -> Result<u8> {
+        self.input.first().copied().ok_or(Error::Eof)
+    }
+
+    fn line(&mut self) -> Result<&'de [u8]> {
+        let end = self
+            .input
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or(Error::Eof)?;
+        let line = &self.input[..end];
+        self.input = &self.input[end + 2..];
+        Ok(line)
+    }
+
+    fn read_len(&mut self) -> Result<Option<usize>> {
+        match parse::<i64>(self.line()?)? {
+            -1 => Ok(None),
+            len => usize::try_from(len)
+                .map(Some)
+                .map_err(|_| Error::Syntax(format!("invalid length {}", len))),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token<'de>> {
+        let prefix = self.peek_byte()?;
+        self.input = &self.input[1..];
+        match prefix {
+            SIMPLESTRING_BYTE => Ok(Token::SimpleString(self.line()?)),
+            ERROR_BYTE => {
+                let msg = String::from_utf8_lossy(self.line()?);
+                Err(Error::Message(format!("error reply: {}", msg)))
+            }
+            INTEGER_BYTE => Ok(Token::Integer(self.line()?)),
+            BULKSTRING_BYTE => {
+                let len = match self.read_len()? {
+                    Some(len) => len,
+                    None => return Ok(Token::Null),
+                };
+                if self.input.len() < len + 2 {
+                    return Err(Error::Eof);
+                }
+                if &self.input[len..len + 2] != b"\r\n" {
+                    return Err(Error::Syntax(
+                        "bulk string is not terminated by CRLF".into(),
+                    ));
+                }
+                let data = &self.input[..len];
+                self.input = &self.input[len + 2..];
+                Ok(Token::BulkString(data))
+            }
+            ARRAY_BYTE => Ok(self.read_len()?.map_or(Token::Null, Token::Array)),
+            prefix => Err(Error::Syntax(format!("unknown type {:?}", prefix as char))),
+        }
+    }
+
+    /// Reads a number from an integer or a string.
+    fn number<T: FromStr>(&mut self) -> Result<T> {
+        match self.next_token()? {
+            Token::Integer(s) | Token::SimpleString(s) | Token::BulkString(s) => parse(s),
+            _ => Err(Error::Syntax("expected a number".into())),
+        }
+    }
+
+    fn string(&mut self) -> Result<&'de str> {
+        match self.next_token()? {
+            Token::SimpleString(s) | Token::BulkString(s) => to_str(s),
+            _ => Err(Error::Syntax("expected a string".into())),
+        }
+    }
+
+    fn array(&mut self) -> Result<usize> {
+        match self.next_token()? {
+            Token::Array(len) => Ok(len),
+            _ => Err(Error::Syntax("expected an array".into())),
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        self.input.starts_with(b"$-1\r\n") || self.input.starts_with(b"*-1\r\n")
+    }
+
+    /// Reads the elements of an array with `f`, one level deeper.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == self.max_depth {
+            return Err(Error::Syntax("arrays are nested too deeply".into()));
+        }
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    fn visit_array<V: Visitor<'de>>(&mut self, len: usize, visitor: V) -> Result<V::Value> {
+        self.nested(|de| {
+            let mut access = Elements { de, len };
+            let value = visitor.visit_seq(&mut access)?;
+            if access.len == 0 {
+                Ok(value)
+            } else {
+                Err(Error::Syntax(
+                    "array has more elements than expected".into(),
+                ))
+            }
+        })
+    }
+
+    fn visit_pairs<V: Visitor<'de>>(&mut self, len: usize, visitor: V) -> Result<V::Value> {
+        if !len.is_multiple_of(2) {
+            return Err(Error::Syntax("expected an even number of elements".into()));
+        }
+        self.nested(|de| {
+            let mut access = Elements { de, len };
+            let value = visitor.visit_map(&mut access)?;
+            if access.len == 0 {
+                Ok(value)
+            } else {
+                Err(Error::Syntax(
+                    "array has more elements than expected".into(),
+                ))
+            }
+        })
+    }
+}
+
+fn to_str(s: &[u8]) -> Result<&str> {
+    std::str::from_utf8(s).map_err(|_| Error::Syntax("invalid UTF-8 string".into()))
+}
+
+fn parse<T: FromStr>(s: &[u8]) -> Result<T> {
+    to_str(s)?
+        .parse()
+        .map_err(|_| Error::Syntax(format!("invalid number {:?}", String::from_utf8_lossy(s))))
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.next_token()? {
+            Token::SimpleString(s) => visitor.visit_borrowed_str(to_str(s)?),
+            Token::Integer(s) => visitor.visit_i64(parse(s)?),
+            Token::BulkString(s) => match std::str::from_utf8(s) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(s),
+            },
+            Token::Array(len) => self.visit_array(len, visitor),
+            Token::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.number::<i64>()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            n => Err(Error::Syntax(format!(
+                "expected 0 or 1 for a bool, found {}",
+                n
+            ))),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.number()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.number()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.number()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.number()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.number()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.number()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.number()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.number()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(self.number()?)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(self.number()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_str(self.string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.next_token()? {
+            Token::SimpleString(s) | Token::BulkString(s) => visitor.visit_borrowed_bytes(s),
+            _ => Err(Error::Syntax("expected a string".into())),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.is_null() {
+            self.next_token()?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.next_token()? {
+            Token::Null => visitor.visit_unit(),
+            _ => Err(Error::Syntax("expected null".into())),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.array()?;
+        self.visit_array(len, visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.array()?;
+        self.visit_pairs(len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.peek_byte()? {
+            // A unit variant is just its name.
+            BULKSTRING_BYTE | SIMPLESTRING_BYTE => {
+                visitor.visit_enum(self.string()?.into_deserializer())
+            }
+            _ => match self.array()? {
+                2 => self.nested(|de| visitor.visit_enum(Variant { de })),
+                len => Err(Error::Syntax(format!(
+                    "expected an array of 2 elements for a variant, found {}",
+                    len
+                ))),
+            },
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+}
+
+/// Gives the elements of an array to a visitor, as a sequence or as key-value pairs.
+struct Elements<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    len: usize,
+}
+
+impl<'de> SeqAccess<'de> for Elements<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de> MapAccess<'de> for Elements<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        self.next_element_seed(seed)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        self.len -= 1;
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len / 2)
+    }
+}
+
+/// Reads a two-element array of a variant name and its content.
+struct Variant<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserializer::deserialize_unit(self.de, de::IgnoredAny).map(|_| ())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
@@ -1,28 +1,64 @@
 use serde::{de, ser};
 
 use std::fmt::{self, Display};
-
+use std::io;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-
 #[derive(Debug)]
 pub enum Error {
     Message(String),
+    /// Reading or writing the RESP data failed
+    Io(io::Error),
+    /// The input ended in the middle of a value
+    Eof,
+    /// The input is not valid RESP or does not have the expected type
+    Syntax(String),
+    /// The input continues after the value
+    TrailingBytes,
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::Message(ref msg) => write!(f, "{}", msg),
+            Error::Io(ref e) => write!(f, "IO error: {}", e),
+            Error::Eof => write!(f, "unexpected end of input"),
+            Error::Syntax(ref msg) => write!(f, "invalid RESP: {}", msg),
+            Error::TrailingBytes => write!(f, "trailing bytes after the value"),
         }
     }
 }
 
 impl ser::Error for Error {
-    fn custom<T>(msg:T) -> Self where T:Display {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
         Error::Message(msg.to_string())
     }
-}
\ No newline at end of file
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -1,3 +1,7 @@
 mod de;
+mod error;
 mod ser;
-mod error;
\ No newline at end of file
+
+pub use crate::de::{from_bytes, from_reader, Deserializer};
+pub use crate::error::{Error, Result};
+pub use crate::ser::{to_bytes, to_writer, Serializer};
@@ -0,0 +1,447 @@
+//! Serialization of Rust values into RESP.
+//!
+//! Values are mapped onto the RESP2 types:
+//!
+//! - integers and booleans become integers, except `u64` values above `i64::MAX`,
+//!   which become bulk strings holding the decimal number
+//! - floats, chars, strings and bytes become bulk strings
+//! - `None`, `()` and unit structs become the null bulk string
+//! - sequences and tuples become arrays
+//! - maps and structs become arrays of alternating keys and values, like the reply
+//!   of `HGETALL`
+//! - unit variants become a bulk string of the variant name, and the other variants
+//!   a two-element array of the name and the content
+
+use crate::error::{Error, Result};
+use redis_protocol::{ARRAY_BYTE, BULKSTRING_BYTE, INTEGER_BYTE, NULL};
+use serde::ser::{self, Serialize};
+use std::io::Write;
+
+/// Serializes `value` as RESP into `writer`.
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(writer);
+    value.serialize(&mut serializer)?;
+    serializer.writer.flush()?;
+    Ok(())
+}
+
+/// Serializes `value` as RESP into a byte vector.
+pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::new(Vec::new());
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+pub struct Serializer<W> {
+    writer: W,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Serializer { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_line(&mut self, prefix: u8, line: &[u8]) -> Result<()> {
+        self.writer.write_all(&[prefix])?;
+        self.writer.write_all(line)?;
+        self.writer.write_all(b"\r\n")?;
+        Ok(())
+    }
+
+    fn write_integer(&mut self, n: i64) -> Result<()> {
+        self.write_line(INTEGER_BYTE, n.to_string().as_bytes())
+    }
+
+    fn write_bulk(&mut self, data: &[u8]) -> Result<()> {
+        self.write_line(BULKSTRING_BYTE, data.len().to_string().as_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(b"\r\n")?;
+        Ok(())
+    }
+
+    fn write_array_header(&mut self, len: usize) -> Result<()> {
+        self.write_line(ARRAY_BYTE, len.to_string().as_bytes())
+    }
+
+    fn write_null(&mut self) -> Result<()> {
+        self.writer.write_all(NULL.as_bytes())?;
+        Ok(())
+    }
+
+    /// Starts an array of `len` elements, or of as many elements as are serialized if
+    /// `len` is unknown.
+    fn compound(&mut self, len: Option<usize>) -> Result<Compound<'_, W>> {
+        match len {
+            Some(len) => {
+                self.write_array_header(len)?;
+                Ok(Compound::Direct {
+                    ser: self,
+                    expected: len,
+                    len: 0,
+                })
+            }
+            None => Ok(Compound::Buffered {
+                ser: self,
+                items: Serializer::new(Vec::new()),
+                len: 0,
+            }),
+        }
+    }
+
+    /// Starts the two-element array of a variant with its content of `len` elements.
+    fn variant_compound(&mut self, variant: &'static str, len: usize) -> Result<Compound<'_, W>> {
+        self.write_array_header(2)?;
+        self.write_bulk(variant.as_bytes())?;
+        self.compound(Some(len))
+    }
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, W>;
+    type SerializeTuple = Compound<'a, W>;
+    type SerializeTupleStruct = Compound<'a, W>;
+    type SerializeTupleVariant = Compound<'a, W>;
+    type SerializeMap = Compound<'a, W>;
+    type SerializeStruct = Compound<'a, W>;
+    type SerializeStructVariant = Compound<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.write_integer(v as i64)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.write_integer(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.write_integer(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.write_integer(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.write_integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.write_integer(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.write_integer(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.write_integer(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        match i64::try_from(v) {
+            Ok(v) => self.write_integer(v),
+            Err(_) => self.write_bulk(v.to_string().as_bytes()),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.write_bulk(v.to_string().as_bytes())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.write_bulk(v.encode_utf8(&mut [0; 4]).as_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write_bulk(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write_bulk(v)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.write_null()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.write_null()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.write_null()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.write_bulk(variant.as_bytes())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.write_array_header(2)?;
+        self.write_bulk(variant.as_bytes())?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.compound(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.compound(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.compound(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.variant_compound(variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.compound(len.map(|len| len * 2))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.compound(Some(len * 2))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.variant_compound(variant, len * 2)
+    }
+}
+
+/// Serializes the elements of an array.
+///
+/// The header of an array holds its length, so the elements of a sequence or map
+/// of unknown length are buffered until the end.
+pub enum Compound<'a, W> {
+    /// The header is written and the elements go straight to the writer.
+    Direct {
+        ser: &'a mut Serializer<W>,
+        expected: usize,
+        len: usize,
+    },
+    /// The length is unknown and the elements are written after the header at the end.
+    Buffered {
+        ser: &'a mut Serializer<W>,
+        items: Serializer<Vec<u8>>,
+        len: usize,
+    },
+}
+
+impl<W: Write> Compound<'_, W> {
+    fn element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self {
+            Compound::Direct { ser, len, .. } => {
+                value.serialize(&mut **ser)?;
+                *len += 1;
+            }
+            Compound::Buffered { items, len, .. } => {
+                value.serialize(items)?;
+                *len += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Compound::Direct { expected, len, .. } if len != expected => Err(Error::Message(
+                format!("expected {} elements but got {}", expected, len),
+            )),
+            Compound::Direct { .. } => Ok(()),
+            Compound::Buffered { ser, items, len } => {
+                ser.write_array_header(len)?;
+                ser.writer.write_all(&items.writer)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<W: Write> ser::SerializeSeq for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTuple for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeMap for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(key)?;
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStructVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(key)?;
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
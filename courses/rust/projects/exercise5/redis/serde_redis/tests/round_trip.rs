@@ -0,0 +1,159 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_redis::{from_bytes, from_reader, to_bytes, to_writer, Error};
+use std::collections::BTreeMap;
+
+// The moves of exercises 1 to 3
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+enum Move {
+    Left { steps: u8 },
+    Right { steps: u8 },
+    Up { steps: u8 },
+    Down { steps: u8 },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Game {
+    name: String,
+    moves: Vec<Move>,
+    best: Option<Move>,
+    worst: Option<Move>,
+    scores: BTreeMap<String, u64>,
+    state: State,
+    origin: (i32, i32),
+    speed: f64,
+    finished: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum State {
+    Running,
+    Paused(u32),
+    Over(String, u8),
+}
+
+fn game() -> Game {
+    Game {
+        name: "maze\r\n2".to_owned(),
+        moves: vec![
+            Move::Left { steps: 3 },
+            Move::Up { steps: 0 },
+            Move::Right { steps: 255 },
+            Move::Down { steps: 1 },
+        ],
+        best: Some(Move::Up { steps: 7 }),
+        worst: None,
+        scores: [("alice".to_owned(), 1), ("bob".to_owned(), u64::MAX)]
+            .into_iter()
+            .collect(),
+        state: State::Over("lost".to_owned(), 2),
+        origin: (-4, 9),
+        speed: 0.1,
+        finished: true,
+    }
+}
+
+// Should write a move as its variant name and its fields
+#[test]
+fn move_encoding() {
+    let bytes = to_bytes(&Move::Left { steps: 3 }).unwrap();
+    assert_eq!(bytes, b"*2\r\n$4\r\nLeft\r\n*2\r\n$5\r\nsteps\r\n:3\r\n");
+    assert_eq!(from_bytes::<Move>(&bytes).unwrap(), Move::Left { steps: 3 });
+}
+
+// Should round-trip every move
+#[test]
+fn moves_round_trip() {
+    for steps in [0, 1, 42, u8::MAX] {
+        for m in [
+            Move::Left { steps },
+            Move::Right { steps },
+            Move::Up { steps },
+            Move::Down { steps },
+        ] {
+            assert_eq!(from_bytes::<Move>(&to_bytes(&m).unwrap()).unwrap(), m);
+        }
+    }
+}
+
+// Should round-trip structs, enums, sequences, maps and options
+#[test]
+fn nested_round_trip() {
+    let bytes = to_bytes(&game()).unwrap();
+    assert_eq!(from_bytes::<Game>(&bytes).unwrap(), game());
+
+    for state in [State::Running, State::Paused(5)] {
+        let bytes = to_bytes(&state).unwrap();
+        assert_eq!(from_bytes::<State>(&bytes).unwrap(), state);
+    }
+    assert_eq!(to_bytes(&State::Running).unwrap(), b"$7\r\nRunning\r\n");
+    assert_eq!(to_bytes(&None::<Move>).unwrap(), b"$-1\r\n");
+}
+
+// Should read back what was written to a writer
+#[test]
+fn writer_and_reader() {
+    let mut buf = Vec::new();
+    to_writer(&mut buf, &game().moves).unwrap();
+    let moves: Vec<Move> = from_reader(buf.as_slice()).unwrap();
+    assert_eq!(moves, game().moves);
+}
+
+// Should count the elements of sequences and maps of unknown length
+#[test]
+fn unknown_length() {
+    struct Evens(u8);
+
+    impl serde::Serialize for Evens {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            // A filtered iterator has no exact length.
+            serializer.collect_seq((0..self.0).filter(|n| n % 2 == 0))
+        }
+    }
+
+    let bytes = to_bytes(&Evens(5)).unwrap();
+    assert_eq!(bytes, b"*3\r\n:0\r\n:2\r\n:4\r\n");
+    assert_eq!(from_bytes::<Vec<u8>>(&bytes).unwrap(), vec![0, 2, 4]);
+}
+
+// Should reject truncated, trailing and mistyped input
+#[test]
+fn invalid_input() {
+    let bytes = to_bytes(&Move::Down { steps: 1 }).unwrap();
+    assert!(matches!(
+        from_bytes::<Move>(&bytes[..bytes.len() - 1]),
+        Err(Error::Eof)
+    ));
+    let mut trailing = bytes.clone();
+    trailing.extend_from_slice(b":1\r\n");
+    assert!(matches!(
+        from_bytes::<Move>(&trailing),
+        Err(Error::TrailingBytes)
+    ));
+    assert!(from_bytes::<Move>(b"$8\r\nSideways\r\n").is_err());
+    assert!(from_bytes::<u8>(b":256\r\n").is_err());
+    assert!(from_bytes::<String>(b"-ERR oops\r\n").is_err());
+}
+
+// Should reject arrays nested deeper than redis_protocol accepts without overflowing
+// the stack
+#[test]
+fn deep_nesting() {
+    #[derive(Debug, Deserialize)]
+    struct Empty {}
+
+    let nested = |depth: usize| {
+        let mut bytes = b"*1\r\n".repeat(depth);
+        bytes.extend_from_slice(b":1\r\n");
+        bytes
+    };
+    assert!(from_bytes::<serde::de::IgnoredAny>(&nested(128)).is_ok());
+    assert!(matches!(
+        from_bytes::<serde::de::IgnoredAny>(&nested(129)),
+        Err(Error::Syntax(_))
+    ));
+
+    // unknown fields are skipped with `deserialize_ignored_any`
+    let mut bytes = b"*2\r\n$7\r\nunknown\r\n".to_vec();
+    bytes.extend_from_slice(&nested(1_000_000));
+    assert!(matches!(from_bytes::<Empty>(&bytes), Err(Error::Syntax(_))));
+}
@@ -0,0 +1,60 @@
+//! The jobs accepted by the pool.
+
+/// A job the pool runs once on one of its threads.
+///
+/// Closures returning a value are callables, and other types can implement the
+/// trait to be spawned directly:
+///
+/// ```
+/// use thread_pool::{Callable, ThreadPool};
+///
+/// struct Sum(Vec<u64>);
+///
+/// impl Callable for Sum {
+///     type Output = u64;
+///
+///     fn call(self) -> u64 {
+///         self.0.iter().sum()
+///     }
+/// }
+///
+/// let pool = ThreadPool::new(2).unwrap();
+/// assert_eq!(pool.spawn(Sum(vec![1, 2, 3])).join().unwrap(), 6);
+/// ```
+pub trait Callable: Send {
+    type Output: Send;
+
+    fn call(self) -> Self::Output;
+}
+
+impl<F, T> Callable for F
+where
+    F: FnOnce() -> T + Send,
+    T: Send,
+{
+    type Output = T;
+
+    fn call(self) -> T {
+        self()
+    }
+}
+
+/// Conversion into a [`Callable`], which is what the pool accepts.
+///
+/// Every callable converts into itself, so implementing this trait is only needed
+/// for types that describe a job without being one.
+pub trait IntoCallable {
+    type Output: Send;
+    type Callable: Callable<Output = Self::Output>;
+
+    fn into_callable(self) -> Self::Callable;
+}
+
+impl<C: Callable> IntoCallable for C {
+    type Output = C::Output;
+    type Callable = C;
+
+    fn into_callable(self) -> C {
+        self
+    }
+}
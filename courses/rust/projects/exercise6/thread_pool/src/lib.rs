@@ -1 +1,22 @@
-mod thread;
\ No newline at end of file
+//! A pool of threads running closures and other [`Callable`] jobs.
+//!
+//! ```
+//! use thread_pool::ThreadPool;
+//!
+//! let pool = ThreadPool::new(4).unwrap();
+//! let handle = pool.spawn(|| 6 * 7);
+//! assert_eq!(handle.join().unwrap(), 42);
+//!
+//! let mut counts = [0; 4];
+//! pool.scope(|s| {
+//!     for (i, count) in counts.iter_mut().enumerate() {
+//!         s.spawn(move || *count = i * 10);
+//!     }
+//! });
+//! assert_eq!(counts, [0, 10, 20, 30]);
+//! ```
+
+mod callable;
+mod thread;
+pub use crate::callable::{Callable, IntoCallable};
+pub use crate::thread::{JoinHandle, Result, Scope, ScopedJoinHandle, ThreadPool};
@@ -1,49 +1,242 @@
+use crate::callable::{Callable, IntoCallable};
 use std::error::Error;
+use std::marker::PhantomData;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
 
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed set of threads running the spawned jobs in order.
+///
+/// A job that panics does not take its thread down: the panic is caught and
+/// returned by [`JoinHandle::join`]. Dropping the pool waits until the jobs already
+/// spawned have run, then stops its threads.
 #[derive(Debug)]
-struct ThreadPool {
-    pool_size: u32,
+pub struct ThreadPool {
+    // Only `None` while the pool is dropped, to disconnect the workers.
+    sender: Option<Sender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>,
 }
 
-trait Callable: FnOnce() + Send + 'static {}
+impl ThreadPool {
+    /// Starts a pool of `threads` threads waiting for jobs.
+    pub fn new(threads: u32) -> Result<Self> {
+        if threads == 0 {
+            return Err("a thread pool needs at least one thread".into());
+        }
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|i| {
+                let receiver = Arc::clone(&receiver);
+                thread::Builder::new()
+                    .name(format!("thread-pool-{}", i))
+                    .spawn(move || run_jobs(&receiver))
+            })
+            .collect::<std::io::Result<_>>()?;
+        Ok(Self {
+            sender: Some(sender),
+            workers,
+        })
+    }
+
+    /// Returns the number of threads of the pool.
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Runs `job` on one of the threads, and returns a handle to wait for its output.
+    pub fn spawn<C>(&self, job: C) -> JoinHandle<C::Output>
+    where
+        C: IntoCallable,
+        C::Callable: 'static,
+        C::Output: 'static,
+    {
+        let callable = job.into_callable();
+        let (tx, rx) = mpsc::channel();
+        self.execute(Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| callable.call()));
+            // The handle may have been dropped, nobody waits for the output then.
+            let _ = tx.send(result);
+        }));
+        JoinHandle { rx }
+    }
 
-trait IntoCallable {
-    fn into_callable(self) -> Box<dyn FnOnce() + Send + 'static>;
+    /// Runs `f` with a scope whose jobs may borrow from the caller's stack, and
+    /// returns once every job spawned in the scope has run.
+    ///
+    /// Like [`std::thread::scope`], this panics if a job panicked and its handle
+    /// was not joined. Waiting for the jobs blocks the calling thread, so calling
+    /// this from a job of the same pool can deadlock once every thread waits.
+    pub fn scope<'env, F, T>(&self, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        let scope = Scope {
+            pool: self,
+            state: Arc::new(ScopeState::default()),
+            scope: PhantomData,
+            env: PhantomData,
+        };
+        // The jobs must not outlive the borrows they hold, even if `f` panics.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.state.wait();
+        match result {
+            Err(payload) => panic::resume_unwind(payload),
+            Ok(_) if scope.state.unjoined_panics.load(Ordering::SeqCst) > 0 => {
+                panic!("a scoped job panicked")
+            }
+            Ok(result) => result,
+        }
+    }
+
+    fn execute(&self, job: Job) {
+        self.sender
+            .as_ref()
+            .expect("the pool is running")
+            .send(job)
+            .expect("the pool threads do not exit while the pool is alive");
+    }
 }
 
-#[derive(Debug)]
-struct Job<F> where F: Callable {
-    inner: F,
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // The workers run the queued jobs, then see the channel disconnected.
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
 }
 
-impl <F: Callable> IntoCallable for F {
-    fn into_callable(self) -> Box<dyn FnOnce() + Send + 'static> {
-        Box::new(self)
+fn run_jobs(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        // The lock is released before the job runs, so the others can take jobs.
+        let job = receiver
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => return,
+        }
     }
 }
 
-impl <F: Callable> IntoCallable for Job<F> {
-    fn into_callable(self) -> Box<dyn FnOnce() + Send + 'static> {
-        Box::new(self.inner)
+/// A handle to wait for the output of a job spawned on a [`ThreadPool`].
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    rx: Receiver<thread::Result<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Waits for the job to finish and returns its output, or the payload of its
+    /// panic.
+    pub fn join(self) -> thread::Result<T> {
+        self.rx.recv().expect("every spawned job sends its result")
     }
 }
 
-impl<F: Callable> From<F> for Job<F> {
-    fn from(f: F) -> Self {
-        Self {
-            inner: f,
+#[derive(Debug, Default)]
+struct ScopeState {
+    running: Mutex<usize>,
+    finished: Condvar,
+    unjoined_panics: AtomicUsize,
+}
+
+impl ScopeState {
+    fn start(&self) {
+        *self.running.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+    }
+
+    fn finish(&self) {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        *running -= 1;
+        if *running == 0 {
+            self.finished.notify_all();
+        }
+    }
+
+    fn wait(&self) {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        while *running > 0 {
+            running = self
+                .finished
+                .wait(running)
+                .unwrap_or_else(PoisonError::into_inner);
         }
     }
 }
 
-pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+/// A scope to spawn jobs borrowing data that outlives it, created by
+/// [`ThreadPool::scope`].
+#[derive(Debug)]
+pub struct Scope<'scope, 'env: 'scope> {
+    pool: &'scope ThreadPool,
+    state: Arc<ScopeState>,
+    // Invariant lifetimes, as in `std::thread::Scope`.
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
 
-impl ThreadPool {
-    pub fn new(threads: u32) -> Result<Self> {
-        Ok(Self {pool_size: threads})
+impl<'scope> Scope<'scope, '_> {
+    /// Runs `job` on one of the threads of the pool, and returns a handle to wait
+    /// for its output.
+    pub fn spawn<C>(&'scope self, job: C) -> ScopedJoinHandle<'scope, C::Output>
+    where
+        C: IntoCallable,
+        C::Callable: 'scope,
+        C::Output: 'scope,
+    {
+        let callable = job.into_callable();
+        let (tx, rx) = mpsc::channel();
+        let state = Arc::clone(&self.state);
+        state.start();
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| callable.call()));
+            if result.is_err() {
+                state.unjoined_panics.fetch_add(1, Ordering::SeqCst);
+            }
+            let _ = tx.send(result);
+            // Everything borrowing from the scope is gone before it may end.
+            drop(tx);
+            state.finish();
+        });
+        // SAFETY: `ThreadPool::scope` does not return before `finish` is called by
+        // every job of the scope, so the job never runs or holds its borrows after
+        // `'scope` ends.
+        let job = unsafe { mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Job>(job) };
+        self.pool.execute(job);
+        ScopedJoinHandle {
+            rx,
+            state: Arc::clone(&self.state),
+            scope: PhantomData,
+        }
     }
+}
 
-    pub fn spawn<F>(&self, job: impl IntoCallable)  where F: Callable{
-        std::thread::spawn(job.into_callable());
+/// A handle to wait for the output of a job spawned in a [`Scope`].
+#[derive(Debug)]
+pub struct ScopedJoinHandle<'scope, T> {
+    rx: Receiver<thread::Result<T>>,
+    state: Arc<ScopeState>,
+    scope: PhantomData<&'scope ()>,
+}
+
+impl<T> ScopedJoinHandle<'_, T> {
+    /// Waits for the job to finish and returns its output, or the payload of its
+    /// panic.
+    pub fn join(self) -> thread::Result<T> {
+        let result = self.rx.recv().expect("every spawned job sends its result");
+        if result.is_err() {
+            self.state.unjoined_panics.fetch_sub(1, Ordering::SeqCst);
+        }
+        result
     }
 }
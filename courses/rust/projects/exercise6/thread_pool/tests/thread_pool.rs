@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+use thread_pool::{Callable, IntoCallable, ThreadPool};
+
+struct Square(u64);
+
+impl Callable for Square {
+    type Output = u64;
+
+    fn call(self) -> u64 {
+        self.0 * self.0
+    }
+}
+
+// Describes a job without being one.
+struct Greeting<'a> {
+    name: &'a str,
+}
+
+impl<'a> IntoCallable for Greeting<'a> {
+    type Output = String;
+    type Callable = Box<dyn FnOnce() -> String + Send + 'a>;
+
+    fn into_callable(self) -> Self::Callable {
+        let name = self.name;
+        Box::new(move || format!("Hello, {}!", name))
+    }
+}
+
+// Should refuse a pool without threads
+#[test]
+fn no_threads() {
+    assert!(ThreadPool::new(0).is_err());
+    assert_eq!(ThreadPool::new(3).unwrap().size(), 3);
+}
+
+// Should return the typed output of closures and callable objects
+#[test]
+fn typed_outputs() {
+    let pool = ThreadPool::new(2).unwrap();
+    let text = pool.spawn(|| "done".to_owned());
+    let square = pool.spawn(Square(12));
+    let greeting = pool.spawn(Greeting { name: "pool" });
+    assert_eq!(text.join().unwrap(), "done");
+    assert_eq!(square.join().unwrap(), 144);
+    assert_eq!(greeting.join().unwrap(), "Hello, pool!");
+}
+
+// Should run jobs on several threads at once
+#[test]
+fn runs_concurrently() {
+    let pool = ThreadPool::new(4).unwrap();
+    let barrier = Arc::new(Barrier::new(4));
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let barrier = Arc::clone(&barrier);
+            // Only returns once four jobs run at the same time.
+            pool.spawn(move || {
+                barrier.wait();
+                thread::current().id()
+            })
+        })
+        .collect();
+    let threads: HashSet<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(threads.len(), 4);
+}
+
+// Should keep its threads when jobs panic
+#[test]
+fn survives_panics() {
+    let pool = ThreadPool::new(2).unwrap();
+    let panics: Vec<_> = (0..4)
+        .map(|_| pool.spawn(|| panic!("job failed")))
+        .collect();
+    for handle in panics {
+        let payload = handle.join().unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"job failed"));
+    }
+    let outputs: Vec<_> = (0..4).map(|i| pool.spawn(move || i)).collect();
+    let outputs: Vec<_> = outputs.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(outputs, vec![0, 1, 2, 3]);
+}
+
+// Should run the queued jobs before the threads stop on drop
+#[test]
+fn shutdown_on_drop() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let pool = ThreadPool::new(2).unwrap();
+    for _ in 0..16 {
+        let count = Arc::clone(&count);
+        pool.spawn(move || {
+            thread::sleep(Duration::from_millis(5));
+            count.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    drop(pool);
+    assert_eq!(count.load(Ordering::SeqCst), 16);
+    assert_eq!(Arc::strong_count(&count), 1);
+}
+
+// Should let scoped jobs borrow from the stack
+#[test]
+fn scoped_borrows() {
+    let pool = ThreadPool::new(3).unwrap();
+    let numbers: Vec<u64> = (1..=100).collect();
+    let mut halves = [0, 0];
+    let total = pool.scope(|s| {
+        let (low, high) = numbers.split_at(50);
+        let [a, b] = &mut halves;
+        s.spawn(move || *a = low.iter().sum());
+        s.spawn(move || *b = high.iter().sum());
+        let greeting = s.spawn(Greeting { name: "scope" });
+        assert_eq!(greeting.join().unwrap(), "Hello, scope!");
+        s.spawn(|| numbers.len()).join().unwrap()
+    });
+    assert_eq!(total, 100);
+    assert_eq!(halves, [1275, 3775]);
+}
+
+// Should panic at the end of a scope whose panicking job was not joined
+#[test]
+fn scoped_panics() {
+    let pool = ThreadPool::new(2).unwrap();
+    let joined = pool.scope(|s| s.spawn(|| panic!("joined")).join().is_err());
+    assert!(joined);
+
+    let finished = AtomicUsize::new(0);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        pool.scope(|s| {
+            s.spawn(|| panic!("not joined"));
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                finished.fetch_add(1, Ordering::SeqCst);
+            });
+        })
+    }));
+    assert!(result.is_err());
+    // The scope waited for the other job before panicking.
+    assert_eq!(finished.load(Ordering::SeqCst), 1);
+    assert_eq!(pool.spawn(|| 1).join().unwrap(), 1);
+}
@@ -0,0 +1,90 @@
+use criterion::{criterion_group, criterion_main, Bencher, Criterion, ParameterizedBenchmark};
+use kvs::thread_pool::*;
+use kvs::{KvStore, KvsEngine, KvsError, SledKvsEngine};
+use rand::prelude::*;
+use tempfile::TempDir;
+use tokio::prelude::*;
+
+const KEY_NUM: usize = 1000;
+const OP_NUM: usize = 1000;
+const THREADS: u32 = 4;
+
+// Sets `KEY_NUM` keys, then measures `OP_NUM` concurrent operations on them of which
+// `read_percent` percent are reads and the rest are overwrites.
+fn mixed_workload<E: KvsEngine>(b: &mut Bencher, engine: E, read_percent: u32) {
+    let keys: Vec<String> = (0..KEY_NUM).map(|i| format!("key{}", i)).collect();
+    let sets: Vec<_> = keys
+        .iter()
+        .map(|key| engine.set(key.clone(), "value".to_owned()))
+        .collect();
+    future::join_all(sets).wait().unwrap();
+
+    let mut rng = SmallRng::seed_from_u64(0);
+    let ops: Vec<(bool, String)> = (0..OP_NUM)
+        .map(|_| {
+            let key = keys.choose(&mut rng).unwrap().clone();
+            (rng.gen_range(0, 100) < read_percent, key)
+        })
+        .collect();
+    b.iter(|| {
+        let futures: Vec<Box<dyn Future<Item = (), Error = KvsError> + Send>> = ops
+            .iter()
+            .map(|(is_read, key)| {
+                if *is_read {
+                    Box::new(engine.get(key.clone()).map(|_| ()))
+                        as Box<dyn Future<Item = (), Error = KvsError> + Send>
+                } else {
+                    engine.set(key.clone(), "new_value".to_owned())
+                }
+            })
+            .collect();
+        future::join_all(futures).wait().unwrap();
+    })
+}
+
+fn engine_bench(c: &mut Criterion) {
+    let read_percents = vec![0, 50, 90, 100];
+    c.bench(
+        "engine",
+        ParameterizedBenchmark::new(
+            "kvs",
+            |b, &read_percent| {
+                let temp_dir = TempDir::new().unwrap();
+                let engine = KvStore::<RayonThreadPool>::open(temp_dir.path(), THREADS).unwrap();
+                mixed_workload(b, engine, read_percent)
+            },
+            read_percents,
+        )
+        .with_function("sled", |b, &read_percent| {
+            let temp_dir = TempDir::new().unwrap();
+            let db = sled::Db::start_default(temp_dir.path()).unwrap();
+            let engine = SledKvsEngine::<RayonThreadPool>::new(db, THREADS).unwrap();
+            mixed_workload(b, engine, read_percent)
+        }),
+    );
+}
+
+// Measures `KvStore` with a 50% read mix on each thread pool.
+fn kvs_thread_pool<P: ThreadPool>(b: &mut Bencher, threads: &u32) {
+    let temp_dir = TempDir::new().unwrap();
+    let engine = KvStore::<P>::open(temp_dir.path(), *threads).unwrap();
+    mixed_workload(b, engine, 50)
+}
+
+fn thread_pool_bench(c: &mut Criterion) {
+    let threads = vec![1, 2, 4, 8];
+    c.bench(
+        "kvs_thread_pool",
+        ParameterizedBenchmark::new(
+            "shared_queue",
+            kvs_thread_pool::<SharedQueueThreadPool>,
+            threads,
+        )
+        .with_function("naive", kvs_thread_pool::<NaiveThreadPool>)
+        .with_function("rayon", kvs_thread_pool::<RayonThreadPool>)
+        .with_function("work_stealing", kvs_thread_pool::<WorkStealingThreadPool>),
+    );
+}
+
+criterion_group!(benches, engine_bench, thread_pool_bench);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+use criterion::{criterion_group, criterion_main, Bencher, Criterion, ParameterizedBenchmark};
+use crossbeam_utils::sync::WaitGroup;
+use kvs::thread_pool::*;
+
+const JOB_NUM: usize = 1000;
+
+fn fib(n: u32) -> u64 {
+    if n < 2 {
+        u64::from(n)
+    } else {
+        fib(n - 1) + fib(n - 2)
+    }
+}
+
+// Spawns small CPU bound jobs and waits for all of them to finish.
+fn spawn_jobs<P: ThreadPool>(b: &mut Bencher, threads: &u32) {
+    let pool = P::new(*threads).unwrap();
+    b.iter(|| {
+        let wg = WaitGroup::new();
+        for _ in 0..JOB_NUM {
+            let wg = wg.clone();
+            pool.spawn(move || {
+                criterion::black_box(fib(15));
+                drop(wg);
+            });
+        }
+        wg.wait();
+    })
+}
+
+fn thread_pool_bench(c: &mut Criterion) {
+    let threads = vec![1, 2, 4, 8];
+    c.bench(
+        "thread_pool",
+        ParameterizedBenchmark::new("naive", spawn_jobs::<NaiveThreadPool>, threads)
+            .with_function("shared_queue", spawn_jobs::<SharedQueueThreadPool>)
+            .with_function("rayon", spawn_jobs::<RayonThreadPool>)
+            .with_function("work_stealing", spawn_jobs::<WorkStealingThreadPool>),
+    );
+}
+
+criterion_group!(benches, thread_pool_bench);
+criterion_main!(benches);
@@ -0,0 +1,326 @@
+#[macro_use]
+extern crate clap;
+
+use kvs::{KvsClient, KvsError, Result};
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::SmallRng;
+use rand::{FromEntropy, Rng};
+use std::net::SocketAddr;
+use std::process::exit;
+use std::thread;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use tokio::prelude::*;
+
+#[derive(StructOpt, Debug)]
+#[structopt(
+    name = "kvs-bench",
+    about = "Drives a running kvs-server with get and set requests and reports the throughput and latencies"
+)]
+struct Opt {
+    #[structopt(
+        long,
+        help = "Sets the server address",
+        value_name = "IP:PORT",
+        default_value = "127.0.0.1:4000",
+        parse(try_from_str)
+    )]
+    addr: SocketAddr,
+    #[structopt(
+        long,
+        help = "Sets the number of concurrent clients",
+        value_name = "N",
+        default_value = "8"
+    )]
+    concurrency: u32,
+    #[structopt(
+        long,
+        help = "Sets the total number of requests",
+        value_name = "N",
+        default_value = "10000"
+    )]
+    requests: u64,
+    #[structopt(
+        long,
+        help = "Sets the number of distinct keys",
+        value_name = "N",
+        default_value = "1000"
+    )]
+    keys: u64,
+    #[structopt(
+        long,
+        help = "Sets the distribution of the requested keys",
+        value_name = "DISTRIBUTION",
+        default_value = "uniform",
+        raw(possible_values = "&KeyDistribution::variants()")
+    )]
+    distribution: KeyDistribution,
+    #[structopt(
+        long = "zipf-theta",
+        help = "Sets the skew of the zipfian distribution, between 0 and 1",
+        value_name = "THETA",
+        default_value = "0.99"
+    )]
+    zipf_theta: f64,
+    #[structopt(
+        long = "value-size",
+        help = "Sets the size of the values in bytes",
+        value_name = "BYTES",
+        default_value = "100"
+    )]
+    value_size: usize,
+    #[structopt(
+        long = "read-ratio",
+        help = "Sets the fraction of requests which are gets",
+        value_name = "RATIO",
+        default_value = "0.5"
+    )]
+    read_ratio: f64,
+    #[structopt(
+        long = "no-load",
+        help = "Skips setting every key before the benchmark"
+    )]
+    no_load: bool,
+}
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    enum KeyDistribution {
+        uniform,
+        zipfian
+    }
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    if let Err(e) = run(opt) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+fn run(opt: Opt) -> Result<()> {
+    if opt.concurrency == 0 || opt.keys == 0 {
+        return Err(KvsError::StringError(
+            "Concurrency and the number of keys must be positive".to_owned(),
+        ));
+    }
+    if !(0.0..=1.0).contains(&opt.read_ratio) {
+        return Err(KvsError::StringError(
+            "The read ratio must be between 0 and 1".to_owned(),
+        ));
+    }
+    let keys = match opt.distribution {
+        KeyDistribution::uniform => Keys::Uniform(Uniform::new(0, opt.keys)),
+        KeyDistribution::zipfian => {
+            if !(opt.zipf_theta > 0.0 && opt.zipf_theta < 1.0) {
+                return Err(KvsError::StringError(
+                    "The zipfian theta must be between 0 and 1".to_owned(),
+                ));
+            }
+            Keys::Zipfian(Zipfian::new(opt.keys, opt.zipf_theta))
+        }
+    };
+    let value = "v".repeat(opt.value_size);
+
+    if !opt.no_load {
+        load(opt.addr, opt.keys, &value)?;
+    }
+
+    let start = Instant::now();
+    let handles: Vec<_> = (0..opt.concurrency)
+        .map(|i| {
+            // spread the remainder over the first clients
+            let requests = opt.requests / u64::from(opt.concurrency)
+                + (u64::from(i) < opt.requests % u64::from(opt.concurrency)) as u64;
+            let worker = Worker {
+                addr: opt.addr,
+                keys: keys.clone(),
+                value: value.clone(),
+                read_ratio: opt.read_ratio,
+            };
+            thread::spawn(move || worker.run(requests))
+        })
+        .collect();
+    let mut report = Report::default();
+    for handle in handles {
+        let worker_report = handle
+            .join()
+            .map_err(|_| KvsError::StringError("Benchmark thread panicked".to_owned()))??;
+        report.merge(worker_report);
+    }
+    report.print(start.elapsed());
+    Ok(())
+}
+
+fn load(addr: SocketAddr, keys: u64, value: &str) -> Result<()> {
+    let mut client = KvsClient::connect(addr).wait()?;
+    for i in 0..keys {
+        client = client.set(key_name(i), value.to_owned()).wait()?;
+    }
+    Ok(())
+}
+
+fn key_name(i: u64) -> String {
+    format!("key{}", i)
+}
+
+/// Generates the keys of the requests.
+#[derive(Clone)]
+enum Keys {
+    Uniform(Uniform<u64>),
+    Zipfian(Zipfian),
+}
+
+impl Distribution<u64> for Keys {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        match self {
+            Keys::Uniform(uniform) => uniform.sample(rng),
+            Keys::Zipfian(zipfian) => zipfian.sample(rng),
+        }
+    }
+}
+
+/// Zipfian distribution over `[0, n)` where small numbers are the most popular.
+///
+/// It uses the algorithm from "Quickly Generating Billion-Record Synthetic Databases"
+/// by Gray et al., which is also used by YCSB.
+#[derive(Clone)]
+struct Zipfian {
+    n: u64,
+    theta: f64,
+    alpha: f64,
+    zetan: f64,
+    eta: f64,
+}
+
+impl Zipfian {
+    fn new(n: u64, theta: f64) -> Zipfian {
+        let zeta2 = zeta(2.min(n), theta);
+        let zetan = zeta(n, theta);
+        Zipfian {
+            n,
+            theta,
+            alpha: 1.0 / (1.0 - theta),
+            zetan,
+            eta: (1.0 - (2.0 / n as f64).powf(1.0 - theta)) / (1.0 - zeta2 / zetan),
+        }
+    }
+}
+
+fn zeta(n: u64, theta: f64) -> f64 {
+    (1..=n).map(|i| 1.0 / (i as f64).powf(theta)).sum()
+}
+
+impl Distribution<u64> for Zipfian {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        let u: f64 = rng.gen();
+        let uz = u * self.zetan;
+        if uz < 1.0 {
+            return 0;
+        }
+        if uz < 1.0 + 0.5f64.powf(self.theta) {
+            return 1.min(self.n - 1);
+        }
+        let rank = (self.n as f64 * (self.eta * u - self.eta + 1.0).powf(self.alpha)) as u64;
+        rank.min(self.n - 1)
+    }
+}
+
+/// A client sending requests in a loop.
+struct Worker {
+    addr: SocketAddr,
+    keys: Keys,
+    value: String,
+    read_ratio: f64,
+}
+
+impl Worker {
+    fn run(self, requests: u64) -> Result<Report> {
+        let mut rng = SmallRng::from_entropy();
+        let mut report = Report::default();
+        let mut client = Some(KvsClient::connect(self.addr).wait()?);
+        for _ in 0..requests {
+            // The client is consumed by a failed request, so reconnect.
+            let conn = match client.take() {
+                Some(conn) => conn,
+                None => KvsClient::connect(self.addr).wait()?,
+            };
+            let key = key_name(self.keys.sample(&mut rng));
+            let is_get = rng.gen_bool(self.read_ratio);
+            let start = Instant::now();
+            let res = if is_get {
+                conn.get(key).map(|(_, conn)| conn).wait()
+            } else {
+                conn.set(key, self.value.clone()).wait()
+            };
+            let latency = start.elapsed();
+            match res {
+                Ok(conn) => {
+                    client = Some(conn);
+                    if is_get {
+                        report.gets += 1;
+                    } else {
+                        report.sets += 1;
+                    }
+                    report.latencies.push(latency);
+                }
+                Err(e) => {
+                    eprintln!("Request failed: {}", e);
+                    report.errors += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    gets: u64,
+    sets: u64,
+    errors: u64,
+    // latencies of the successful requests
+    latencies: Vec<Duration>,
+}
+
+impl Report {
+    fn merge(&mut self, other: Report) {
+        self.gets += other.gets;
+        self.sets += other.sets;
+        self.errors += other.errors;
+        self.latencies.extend(other.latencies);
+    }
+
+    fn print(mut self, elapsed: Duration) {
+        let ops = self.gets + self.sets;
+        let secs = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        println!(
+            "Requests: {} ({} get, {} set), errors: {}",
+            ops, self.gets, self.sets, self.errors
+        );
+        println!("Elapsed: {:.3}s", secs);
+        println!("Throughput: {:.1} ops/s", ops as f64 / secs);
+        if self.latencies.is_empty() {
+            return;
+        }
+        self.latencies.sort_unstable();
+        let micros = |d: Duration| d.as_secs() * 1_000_000 + u64::from(d.subsec_micros());
+        let percentile = |p: f64| {
+            let index = ((self.latencies.len() as f64 * p).ceil() as usize).max(1) - 1;
+            micros(self.latencies[index])
+        };
+        let total: u64 = self.latencies.iter().map(|&d| micros(d)).sum();
+        println!(
+            "Latency (us): min {}, avg {}, p50 {}, p90 {}, p99 {}, p99.9 {}, max {}",
+            micros(self.latencies[0]),
+            total / self.latencies.len() as u64,
+            percentile(0.5),
+            percentile(0.9),
+            percentile(0.99),
+            percentile(0.999),
+            micros(self.latencies[self.latencies.len() - 1]),
+        );
+    }
+}
@@ -1,95 +0,0 @@
-use clap::AppSettings;
-use kvs::{KvsClient, Result};
-use std::net::SocketAddr;
-use std::process::exit;
-use structopt::StructOpt;
-use tokio::prelude::*;
-
-#[derive(StructOpt, Debug)]
-#[structopt(
-    name = "kvs-client",
-    raw(global_settings = "&[\
-                           AppSettings::DisableHelpSubcommand,\
-                           AppSettings::VersionlessSubcommands]")
-)]
-struct Opt {
-    #[structopt(subcommand)]
-    command: Command,
-}
-
-#[derive(StructOpt, Debug)]
-enum Command {
-    #[structopt(name = "get", about = "Get the string value of a given string key")]
-    Get {
-        #[structopt(name = "KEY", help = "A string key")]
-        key: String,
-        #[structopt(
-            long,
-            help = "Sets the server address",
-            value_name = "IP:PORT",
-            default_value = "127.0.0.1:4000",
-            parse(try_from_str)
-        )]
-        addr: SocketAddr,
-    },
-    #[structopt(name = "set", about = "Set the value of a string key to a string")]
-    Set {
-        #[structopt(name = "KEY", help = "A string key")]
-        key: String,
-        #[structopt(name = "VALUE", help = "The string value of the key")]
-        value: String,
-        #[structopt(
-            long,
-            help = "Sets the server address",
-            value_name = "IP:PORT",
-            default_value = "127.0.0.1:4000",
-            parse(try_from_str)
-        )]
-        addr: SocketAddr,
-    },
-    #[structopt(name = "rm", about = "Remove a given string key")]
-    Remove {
-        #[structopt(name = "KEY", help = "A string key")]
-        key: String,
-        #[structopt(
-            long,
-            help = "Sets the server address",
-            value_name = "IP:PORT",
-            default_value = "127.0.0.1:4000",
-            parse(try_from_str)
-        )]
-        addr: SocketAddr,
-    },
-}
-
-fn main() {
-    let opt = Opt::from_args();
-    if let Err(e) = run(opt) {
-        eprintln!("{}", e);
-        exit(1);
-    }
-}
-
-fn run(opt: Opt) -> Result<()> {
-    match opt.command {
-        Command::Get { key, addr } => {
-            let client = KvsClient::connect(addr);
-            if let (Some(value), _) = client.and_then(move |client| client.get(key)).wait()? {
-                println!("{}", value);
-            } else {
-                println!("Key not found");
-            }
-        }
-        Command::Set { key, value, addr } => {
-            let client = KvsClient::connect(addr);
-            client
-                .and_then(move |client| client.set(key, value))
-                .wait()?;
-        }
-        Command::Remove { key, addr } => {
-            let client = KvsClient::connect(addr);
-            client.and_then(move |client| client.remove(key)).wait()?;
-        }
-    }
-    Ok(())
-}
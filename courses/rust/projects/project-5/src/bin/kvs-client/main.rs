@@ -0,0 +1,415 @@
+#[macro_use]
+extern crate clap;
+
+use clap::AppSettings;
+use kvs::{Event, KvsClient, Result, ShardedKvsClient};
+use shell::{Format, Session};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::exit;
+use structopt::StructOpt;
+use tokio::prelude::*;
+use transfer::FileFormat;
+
+mod shell;
+mod transfer;
+
+#[derive(StructOpt, Debug)]
+#[structopt(
+    name = "kvs-client",
+    raw(global_settings = "&[\
+                           AppSettings::DisableHelpSubcommand,\
+                           AppSettings::VersionlessSubcommands]")
+)]
+struct Opt {
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    #[structopt(name = "get", about = "Get the string value of a given string key")]
+    Get {
+        #[structopt(name = "KEY", help = "A string key")]
+        key: String,
+        #[structopt(long, help = "Sets the namespace of the key", value_name = "NAME")]
+        namespace: Option<String>,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(name = "set", about = "Set the value of a string key to a string")]
+    Set {
+        #[structopt(name = "KEY", help = "A string key")]
+        key: String,
+        #[structopt(name = "VALUE", help = "The string value of the key")]
+        value: String,
+        #[structopt(long, help = "Sets the namespace of the key", value_name = "NAME")]
+        namespace: Option<String>,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(name = "rm", about = "Remove a given string key")]
+    Remove {
+        #[structopt(name = "KEY", help = "A string key")]
+        key: String,
+        #[structopt(long, help = "Sets the namespace of the key", value_name = "NAME")]
+        namespace: Option<String>,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(
+        name = "watch",
+        about = "Print changes of keys starting with a given prefix"
+    )]
+    Watch {
+        #[structopt(name = "PREFIX", help = "A key prefix", default_value = "")]
+        prefix: String,
+        #[structopt(
+            long,
+            help = "Replays changes since the sequence number first",
+            value_name = "SEQ"
+        )]
+        from: Option<u64>,
+        #[structopt(long, help = "Sets the namespace of the key", value_name = "NAME")]
+        namespace: Option<String>,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(name = "create-namespace", about = "Create a namespace")]
+    CreateNamespace {
+        #[structopt(name = "NAME", help = "A namespace name")]
+        name: String,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(name = "drop-namespace", about = "Drop a namespace and all keys in it")]
+    DropNamespace {
+        #[structopt(name = "NAME", help = "A namespace name")]
+        name: String,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(name = "list-namespaces", about = "List all namespaces")]
+    ListNamespaces {
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(
+        name = "rebalance",
+        about = "Add a server to the shards and move the keys it takes over to it"
+    )]
+    Rebalance {
+        #[structopt(
+            name = "IP:PORT",
+            help = "The address of the new server",
+            parse(try_from_str)
+        )]
+        new_server: SocketAddr,
+        #[structopt(
+            long,
+            help = "Sets the addresses of the current shards in order",
+            value_name = "IP:PORT,...",
+            parse(try_from_str),
+            raw(use_delimiter = "true", required = "true")
+        )]
+        servers: Vec<SocketAddr>,
+        #[structopt(long, help = "Sets the namespace of the keys", value_name = "NAME")]
+        namespace: Option<String>,
+    },
+    #[structopt(
+        name = "shell",
+        about = "Run commands interactively over one connection"
+    )]
+    Shell {
+        #[structopt(
+            long,
+            help = "Sets the output format",
+            value_name = "FORMAT",
+            default_value = "text",
+            raw(possible_values = "&Format::variants()")
+        )]
+        format: Format,
+        #[structopt(long, help = "Sets the namespace of the keys", value_name = "NAME")]
+        namespace: Option<String>,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(
+        name = "batch",
+        about = "Run the commands in a file or stdin over one connection"
+    )]
+    Batch {
+        #[structopt(
+            name = "FILE",
+            help = "A file of commands, one per line; stdin if not given",
+            parse(from_os_str)
+        )]
+        file: Option<PathBuf>,
+        #[structopt(
+            long,
+            help = "Sets the output format",
+            value_name = "FORMAT",
+            default_value = "text",
+            raw(possible_values = "&Format::variants()")
+        )]
+        format: Format,
+        #[structopt(long, help = "Sets the namespace of the keys", value_name = "NAME")]
+        namespace: Option<String>,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(name = "export", about = "Write all key/value pairs to a file")]
+    Export {
+        #[structopt(name = "FILE", help = "The file to write", parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(
+            long,
+            help = "Sets the file format",
+            value_name = "FORMAT",
+            default_value = "jsonl",
+            raw(possible_values = "&FileFormat::variants()")
+        )]
+        format: FileFormat,
+        #[structopt(long, help = "Sets the namespace of the keys", value_name = "NAME")]
+        namespace: Option<String>,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(name = "import", about = "Set the key/value pairs in a file")]
+    Import {
+        #[structopt(name = "FILE", help = "The file to read", parse(from_os_str))]
+        file: PathBuf,
+        #[structopt(
+            long,
+            help = "Sets the file format",
+            value_name = "FORMAT",
+            default_value = "jsonl",
+            raw(possible_values = "&FileFormat::variants()")
+        )]
+        format: FileFormat,
+        #[structopt(
+            long = "batch-size",
+            help = "Sets the number of pairs sent in one request",
+            value_name = "N",
+            default_value = "1000"
+        )]
+        batch_size: usize,
+        #[structopt(long, help = "Skips the pairs imported by an interrupted import")]
+        resume: bool,
+        #[structopt(long, help = "Sets the namespace of the keys", value_name = "NAME")]
+        namespace: Option<String>,
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+    #[structopt(name = "promote", about = "Promote a replica to a primary")]
+    Promote {
+        #[structopt(
+            long,
+            help = "Sets the server address",
+            value_name = "IP:PORT",
+            default_value = "127.0.0.1:4000",
+            parse(try_from_str)
+        )]
+        addr: SocketAddr,
+    },
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    if let Err(e) = run(opt) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+fn run(opt: Opt) -> Result<()> {
+    match opt.command {
+        Command::Get {
+            key,
+            namespace,
+            addr,
+        } => {
+            let client = KvsClient::connect(addr).map(|client| client.with_namespace(namespace));
+            if let (Some(value), _) = client.and_then(move |client| client.get(key)).wait()? {
+                println!("{}", value);
+            } else {
+                println!("Key not found");
+            }
+        }
+        Command::Set {
+            key,
+            value,
+            namespace,
+            addr,
+        } => {
+            let client = KvsClient::connect(addr).map(|client| client.with_namespace(namespace));
+            client
+                .and_then(move |client| client.set(key, value))
+                .wait()?;
+        }
+        Command::Remove {
+            key,
+            namespace,
+            addr,
+        } => {
+            let client = KvsClient::connect(addr).map(|client| client.with_namespace(namespace));
+            client.and_then(move |client| client.remove(key)).wait()?;
+        }
+        Command::Watch {
+            prefix,
+            from,
+            namespace,
+            addr,
+        } => {
+            let client = KvsClient::connect(addr).map(|client| client.with_namespace(namespace));
+            client
+                .map(move |client| client.watch(prefix, from))
+                .flatten_stream()
+                .for_each(|event| {
+                    match event {
+                        Event::Set { seq, key, value } => println!("{} set {} {}", seq, key, value),
+                        Event::Remove { seq, key } => println!("{} rm {}", seq, key),
+                    }
+                    Ok(())
+                })
+                .wait()?;
+        }
+        Command::CreateNamespace { name, addr } => {
+            let client = KvsClient::connect(addr);
+            client
+                .and_then(move |client| client.create_namespace(name))
+                .wait()?;
+        }
+        Command::DropNamespace { name, addr } => {
+            let client = KvsClient::connect(addr);
+            client
+                .and_then(move |client| client.drop_namespace(name))
+                .wait()?;
+        }
+        Command::ListNamespaces { addr } => {
+            let client = KvsClient::connect(addr);
+            let (names, _) = client
+                .and_then(move |client| client.list_namespaces())
+                .wait()?;
+            for name in names {
+                println!("{}", name);
+            }
+        }
+        Command::Rebalance {
+            new_server,
+            servers,
+            namespace,
+        } => {
+            let client =
+                ShardedKvsClient::connect(servers).map(|client| client.with_namespace(namespace));
+            let (moved, _) = client
+                .and_then(move |client| client.add_server(new_server))
+                .wait()?;
+            println!("Moved {} keys to {}", moved, new_server);
+        }
+        Command::Shell {
+            format,
+            namespace,
+            addr,
+        } => shell::run_shell(Session::connect(addr, namespace, format)?)?,
+        Command::Batch {
+            file,
+            format,
+            namespace,
+            addr,
+        } => {
+            let session = Session::connect(addr, namespace, format)?;
+            match file {
+                Some(path) => shell::run_batch(session, BufReader::new(File::open(path)?))?,
+                None => shell::run_batch(session, io::stdin().lock())?,
+            }
+        }
+        Command::Export {
+            file,
+            format,
+            namespace,
+            addr,
+        } => transfer::export(addr, namespace, &file, format)?,
+        Command::Import {
+            file,
+            format,
+            batch_size,
+            resume,
+            namespace,
+            addr,
+        } => transfer::import(addr, namespace, &file, format, batch_size, resume)?,
+        Command::Promote { addr } => {
+            let client = KvsClient::connect(addr);
+            client.and_then(move |client| client.promote()).wait()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,343 @@
+use kvs::{KvsClient, KvsError, Result};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::{Context, Editor, Helper};
+use serde_json::json;
+use std::env;
+use std::io::BufRead;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::prelude::*;
+
+const HISTORY_FILE: &str = ".kvs_history";
+
+const COMMANDS: &[&str] = &[
+    "get",
+    "set",
+    "rm",
+    "use",
+    "create-namespace",
+    "drop-namespace",
+    "list-namespaces",
+    "help",
+    "exit",
+];
+
+const HELP: &str = "\
+get KEY                 Get the string value of a given string key
+set KEY VALUE           Set the value of a string key to a string
+rm KEY                  Remove a given string key
+use [NAME]              Operate on the namespace, or the default one without NAME
+create-namespace NAME   Create a namespace
+drop-namespace NAME     Drop a namespace and all keys in it
+list-namespaces         List all namespaces
+help                    Print this message
+exit                    Leave the shell
+
+Arguments containing spaces can be quoted with \"\". Lines starting with # are ignored.";
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum Format {
+        text,
+        json
+    }
+}
+
+/// Runs commands typed by the user until `exit` or the end of input.
+///
+/// The history is kept in `~/.kvs_history`.
+pub fn run_shell(mut session: Session) -> Result<()> {
+    let mut editor = Editor::<CommandCompleter>::new();
+    editor.set_helper(Some(CommandCompleter));
+    let history = history_path();
+    if let Some(history) = &history {
+        // there is no history at the first run
+        let _ = editor.load_history(history);
+    }
+    loop {
+        match editor.readline("kvs> ") {
+            Ok(line) => {
+                editor.add_history_entry(line.as_str());
+                if !session.execute(&line) {
+                    break;
+                }
+            }
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(KvsError::StringError(format!("{}", e))),
+        }
+    }
+    if let Some(history) = &history {
+        if let Err(e) = editor.save_history(history) {
+            eprintln!("Cannot save history: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the commands in `input` line by line.
+///
+/// All commands are run even if some of them fail. It fails at the end if any
+/// command failed.
+pub fn run_batch(mut session: Session, input: impl BufRead) -> Result<()> {
+    for line in input.lines() {
+        if !session.execute(&line?) {
+            break;
+        }
+    }
+    match session.failed {
+        0 => Ok(()),
+        n => Err(KvsError::StringError(format!("{} commands failed", n))),
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// A connection to the server running the commands of a shell or a script.
+pub struct Session {
+    addr: SocketAddr,
+    // `None` after a failed request, which consumes the connection
+    client: Option<KvsClient>,
+    namespace: Option<String>,
+    format: Format,
+    // number of commands failed
+    failed: u64,
+}
+
+impl Session {
+    pub fn connect(addr: SocketAddr, namespace: Option<String>, format: Format) -> Result<Self> {
+        let client = KvsClient::connect(addr)
+            .wait()?
+            .with_namespace(namespace.clone());
+        Ok(Session {
+            addr,
+            client: Some(client),
+            namespace,
+            format,
+            failed: 0,
+        })
+    }
+
+    /// Parses and runs one line, and prints the result with the time it took.
+    ///
+    /// Returns `false` if the line asks to exit.
+    fn execute(&mut self, line: &str) -> bool {
+        let command = match parse(line) {
+            Ok(Some(command)) => command,
+            Ok(None) => return true,
+            Err(e) => {
+                self.print(line.trim(), Err(e), Duration::default());
+                return true;
+            }
+        };
+        match command {
+            Command::Exit => return false,
+            Command::Help => {
+                println!("{}", HELP);
+                return true;
+            }
+            _ => {}
+        }
+        let start = Instant::now();
+        let res = self.run(command);
+        self.print(line.trim(), res, start.elapsed());
+        true
+    }
+
+    fn run(&mut self, command: Command) -> Result<Reply> {
+        let client = match self.client.take() {
+            Some(client) => client,
+            None => KvsClient::connect(self.addr)
+                .wait()?
+                .with_namespace(self.namespace.clone()),
+        };
+        let (reply, client) = match command {
+            Command::Get(key) => client
+                .get(key)
+                .map(|(value, client)| (Reply::Value(value), client))
+                .wait()?,
+            Command::Set(key, value) => (Reply::Done, client.set(key, value).wait()?),
+            Command::Remove(key) => (Reply::Done, client.remove(key).wait()?),
+            Command::CreateNamespace(name) => (Reply::Done, client.create_namespace(name).wait()?),
+            Command::DropNamespace(name) => (Reply::Done, client.drop_namespace(name).wait()?),
+            Command::ListNamespaces => client
+                .list_namespaces()
+                .map(|(names, client)| (Reply::Names(names), client))
+                .wait()?,
+            Command::Use(name) => {
+                self.namespace = name;
+                (Reply::Done, client.with_namespace(self.namespace.clone()))
+            }
+            Command::Help | Command::Exit => unreachable!(),
+        };
+        self.client = Some(client);
+        Ok(reply)
+    }
+
+    fn print(&mut self, line: &str, res: Result<Reply>, elapsed: Duration) {
+        if res.is_err() {
+            self.failed += 1;
+        }
+        let micros = elapsed.as_secs() * 1_000_000 + u64::from(elapsed.subsec_micros());
+        match self.format {
+            Format::text => {
+                let output = match res {
+                    Ok(Reply::Done) => "OK".to_owned(),
+                    Ok(Reply::Value(Some(value))) => value,
+                    Ok(Reply::Value(None)) => "Key not found".to_owned(),
+                    Ok(Reply::Names(names)) => names.join(" "),
+                    Err(e) => format!("Error: {}", e),
+                };
+                println!("{} ({:.3} ms)", output, micros as f64 / 1000.0);
+            }
+            Format::json => {
+                let mut output = json!({ "command": line, "elapsed_us": micros });
+                match res {
+                    Ok(Reply::Done) => {}
+                    Ok(Reply::Value(value)) => output["value"] = json!(value),
+                    Ok(Reply::Names(names)) => output["namespaces"] = json!(names),
+                    Err(e) => output["error"] = json!(format!("{}", e)),
+                }
+                println!("{}", output);
+            }
+        }
+    }
+}
+
+enum Command {
+    Get(String),
+    Set(String, String),
+    Remove(String),
+    Use(Option<String>),
+    CreateNamespace(String),
+    DropNamespace(String),
+    ListNamespaces,
+    Help,
+    Exit,
+}
+
+enum Reply {
+    Done,
+    Value(Option<String>),
+    Names(Vec<String>),
+}
+
+/// Parses a line into a command.
+///
+/// Returns `None` for blank lines and comments.
+fn parse(line: &str) -> Result<Option<Command>> {
+    let words = split(line)?;
+    let (name, args) = match words.split_first() {
+        Some((name, args)) if !name.starts_with('#') => (name.as_str(), args),
+        _ => return Ok(None),
+    };
+    let mut args = args.iter().cloned();
+    let command = match (name, args.len()) {
+        ("get", 1) => Command::Get(args.next().unwrap()),
+        ("set", 2) => Command::Set(args.next().unwrap(), args.next().unwrap()),
+        ("rm", 1) => Command::Remove(args.next().unwrap()),
+        ("use", 0) | ("use", 1) => Command::Use(args.next()),
+        ("create-namespace", 1) => Command::CreateNamespace(args.next().unwrap()),
+        ("drop-namespace", 1) => Command::DropNamespace(args.next().unwrap()),
+        ("list-namespaces", 0) => Command::ListNamespaces,
+        ("help", 0) => Command::Help,
+        ("exit", 0) | ("quit", 0) => Command::Exit,
+        _ if COMMANDS.contains(&name) => {
+            return Err(KvsError::StringError(format!(
+                "Wrong number of arguments for {}",
+                name
+            )))
+        }
+        _ => {
+            return Err(KvsError::StringError(format!(
+                "Unknown command {:?}, type help for the commands",
+                name
+            )))
+        }
+    };
+    Ok(Some(command))
+}
+
+/// Splits a line into words separated by whitespace.
+///
+/// A word can be quoted with `"` to contain whitespace, where `\` escapes the next
+/// character.
+fn split(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.peek().map_or(false, |c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut word = String::new();
+        match chars.peek() {
+            None => return Ok(words),
+            Some('"') => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            if let Some(c) = chars.next() {
+                                word.push(c);
+                            }
+                        }
+                        Some(c) => word.push(c),
+                        None => return Err(KvsError::StringError("Unterminated quote".to_owned())),
+                    }
+                }
+            }
+            Some(_) => {
+                while let Some(c) = chars.peek().cloned() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+            }
+        }
+        words.push(word);
+    }
+}
+
+/// Completes the command names in the shell.
+struct CommandCompleter;
+
+impl Completer for CommandCompleter {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let prefix = &line[..pos];
+        if prefix.contains(char::is_whitespace) {
+            return Ok((pos, Vec::new()));
+        }
+        let candidates = COMMANDS
+            .iter()
+            .filter(|command| command.starts_with(prefix))
+            .map(|command| Pair {
+                display: command.to_string(),
+                replacement: format!("{} ", command),
+            })
+            .collect();
+        Ok((0, candidates))
+    }
+}
+
+impl Hinter for CommandCompleter {}
+
+impl Highlighter for CommandCompleter {}
+
+impl Helper for CommandCompleter {}
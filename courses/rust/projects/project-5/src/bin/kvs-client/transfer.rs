@@ -0,0 +1,119 @@
+use kvs::transfer::{self, DataFormat};
+use kvs::{KvsClient, KvsError, Result};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tokio::prelude::*;
+
+// number of pairs requested in one scan during an export
+const EXPORT_BATCH: usize = 1000;
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum FileFormat {
+        jsonl,
+        csv
+    }
+}
+
+impl From<FileFormat> for DataFormat {
+    fn from(format: FileFormat) -> DataFormat {
+        match format {
+            FileFormat::jsonl => DataFormat::JsonLines,
+            FileFormat::csv => DataFormat::Csv,
+        }
+    }
+}
+
+/// Writes all key/value pairs in the server to the file at `path`.
+///
+/// The pairs are scanned in batches over one connection while they are written.
+pub fn export(
+    addr: SocketAddr,
+    namespace: Option<String>,
+    path: &Path,
+    format: FileFormat,
+) -> Result<()> {
+    // taken out while a scan is in flight; it is not put back after an error, which
+    // ends the export
+    let mut client = Some(KvsClient::connect(addr).wait()?.with_namespace(namespace));
+    let pairs = transfer::scan_pages(|after| {
+        let (page, next) = client.take().unwrap().scan(after, EXPORT_BATCH).wait()?;
+        client = Some(next);
+        Ok(page)
+    });
+    let writer = BufWriter::new(File::create(path)?);
+    let count = transfer::write_pairs(writer, format.into(), pairs)?;
+    eprintln!("Exported {} pairs", count);
+    Ok(())
+}
+
+/// Sets the key/value pairs in the file at `path` in batches over one connection.
+///
+/// The number of pairs imported is saved in a progress file next to the imported
+/// file after every batch. With `resume`, the pairs recorded in the progress file
+/// are skipped. The progress file is deleted when the import finishes.
+pub fn import(
+    addr: SocketAddr,
+    namespace: Option<String>,
+    path: &Path,
+    format: FileFormat,
+    batch_size: usize,
+    resume: bool,
+) -> Result<()> {
+    if batch_size == 0 {
+        return Err(KvsError::StringError(
+            "The batch size must be positive".to_owned(),
+        ));
+    }
+    let progress_path = progress_path(path);
+    let skip = if resume && progress_path.exists() {
+        fs::read_to_string(&progress_path)?
+            .trim()
+            .parse::<u64>()
+            .map_err(|e| {
+                KvsError::StringError(format!(
+                    "Invalid progress file {}: {}",
+                    progress_path.display(),
+                    e
+                ))
+            })?
+    } else {
+        0
+    };
+    if skip > 0 {
+        eprintln!("Resuming after {} pairs", skip);
+    }
+
+    let reader = BufReader::new(File::open(path)?);
+    let mut pairs = transfer::read_pairs(reader, format.into()).skip(skip as usize);
+    let mut client = KvsClient::connect(addr).wait()?.with_namespace(namespace);
+    let mut count = skip;
+    loop {
+        let batch = pairs
+            .by_ref()
+            .take(batch_size)
+            .collect::<Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            break;
+        }
+        let len = batch.len() as u64;
+        client = client.set_many(batch).wait()?;
+        count += len;
+        fs::write(&progress_path, count.to_string())?;
+        eprintln!("Imported {} pairs", count);
+    }
+    if progress_path.exists() {
+        fs::remove_file(&progress_path)?;
+    }
+    Ok(())
+}
+
+fn progress_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(".progress");
+    path.with_file_name(name)
+}
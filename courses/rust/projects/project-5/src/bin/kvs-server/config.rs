@@ -0,0 +1,200 @@
+use kvs::{Durability, KvStoreOptions, KvsError, Limits, Result};
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+// read if it exists in the current directory and no config file is given
+const DEFAULT_CONFIG_FILE: &str = "kvs-server.toml";
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum Engine {
+        kvs,
+        sled
+    }
+}
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum ThreadPoolKind {
+        shared_queue,
+        rayon,
+        work_stealing
+    }
+}
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum DurabilityMode {
+        flush,
+        sync
+    }
+}
+
+arg_enum! {
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub enum LogLevel {
+        off,
+        error,
+        warn,
+        info,
+        debug,
+        trace
+    }
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> LevelFilter {
+        match level {
+            LogLevel::off => LevelFilter::Off,
+            LogLevel::error => LevelFilter::Error,
+            LogLevel::warn => LevelFilter::Warn,
+            LogLevel::info => LevelFilter::Info,
+            LogLevel::debug => LevelFilter::Debug,
+            LogLevel::trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// The configuration of `kvs-server`.
+///
+/// It is read from a TOML file. Missing fields take their default values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub addr: SocketAddr,
+    // `None` to use the engine of the existing data, or kvs for new data
+    pub engine: Option<Engine>,
+    pub data_dir: PathBuf,
+    pub replica_of: Option<SocketAddr>,
+    pub thread_pool: ThreadPoolConfig,
+    pub storage: StorageConfig,
+    pub limits: LimitsConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThreadPoolConfig {
+    pub kind: ThreadPoolKind,
+    pub threads: u32,
+}
+
+/// Storage options, which apply to the kvs engine only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub compaction_threshold: u64,
+    pub durability: DurabilityMode,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_key_size: Option<usize>,
+    pub max_value_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: LogLevel,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            addr: "127.0.0.1:4000".parse().unwrap(),
+            engine: None,
+            data_dir: PathBuf::from("."),
+            replica_of: None,
+            thread_pool: ThreadPoolConfig::default(),
+            storage: StorageConfig::default(),
+            limits: LimitsConfig::default(),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+impl Default for ThreadPoolConfig {
+    fn default() -> Self {
+        ThreadPoolConfig {
+            kind: ThreadPoolKind::rayon,
+            threads: num_cpus::get() as u32,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        let options = KvStoreOptions::default();
+        StorageConfig {
+            compaction_threshold: options.compaction_threshold,
+            durability: DurabilityMode::flush,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: LogLevel::info,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file at `path`, or `kvs-server.toml` in the current directory
+    /// if it exists. The default config is returned if there is no config file.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let default_path = Path::new(DEFAULT_CONFIG_FILE);
+        let path = match path {
+            Some(path) => path,
+            None if default_path.is_file() => default_path,
+            None => return Ok(Config::default()),
+        };
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| {
+            KvsError::StringError(format!("Invalid config file {}: {}", path.display(), e))
+        })
+    }
+
+    /// Checks the values which are valid for TOML but not for the server.
+    pub fn validate(&self) -> Result<()> {
+        if self.thread_pool.threads == 0 {
+            return Err(KvsError::StringError(
+                "The number of threads must be positive".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self)
+            .map_err(|e| KvsError::StringError(format!("Cannot serialize the config: {}", e)))
+    }
+
+    pub fn store_options(&self) -> KvStoreOptions {
+        KvStoreOptions {
+            compaction_threshold: self.storage.compaction_threshold,
+            durability: match self.storage.durability {
+                DurabilityMode::flush => Durability::Flush,
+                DurabilityMode::sync => Durability::Sync,
+            },
+            ..KvStoreOptions::default()
+        }
+    }
+
+    pub fn server_limits(&self) -> Limits {
+        Limits {
+            max_key_size: self.limits.max_key_size,
+            max_value_size: self.limits.max_value_size,
+        }
+    }
+}
@@ -0,0 +1,267 @@
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate clap;
+
+use config::{Config, DurabilityMode, Engine, LogLevel, ThreadPoolKind};
+use kvs::thread_pool::*;
+use kvs::{KvStore, KvsEngine, KvsServer, Result, SledKvsEngine};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use structopt::StructOpt;
+
+mod config;
+
+const DEFAULT_ENGINE: Engine = Engine::kvs;
+
+// Options given on the command line or in the environment override the config file.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "kvs-server")]
+struct Opt {
+    #[structopt(
+        long,
+        help = "Reads the configuration from a TOML file [default: kvs-server.toml if it exists]",
+        value_name = "FILE",
+        env = "KVS_CONFIG",
+        parse(from_os_str)
+    )]
+    config: Option<PathBuf>,
+    #[structopt(
+        long,
+        help = "Sets the listening address",
+        value_name = "IP:PORT",
+        env = "KVS_ADDR",
+        parse(try_from_str)
+    )]
+    addr: Option<SocketAddr>,
+    #[structopt(
+        long,
+        help = "Sets the storage engine",
+        value_name = "ENGINE-NAME",
+        env = "KVS_ENGINE",
+        raw(possible_values = "&Engine::variants()")
+    )]
+    engine: Option<Engine>,
+    #[structopt(
+        long = "data-dir",
+        help = "Sets the directory of the data",
+        value_name = "DIR",
+        env = "KVS_DATA_DIR",
+        parse(from_os_str)
+    )]
+    data_dir: Option<PathBuf>,
+    #[structopt(
+        long = "replica-of",
+        help = "Runs as a read-only replica of the primary server",
+        value_name = "IP:PORT",
+        env = "KVS_REPLICA_OF",
+        parse(try_from_str)
+    )]
+    replica_of: Option<SocketAddr>,
+    #[structopt(
+        long = "thread-pool",
+        help = "Sets the thread pool",
+        value_name = "KIND",
+        env = "KVS_THREAD_POOL",
+        raw(possible_values = "&ThreadPoolKind::variants()")
+    )]
+    thread_pool: Option<ThreadPoolKind>,
+    #[structopt(
+        long,
+        help = "Sets the number of threads",
+        value_name = "N",
+        env = "KVS_THREADS"
+    )]
+    threads: Option<u32>,
+    #[structopt(
+        long = "compaction-threshold",
+        help = "Sets the size of stale log entries triggering a compaction",
+        value_name = "BYTES",
+        env = "KVS_COMPACTION_THRESHOLD"
+    )]
+    compaction_threshold: Option<u64>,
+    #[structopt(
+        long,
+        help = "Sets when writes are persisted",
+        value_name = "MODE",
+        env = "KVS_DURABILITY",
+        raw(possible_values = "&DurabilityMode::variants()")
+    )]
+    durability: Option<DurabilityMode>,
+    #[structopt(
+        long = "max-key-size",
+        help = "Sets the maximum length of keys",
+        value_name = "BYTES",
+        env = "KVS_MAX_KEY_SIZE"
+    )]
+    max_key_size: Option<usize>,
+    #[structopt(
+        long = "max-value-size",
+        help = "Sets the maximum length of values",
+        value_name = "BYTES",
+        env = "KVS_MAX_VALUE_SIZE"
+    )]
+    max_value_size: Option<usize>,
+    #[structopt(
+        long = "log-level",
+        help = "Sets the log level",
+        value_name = "LEVEL",
+        env = "KVS_LOG_LEVEL",
+        raw(possible_values = "&LogLevel::variants()")
+    )]
+    log_level: Option<LogLevel>,
+    #[structopt(
+        long = "print-config",
+        help = "Prints the effective configuration and exits"
+    )]
+    print_config: bool,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    let config = match load_config(&opt) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    if opt.print_config {
+        match config.to_toml() {
+            Ok(toml) => print!("{}", toml),
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    env_logger::builder()
+        .filter_level(config.log.level.into())
+        .init();
+    if let Err(e) = run(config) {
+        error!("{}", e);
+        exit(1);
+    }
+}
+
+fn load_config(opt: &Opt) -> Result<Config> {
+    let mut config = Config::load(opt.config.as_ref().map(PathBuf::as_path))?;
+    if let Some(addr) = opt.addr {
+        config.addr = addr;
+    }
+    if let Some(engine) = opt.engine {
+        config.engine = Some(engine);
+    }
+    if let Some(data_dir) = &opt.data_dir {
+        config.data_dir = data_dir.clone();
+    }
+    if let Some(primary) = opt.replica_of {
+        config.replica_of = Some(primary);
+    }
+    if let Some(kind) = opt.thread_pool {
+        config.thread_pool.kind = kind;
+    }
+    if let Some(threads) = opt.threads {
+        config.thread_pool.threads = threads;
+    }
+    if let Some(threshold) = opt.compaction_threshold {
+        config.storage.compaction_threshold = threshold;
+    }
+    if let Some(durability) = opt.durability {
+        config.storage.durability = durability;
+    }
+    if let Some(size) = opt.max_key_size {
+        config.limits.max_key_size = Some(size);
+    }
+    if let Some(size) = opt.max_value_size {
+        config.limits.max_value_size = Some(size);
+    }
+    if let Some(level) = opt.log_level {
+        config.log.level = level;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+fn run(mut config: Config) -> Result<()> {
+    fs::create_dir_all(&config.data_dir)?;
+    let curr_engine = current_engine(&config.data_dir)?;
+    if config.engine.is_none() {
+        config.engine = curr_engine;
+    }
+    if curr_engine.is_some() && config.engine != curr_engine {
+        error!("Wrong engine!");
+        exit(1);
+    }
+
+    match config.thread_pool.kind {
+        ThreadPoolKind::shared_queue => run_with_pool::<SharedQueueThreadPool>(config),
+        ThreadPoolKind::rayon => run_with_pool::<RayonThreadPool>(config),
+        ThreadPoolKind::work_stealing => run_with_pool::<WorkStealingThreadPool>(config),
+    }
+}
+
+fn run_with_pool<P: ThreadPool>(config: Config) -> Result<()> {
+    let engine = config.engine.unwrap_or(DEFAULT_ENGINE);
+    info!("kvs-server {}", env!("CARGO_PKG_VERSION"));
+    info!("Storage engine: {}", engine);
+    info!(
+        "Thread pool: {} with {} threads",
+        config.thread_pool.kind, config.thread_pool.threads
+    );
+    info!("Data directory: {}", config.data_dir.display());
+    info!("Listening on {}", config.addr);
+    if let Some(primary) = config.replica_of {
+        info!("Replica of {}", primary);
+    }
+
+    // The engine file is written once the engine holds the data directory, so a
+    // server failing to open it leaves the directory as it was.
+    let threads = config.thread_pool.threads;
+    match engine {
+        Engine::kvs => {
+            let store =
+                KvStore::<P>::open_with_options(&config.data_dir, threads, config.store_options())?;
+            write_engine(&config.data_dir, engine)?;
+            run_with(store, &config)
+        }
+        Engine::sled => {
+            let store =
+                SledKvsEngine::<P>::new(sled::Db::start_default(&config.data_dir)?, threads)?;
+            write_engine(&config.data_dir, engine)?;
+            run_with(store, &config)
+        }
+    }
+}
+
+fn write_engine(data_dir: &Path, engine: Engine) -> Result<()> {
+    fs::write(data_dir.join("engine"), format!("{}", engine))?;
+    Ok(())
+}
+
+fn run_with<E: KvsEngine>(engine: E, config: &Config) -> Result<()> {
+    let mut server = KvsServer::new(engine).with_limits(config.server_limits());
+    if let Some(primary) = config.replica_of {
+        server = server.replica_of(primary);
+    }
+    server.run(config.addr)
+}
+
+fn current_engine(data_dir: &Path) -> Result<Option<Engine>> {
+    let engine = data_dir.join("engine");
+    if !engine.exists() {
+        return Ok(None);
+    }
+
+    match fs::read_to_string(engine)?.parse() {
+        Ok(engine) => Ok(Some(engine)),
+        Err(e) => {
+            warn!("The content of engine file is invalid: {}", e);
+            Ok(None)
+        }
+    }
+}
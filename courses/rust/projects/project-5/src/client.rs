@@ -1,9 +1,10 @@
 use crate::common::{Request, Response};
-use crate::KvsError;
+use crate::{Event, KvsError, Snapshot};
 use std::net::SocketAddr;
 use tokio::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 use tokio::io::{ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
+use tokio::prelude::future::Loop;
 use tokio::prelude::*;
 use tokio_serde_json::{ReadJson, WriteJson};
 
@@ -11,6 +12,8 @@ use tokio_serde_json::{ReadJson, WriteJson};
 pub struct KvsClient {
     read_json: ReadJson<FramedRead<ReadHalf<TcpStream>, LengthDelimitedCodec>, Response>,
     write_json: WriteJson<FramedWrite<WriteHalf<TcpStream>, LengthDelimitedCodec>, Request>,
+    // namespace of the keys, `None` for the default namespace
+    namespace: Option<String>,
 }
 
 impl KvsClient {
@@ -26,50 +29,232 @@ impl KvsClient {
                 KvsClient {
                     read_json,
                     write_json,
+                    namespace: None,
                 }
             })
             .map_err(|e| e.into())
     }
 
+    /// Operate on keys in the namespace `name` in subsequent requests.
+    ///
+    /// `None` switches back to the default namespace.
+    pub fn with_namespace(mut self, name: Option<String>) -> Self {
+        self.namespace = name;
+        self
+    }
+
     /// Get the value of a given key from the server.
     pub fn get(self, key: String) -> impl Future<Item = (Option<String>, Self), Error = KvsError> {
-        self.send_request(Request::Get { key })
-            .and_then(move |(resp, client)| match resp {
+        let namespace = self.namespace.clone();
+        self.send_request(Request::Get { namespace, key }).and_then(
+            move |(resp, client)| match resp {
                 Some(Response::Get(value)) => Ok((value, client)),
                 Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
                 Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
                 None => Err(KvsError::StringError("No response received".to_owned())),
-            })
+            },
+        )
     }
 
     /// Set the value of a string key in the server.
     pub fn set(self, key: String, value: String) -> impl Future<Item = Self, Error = KvsError> {
-        self.send_request(Request::Set { key, value })
+        let namespace = self.namespace.clone();
+        self.send_request(Request::Set {
+            namespace,
+            key,
+            value,
+        })
+        .and_then(move |(resp, client)| match resp {
+            Some(Response::Set) => Ok(client),
+            Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
+            Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
+            None => Err(KvsError::StringError("No response received".to_owned())),
+        })
+    }
+
+    /// Remove a string key in the server.
+    pub fn remove(self, key: String) -> impl Future<Item = Self, Error = KvsError> {
+        let namespace = self.namespace.clone();
+        self.send_request(Request::Remove { namespace, key })
             .and_then(move |(resp, client)| match resp {
-                Some(Response::Set) => Ok(client),
+                Some(Response::Remove) => Ok(client),
                 Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
                 Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
                 None => Err(KvsError::StringError("No response received".to_owned())),
             })
     }
 
-    /// Remove a string key in the server.
-    pub fn remove(self, key: String) -> impl Future<Item = Self, Error = KvsError> {
-        self.send_request(Request::Remove { key })
+    /// Set the values of many string keys in the server with one request.
+    ///
+    /// The pairs are set in order. If it fails, the pairs before the failed one
+    /// have been set.
+    pub fn set_many(
+        self,
+        pairs: Vec<(String, String)>,
+    ) -> impl Future<Item = Self, Error = KvsError> {
+        let namespace = self.namespace.clone();
+        self.send_request(Request::SetMany { namespace, pairs })
             .and_then(move |(resp, client)| match resp {
-                Some(Response::Remove) => Ok(client),
+                Some(Response::SetMany) => Ok(client),
+                Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
+                Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
+                None => Err(KvsError::StringError("No response received".to_owned())),
+            })
+    }
+
+    /// Get up to `limit` key/value pairs with keys greater than `after`, sorted by key.
+    ///
+    /// The server may return fewer pairs than `limit`. An empty result means there
+    /// are no more keys.
+    pub fn scan(
+        self,
+        after: Option<String>,
+        limit: usize,
+    ) -> impl Future<Item = (Vec<(String, String)>, Self), Error = KvsError> {
+        let namespace = self.namespace.clone();
+        self.send_request(Request::Scan {
+            namespace,
+            after,
+            limit,
+        })
+        .and_then(move |(resp, client)| match resp {
+            Some(Response::Scan(pairs)) => Ok((pairs, client)),
+            Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
+            Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
+            None => Err(KvsError::StringError("No response received".to_owned())),
+        })
+    }
+
+    /// Watch changes of keys starting with `prefix` in the server.
+    ///
+    /// If `from` is given, the server replays the changes since that sequence number
+    /// first. The connection is dedicated to the returned stream.
+    pub fn watch(
+        self,
+        prefix: String,
+        from: Option<u64>,
+    ) -> impl Stream<Item = Event, Error = KvsError> {
+        let request = Request::Watch {
+            namespace: self.namespace,
+            prefix,
+            from,
+        };
+        let read_json = self.read_json;
+        self.write_json
+            .send(request)
+            .map_err(KvsError::from)
+            .map(move |_| {
+                read_json
+                    .map_err(KvsError::from)
+                    .and_then(|resp| match resp {
+                        Response::Event(event) => Ok(event),
+                        Response::Err(msg) => Err(KvsError::StringError(msg)),
+                        _ => Err(KvsError::StringError("Invalid response".to_owned())),
+                    })
+            })
+            .flatten_stream()
+    }
+
+    /// Create a namespace in the server.
+    pub fn create_namespace(self, name: String) -> impl Future<Item = Self, Error = KvsError> {
+        self.send_request(Request::CreateNamespace { name })
+            .and_then(move |(resp, client)| match resp {
+                Some(Response::CreateNamespace) => Ok(client),
                 Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
                 Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
                 None => Err(KvsError::StringError("No response received".to_owned())),
             })
     }
 
+    /// Drop a namespace and all keys in it in the server.
+    pub fn drop_namespace(self, name: String) -> impl Future<Item = Self, Error = KvsError> {
+        self.send_request(Request::DropNamespace { name }).and_then(
+            move |(resp, client)| match resp {
+                Some(Response::DropNamespace) => Ok(client),
+                Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
+                Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
+                None => Err(KvsError::StringError("No response received".to_owned())),
+            },
+        )
+    }
+
+    /// List the namespaces in the server.
+    pub fn list_namespaces(self) -> impl Future<Item = (Vec<String>, Self), Error = KvsError> {
+        self.send_request(Request::ListNamespaces)
+            .and_then(move |(resp, client)| match resp {
+                Some(Response::ListNamespaces(names)) => Ok((names, client)),
+                Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
+                Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
+                None => Err(KvsError::StringError("No response received".to_owned())),
+            })
+    }
+
+    /// Promote the server to a primary if it is a replica.
+    ///
+    /// The server stops following its primary and accepts writes afterwards.
+    pub fn promote(self) -> impl Future<Item = Self, Error = KvsError> {
+        self.send_request(Request::Promote)
+            .and_then(move |(resp, client)| match resp {
+                Some(Response::Promote) => Ok(client),
+                Some(Response::Err(msg)) => Err(KvsError::StringError(msg)),
+                Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
+                None => Err(KvsError::StringError("No response received".to_owned())),
+            })
+    }
+
+    /// Get a snapshot of all key/value pairs in the server.
+    ///
+    /// The connection is closed afterwards.
+    pub fn snapshot(self) -> impl Future<Item = Snapshot, Error = KvsError> {
+        future::loop_fn((self.replicate(), Vec::new()), |(resps, mut pairs)| {
+            resps
+                .into_future()
+                .map_err(|(err, _)| err)
+                .and_then(move |(resp, resps)| match resp {
+                    Some(Response::Snapshot(chunk)) => {
+                        pairs.extend(chunk);
+                        Ok(Loop::Continue((resps, pairs)))
+                    }
+                    Some(Response::SnapshotEnd(seq)) => Ok(Loop::Break(Snapshot { seq, pairs })),
+                    Some(_) => Err(KvsError::StringError("Invalid response".to_owned())),
+                    None => Err(KvsError::StringError("No response received".to_owned())),
+                })
+        })
+    }
+
+    /// Follow the keys of the server as a replica.
+    ///
+    /// The returned stream yields the snapshot and then the changes. The connection is
+    /// dedicated to the stream.
+    pub(crate) fn replicate(self) -> impl Stream<Item = Response, Error = KvsError> {
+        let request = Request::Replicate {
+            namespace: self.namespace,
+        };
+        let read_json = self.read_json;
+        self.write_json
+            .send(request)
+            .map_err(KvsError::from)
+            .map(move |_| {
+                read_json
+                    .map_err(KvsError::from)
+                    .and_then(|resp| match resp {
+                        Response::Err(msg) => Err(KvsError::StringError(msg)),
+                        resp => Ok(resp),
+                    })
+            })
+            .flatten_stream()
+    }
+
     fn send_request(
         self,
         req: Request,
     ) -> impl Future<Item = (Option<Response>, Self), Error = KvsError> {
-        let read_json = self.read_json;
-        self.write_json
+        let KvsClient {
+            read_json,
+            write_json,
+            namespace,
+        } = self;
+        write_json
             .send(req)
             .and_then(move |write_json| {
                 read_json
@@ -78,6 +263,7 @@ impl KvsClient {
                         let client = KvsClient {
                             read_json,
                             write_json,
+                            namespace,
                         };
                         (resp, client)
                     })
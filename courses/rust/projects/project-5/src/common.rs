@@ -1,10 +1,50 @@
+use crate::Event;
 use serde::{Deserialize, Serialize};
 
+/// Requests on keys operate on the default namespace if `namespace` is `None`.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Request {
-    Get { key: String },
-    Set { key: String, value: String },
-    Remove { key: String },
+    Get {
+        namespace: Option<String>,
+        key: String,
+    },
+    Set {
+        namespace: Option<String>,
+        key: String,
+        value: String,
+    },
+    Remove {
+        namespace: Option<String>,
+        key: String,
+    },
+    /// Sets the pairs in order
+    SetMany {
+        namespace: Option<String>,
+        pairs: Vec<(String, String)>,
+    },
+    /// Reads the pairs with keys greater than `after`, at most `limit` of them
+    Scan {
+        namespace: Option<String>,
+        after: Option<String>,
+        limit: usize,
+    },
+    Watch {
+        namespace: Option<String>,
+        prefix: String,
+        from: Option<u64>,
+    },
+    CreateNamespace {
+        name: String,
+    },
+    DropNamespace {
+        name: String,
+    },
+    ListNamespaces,
+    /// Streams a snapshot of the namespace followed by its changes
+    Replicate {
+        namespace: Option<String>,
+    },
+    Promote,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -12,5 +52,17 @@ pub enum Response {
     Get(Option<String>),
     Set,
     Remove,
+    SetMany,
+    /// The pairs sorted by key, empty if there are no more keys
+    Scan(Vec<(String, String)>),
+    Event(Event),
+    CreateNamespace,
+    DropNamespace,
+    ListNamespaces(Vec<String>),
+    /// A part of the snapshot sent to a replica, followed by `SnapshotEnd`
+    Snapshot(Vec<(String, String)>),
+    /// The end of the snapshot with its sequence number
+    SnapshotEnd(u64),
+    Promote,
     Err(String),
 }
@@ -1,31 +1,79 @@
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
-use std::ops::Range;
+use std::ops::{Bound, Range};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 
 use crossbeam::queue::ArrayQueue;
 use crossbeam_skiplist::SkipMap;
+use fs2::FileExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Deserializer;
 use tokio::prelude::*;
-use tokio::sync::oneshot;
 
-use super::KvsEngine;
-use crate::thread_pool::ThreadPool;
+use super::watch::{self, Watchers};
+use super::{check_namespace, Event, EventStream, KvsEngine, Snapshot};
+use crate::thread_pool::{with_handle, Priority, ThreadPool};
 use crate::{KvsError, Result};
 
 const COMPACTION_THRESHOLD: u64 = 1024 * 1024;
+const NAMESPACES_DIR: &str = "namespaces";
+const LOCK_FILE: &str = "LOCK";
+
+/// Options of a `KvStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KvStoreOptions {
+    /// The number of bytes of stale commands in the log of a namespace which
+    /// triggers a compaction
+    pub compaction_threshold: u64,
+    /// When a write is persisted
+    pub durability: Durability,
+    /// Whether the store is opened without writing to the data directory
+    ///
+    /// A read-only store does not take the lock of the directory, so it can be
+    /// opened while another process writes to it. A namespace is reloaded from its
+    /// directory when a read finds that the writer has compacted away the log file of
+    /// a value, and namespaces created or dropped later are not seen. All writes fail
+    /// with `KvsError::ReadOnly`.
+    pub read_only: bool,
+}
+
+impl Default for KvStoreOptions {
+    fn default() -> Self {
+        KvStoreOptions {
+            compaction_threshold: COMPACTION_THRESHOLD,
+            durability: Durability::Flush,
+            read_only: false,
+        }
+    }
+}
+
+/// When a write of a `KvStore` is persisted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Durability {
+    /// A write is handed to the operating system before it returns. It survives a
+    /// crash of the process but may be lost if the machine crashes.
+    Flush,
+    /// A write is synced to the disk before it returns.
+    Sync,
+}
 
 /// The `KvStore` stores string key/value pairs.
 ///
 /// Key/value pairs are persisted to disk in log files. Log files are named after
 /// monotonically increasing generation numbers with a `log` extension name.
 /// A skip list in memory stores the keys and the value locations for fast query.
+/// Every command in the log carries a sequence number, which is also used to
+/// replay recent changes to watchers.
+///
+/// Named namespaces are stored in subdirectories of `namespaces` in the same layout.
+///
+/// The data directory is locked while a store opened on it is alive, so only one
+/// process can write to it at a time.
 ///
 /// ```rust
 /// # use kvs::{KvStore, Result};
@@ -43,13 +91,10 @@ const COMPACTION_THRESHOLD: u64 = 1024 * 1024;
 /// ```
 #[derive(Clone)]
 pub struct KvStore<P: ThreadPool> {
-    // directory for the log and other data
-    path: Arc<PathBuf>,
-    // map generation number to the file reader
-    index: Arc<SkipMap<String, CommandPos>>,
-    writer: Arc<Mutex<KvStoreWriter>>,
+    // the namespace this handle operates on
+    space: Arc<KeySpace>,
+    namespaces: Arc<Namespaces>,
     thread_pool: P,
-    reader_pool: Arc<ArrayQueue<KvStoreReader>>,
 }
 
 impl<P: ThreadPool> KvStore<P> {
@@ -57,59 +102,74 @@ impl<P: ThreadPool> KvStore<P> {
     ///
     /// This will create a new directory if the given one does not exist.
     ///
-    /// `concurrency` specifies how many threads at most can read the database at the same time.
+    /// `concurrency` specifies the number of threads and of pooled readers. Jobs reading
+    /// beyond that, e.g. in a larger thread pool, open their own files.
     ///
     /// # Errors
     ///
+    /// It returns `KvsError::Locked` if the directory is opened by another store.
+    ///
     /// It propagates I/O or deserialization errors during the log replay.
     pub fn open(path: impl Into<PathBuf>, concurrency: u32) -> Result<Self> {
-        let path = Arc::new(path.into());
-        fs::create_dir_all(&*path)?;
-
-        let mut readers = BTreeMap::new();
-        let index = Arc::new(SkipMap::new());
-
-        let gen_list = sorted_gen_list(&path)?;
-        let mut uncompacted = 0;
-
-        for &gen in &gen_list {
-            let mut reader = BufReaderWithPos::new(File::open(log_path(&path, gen))?)?;
-            uncompacted += load(gen, &mut reader, &*index)?;
-            readers.insert(gen, reader);
-        }
-
-        let current_gen = gen_list.last().unwrap_or(&0) + 1;
-        let writer = new_log_file(&path, current_gen)?;
-        let safe_point = Arc::new(AtomicU64::new(0));
+        Self::open_with_options(path, concurrency, KvStoreOptions::default())
+    }
 
-        let reader = KvStoreReader {
-            path: Arc::clone(&path),
-            safe_point,
-            readers: RefCell::new(BTreeMap::new()),
+    /// Opens a `KvStore` with the given path and options.
+    ///
+    /// A read-only store requires the directory to exist.
+    ///
+    /// # Errors
+    ///
+    /// It returns `KvsError::Locked` if the directory is opened by another store
+    /// which is not read-only.
+    ///
+    /// It propagates I/O or deserialization errors during the log replay.
+    pub fn open_with_options(
+        path: impl Into<PathBuf>,
+        concurrency: u32,
+        options: KvStoreOptions,
+    ) -> Result<Self> {
+        let path = path.into();
+        let lock = if options.read_only {
+            None
+        } else {
+            Some(lock_dir(&path)?)
         };
+        let space = Arc::new(KeySpace::open(
+            path.clone(),
+            String::new(),
+            concurrency,
+            options,
+        )?);
 
-        let writer = KvStoreWriter {
-            reader: reader.clone(),
-            writer,
-            current_gen,
-            uncompacted,
-            path: Arc::clone(&path),
-            index: Arc::clone(&index),
+        let namespaces_path = path.join(NAMESPACES_DIR);
+        let mut spaces = HashMap::new();
+        if namespaces_path.is_dir() {
+            for entry in fs::read_dir(&namespaces_path)? {
+                let entry = entry?;
+                if let Some(name) = entry.file_name().to_str() {
+                    if entry.path().is_dir() && check_namespace(name).is_ok() {
+                        let space =
+                            KeySpace::open(entry.path(), name.to_owned(), concurrency, options)?;
+                        spaces.insert(name.to_owned(), Arc::new(space));
+                    }
+                }
+            }
+        }
+        let namespaces = Namespaces {
+            path: namespaces_path,
+            concurrency,
+            options,
+            spaces: RwLock::new(spaces),
+            _lock: lock,
         };
 
         let thread_pool = P::new(concurrency)?;
-        let reader_pool = Arc::new(ArrayQueue::new(concurrency as usize));
-        for _ in 1..concurrency {
-            reader_pool.push(reader.clone()).unwrap();
-        }
-        reader_pool.push(reader).unwrap();
 
         Ok(KvStore {
-            path,
-            index,
-            writer: Arc::new(Mutex::new(writer)),
+            space,
+            namespaces: Arc::new(namespaces),
             thread_pool,
-            reader_pool,
         })
     }
 }
@@ -123,52 +183,44 @@ impl<P: ThreadPool> KvsEngine for KvStore<P> {
     ///
     /// It propagates I/O or serialization errors during writing the log.
     fn set(&self, key: String, value: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
-        let writer = self.writer.clone();
-        let (tx, rx) = oneshot::channel();
-        self.thread_pool.spawn(move || {
-            let res = writer.lock().unwrap().set(key, value);
-            if tx.send(res).is_err() {
-                error!("Receiving end is dropped");
+        let space = self.space.clone();
+        let thread_pool = self.thread_pool.clone();
+        let handle = self.thread_pool.spawn_with_handle(move || {
+            let mut writer = space.writer()?;
+            writer.set(key, value)?;
+            if writer.schedule_compaction() {
+                spawn_compaction(&thread_pool, space.clone());
             }
+            Ok(())
         });
-        Box::new(
-            rx.map_err(|e| KvsError::StringError(format!("{}", e)))
-                .flatten(),
-        )
+        Box::new(handle.flatten())
     }
 
     /// Gets the string value of a given string key.
     ///
     /// Returns `None` if the given key does not exist.
+    ///
+    /// Reads are spawned with a high priority so they are not delayed by queued writes
+    /// and compactions.
     fn get(&self, key: String) -> Box<dyn Future<Item = Option<String>, Error = KvsError> + Send> {
-        let reader_pool = self.reader_pool.clone();
-        let index = self.index.clone();
-        let (tx, rx) = oneshot::channel();
-        self.thread_pool.spawn(move || {
-            let res = (|| {
-                if let Some(cmd_pos) = index.get(&key) {
-                    let reader = reader_pool.pop().unwrap();
-                    let res = if let Command::Set { value, .. } =
-                        reader.read_command(*cmd_pos.value())?
-                    {
-                        Ok(Some(value))
-                    } else {
-                        Err(KvsError::UnexpectedCommandType)
-                    };
-                    reader_pool.push(reader).unwrap();
-                    res
-                } else {
-                    Ok(None)
+        let space = self.space.clone();
+        let (job, handle) = with_handle(move || {
+            space.check_dropped()?;
+            loop {
+                let cmd_pos = match space.index.get(&key) {
+                    Some(entry) => *entry.value(),
+                    None => return Ok(None),
+                };
+                match space.read_command(cmd_pos)? {
+                    Some(Command::Set { value, .. }) => return Ok(Some(value)),
+                    Some(_) => return Err(KvsError::UnexpectedCommandType),
+                    // the index is reloaded, so look the key up again
+                    None => continue,
                 }
-            })();
-            if tx.send(res).is_err() {
-                error!("Receiving end is dropped");
             }
         });
-        Box::new(
-            rx.map_err(|e| KvsError::StringError(format!("{}", e)))
-                .flatten(),
-        )
+        self.thread_pool.spawn_with_priority(Priority::High, job);
+        Box::new(handle.flatten())
     }
 
     /// Removes a given key.
@@ -179,21 +231,475 @@ impl<P: ThreadPool> KvsEngine for KvStore<P> {
     ///
     /// It propagates I/O or serialization errors during writing the log.
     fn remove(&self, key: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
-        let writer = self.writer.clone();
-        let (tx, rx) = oneshot::channel();
+        let space = self.space.clone();
+        let thread_pool = self.thread_pool.clone();
+        let handle = self.thread_pool.spawn_with_handle(move || {
+            let mut writer = space.writer()?;
+            writer.remove(key)?;
+            if writer.schedule_compaction() {
+                spawn_compaction(&thread_pool, space.clone());
+            }
+            Ok(())
+        });
+        Box::new(handle.flatten())
+    }
+
+    /// Sets the values of many string keys in order under one lock of the writer.
+    ///
+    /// # Errors
+    ///
+    /// It propagates I/O or serialization errors during writing the log.
+    fn set_many(
+        &self,
+        pairs: Vec<(String, String)>,
+    ) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
+        let space = self.space.clone();
+        let thread_pool = self.thread_pool.clone();
+        let handle = self.thread_pool.spawn_with_handle(move || {
+            let mut writer = space.writer()?;
+            for (key, value) in pairs {
+                writer.set(key, value)?;
+            }
+            if writer.schedule_compaction() {
+                spawn_compaction(&thread_pool, space.clone());
+            }
+            Ok(())
+        });
+        Box::new(handle.flatten())
+    }
+
+    /// Returns up to `limit` key/value pairs with keys greater than `after`.
+    ///
+    /// The keys are taken from the index without blocking writes and their values
+    /// are read from the log afterwards.
+    ///
+    /// # Errors
+    ///
+    /// It propagates I/O or deserialization errors during reading the log.
+    fn scan(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Box<dyn Future<Item = Vec<(String, String)>, Error = KvsError> + Send> {
+        let space = self.space.clone();
+        let handle = self.thread_pool.spawn_with_handle(move || {
+            space.check_dropped()?;
+            let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+            'scan: loop {
+                let entries: Vec<(String, CommandPos)> = space
+                    .index
+                    .range((start.clone(), Bound::Unbounded))
+                    .take(limit)
+                    .map(|entry| (entry.key().clone(), *entry.value()))
+                    .collect();
+                let mut pairs = Vec::with_capacity(entries.len());
+                for (key, cmd_pos) in entries {
+                    match space.read_command(cmd_pos)? {
+                        Some(Command::Set { value, .. }) => pairs.push((key, value)),
+                        Some(_) => return Err(KvsError::UnexpectedCommandType),
+                        // the index is reloaded, so take the keys again
+                        None => continue 'scan,
+                    }
+                }
+                return Ok(pairs);
+            }
+        });
+        Box::new(handle.flatten())
+    }
+
+    /// Watches changes of keys starting with `prefix`.
+    ///
+    /// If `from` is given, matching commands in the log with a sequence number not
+    /// less than `from` are replayed before the live changes.
+    ///
+    /// # Errors
+    ///
+    /// The stream fails with `KvsError::Compacted` if commands after `from` have been
+    /// merged by a compaction.
+    ///
+    /// It propagates I/O or deserialization errors during reading the log.
+    fn watch(&self, prefix: String, from: Option<u64>) -> EventStream {
+        let space = self.space.clone();
+        let (mut tx, rx) = watch::channel();
         self.thread_pool.spawn(move || {
-            let res = writer.lock().unwrap().remove(key);
-            if tx.send(res).is_err() {
-                error!("Receiving end is dropped");
+            let res = space.writer().and_then(|writer| {
+                let events = match from {
+                    Some(from) => writer.history(&prefix, from)?,
+                    None => Vec::new(),
+                };
+                Ok((writer, events))
+            });
+            match res {
+                Ok((mut writer, events)) => {
+                    for event in events {
+                        if tx.try_send(Ok(event)).is_err() {
+                            return;
+                        }
+                    }
+                    writer.watchers.subscribe(prefix, from.unwrap_or(0), tx);
+                }
+                Err(e) => {
+                    if tx.try_send(Err(e)).is_err() {
+                        error!("Receiving end is dropped");
+                    }
+                }
             }
         });
-        Box::new(
-            rx.map_err(|e| KvsError::StringError(format!("{}", e)))
-                .flatten(),
-        )
+        rx
+    }
+
+    /// Returns a `KvStore` operating on the namespace `name`.
+    ///
+    /// The returned store shares the thread pool and other namespaces with this one.
+    fn namespace(&self, name: &str) -> Result<Self> {
+        let space = self.namespaces.get(name)?;
+        Ok(KvStore {
+            space,
+            namespaces: self.namespaces.clone(),
+            thread_pool: self.thread_pool.clone(),
+        })
+    }
+
+    /// Creates the namespace `name` in its own directory.
+    fn create_namespace(&self, name: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
+        let namespaces = self.namespaces.clone();
+        let handle = self
+            .thread_pool
+            .spawn_with_handle(move || namespaces.create(name));
+        Box::new(handle.flatten())
+    }
+
+    /// Drops the namespace `name` and deletes its directory.
+    ///
+    /// Stores still operating on the namespace fail with `KvsError::NamespaceNotFound`
+    /// afterwards.
+    fn drop_namespace(&self, name: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
+        let namespaces = self.namespaces.clone();
+        let handle = self
+            .thread_pool
+            .spawn_with_handle(move || namespaces.remove(&name));
+        Box::new(handle.flatten())
+    }
+
+    fn list_namespaces(&self) -> Box<dyn Future<Item = Vec<String>, Error = KvsError> + Send> {
+        Box::new(future::ok(self.namespaces.names()))
+    }
+
+    /// Takes a snapshot of the namespace and watches all keys in it.
+    ///
+    /// The snapshot holds what the log would contain right after a compaction. Writes
+    /// are blocked while it is read.
+    ///
+    /// # Errors
+    ///
+    /// It propagates I/O or deserialization errors during reading the log.
+    fn snapshot(&self) -> Box<dyn Future<Item = (Snapshot, EventStream), Error = KvsError> + Send> {
+        let space = self.space.clone();
+        let handle = self.thread_pool.spawn_with_handle(move || {
+            let mut writer = space.writer()?;
+            let mut pairs = Vec::new();
+            for entry in space.index.iter() {
+                let value = writer.value(*entry.value())?;
+                pairs.push((entry.key().clone(), value));
+            }
+            let (tx, rx) = watch::channel();
+            writer.watchers.subscribe(String::new(), 0, tx);
+            let snapshot = Snapshot {
+                seq: writer.seqs.last,
+                pairs,
+            };
+            Ok((snapshot, rx))
+        });
+        Box::new(handle.flatten())
+    }
+
+    /// Replaces the keys of the namespace with those in the snapshot.
+    ///
+    /// # Errors
+    ///
+    /// It propagates I/O or serialization errors during writing the log.
+    fn restore(&self, snapshot: Snapshot) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
+        let space = self.space.clone();
+        let thread_pool = self.thread_pool.clone();
+        let handle = self.thread_pool.spawn_with_handle(move || {
+            let mut writer = space.writer()?;
+            let pairs: HashMap<String, String> = snapshot.pairs.into_iter().collect();
+            let stale: Vec<String> = space
+                .index
+                .iter()
+                .map(|entry| entry.key().clone())
+                .filter(|key| !pairs.contains_key(key))
+                .collect();
+            for key in stale {
+                writer.remove(key)?;
+            }
+            for (key, value) in pairs {
+                let cmd_pos = space.index.get(&key).map(|entry| *entry.value());
+                if let Some(cmd_pos) = cmd_pos {
+                    if writer.value(cmd_pos)? == value {
+                        continue;
+                    }
+                }
+                writer.set(key, value)?;
+            }
+            if writer.schedule_compaction() {
+                spawn_compaction(&thread_pool, space.clone());
+            }
+            Ok(())
+        });
+        Box::new(handle.flatten())
+    }
+}
+
+/// The named namespaces of a `KvStore`.
+///
+/// Each namespace is stored in its own directory under the `namespaces` directory
+/// of the store, so it has its own log files, index and compaction accounting.
+/// The default namespace is stored in the directory of the store itself.
+struct Namespaces {
+    path: PathBuf,
+    concurrency: u32,
+    options: KvStoreOptions,
+    spaces: RwLock<HashMap<String, Arc<KeySpace>>>,
+    // the locked file of the data directory, released when the last store is dropped
+    _lock: Option<File>,
+}
+
+impl Namespaces {
+    fn get(&self, name: &str) -> Result<Arc<KeySpace>> {
+        self.spaces
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| KvsError::NamespaceNotFound(name.to_owned()))
+    }
+
+    fn create(&self, name: String) -> Result<()> {
+        if self.options.read_only {
+            return Err(KvsError::ReadOnly);
+        }
+        check_namespace(&name)?;
+        let mut spaces = self.spaces.write().unwrap();
+        if spaces.contains_key(&name) {
+            return Err(KvsError::NamespaceExists(name));
+        }
+        let space = KeySpace::open(
+            self.path.join(&name),
+            name.clone(),
+            self.concurrency,
+            self.options,
+        )?;
+        spaces.insert(name, Arc::new(space));
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        if self.options.read_only {
+            return Err(KvsError::ReadOnly);
+        }
+        let space = self
+            .spaces
+            .write()
+            .unwrap()
+            .remove(name)
+            .ok_or_else(|| KvsError::NamespaceNotFound(name.to_owned()))?;
+        // Wait for the running write and prevent further ones before deleting the files.
+        let _writer = space.writer()?;
+        space.dropped.store(true, Ordering::SeqCst);
+        fs::remove_dir_all(self.path.join(name))?;
+        Ok(())
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.spaces.read().unwrap().keys().cloned().collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// The log files, index and readers of a single namespace.
+struct KeySpace {
+    // name of the namespace, empty for the default one
+    name: String,
+    // map generation number to the file reader
+    index: Arc<SkipMap<String, CommandPos>>,
+    writer: Mutex<KvStoreWriter>,
+    reader_pool: ArrayQueue<KvStoreReader>,
+    // what readers created when the pool is empty are made of
+    path: Arc<PathBuf>,
+    safe_point: Arc<AtomicU64>,
+    read_only: bool,
+    // set when the namespace is dropped
+    dropped: AtomicBool,
+}
+
+impl KeySpace {
+    /// Opens the log files in the given directory and builds the index.
+    fn open(
+        path: PathBuf,
+        name: String,
+        concurrency: u32,
+        options: KvStoreOptions,
+    ) -> Result<Self> {
+        let path = Arc::new(path);
+        if !options.read_only {
+            fs::create_dir_all(&*path)?;
+        }
+
+        let mut readers = BTreeMap::new();
+        let index = Arc::new(SkipMap::new());
+
+        let gen_list = sorted_gen_list(&path)?;
+        let mut uncompacted = 0;
+        let mut seqs = SeqRange::default();
+
+        for &gen in &gen_list {
+            if let Some(mut reader) = open_log(&path, gen)? {
+                uncompacted += load(gen, &mut reader, &*index, &mut seqs)?;
+                readers.insert(gen, reader);
+            }
+        }
+
+        let current_gen = gen_list.last().unwrap_or(&0) + 1;
+        let writer = if options.read_only {
+            None
+        } else {
+            Some(new_log_file(&path, current_gen)?)
+        };
+        let safe_point = Arc::new(AtomicU64::new(0));
+
+        let reader = KvStoreReader {
+            path: Arc::clone(&path),
+            safe_point: Arc::clone(&safe_point),
+            readers: RefCell::new(BTreeMap::new()),
+        };
+
+        let writer = KvStoreWriter {
+            reader: reader.clone(),
+            writer,
+            current_gen,
+            uncompacted,
+            compaction_scheduled: false,
+            options,
+            seqs,
+            watchers: Watchers::default(),
+            path: Arc::clone(&path),
+            index: Arc::clone(&index),
+        };
+
+        let reader_pool = ArrayQueue::new(concurrency as usize);
+        for _ in 1..concurrency {
+            reader_pool.push(reader.clone()).unwrap();
+        }
+        reader_pool.push(reader).unwrap();
+
+        Ok(KeySpace {
+            name,
+            index,
+            writer: Mutex::new(writer),
+            reader_pool,
+            path,
+            safe_point,
+            read_only: options.read_only,
+            dropped: AtomicBool::new(false),
+        })
+    }
+
+    fn check_dropped(&self) -> Result<()> {
+        if self.dropped.load(Ordering::SeqCst) {
+            Err(KvsError::NamespaceNotFound(self.name.clone()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Takes a reader from the pool, or creates one if more jobs are reading than
+    /// the pool holds.
+    fn take_reader(&self) -> KvStoreReader {
+        self.reader_pool.pop().unwrap_or_else(|_| KvStoreReader {
+            path: Arc::clone(&self.path),
+            safe_point: Arc::clone(&self.safe_point),
+            readers: RefCell::new(BTreeMap::new()),
+        })
+    }
+
+    /// Returns a reader to the pool, or drops it if the pool is full.
+    fn put_reader(&self, reader: KvStoreReader) {
+        let _ = self.reader_pool.push(reader);
+    }
+
+    /// Reads the command at the given `CommandPos` with a pooled reader.
+    ///
+    /// Returns `None` if the namespace is read-only and the log file has been deleted
+    /// by a compaction of the writer. The index is reloaded before returning.
+    fn read_command(&self, cmd_pos: CommandPos) -> Result<Option<Command>> {
+        if self.is_compacted(cmd_pos.gen) {
+            self.reload()?;
+            return Ok(None);
+        }
+        let reader = self.take_reader();
+        let res = reader.read_command(cmd_pos);
+        // return the reader to the pool before propagating errors
+        self.put_reader(reader);
+        match res {
+            Err(_) if self.is_compacted(cmd_pos.gen) => {
+                self.reload()?;
+                Ok(None)
+            }
+            res => res.map(Some),
+        }
+    }
+
+    fn is_compacted(&self, gen: u64) -> bool {
+        self.read_only && !log_path(&self.path, gen).is_file()
+    }
+
+    /// Rebuilds the index of a read-only namespace from the log files in its directory.
+    fn reload(&self) -> Result<()> {
+        let mut writer = self.writer()?;
+        let index = SkipMap::new();
+        let mut seqs = SeqRange::default();
+        let mut first_gen = None;
+        for gen in sorted_gen_list(&self.path)? {
+            if let Some(mut reader) = open_log(&self.path, gen)? {
+                first_gen.get_or_insert(gen);
+                load(gen, &mut reader, &index, &mut seqs)?;
+            }
+        }
+
+        for entry in self.index.iter() {
+            if !index.contains_key(entry.key()) {
+                self.index.remove(entry.key());
+            }
+        }
+        for entry in index.iter() {
+            self.index.insert(entry.key().clone(), *entry.value());
+        }
+        writer.seqs = seqs;
+        // the readers close their handles of the deleted files
+        self.safe_point
+            .store(first_gen.unwrap_or(0), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Locks the writer of the namespace.
+    fn writer(&self) -> Result<MutexGuard<'_, KvStoreWriter>> {
+        let writer = self.writer.lock().unwrap();
+        self.check_dropped()?;
+        Ok(writer)
     }
 }
 
+/// Compacts the log of the namespace in a low priority job.
+fn spawn_compaction<P: ThreadPool>(thread_pool: &P, space: Arc<KeySpace>) {
+    thread_pool.spawn_with_priority(Priority::Low, move || {
+        if let Err(e) = space.writer().and_then(|mut writer| writer.compact()) {
+            error!("Compaction failed: {}", e);
+        }
+    });
+}
+
 /// A single thread reader.
 ///
 /// Each `KvStore` instance has its own `KvStoreReader` and
@@ -266,51 +772,49 @@ impl Clone for KvStoreReader {
 
 struct KvStoreWriter {
     reader: KvStoreReader,
-    writer: BufWriterWithPos<File>,
+    // `None` if the store is read-only
+    writer: Option<BufWriterWithPos<File>>,
     current_gen: u64,
     // the number of bytes representing "stale" commands that could be
     // deleted during a compaction
     uncompacted: u64,
+    // whether a compaction job is waiting to run
+    compaction_scheduled: bool,
+    options: KvStoreOptions,
+    seqs: SeqRange,
+    watchers: Watchers,
     path: Arc<PathBuf>,
     index: Arc<SkipMap<String, CommandPos>>,
 }
 
 impl KvStoreWriter {
     fn set(&mut self, key: String, value: String) -> Result<()> {
-        let cmd = Command::set(key, value);
-        let pos = self.writer.pos;
-        serde_json::to_writer(&mut self.writer, &cmd)?;
-        self.writer.flush()?;
-        if let Command::Set { key, .. } = cmd {
+        let cmd = Command::set(self.seqs.last + 1, key, value);
+        let range = self.append(&cmd)?;
+        if let Command::Set { seq, key, value } = cmd {
+            self.seqs.last = seq;
             if let Some(old_cmd) = self.index.get(&key) {
                 self.uncompacted += old_cmd.value().len;
             }
             self.index
-                .insert(key, (self.current_gen, pos..self.writer.pos).into());
-        }
-
-        if self.uncompacted > COMPACTION_THRESHOLD {
-            self.compact()?;
+                .insert(key.clone(), (self.current_gen, range).into());
+            self.watchers.publish(&Event::Set { seq, key, value });
         }
         Ok(())
     }
 
     fn remove(&mut self, key: String) -> Result<()> {
         if self.index.contains_key(&key) {
-            let cmd = Command::remove(key);
-            let pos = self.writer.pos;
-            serde_json::to_writer(&mut self.writer, &cmd)?;
-            self.writer.flush()?;
-            if let Command::Remove { key } = cmd {
+            let cmd = Command::remove(self.seqs.last + 1, key);
+            let range = self.append(&cmd)?;
+            if let Command::Remove { seq, key } = cmd {
+                self.seqs.last = seq;
                 let old_cmd = self.index.remove(&key).expect("key not found");
                 self.uncompacted += old_cmd.value().len;
                 // the "remove" command itself can be deleted in the next compaction
                 // so we add its length to `uncompacted`
-                self.uncompacted += self.writer.pos - pos;
-            }
-
-            if self.uncompacted > COMPACTION_THRESHOLD {
-                self.compact()?;
+                self.uncompacted += range.end - range.start;
+                self.watchers.publish(&Event::Remove { seq, key });
             }
             Ok(())
         } else {
@@ -318,27 +822,80 @@ impl KvStoreWriter {
         }
     }
 
+    /// Writes the command to the end of the log, and flushes it and syncs it if
+    /// required by the durability.
+    ///
+    /// Returns the range of the command in the current log file.
+    fn append(&mut self, cmd: &Command) -> Result<Range<u64>> {
+        let writer = self.writer.as_mut().ok_or(KvsError::ReadOnly)?;
+        let pos = writer.pos;
+        serde_json::to_writer(&mut *writer, cmd)?;
+        writer.flush()?;
+        if self.options.durability == Durability::Sync {
+            writer.writer.get_ref().sync_data()?;
+        }
+        Ok(pos..writer.pos)
+    }
+
+    /// Reads the value of the set command at the given `CommandPos`.
+    fn value(&self, cmd_pos: CommandPos) -> Result<String> {
+        if let Command::Set { value, .. } = self.reader.read_command(cmd_pos)? {
+            Ok(value)
+        } else {
+            Err(KvsError::UnexpectedCommandType)
+        }
+    }
+
+    /// Returns whether a compaction job should be spawned.
+    ///
+    /// It is true when the stale entries exceed the threshold and no compaction is
+    /// waiting to run yet.
+    fn schedule_compaction(&mut self) -> bool {
+        if self.uncompacted > self.options.compaction_threshold && !self.compaction_scheduled {
+            self.compaction_scheduled = true;
+            true
+        } else {
+            false
+        }
+    }
+
     /// Clears stale entries in the log.
     fn compact(&mut self) -> Result<()> {
+        self.compaction_scheduled = false;
+        if self.writer.is_none() {
+            return Err(KvsError::ReadOnly);
+        }
         // increase current gen by 2. current_gen + 1 is for the compaction file
         let compaction_gen = self.current_gen + 1;
         self.current_gen += 2;
-        self.writer = new_log_file(&self.path, self.current_gen)?;
+        self.writer = Some(new_log_file(&self.path, self.current_gen)?);
 
         let mut compaction_writer = new_log_file(&self.path, compaction_gen)?;
 
-        let mut new_pos = 0; // pos in the new log file
+        // history before this point is lost after the compaction
+        let marker = Command::Compacted {
+            seq: self.seqs.last,
+        };
+        serde_json::to_writer(&mut compaction_writer, &marker)?;
+
+        let mut new_pos = compaction_writer.pos; // pos in the new log file
+        let mut new_index = Vec::new();
         for entry in self.index.iter() {
             let len = self.reader.read_and(*entry.value(), |mut entry_reader| {
                 Ok(io::copy(&mut entry_reader, &mut compaction_writer)?)
             })?;
-            self.index.insert(
-                entry.key().clone(),
-                (compaction_gen, new_pos..new_pos + len).into(),
-            );
+            new_index.push((entry.key().clone(), new_pos..new_pos + len));
             new_pos += len;
         }
         compaction_writer.flush()?;
+        if self.options.durability == Durability::Sync {
+            compaction_writer.writer.get_ref().sync_data()?;
+        }
+
+        // Concurrent readers must not see the new positions before they are flushed.
+        for (key, range) in new_index {
+            self.index.insert(key, (compaction_gen, range).into());
+        }
 
         self.reader
             .safe_point
@@ -362,9 +919,36 @@ impl KvStoreWriter {
             }
         }
         self.uncompacted = 0;
+        self.seqs.oldest = self.seqs.last + 1;
 
         Ok(())
     }
+
+    /// Reads the commands in the log with a sequence number not less than `from`
+    /// for keys starting with `prefix`.
+    ///
+    /// Returns the commands as events in sequence order.
+    fn history(&self, prefix: &str, from: u64) -> Result<Vec<Event>> {
+        if from < self.seqs.oldest {
+            return Err(KvsError::Compacted(from));
+        }
+        let mut events = Vec::new();
+        // Stale files that failed to be deleted only contain commands before `oldest`,
+        // so they are filtered out below.
+        for gen in sorted_gen_list(&self.path)? {
+            let reader = BufReader::new(File::open(log_path(&self.path, gen))?);
+            let stream = Deserializer::from_reader(reader).into_iter::<Command>();
+            for cmd in stream {
+                if let Some(event) = cmd?.into_event() {
+                    if event.seq() >= from && event.key().starts_with(prefix) {
+                        events.push(event);
+                    }
+                }
+            }
+        }
+        events.sort_by_key(Event::seq);
+        Ok(events)
+    }
 }
 
 /// Create a new log file with given generation number and add the reader to the readers map.
@@ -382,6 +966,37 @@ fn new_log_file(path: &Path, gen: u64) -> Result<BufWriterWithPos<File>> {
     Ok(writer)
 }
 
+/// Creates the directory if it does not exist and locks it for the process.
+///
+/// Returns the lock file, which is unlocked when it is closed.
+fn lock_dir(path: &Path) -> Result<File> {
+    fs::create_dir_all(path)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.join(LOCK_FILE))?;
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(file),
+        Err(ref e) if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() => {
+            Err(KvsError::Locked(path.display().to_string()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Opens the log file with the given generation number.
+///
+/// Returns `None` if the file has been deleted, which a compaction only does after
+/// copying its commands into a later file.
+fn open_log(path: &Path, gen: u64) -> Result<Option<BufReaderWithPos<File>>> {
+    match File::open(log_path(path, gen)) {
+        Ok(file) => Ok(Some(BufReaderWithPos::new(file)?)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// Returns sorted generation numbers in the given directory
 fn sorted_gen_list(path: &Path) -> Result<Vec<u64>> {
     let mut gen_list: Vec<u64> = fs::read_dir(&path)?
@@ -401,11 +1016,14 @@ fn sorted_gen_list(path: &Path) -> Result<Vec<u64>> {
 
 /// Load the whole log file and store value locations in the index map.
 ///
+/// The sequence numbers found in the log are accumulated in `seqs`.
+///
 /// Returns how many bytes can be saved after a compaction.
 fn load(
     gen: u64,
     reader: &mut BufReaderWithPos<File>,
     index: &SkipMap<String, CommandPos>,
+    seqs: &mut SeqRange,
 ) -> Result<u64> {
     // To make sure we read from the beginning of the file
     let mut pos = reader.seek(SeekFrom::Start(0))?;
@@ -413,14 +1031,16 @@ fn load(
     let mut uncompacted = 0; // number of bytes that can be saved after a compaction
     while let Some(cmd) = stream.next() {
         let new_pos = stream.byte_offset() as u64;
-        match cmd? {
+        let cmd = cmd?;
+        seqs.last = seqs.last.max(cmd.seq());
+        match cmd {
             Command::Set { key, .. } => {
                 if let Some(old_cmd) = index.get(&key) {
                     uncompacted += old_cmd.value().len;
                 }
                 index.insert(key, (gen, pos..new_pos).into());
             }
-            Command::Remove { key } => {
+            Command::Remove { key, .. } => {
                 if let Some(old_cmd) = index.remove(&key) {
                     uncompacted += old_cmd.value().len;
                 }
@@ -428,6 +1048,7 @@ fn load(
                 // so we add its length to `uncompacted`
                 uncompacted += new_pos - pos;
             }
+            Command::Compacted { seq } => seqs.oldest = seq + 1,
         }
         pos = new_pos;
     }
@@ -439,20 +1060,60 @@ fn log_path(dir: &Path, gen: u64) -> PathBuf {
 }
 
 /// Struct representing a command
+///
+/// Logs written before sequence numbers were introduced are read with a
+/// sequence number of 0.
 #[derive(Serialize, Deserialize, Debug)]
 enum Command {
-    Set { key: String, value: String },
-    Remove { key: String },
+    Set {
+        #[serde(default)]
+        seq: u64,
+        key: String,
+        value: String,
+    },
+    Remove {
+        #[serde(default)]
+        seq: u64,
+        key: String,
+    },
+    /// Written at the beginning of a compaction file. Commands with a sequence
+    /// number not greater than `seq` are no longer in the log completely.
+    Compacted { seq: u64 },
 }
 
 impl Command {
-    fn set(key: String, value: String) -> Command {
-        Command::Set { key, value }
+    fn set(seq: u64, key: String, value: String) -> Command {
+        Command::Set { seq, key, value }
     }
 
-    fn remove(key: String) -> Command {
-        Command::Remove { key }
+    fn remove(seq: u64, key: String) -> Command {
+        Command::Remove { seq, key }
     }
+
+    fn seq(&self) -> u64 {
+        match *self {
+            Command::Set { seq, .. } | Command::Remove { seq, .. } | Command::Compacted { seq } => {
+                seq
+            }
+        }
+    }
+
+    fn into_event(self) -> Option<Event> {
+        match self {
+            Command::Set { seq, key, value } => Some(Event::Set { seq, key, value }),
+            Command::Remove { seq, key } => Some(Event::Remove { seq, key }),
+            Command::Compacted { .. } => None,
+        }
+    }
+}
+
+/// The range of sequence numbers kept in the log.
+#[derive(Debug, Default, Clone, Copy)]
+struct SeqRange {
+    // the smallest sequence number whose history is still complete in the log
+    oldest: u64,
+    // the sequence number of the latest command
+    last: u64,
 }
 
 /// Represents the position and length of a json-serialized command in the log
@@ -1,11 +1,21 @@
-pub use self::kvs::KvStore;
+pub use self::kvs::{Durability, KvStore, KvStoreOptions};
 pub use self::sled::SledKvsEngine;
-use crate::KvsError;
+use crate::transfer::{self, DataFormat};
+use crate::{KvsError, Result};
 
-use tokio::prelude::Future;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::mem;
+use tokio::prelude::{Future, Stream};
+
+// number of pairs set in one call during an import, and between two progress reports
+const IMPORT_BATCH: usize = 1000;
+// number of pairs read in one scan during an export
+const EXPORT_BATCH: usize = 1000;
 
 mod kvs;
 mod sled;
+mod watch;
 
 /// Trait for a key value storage engine.
 pub trait KvsEngine: Clone + Send + 'static {
@@ -25,4 +35,190 @@ pub trait KvsEngine: Clone + Send + 'static {
     ///
     /// It returns `KvsError::KeyNotFound` if the given key is not found.
     fn remove(&self, key: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send>;
+
+    /// Sets the values of many string keys in order.
+    ///
+    /// If it fails, the pairs before the failed one have been set.
+    fn set_many(
+        &self,
+        pairs: Vec<(String, String)>,
+    ) -> Box<dyn Future<Item = (), Error = KvsError> + Send>;
+
+    /// Returns up to `limit` key/value pairs with keys greater than `after`, sorted by key.
+    ///
+    /// All pairs are returned from the first key if `after` is `None`. An empty result
+    /// means there are no more keys.
+    fn scan(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Box<dyn Future<Item = Vec<(String, String)>, Error = KvsError> + Send>;
+
+    /// Watches changes of keys starting with `prefix`.
+    ///
+    /// The returned stream yields an `Event` for every set or remove that happens
+    /// after the watch is registered. If `from` is given, the events still in the
+    /// log with a sequence number not less than `from` are replayed first, and later
+    /// events are only delivered from `from` on.
+    ///
+    /// # Errors
+    ///
+    /// The stream fails with `KvsError::Compacted` if `from` refers to history that
+    /// is no longer available.
+    fn watch(&self, prefix: String, from: Option<u64>) -> EventStream;
+
+    /// Returns an engine operating on the namespace `name`.
+    ///
+    /// Keys in different namespaces are independent of each other. The engine itself
+    /// operates on the default namespace.
+    ///
+    /// # Errors
+    ///
+    /// It returns `KvsError::NamespaceNotFound` if the namespace does not exist.
+    fn namespace(&self, name: &str) -> Result<Self>;
+
+    /// Creates a new namespace.
+    ///
+    /// # Errors
+    ///
+    /// It returns `KvsError::InvalidNamespace` if the name is not valid and
+    /// `KvsError::NamespaceExists` if the namespace already exists.
+    fn create_namespace(&self, name: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send>;
+
+    /// Drops a namespace and all keys in it.
+    ///
+    /// # Errors
+    ///
+    /// It returns `KvsError::NamespaceNotFound` if the namespace does not exist.
+    fn drop_namespace(&self, name: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send>;
+
+    /// Lists the names of all namespaces except the default one.
+    fn list_namespaces(&self) -> Box<dyn Future<Item = Vec<String>, Error = KvsError> + Send>;
+
+    /// Takes a snapshot of all key/value pairs and watches the changes made after it.
+    ///
+    /// No change is missed or seen twice between the snapshot and the stream, so the
+    /// two together are enough to replicate the key space.
+    fn snapshot(&self) -> Box<dyn Future<Item = (Snapshot, EventStream), Error = KvsError> + Send>;
+
+    /// Replaces all key/value pairs with those in the snapshot.
+    ///
+    /// Only the keys that differ are changed. The changes are ordinary sets and removes,
+    /// so they are seen by watchers.
+    fn restore(&self, snapshot: Snapshot) -> Box<dyn Future<Item = (), Error = KvsError> + Send>;
+
+    /// Writes all key/value pairs to `writer` in `format`.
+    ///
+    /// The pairs are scanned in batches while they are written, so changes made during
+    /// the export may or may not be included. Returns the number of pairs written. It
+    /// blocks the current thread.
+    fn export<W: Write>(&self, writer: W, format: DataFormat) -> Result<u64> {
+        let pairs = transfer::scan_pages(|after| self.scan(after, EXPORT_BATCH).wait());
+        transfer::write_pairs(writer, format, pairs)
+    }
+
+    /// Sets the key/value pairs read from `reader` in `format`.
+    ///
+    /// The pairs are set in batches with `set_many`. The first `skip` pairs are
+    /// skipped, so an interrupted import can be resumed. `progress` is called with the
+    /// number of pairs read so far, including the skipped ones, after every batch.
+    /// Returns the number of pairs read. It blocks the current thread.
+    fn import<R, F>(&self, reader: R, format: DataFormat, skip: u64, mut progress: F) -> Result<u64>
+    where
+        R: Read,
+        F: FnMut(u64),
+    {
+        let mut count = 0;
+        let mut batch = Vec::with_capacity(IMPORT_BATCH);
+        for pair in transfer::read_pairs(reader, format) {
+            let pair = pair?;
+            count += 1;
+            if count <= skip {
+                continue;
+            }
+            batch.push(pair);
+            if batch.len() == IMPORT_BATCH {
+                self.set_many(mem::replace(&mut batch, Vec::with_capacity(IMPORT_BATCH)))
+                    .wait()?;
+                progress(count);
+            }
+        }
+        if !batch.is_empty() {
+            self.set_many(batch).wait()?;
+        }
+        progress(count);
+        Ok(count)
+    }
+}
+
+/// Checks that `name` is usable as a namespace name.
+///
+/// A name consists of ASCII letters, digits, `-` and `_`, and starts with a letter
+/// or a digit.
+pub(crate) fn check_namespace(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(first) => {
+            first.is_ascii_alphanumeric()
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(KvsError::InvalidNamespace(name.to_owned()))
+    }
+}
+
+/// A stream of changes returned by `KvsEngine::watch`.
+pub type EventStream = Box<dyn Stream<Item = Event, Error = KvsError> + Send>;
+
+/// A change made to the key space.
+///
+/// Every change has a sequence number which increases monotonically in the order
+/// the changes are applied to the engine.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    /// The key is set to the value.
+    Set {
+        /// Sequence number of the change
+        seq: u64,
+        /// The key that is set
+        key: String,
+        /// The new value of the key
+        value: String,
+    },
+    /// The key is removed.
+    Remove {
+        /// Sequence number of the change
+        seq: u64,
+        /// The key that is removed
+        key: String,
+    },
+}
+
+/// A consistent copy of the key/value pairs returned by `KvsEngine::snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Sequence number of the latest change included in the snapshot
+    pub seq: u64,
+    /// The key/value pairs sorted by key
+    pub pairs: Vec<(String, String)>,
+}
+
+impl Event {
+    /// Returns the sequence number of the change.
+    pub fn seq(&self) -> u64 {
+        match *self {
+            Event::Set { seq, .. } | Event::Remove { seq, .. } => seq,
+        }
+    }
+
+    /// Returns the key that is changed.
+    pub fn key(&self) -> &str {
+        match self {
+            Event::Set { key, .. } | Event::Remove { key, .. } => key,
+        }
+    }
 }
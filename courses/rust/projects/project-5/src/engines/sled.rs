@@ -1,14 +1,85 @@
+use super::watch::{self, Watchers};
+use super::{check_namespace, Event, EventStream, Snapshot};
 use crate::thread_pool::ThreadPool;
 use crate::{KvsEngine, KvsError, Result};
-use sled::Db;
+use sled::{Db, Tree};
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::prelude::*;
-use tokio::sync::oneshot;
 
 /// Wrapper of `sled::Db`
+///
+/// Writes are serialized so that their sequence numbers, generated by sled, are
+/// published to watchers in order. Sled keeps no history of the changes, so a watch
+/// can only be resumed from the next sequence number.
+///
+/// Namespaces are mapped to separate sled trees.
 #[derive(Clone)]
 pub struct SledKvsEngine<P: ThreadPool> {
     pool: P,
     db: Db,
+    space: Space,
+    writer: Arc<Mutex<SledWriter>>,
+}
+
+/// The tree a `SledKvsEngine` operates on.
+#[derive(Clone)]
+enum Space {
+    Default,
+    Named(Arc<NamedTree>),
+}
+
+/// The tree of a namespace, shared by the engines operating on it.
+struct NamedTree {
+    name: String,
+    tree: Arc<Tree>,
+    // set when the namespace is dropped
+    dropped: AtomicBool,
+}
+
+impl Space {
+    /// Returns the tree, or an error if the namespace has been dropped.
+    ///
+    /// Writers call it while holding the `SledWriter` lock, under which namespaces
+    /// are dropped.
+    fn tree<'a>(&'a self, db: &'a Db) -> Result<&'a Tree> {
+        let tree: &Tree = match self {
+            Space::Default => db,
+            Space::Named(named) => {
+                if named.dropped.load(Ordering::SeqCst) {
+                    return Err(KvsError::NamespaceNotFound(named.name.clone()));
+                }
+                &named.tree
+            }
+        };
+        Ok(tree)
+    }
+
+    fn name(&self) -> Option<String> {
+        match self {
+            Space::Default => None,
+            Space::Named(named) => Some(named.name.clone()),
+        }
+    }
+}
+
+struct SledWriter {
+    last_seq: u64,
+    // watchers of each namespace
+    watchers: HashMap<Option<String>, Watchers>,
+    // the namespaces opened by an engine
+    trees: HashMap<String, Arc<NamedTree>>,
+}
+
+impl SledWriter {
+    fn publish(&mut self, space: &Space, event: Event) {
+        self.last_seq = event.seq();
+        if let Some(watchers) = self.watchers.get_mut(&space.name()) {
+            watchers.publish(&event);
+        }
+    }
 }
 
 impl<P: ThreadPool> SledKvsEngine<P> {
@@ -18,67 +89,274 @@ impl<P: ThreadPool> SledKvsEngine<P> {
     /// threads in the thread pool.
     pub fn new(db: Db, concurrency: u32) -> Result<Self> {
         let pool = P::new(concurrency)?;
-        Ok(SledKvsEngine { pool, db })
+        let writer = SledWriter {
+            last_seq: db.generate_id()?,
+            watchers: HashMap::new(),
+            trees: HashMap::new(),
+        };
+        Ok(SledKvsEngine {
+            pool,
+            db,
+            space: Space::Default,
+            writer: Arc::new(Mutex::new(writer)),
+        })
     }
 }
 
 impl<P: ThreadPool> KvsEngine for SledKvsEngine<P> {
     fn set(&self, key: String, value: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
         let db = self.db.clone();
-        let (tx, rx) = oneshot::channel();
-        self.pool.spawn(move || {
-            let res = db
-                .set(key, value.into_bytes())
-                .and_then(|_| db.flush())
-                .map(|_| ())
-                .map_err(KvsError::from);
-            if tx.send(res).is_err() {
-                error!("Receiving end is dropped");
-            }
+        let space = self.space.clone();
+        let writer = self.writer.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            let mut writer = writer.lock().unwrap();
+            let tree = space.tree(&db)?;
+            tree.set(key.as_bytes(), value.clone().into_bytes())?;
+            tree.flush()?;
+            let seq = db.generate_id()?;
+            writer.publish(&space, Event::Set { seq, key, value });
+            Ok(())
         });
-        Box::new(
-            rx.map_err(|e| KvsError::StringError(format!("{}", e)))
-                .flatten(),
-        )
+        Box::new(handle.flatten())
     }
 
     fn get(&self, key: String) -> Box<dyn Future<Item = Option<String>, Error = KvsError> + Send> {
         let db = self.db.clone();
-        let (tx, rx) = oneshot::channel();
-        self.pool.spawn(move || {
-            let res = (move || {
-                Ok(db
-                    .get(key)?
-                    .map(|i_vec| AsRef::<[u8]>::as_ref(&i_vec).to_vec())
-                    .map(String::from_utf8)
-                    .transpose()?)
-            })();
-            if tx.send(res).is_err() {
-                error!("Receiving end is dropped");
-            }
+        let space = self.space.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            Ok(space
+                .tree(&db)?
+                .get(key)?
+                .map(|i_vec| AsRef::<[u8]>::as_ref(&i_vec).to_vec())
+                .map(String::from_utf8)
+                .transpose()?)
         });
-        Box::new(
-            rx.map_err(|e| KvsError::StringError(format!("{}", e)))
-                .flatten(),
-        )
+        Box::new(handle.flatten())
     }
 
     fn remove(&self, key: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
         let db = self.db.clone();
-        let (tx, rx) = oneshot::channel();
-        self.pool.spawn(move || {
-            let res = (|| {
-                db.del(key)?.ok_or(KvsError::KeyNotFound)?;
-                db.flush()?;
-                Ok(())
-            })();
-            if tx.send(res).is_err() {
-                error!("Receiving end is dropped");
+        let space = self.space.clone();
+        let writer = self.writer.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            let mut writer = writer.lock().unwrap();
+            let tree = space.tree(&db)?;
+            tree.del(key.as_bytes())?.ok_or(KvsError::KeyNotFound)?;
+            tree.flush()?;
+            let seq = db.generate_id()?;
+            writer.publish(&space, Event::Remove { seq, key });
+            Ok(())
+        });
+        Box::new(handle.flatten())
+    }
+
+    fn set_many(
+        &self,
+        pairs: Vec<(String, String)>,
+    ) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
+        let db = self.db.clone();
+        let space = self.space.clone();
+        let writer = self.writer.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            let mut writer = writer.lock().unwrap();
+            let tree = space.tree(&db)?;
+            let mut events = Vec::new();
+            for (key, value) in pairs {
+                tree.set(key.as_bytes(), value.clone().into_bytes())?;
+                let seq = db.generate_id()?;
+                events.push(Event::Set { seq, key, value });
+            }
+            tree.flush()?;
+            for event in events {
+                writer.publish(&space, event);
+            }
+            Ok(())
+        });
+        Box::new(handle.flatten())
+    }
+
+    fn scan(
+        &self,
+        after: Option<String>,
+        limit: usize,
+    ) -> Box<dyn Future<Item = Vec<(String, String)>, Error = KvsError> + Send> {
+        let db = self.db.clone();
+        let space = self.space.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            let start = after.map_or(Bound::Unbounded, |key| Bound::Excluded(key.into_bytes()));
+            let tree = space.tree(&db)?;
+            let mut pairs = Vec::new();
+            for item in tree.range((start, Bound::Unbounded)).take(limit) {
+                let (key, value) = item?;
+                pairs.push((into_string(key)?, into_string(value)?));
             }
+            Ok(pairs)
         });
-        Box::new(
-            rx.map_err(|e| KvsError::StringError(format!("{}", e)))
-                .flatten(),
-        )
+        Box::new(handle.flatten())
     }
+
+    fn watch(&self, prefix: String, from: Option<u64>) -> EventStream {
+        let (mut tx, rx) = watch::channel();
+        let mut writer = self.writer.lock().unwrap();
+        match from {
+            Some(from) if from <= writer.last_seq => {
+                if tx.try_send(Err(KvsError::Compacted(from))).is_err() {
+                    error!("Receiving end is dropped");
+                }
+            }
+            _ => writer
+                .watchers
+                .entry(self.space.name())
+                .or_default()
+                .subscribe(prefix, from.unwrap_or(0), tx),
+        }
+        rx
+    }
+
+    fn namespace(&self, name: &str) -> Result<Self> {
+        check_namespace(name)?;
+        let mut writer = self.writer.lock().unwrap();
+        let named = match writer.trees.get(name) {
+            Some(named) => named.clone(),
+            None => {
+                if !tree_exists(&self.db, name) {
+                    return Err(KvsError::NamespaceNotFound(name.to_owned()));
+                }
+                let named = Arc::new(NamedTree {
+                    name: name.to_owned(),
+                    tree: self.db.open_tree(name.as_bytes().to_vec())?,
+                    dropped: AtomicBool::new(false),
+                });
+                writer.trees.insert(name.to_owned(), named.clone());
+                named
+            }
+        };
+        Ok(SledKvsEngine {
+            pool: self.pool.clone(),
+            db: self.db.clone(),
+            space: Space::Named(named),
+            writer: self.writer.clone(),
+        })
+    }
+
+    fn create_namespace(&self, name: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
+        let db = self.db.clone();
+        let writer = self.writer.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            check_namespace(&name)?;
+            let _writer = writer.lock().unwrap();
+            if tree_exists(&db, &name) {
+                return Err(KvsError::NamespaceExists(name));
+            }
+            db.open_tree(name.into_bytes())?;
+            db.flush()?;
+            Ok(())
+        });
+        Box::new(handle.flatten())
+    }
+
+    fn drop_namespace(&self, name: String) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
+        let db = self.db.clone();
+        let writer = self.writer.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            check_namespace(&name).map_err(|_| KvsError::NamespaceNotFound(name.clone()))?;
+            let mut writer = writer.lock().unwrap();
+            if !db.drop_tree(name.as_bytes())? {
+                return Err(KvsError::NamespaceNotFound(name));
+            }
+            db.flush()?;
+            if let Some(named) = writer.trees.remove(&name) {
+                named.dropped.store(true, Ordering::SeqCst);
+            }
+            writer.watchers.remove(&Some(name));
+            Ok(())
+        });
+        Box::new(handle.flatten())
+    }
+
+    fn list_namespaces(&self) -> Box<dyn Future<Item = Vec<String>, Error = KvsError> + Send> {
+        let mut names: Vec<String> = self
+            .db
+            .tree_names()
+            .into_iter()
+            .filter_map(|name| String::from_utf8(name).ok())
+            .filter(|name| check_namespace(name).is_ok())
+            .collect();
+        names.sort_unstable();
+        Box::new(future::ok(names))
+    }
+
+    fn snapshot(&self) -> Box<dyn Future<Item = (Snapshot, EventStream), Error = KvsError> + Send> {
+        let db = self.db.clone();
+        let space = self.space.clone();
+        let writer = self.writer.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            let mut writer = writer.lock().unwrap();
+            let mut pairs = Vec::new();
+            for item in space.tree(&db)?.iter() {
+                let (key, value) = item?;
+                pairs.push((into_string(key)?, into_string(value)?));
+            }
+            let (tx, rx) = watch::channel();
+            writer
+                .watchers
+                .entry(space.name())
+                .or_default()
+                .subscribe(String::new(), 0, tx);
+            let snapshot = Snapshot {
+                seq: writer.last_seq,
+                pairs,
+            };
+            Ok((snapshot, rx))
+        });
+        Box::new(handle.flatten())
+    }
+
+    fn restore(&self, snapshot: Snapshot) -> Box<dyn Future<Item = (), Error = KvsError> + Send> {
+        let db = self.db.clone();
+        let space = self.space.clone();
+        let writer = self.writer.clone();
+        let handle = self.pool.spawn_with_handle(move || {
+            let mut writer = writer.lock().unwrap();
+            let tree = space.tree(&db)?;
+            let keys: HashSet<&str> = snapshot.pairs.iter().map(|(key, _)| &key[..]).collect();
+            let mut stale = Vec::new();
+            for item in tree.iter() {
+                let key = into_string(item?.0)?;
+                if !keys.contains(&key[..]) {
+                    stale.push(key);
+                }
+            }
+            let mut events = Vec::new();
+            for key in stale {
+                tree.del(key.as_bytes())?;
+                let seq = db.generate_id()?;
+                events.push(Event::Remove { seq, key });
+            }
+            for (key, value) in snapshot.pairs.iter().cloned() {
+                if let Some(old) = tree.get(key.as_bytes())? {
+                    if AsRef::<[u8]>::as_ref(&old) == value.as_bytes() {
+                        continue;
+                    }
+                }
+                tree.set(key.as_bytes(), value.clone().into_bytes())?;
+                let seq = db.generate_id()?;
+                events.push(Event::Set { seq, key, value });
+            }
+            tree.flush()?;
+            for event in events {
+                writer.publish(&space, event);
+            }
+            Ok(())
+        });
+        Box::new(handle.flatten())
+    }
+}
+
+fn into_string(bytes: impl AsRef<[u8]>) -> Result<String> {
+    Ok(String::from_utf8(bytes.as_ref().to_vec())?)
+}
+
+fn tree_exists(db: &Db, name: &str) -> bool {
+    db.tree_names().iter().any(|n| n == name.as_bytes())
 }
@@ -0,0 +1,71 @@
+use std::mem;
+
+use tokio::prelude::*;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use super::{Event, EventStream};
+use crate::{KvsError, Result};
+
+/// Creates the channel through which events are delivered to a watcher.
+///
+/// The sender carries `Result`s so a watch that cannot be registered can report
+/// its error through the stream.
+pub(crate) fn channel() -> (UnboundedSender<Result<Event>>, EventStream) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (tx, into_stream(rx))
+}
+
+fn into_stream(rx: UnboundedReceiver<Result<Event>>) -> EventStream {
+    Box::new(
+        rx.map_err(|e| KvsError::StringError(format!("{}", e)))
+            .and_then(|res| res),
+    )
+}
+
+/// The registered watchers of an engine.
+///
+/// It is not synchronized by itself. Engines keep it under the same lock that
+/// orders their writes, so that events are published in sequence order.
+#[derive(Default)]
+pub(crate) struct Watchers {
+    subscribers: Vec<Subscriber>,
+}
+
+struct Subscriber {
+    prefix: String,
+    // events before this sequence number are not delivered
+    from: u64,
+    tx: UnboundedSender<Result<Event>>,
+}
+
+impl Watchers {
+    /// Registers a watcher for keys starting with `prefix`, changed at sequence
+    /// number `from` or later.
+    pub fn subscribe(&mut self, prefix: String, from: u64, tx: UnboundedSender<Result<Event>>) {
+        self.subscribers.push(Subscriber { prefix, from, tx });
+    }
+
+    /// Delivers the event to all interested watchers.
+    ///
+    /// Watchers whose receiving end is dropped are removed.
+    pub fn publish(&mut self, event: &Event) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let subscribers = mem::take(&mut self.subscribers);
+        self.subscribers = subscribers
+            .into_iter()
+            .filter_map(|mut sub| {
+                if event.seq() >= sub.from
+                    && event.key().starts_with(&sub.prefix)
+                    && sub.tx.try_send(Ok(event.clone())).is_err()
+                {
+                    debug!("Watcher of {:?} is dropped", sub.prefix);
+                    None
+                } else {
+                    Some(sub)
+                }
+            })
+            .collect();
+    }
+}
@@ -11,6 +11,9 @@ pub enum KvsError {
     /// Serialization or deserialization error
     #[fail(display = "serde_json error: {}", _0)]
     Serde(#[cause] serde_json::Error),
+    /// CSV error
+    #[fail(display = "CSV error: {}", _0)]
+    Csv(#[cause] csv::Error),
     /// Removing non-existent key error
     #[fail(display = "Key not found")]
     KeyNotFound,
@@ -24,6 +27,45 @@ pub enum KvsError {
     /// Sled error
     #[fail(display = "sled error: {}", _0)]
     Sled(#[cause] sled::Error),
+    /// The requested sequence number is no longer in the log
+    #[fail(display = "Sequence number {} has been compacted", _0)]
+    Compacted(u64),
+    /// Namespace does not exist
+    #[fail(display = "Namespace not found: {}", _0)]
+    NamespaceNotFound(String),
+    /// Creating a namespace that already exists
+    #[fail(display = "Namespace already exists: {}", _0)]
+    NamespaceExists(String),
+    /// Namespace name is not valid
+    #[fail(display = "Invalid namespace name: {:?}", _0)]
+    InvalidNamespace(String),
+    /// The thread pool has been shut down
+    #[fail(display = "Thread pool has been shut down")]
+    ThreadPoolShutdown,
+    /// Threads of the thread pool did not exit in time
+    #[fail(display = "Thread pool shutdown timed out")]
+    ShutdownTimeout,
+    /// A job spawned into a thread pool panicked
+    #[fail(display = "Job panicked")]
+    JobPanicked,
+    /// A job spawned into a thread pool was dropped without running
+    #[fail(display = "Job was canceled")]
+    JobCanceled,
+    /// Key is longer than the server accepts
+    #[fail(display = "Key is too large: {} bytes, at most {} allowed", _0, _1)]
+    KeyTooLarge(usize, usize),
+    /// Value is longer than the server accepts
+    #[fail(display = "Value is too large: {} bytes, at most {} allowed", _0, _1)]
+    ValueTooLarge(usize, usize),
+    /// Writing to a server which is a replica
+    #[fail(display = "Server is a read-only replica")]
+    ReadOnlyReplica,
+    /// The data directory is opened for writing by another store
+    #[fail(display = "Data directory is locked by another process: {}", _0)]
+    Locked(String),
+    /// Writing to a store opened read-only
+    #[fail(display = "Store is opened read-only")]
+    ReadOnly,
     /// Error with a string message
     #[fail(display = "{}", _0)]
     StringError(String),
@@ -41,6 +83,12 @@ impl From<serde_json::Error> for KvsError {
     }
 }
 
+impl From<csv::Error> for KvsError {
+    fn from(err: csv::Error) -> KvsError {
+        KvsError::Csv(err)
+    }
+}
+
 impl From<FromUtf8Error> for KvsError {
     fn from(err: FromUtf8Error) -> KvsError {
         KvsError::Utf8(err)
@@ -5,13 +5,19 @@
 extern crate log;
 
 pub use client::KvsClient;
-pub use engines::{KvStore, KvsEngine, SledKvsEngine};
+pub use engines::{
+    Durability, Event, EventStream, KvStore, KvStoreOptions, KvsEngine, SledKvsEngine, Snapshot,
+};
 pub use error::{KvsError, Result};
-pub use server::KvsServer;
+pub use server::{KvsServer, Limits};
+pub use sharding::ShardedKvsClient;
 
 mod client;
 mod common;
 mod engines;
 mod error;
+mod replication;
 mod server;
+mod sharding;
 pub mod thread_pool;
+pub mod transfer;
@@ -0,0 +1,226 @@
+//! Replication of a primary into a replica.
+//!
+//! A replica follows every namespace of its primary with one `Replicate` connection
+//! per namespace. Each connection streams a snapshot of the namespace followed by its
+//! changes, which is simpler than shipping the log but has some gaps:
+//!
+//! - Writes are acknowledged by the primary before replicas apply them, so a replica
+//!   promoted after the primary fails may miss the latest writes.
+//! - Every reconnection restores a full snapshot instead of resuming from the last
+//!   applied sequence number.
+//! - Namespaces created or dropped on the primary are noticed by polling its list of
+//!   namespaces, and all namespaces are restored again when it changes.
+
+use crate::common::Response;
+use crate::{Event, KvsClient, KvsEngine, KvsError, Snapshot};
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::prelude::future::{Either, Loop};
+use tokio::prelude::*;
+use tokio::sync::oneshot;
+use tokio::timer::Delay;
+
+// interval between attempts to reconnect to the primary
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+// interval between checks of the namespaces of the primary
+const NAMESPACE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The replication role of a server.
+///
+/// It is shared by the connections of the server and the task following the primary.
+#[derive(Clone, Default)]
+pub(crate) struct Role {
+    state: Arc<Mutex<RoleState>>,
+}
+
+#[derive(Default)]
+struct RoleState {
+    // address of the primary while the server is a replica
+    primary: Option<SocketAddr>,
+    // notifies the task following the primary when the server is promoted
+    promoted: Option<oneshot::Sender<()>>,
+}
+
+impl Role {
+    pub fn replica_of(primary: SocketAddr) -> Role {
+        Role {
+            state: Arc::new(Mutex::new(RoleState {
+                primary: Some(primary),
+                promoted: None,
+            })),
+        }
+    }
+
+    pub fn primary(&self) -> Option<SocketAddr> {
+        self.state.lock().unwrap().primary
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.primary().is_some()
+    }
+
+    /// Makes the server a primary.
+    ///
+    /// Returns whether the server was a replica.
+    pub fn promote(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if let Some(tx) = state.promoted.take() {
+            let _ = tx.send(());
+        }
+        state.primary.take().is_some()
+    }
+
+    /// Returns a future which resolves once the server is a primary.
+    ///
+    /// Only the future returned last is notified, as there is one task following the
+    /// primary.
+    fn promoted(&self) -> impl Future<Item = (), Error = KvsError> {
+        let (tx, rx) = oneshot::channel();
+        let mut state = self.state.lock().unwrap();
+        if state.primary.is_some() {
+            state.promoted = Some(tx);
+        } else {
+            let _ = tx.send(());
+        }
+        rx.then(|_| Ok::<(), KvsError>(()))
+    }
+}
+
+/// Replicates all namespaces of `primary` into `engine` until the server is promoted.
+///
+/// Every connection starts with a snapshot which is restored into the engine, so the
+/// replica catches up with the primary after reconnecting.
+pub(crate) fn follow<E: KvsEngine>(
+    engine: E,
+    primary: SocketAddr,
+    role: Role,
+) -> impl Future<Item = (), Error = ()> {
+    future::loop_fn((), move |()| {
+        let role = role.clone();
+        follow_once(engine.clone(), primary, role.clone()).then(move |res| {
+            if !role.is_replica() {
+                info!("Stopped following primary {}", primary);
+                return Either::A(future::ok(Loop::Break(())));
+            }
+            match res {
+                Ok(()) => warn!("Disconnected from primary {}", primary),
+                Err(e) => error!("Replication from {} failed: {}", primary, e),
+            }
+            Either::B(
+                Delay::new(Instant::now() + RETRY_INTERVAL)
+                    .map(|_| Loop::Continue(()))
+                    .map_err(|e| error!("Timer error: {}", e)),
+            )
+        })
+    })
+}
+
+/// Follows the namespaces of `primary` until a connection ends, the namespaces of the
+/// primary change or the server is promoted.
+fn follow_once<E: KvsEngine>(
+    engine: E,
+    primary: SocketAddr,
+    role: Role,
+) -> impl Future<Item = (), Error = KvsError> {
+    let replication = KvsClient::connect(primary)
+        .and_then(|client| client.list_namespaces())
+        .and_then(move |(names, client)| {
+            sync_namespaces(engine.clone(), names.clone()).and_then(move |()| {
+                let mut tasks: Vec<Box<dyn Future<Item = (), Error = KvsError> + Send>> =
+                    vec![Box::new(namespace_changed(client, names.clone()))];
+                tasks.push(Box::new(follow_namespace(engine.clone(), primary, None)));
+                for name in names {
+                    let engine = engine.namespace(&name)?;
+                    tasks.push(Box::new(follow_namespace(engine, primary, Some(name))));
+                }
+                Ok(future::select_all(tasks).map(|_| ()).map_err(|(e, _, _)| e))
+            })
+        })
+        .flatten();
+    replication
+        .select2(role.promoted())
+        .map(|_| ())
+        .map_err(|either| match either {
+            Either::A((e, _)) | Either::B((e, _)) => e,
+        })
+}
+
+/// Creates and drops namespaces of `engine` so they match `names`.
+fn sync_namespaces<E: KvsEngine>(
+    engine: E,
+    names: Vec<String>,
+) -> impl Future<Item = (), Error = KvsError> {
+    engine.list_namespaces().and_then(move |local| {
+        let dropped = local
+            .iter()
+            .filter(|name| !names.contains(*name))
+            .map(|name| engine.drop_namespace(name.clone()));
+        let created = names
+            .iter()
+            .filter(|name| !local.contains(*name))
+            .map(|name| engine.create_namespace(name.clone()));
+        future::join_all(dropped.chain(created).collect::<Vec<_>>()).map(|_| ())
+    })
+}
+
+/// Resolves when the namespaces of the primary are no longer `names`.
+fn namespace_changed(
+    client: KvsClient,
+    names: Vec<String>,
+) -> impl Future<Item = (), Error = KvsError> {
+    future::loop_fn(client, move |client| {
+        let names = names.clone();
+        Delay::new(Instant::now() + NAMESPACE_POLL_INTERVAL)
+            .map_err(|e| KvsError::StringError(format!("Timer error: {}", e)))
+            .and_then(move |_| client.list_namespaces())
+            .map(move |(current, client)| {
+                if current == names {
+                    Loop::Continue(client)
+                } else {
+                    info!("Namespaces of the primary changed");
+                    Loop::Break(())
+                }
+            })
+    })
+}
+
+/// Replicates a namespace of `primary` into `engine`, which is the same namespace of
+/// the replica.
+fn follow_namespace<E: KvsEngine>(
+    engine: E,
+    primary: SocketAddr,
+    namespace: Option<String>,
+) -> impl Future<Item = (), Error = KvsError> {
+    KvsClient::connect(primary)
+        .map(move |client| client.with_namespace(namespace).replicate())
+        .flatten_stream()
+        .fold(Vec::new(), move |mut pairs, resp| {
+            let applied: Box<dyn Future<Item = (), Error = KvsError> + Send> = match resp {
+                Response::Snapshot(chunk) => {
+                    pairs.extend(chunk);
+                    return Either::A(future::ok(pairs));
+                }
+                Response::SnapshotEnd(seq) => {
+                    info!("Restoring snapshot at {} from primary {}", seq, primary);
+                    let pairs = mem::take(&mut pairs);
+                    engine.restore(Snapshot { seq, pairs })
+                }
+                Response::Event(Event::Set { key, value, .. }) => engine.set(key, value),
+                Response::Event(Event::Remove { key, .. }) => {
+                    Box::new(engine.remove(key).or_else(|e| match e {
+                        // already removed by the snapshot
+                        KvsError::KeyNotFound => Ok(()),
+                        e => Err(e),
+                    }))
+                }
+                _ => Box::new(future::err(KvsError::StringError(
+                    "Invalid response".to_owned(),
+                ))),
+            };
+            Either::B(applied.map(move |_| pairs))
+        })
+        .map(|_| ())
+}
@@ -1,4 +1,5 @@
 use crate::common::{Request, Response};
+use crate::replication::{self, Role};
 use crate::{KvsEngine, KvsError, Result};
 use std::net::SocketAddr;
 use tokio::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
@@ -6,50 +7,117 @@ use tokio::net::{TcpListener, TcpStream};
 use tokio::prelude::*;
 use tokio_serde_json::{ReadJson, WriteJson};
 
+// number of key/value pairs in each snapshot message sent to a replica, and the most
+// returned by a scan
+const SNAPSHOT_CHUNK: usize = 1024;
+
 /// The server of a key value store.
+///
+/// A server is either a primary or a replica. A replica follows every namespace of its
+/// primary and rejects writes until it is promoted.
 pub struct KvsServer<E: KvsEngine> {
     engine: E,
+    role: Role,
+    limits: Limits,
+}
+
+/// Limits on the requests accepted by a `KvsServer`.
+///
+/// `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum length of a key in bytes
+    pub max_key_size: Option<usize>,
+    /// The maximum length of a value in bytes
+    pub max_value_size: Option<usize>,
+}
+
+impl Limits {
+    fn check(&self, key: &str, value: Option<&str>) -> Result<()> {
+        if let Some(limit) = self.max_key_size {
+            if key.len() > limit {
+                return Err(KvsError::KeyTooLarge(key.len(), limit));
+            }
+        }
+        if let (Some(limit), Some(value)) = (self.max_value_size, value) {
+            if value.len() > limit {
+                return Err(KvsError::ValueTooLarge(value.len(), limit));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<E: KvsEngine> KvsServer<E> {
     /// Create a `KvsServer` with a given storage engine.
     pub fn new(engine: E) -> Self {
-        KvsServer { engine }
+        KvsServer {
+            engine,
+            role: Role::default(),
+            limits: Limits::default(),
+        }
+    }
+
+    /// Reject requests exceeding the limits.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Make the server a replica of the server at `primary`.
+    pub fn replica_of(mut self, primary: SocketAddr) -> Self {
+        self.role = Role::replica_of(primary);
+        self
     }
 
     /// Run the server listening on the given address
     pub fn run(self, addr: SocketAddr) -> Result<()> {
         let listener = TcpListener::bind(&addr)?;
-        let server = listener
-            .incoming()
-            .map_err(|e| error!("IO error: {}", e))
-            .for_each(move |tcp| {
-                let engine = self.engine.clone();
-                serve(engine, tcp).map_err(|e| error!("Error on serving client: {}", e))
-            });
+        let KvsServer {
+            engine,
+            role,
+            limits,
+        } = self;
+        let server = future::lazy(move || {
+            if let Some(primary) = role.primary() {
+                tokio::spawn(replication::follow(engine.clone(), primary, role.clone()));
+            }
+            listener
+                .incoming()
+                .map_err(|e| error!("IO error: {}", e))
+                .for_each(move |tcp| {
+                    let engine = engine.clone();
+                    // Serve each connection in its own task so that a long-lived watch
+                    // does not block other clients.
+                    tokio::spawn(
+                        serve(engine, role.clone(), limits, tcp)
+                            .map_err(|e| error!("Error on serving client: {}", e)),
+                    );
+                    Ok(())
+                })
+        });
         tokio::run(server);
         Ok(())
     }
 }
 
-fn serve<E: KvsEngine>(engine: E, tcp: TcpStream) -> impl Future<Item = (), Error = KvsError> {
+fn serve<E: KvsEngine>(
+    engine: E,
+    role: Role,
+    limits: Limits,
+    tcp: TcpStream,
+) -> impl Future<Item = (), Error = KvsError> {
     let (read_half, write_half) = tcp.split();
     let read_json = ReadJson::new(FramedRead::new(read_half, LengthDelimitedCodec::new()));
+    // Each request is answered by a stream of responses. A watch request streams
+    // events until the client disconnects, while other requests have exactly one
+    // response.
     let resp_stream = read_json
         .map_err(KvsError::from)
-        .and_then(
-            move |req| -> Box<dyn Future<Item = Response, Error = KvsError> + Send> {
-                match req {
-                    Request::Get { key } => Box::new(engine.get(key).map(Response::Get)),
-                    Request::Set { key, value } => {
-                        Box::new(engine.set(key, value).map(|_| Response::Set))
-                    }
-                    Request::Remove { key } => {
-                        Box::new(engine.remove(key).map(|_| Response::Remove))
-                    }
-                }
-            },
-        )
+        .map(move |req| {
+            respond(&engine, &role, &limits, req).unwrap_or_else(|e| Box::new(stream::once(Err(e))))
+        })
+        .flatten()
         .then(|resp| -> Result<Response> {
             match resp {
                 Ok(resp) => Ok(resp),
@@ -62,3 +130,130 @@ fn serve<E: KvsEngine>(engine: E, tcp: TcpStream) -> impl Future<Item = (), Erro
         .send_all(resp_stream)
         .map(|_| ())
 }
+
+type ResponseStream = Box<dyn Stream<Item = Response, Error = KvsError> + Send>;
+
+fn respond<E: KvsEngine>(
+    engine: &E,
+    role: &Role,
+    limits: &Limits,
+    req: Request,
+) -> Result<ResponseStream> {
+    match req {
+        Request::Set { .. }
+        | Request::Remove { .. }
+        | Request::SetMany { .. }
+        | Request::CreateNamespace { .. }
+        | Request::DropNamespace { .. }
+            if role.is_replica() =>
+        {
+            return Err(KvsError::ReadOnlyReplica)
+        }
+        _ => {}
+    }
+    match &req {
+        Request::Get { key, .. } | Request::Remove { key, .. } => limits.check(key, None)?,
+        Request::Set { key, value, .. } => limits.check(key, Some(value))?,
+        Request::SetMany { pairs, .. } => {
+            for (key, value) in pairs {
+                limits.check(key, Some(value))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(match req {
+        Request::Get { namespace, key } => Box::new(
+            in_namespace(engine, namespace)?
+                .get(key)
+                .map(Response::Get)
+                .into_stream(),
+        ),
+        Request::Set {
+            namespace,
+            key,
+            value,
+        } => Box::new(
+            in_namespace(engine, namespace)?
+                .set(key, value)
+                .map(|_| Response::Set)
+                .into_stream(),
+        ),
+        Request::Remove { namespace, key } => Box::new(
+            in_namespace(engine, namespace)?
+                .remove(key)
+                .map(|_| Response::Remove)
+                .into_stream(),
+        ),
+        Request::SetMany { namespace, pairs } => Box::new(
+            in_namespace(engine, namespace)?
+                .set_many(pairs)
+                .map(|_| Response::SetMany)
+                .into_stream(),
+        ),
+        Request::Scan {
+            namespace,
+            after,
+            limit,
+        } => Box::new(
+            in_namespace(engine, namespace)?
+                .scan(after, limit.min(SNAPSHOT_CHUNK))
+                .map(Response::Scan)
+                .into_stream(),
+        ),
+        Request::Watch {
+            namespace,
+            prefix,
+            from,
+        } => Box::new(
+            in_namespace(engine, namespace)?
+                .watch(prefix, from)
+                .map(Response::Event),
+        ),
+        Request::CreateNamespace { name } => Box::new(
+            engine
+                .create_namespace(name)
+                .map(|_| Response::CreateNamespace)
+                .into_stream(),
+        ),
+        Request::DropNamespace { name } => Box::new(
+            engine
+                .drop_namespace(name)
+                .map(|_| Response::DropNamespace)
+                .into_stream(),
+        ),
+        Request::ListNamespaces => Box::new(
+            engine
+                .list_namespaces()
+                .map(Response::ListNamespaces)
+                .into_stream(),
+        ),
+        Request::Replicate { namespace } => Box::new(
+            in_namespace(engine, namespace)?
+                .snapshot()
+                .map(|(snapshot, events)| {
+                    let chunks: Vec<Response> = snapshot
+                        .pairs
+                        .chunks(SNAPSHOT_CHUNK)
+                        .map(|chunk| Response::Snapshot(chunk.to_vec()))
+                        .collect();
+                    stream::iter_ok(chunks)
+                        .chain(stream::once(Ok(Response::SnapshotEnd(snapshot.seq))))
+                        .chain(events.map(Response::Event))
+                })
+                .flatten_stream(),
+        ),
+        Request::Promote => {
+            if role.promote() {
+                info!("Promoted to primary");
+            }
+            Box::new(stream::once(Ok(Response::Promote)))
+        }
+    })
+}
+
+fn in_namespace<E: KvsEngine>(engine: &E, namespace: Option<String>) -> Result<E> {
+    match namespace {
+        Some(name) => engine.namespace(&name),
+        None => Ok(engine.clone()),
+    }
+}
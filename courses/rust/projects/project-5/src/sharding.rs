@@ -0,0 +1,336 @@
+use crate::{KvsClient, KvsError, Result};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use tokio::prelude::*;
+
+// number of points each server has on the hash ring
+const VIRTUAL_NODES: u32 = 128;
+
+/// Key value store client spreading the keys over multiple `KvsServer`s.
+///
+/// Keys are routed with a consistent-hash ring, so adding a server only moves the
+/// keys that the new server takes over. The ring only depends on the set of
+/// addresses, not their order, but all clients must know the same servers: a client
+/// which has not added a server still routes the keys moved to it to their old
+/// servers.
+pub struct ShardedKvsClient {
+    addrs: Vec<SocketAddr>,
+    ring: Ring,
+    // connections in the same order as `addrs`
+    shards: Vec<KvsClient>,
+    namespace: Option<String>,
+}
+
+impl ShardedKvsClient {
+    /// Connect to all servers in `addrs`.
+    pub fn connect(addrs: Vec<SocketAddr>) -> impl Future<Item = Self, Error = KvsError> {
+        future::result(check_addrs(&addrs)).and_then(move |()| {
+            future::join_all(addrs.clone().into_iter().map(connect)).map(move |shards| {
+                ShardedKvsClient {
+                    ring: Ring::new(&addrs),
+                    addrs,
+                    shards,
+                    namespace: None,
+                }
+            })
+        })
+    }
+
+    /// Operate on keys in the namespace `name` in subsequent requests.
+    ///
+    /// `None` switches back to the default namespace. The namespace must exist in
+    /// every server.
+    pub fn with_namespace(mut self, name: Option<String>) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|client| client.with_namespace(name.clone()))
+            .collect();
+        self.namespace = name;
+        self
+    }
+
+    /// Returns the address of the server storing `key`.
+    pub fn server_of(&self, key: &str) -> SocketAddr {
+        self.addrs[self.ring.route(key)]
+    }
+
+    /// Get the value of a given key.
+    pub fn get(self, key: String) -> impl Future<Item = (Option<String>, Self), Error = KvsError> {
+        self.get_many(vec![key])
+            .map(|(mut values, client)| (values.pop().unwrap(), client))
+    }
+
+    /// Set the value of a string key.
+    pub fn set(self, key: String, value: String) -> impl Future<Item = Self, Error = KvsError> {
+        self.set_many(vec![(key, value)])
+    }
+
+    /// Remove a string key.
+    pub fn remove(self, key: String) -> impl Future<Item = Self, Error = KvsError> {
+        self.remove_many(vec![key])
+    }
+
+    /// Get the values of the given keys in the same order.
+    ///
+    /// The servers are queried concurrently.
+    pub fn get_many(
+        self,
+        keys: Vec<String>,
+    ) -> impl Future<Item = (Vec<Option<String>>, Self), Error = KvsError> {
+        self.fan_out(keys, |key| key, |client, key| client.get(key))
+    }
+
+    /// Set the values of the given keys.
+    ///
+    /// Each server receives its pairs in one request, and the servers are written
+    /// concurrently. If it fails, some of the keys may have been set.
+    pub fn set_many(
+        self,
+        pairs: Vec<(String, String)>,
+    ) -> impl Future<Item = Self, Error = KvsError> {
+        let ShardedKvsClient {
+            addrs,
+            ring,
+            shards,
+            namespace,
+        } = self;
+        let mut groups: Vec<Vec<(String, String)>> = shards.iter().map(|_| Vec::new()).collect();
+        for (key, value) in pairs {
+            groups[ring.route(&key)].push((key, value));
+        }
+        let jobs = shards.into_iter().zip(groups).map(|(shard, group)| {
+            if group.is_empty() {
+                future::Either::A(future::ok(shard))
+            } else {
+                future::Either::B(shard.set_many(group))
+            }
+        });
+        future::join_all(jobs).map(move |shards| ShardedKvsClient {
+            addrs,
+            ring,
+            shards,
+            namespace,
+        })
+    }
+
+    /// Remove the given keys.
+    ///
+    /// The servers are written concurrently. If it fails, some of the keys may have
+    /// been removed.
+    pub fn remove_many(self, keys: Vec<String>) -> impl Future<Item = Self, Error = KvsError> {
+        self.fan_out(
+            keys,
+            |key| key,
+            |client, key| client.remove(key).map(|client| ((), client)),
+        )
+        .map(|(_, client)| client)
+    }
+
+    /// Add the server at `addr` and move the keys it takes over to it.
+    ///
+    /// Returns the number of keys moved. Writes made by other clients to the moved
+    /// keys during the migration may be lost.
+    pub fn add_server(self, addr: SocketAddr) -> impl Future<Item = (u64, Self), Error = KvsError> {
+        let ShardedKvsClient {
+            mut addrs,
+            shards,
+            namespace,
+            ..
+        } = self;
+        if addrs.contains(&addr) {
+            let err = KvsError::StringError(format!("{} is already a shard", addr));
+            return future::Either::A(future::err(err));
+        }
+        let snapshots: Vec<_> = addrs
+            .iter()
+            .map(|&shard_addr| {
+                let namespace = namespace.clone();
+                connect(shard_addr)
+                    .and_then(move |client| client.with_namespace(namespace).snapshot())
+            })
+            .collect();
+        let migration = connect(addr).join(future::join_all(snapshots)).and_then(
+            move |(new_shard, snapshots)| {
+                let new_index = addrs.len();
+                addrs.push(addr);
+                let ring = Ring::new(&addrs);
+                let moves: Vec<(usize, String, String)> = snapshots
+                    .into_iter()
+                    .enumerate()
+                    .flat_map(|(index, snapshot)| {
+                        snapshot
+                            .pairs
+                            .into_iter()
+                            .map(move |(key, value)| (index, key, value))
+                    })
+                    .filter(|(_, key, _)| ring.route(key) == new_index)
+                    .collect();
+                let moved = moves.len() as u64;
+
+                let mut shards = shards;
+                shards.push(new_shard.with_namespace(namespace.clone()));
+                let client = ShardedKvsClient {
+                    addrs,
+                    ring,
+                    shards,
+                    namespace,
+                };
+                // Set the key in the new server before removing it from the old one,
+                // so it can always be found in one of them.
+                stream::iter_ok::<_, KvsError>(moves)
+                    .fold(client, move |client, (index, key, value)| {
+                        let old_key = key.clone();
+                        client
+                            .on_shard(new_index, move |shard| {
+                                shard.set(key, value).map(|shard| ((), shard))
+                            })
+                            .and_then(move |(_, client)| {
+                                client.on_shard(index, move |shard| {
+                                    shard.remove(old_key).map(|shard| ((), shard))
+                                })
+                            })
+                            .map(|(_, client)| client)
+                    })
+                    .map(move |client| (moved, client))
+            },
+        );
+        future::Either::B(migration)
+    }
+
+    /// Runs `op` on the items in the servers storing their keys and returns the
+    /// results in the order of the items.
+    ///
+    /// Items of the same server are sent one by one through its connection, while
+    /// different servers are accessed concurrently.
+    fn fan_out<T, R, K, F, Fut>(
+        self,
+        items: Vec<T>,
+        key: K,
+        op: F,
+    ) -> impl Future<Item = (Vec<R>, Self), Error = KvsError>
+    where
+        K: Fn(&T) -> &String,
+        F: Fn(KvsClient, T) -> Fut + Clone,
+        Fut: Future<Item = (R, KvsClient), Error = KvsError>,
+    {
+        let ShardedKvsClient {
+            addrs,
+            ring,
+            shards,
+            namespace,
+        } = self;
+        let total = items.len();
+        let mut groups: Vec<Vec<(usize, T)>> = shards.iter().map(|_| Vec::new()).collect();
+        for (pos, item) in items.into_iter().enumerate() {
+            groups[ring.route(key(&item))].push((pos, item));
+        }
+        let jobs = shards.into_iter().zip(groups).map(move |(shard, group)| {
+            let op = op.clone();
+            stream::iter_ok::<_, KvsError>(group).fold(
+                (shard, Vec::new()),
+                move |(shard, mut results), (pos, item)| {
+                    op(shard, item).map(move |(result, shard)| {
+                        results.push((pos, result));
+                        (shard, results)
+                    })
+                },
+            )
+        });
+        future::join_all(jobs).map(move |parts| {
+            let mut results: Vec<Option<R>> = (0..total).map(|_| None).collect();
+            let mut shards = Vec::with_capacity(parts.len());
+            for (shard, part) in parts {
+                shards.push(shard);
+                for (pos, result) in part {
+                    results[pos] = Some(result);
+                }
+            }
+            let results = results.into_iter().map(Option::unwrap).collect();
+            let client = ShardedKvsClient {
+                addrs,
+                ring,
+                shards,
+                namespace,
+            };
+            (results, client)
+        })
+    }
+
+    // Runs `op` on the connection to the server at `index`.
+    fn on_shard<R, F, Fut>(
+        mut self,
+        index: usize,
+        op: F,
+    ) -> impl Future<Item = (R, Self), Error = KvsError>
+    where
+        F: FnOnce(KvsClient) -> Fut,
+        Fut: Future<Item = (R, KvsClient), Error = KvsError>,
+    {
+        let shard = self.shards.remove(index);
+        op(shard).map(move |(result, shard)| {
+            self.shards.insert(index, shard);
+            (result, self)
+        })
+    }
+}
+
+fn connect(addr: SocketAddr) -> impl Future<Item = KvsClient, Error = KvsError> {
+    KvsClient::connect(addr)
+        .map_err(move |e| KvsError::StringError(format!("Cannot connect to {}: {}", addr, e)))
+}
+
+fn check_addrs(addrs: &[SocketAddr]) -> Result<()> {
+    if addrs.is_empty() {
+        return Err(KvsError::StringError("No server is given".to_owned()));
+    }
+    for (i, addr) in addrs.iter().enumerate() {
+        if addrs[..i].contains(addr) {
+            return Err(KvsError::StringError(format!("{} is given twice", addr)));
+        }
+    }
+    Ok(())
+}
+
+/// A consistent-hash ring mapping keys to the indexes of servers.
+struct Ring {
+    points: BTreeMap<u64, usize>,
+}
+
+impl Ring {
+    fn new(addrs: &[SocketAddr]) -> Ring {
+        let mut points = BTreeMap::new();
+        for (index, addr) in addrs.iter().enumerate() {
+            for vnode in 0..VIRTUAL_NODES {
+                points.insert(hash(format!("{}#{}", addr, vnode).as_bytes()), index);
+            }
+        }
+        Ring { points }
+    }
+
+    /// Returns the index of the first server clockwise from the hash of `key`.
+    fn route(&self, key: &str) -> usize {
+        let hash = hash(key.as_bytes());
+        let (_, &index) = self
+            .points
+            .range(hash..)
+            .next()
+            .or_else(|| self.points.iter().next())
+            .expect("ring is empty");
+        index
+    }
+}
+
+/// 64-bit FNV-1a followed by the finalizer of SplitMix64.
+///
+/// The hash must be the same on every client, so `DefaultHasher` is not used.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        h ^= u64::from(b);
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
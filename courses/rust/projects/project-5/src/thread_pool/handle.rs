@@ -0,0 +1,74 @@
+use std::thread;
+
+use tokio::prelude::*;
+use tokio::sync::oneshot;
+
+use crate::{KvsError, Result};
+
+/// A handle to the result of a job spawned with `ThreadPool::spawn_with_handle`.
+///
+/// The handle is a future resolving to the return value of the job. `join` blocks
+/// the current thread instead.
+///
+/// It fails with `KvsError::JobPanicked` if the job panics, and with
+/// `KvsError::JobCanceled` if the job is dropped without running, e.g. because it is
+/// spawned after the thread pool is shut down.
+pub struct JoinHandle<T> {
+    // `None` is sent if the job panics
+    rx: oneshot::Receiver<Option<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Blocks the current thread until the job finishes and returns its result.
+    pub fn join(self) -> Result<T> {
+        self.wait()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Item = T;
+    type Error = KvsError;
+
+    fn poll(&mut self) -> Poll<T, KvsError> {
+        match self.rx.poll() {
+            Ok(Async::Ready(Some(value))) => Ok(Async::Ready(value)),
+            Ok(Async::Ready(None)) => Err(KvsError::JobPanicked),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(_) => Err(KvsError::JobCanceled),
+        }
+    }
+}
+
+/// Wraps `job` into a job sending its result to the returned handle.
+///
+/// The panic of the job is not caught, so the thread pool still sees it.
+pub(crate) fn with_handle<F, T>(job: F) -> (impl FnOnce() + Send + 'static, JoinHandle<T>)
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    let job = move || {
+        let mut result = ResultSender(Some(tx));
+        let value = job();
+        if let Some(tx) = result.0.take() {
+            if tx.send(Some(value)).is_err() {
+                debug!("Job handle is dropped");
+            }
+        }
+    };
+    (job, JoinHandle { rx })
+}
+
+// Reports the panic of the job to its handle when dropped during unwinding.
+struct ResultSender<T>(Option<oneshot::Sender<Option<T>>>);
+
+impl<T> Drop for ResultSender<T> {
+    fn drop(&mut self) {
+        if let Some(tx) = self.0.take() {
+            if thread::panicking() {
+                let _ = tx.send(None);
+            }
+        }
+    }
+}
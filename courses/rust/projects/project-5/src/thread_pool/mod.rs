@@ -1,15 +1,49 @@
 //! This module provides various thread pools. All thread pools should implement
 //! the `ThreadPool` trait.
 
+use std::time::Duration;
+
 use crate::Result;
 
+mod handle;
+mod monitor;
 mod naive;
 mod rayon;
 mod shared_queue;
+mod work_stealing;
 
+pub(crate) use self::handle::with_handle;
+pub use self::handle::JoinHandle;
 pub use self::naive::NaiveThreadPool;
 pub use self::rayon::RayonThreadPool;
 pub use self::shared_queue::SharedQueueThreadPool;
+pub use self::work_stealing::WorkStealingThreadPool;
+
+/// Priority of a job spawned with `ThreadPool::spawn_with_priority`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Background work that can be delayed, e.g. log compaction
+    Low,
+    /// The priority of jobs spawned with `ThreadPool::spawn`
+    Normal,
+    /// Latency sensitive work, e.g. serving reads
+    High,
+}
+
+/// A snapshot of the state of a thread pool, returned by `ThreadPool::metrics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of running worker threads
+    pub threads: usize,
+    /// Number of spawned jobs waiting for a thread
+    pub queued: usize,
+    /// Number of jobs being executed
+    pub active: usize,
+    /// Number of jobs that have returned
+    pub completed: u64,
+    /// Number of jobs that have panicked
+    pub panicked: u64,
+}
 
 /// The trait that all thread pools should implement.
 pub trait ThreadPool: Clone + Send + 'static {
@@ -30,4 +64,48 @@ pub trait ThreadPool: Clone + Send + 'static {
     fn spawn<F>(&self, job: F)
     where
         F: FnOnce() + Send + 'static;
+
+    /// Spawns a function into the thread pool with the given priority.
+    ///
+    /// Queued jobs with a higher priority are started before those with a lower
+    /// priority. Thread pools without priority support ignore it and behave like
+    /// `spawn`.
+    fn spawn_with_priority<F>(&self, priority: Priority, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let _ = priority;
+        self.spawn(job)
+    }
+
+    /// Spawns a function into the thread pool and returns a handle to its return value.
+    ///
+    /// Like `spawn`, a panicking function does not affect the thread pool. The handle
+    /// fails with `KvsError::JobPanicked` in that case.
+    fn spawn_with_handle<F, T>(&self, job: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (job, handle) = handle::with_handle(job);
+        self.spawn(job);
+        handle
+    }
+
+    /// Stops the thread pool and waits for its threads to exit.
+    ///
+    /// Jobs spawned before the call are still executed, jobs spawned after it are
+    /// discarded. Returns `KvsError::ShutdownTimeout` if the threads have not exited
+    /// within `timeout`; they keep running the remaining jobs in the background.
+    fn shutdown(&self, timeout: Duration) -> Result<()>;
+
+    /// Changes the number of threads in the thread pool.
+    ///
+    /// New threads are spawned immediately. Surplus threads exit without abandoning
+    /// any queued job. Returns an error if the thread pool has been shut down or a thread
+    /// fails to spawn.
+    fn resize(&self, threads: u32) -> Result<()>;
+
+    /// Returns the current thread and job counters of the thread pool.
+    fn metrics(&self) -> Metrics;
 }
@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::Metrics;
+use crate::{KvsError, Result};
+
+/// Job and thread counters shared by a thread pool and its worker threads.
+#[derive(Clone, Default)]
+pub(crate) struct Monitor(Arc<Counters>);
+
+#[derive(Default)]
+struct Counters {
+    queued: AtomicUsize,
+    active: AtomicUsize,
+    completed: AtomicU64,
+    panicked: AtomicU64,
+    threads: Mutex<usize>,
+    thread_exited: Condvar,
+}
+
+impl Monitor {
+    /// Counts the job as queued and wraps it to update the counters when it runs.
+    pub(crate) fn track<F>(&self, job: F) -> impl FnOnce() + Send + 'static
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.0.queued.fetch_add(1, Ordering::SeqCst);
+        let monitor = self.clone();
+        move || {
+            monitor.0.queued.fetch_sub(1, Ordering::SeqCst);
+            monitor.0.active.fetch_add(1, Ordering::SeqCst);
+            let _running = RunningJob(&monitor);
+            job()
+        }
+    }
+
+    /// Uncounts a queued job that is dropped without running.
+    pub(crate) fn job_discarded(&self) {
+        self.0.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn threads_started(&self, n: usize) {
+        *self.0.threads.lock().unwrap() += n;
+    }
+
+    pub(crate) fn thread_exited(&self) {
+        *self.0.threads.lock().unwrap() -= 1;
+        self.0.thread_exited.notify_all();
+    }
+
+    /// Blocks until all worker threads have exited or the timeout elapses.
+    pub(crate) fn wait_threads_exited(&self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut threads = self.0.threads.lock().unwrap();
+        while *threads > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(KvsError::ShutdownTimeout);
+            }
+            threads = self
+                .0
+                .thread_exited
+                .wait_timeout(threads, deadline - now)
+                .unwrap()
+                .0;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn metrics(&self) -> Metrics {
+        Metrics {
+            threads: *self.0.threads.lock().unwrap(),
+            queued: self.0.queued.load(Ordering::SeqCst),
+            active: self.0.active.load(Ordering::SeqCst),
+            completed: self.0.completed.load(Ordering::SeqCst),
+            panicked: self.0.panicked.load(Ordering::SeqCst),
+        }
+    }
+}
+
+// Moves a job from active to completed or panicked when it finishes. The job panicked
+// if the guard is dropped during unwinding.
+struct RunningJob<'a>(&'a Monitor);
+
+impl Drop for RunningJob<'_> {
+    fn drop(&mut self) {
+        let counters = &(self.0).0;
+        counters.active.fetch_sub(1, Ordering::SeqCst);
+        if thread::panicking() {
+            counters.panicked.fetch_add(1, Ordering::SeqCst);
+        } else {
+            counters.completed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
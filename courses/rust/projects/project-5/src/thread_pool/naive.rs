@@ -1,22 +1,68 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
-use super::ThreadPool;
-use crate::Result;
+use super::monitor::Monitor;
+use super::{Metrics, ThreadPool};
+use crate::{KvsError, Result};
 
 /// It is actually not a thread pool. It spawns a new thread every time
 /// the `spawn` method is called.
 #[derive(Clone)]
-pub struct NaiveThreadPool;
+pub struct NaiveThreadPool {
+    monitor: Monitor,
+    shutdown: Arc<AtomicBool>,
+}
 
 impl ThreadPool for NaiveThreadPool {
     fn new(_threads: u32) -> Result<Self> {
-        Ok(NaiveThreadPool)
+        Ok(NaiveThreadPool {
+            monitor: Monitor::default(),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        })
     }
 
     fn spawn<F>(&self, job: F)
     where
         F: FnOnce() + Send + 'static,
     {
-        thread::spawn(job);
+        if self.shutdown.load(Ordering::SeqCst) {
+            warn!("Job discarded because the thread pool is shut down.");
+            return;
+        }
+        let job = self.monitor.track(job);
+        let exit = ThreadExit(self.monitor.clone());
+        self.monitor.threads_started(1);
+        thread::spawn(move || {
+            let _exit = exit;
+            job()
+        });
+    }
+
+    /// Waits for the threads of all spawned jobs to exit.
+    fn shutdown(&self, timeout: Duration) -> Result<()> {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.monitor.wait_threads_exited(timeout)
+    }
+
+    /// Does nothing but checking for shutdown because every job gets its own thread.
+    fn resize(&self, _threads: u32) -> Result<()> {
+        if self.shutdown.load(Ordering::SeqCst) {
+            return Err(KvsError::ThreadPoolShutdown);
+        }
+        Ok(())
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.monitor.metrics()
+    }
+}
+
+struct ThreadExit(Monitor);
+
+impl Drop for ThreadExit {
+    fn drop(&mut self) {
+        self.0.thread_exited();
     }
 }
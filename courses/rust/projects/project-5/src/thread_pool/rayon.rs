@@ -1,24 +1,71 @@
-use super::ThreadPool;
+use super::monitor::Monitor;
+use super::{Metrics, ThreadPool};
 use crate::{KvsError, Result};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 /// Wrapper of rayon::ThreadPool
+///
+/// A rayon thread pool cannot change its size, so resizing replaces it with a new
+/// one. The threads of the old pool exit after finishing the jobs spawned into it.
 #[derive(Clone)]
-pub struct RayonThreadPool(Arc<rayon::ThreadPool>);
+pub struct RayonThreadPool(Arc<Inner>);
+
+struct Inner {
+    // `None` after shutdown
+    pool: RwLock<Option<rayon::ThreadPool>>,
+    monitor: Monitor,
+}
 
 impl ThreadPool for RayonThreadPool {
     fn new(threads: u32) -> Result<Self> {
-        let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(threads as usize)
-            .build()
-            .map_err(|e| KvsError::StringError(format!("{}", e)))?;
-        Ok(RayonThreadPool(Arc::new(pool)))
+        let monitor = Monitor::default();
+        let pool = build_pool(threads, &monitor)?;
+        Ok(RayonThreadPool(Arc::new(Inner {
+            pool: RwLock::new(Some(pool)),
+            monitor,
+        })))
     }
 
     fn spawn<F>(&self, job: F)
     where
         F: FnOnce() + Send + 'static,
     {
-        self.0.spawn(job)
+        match *self.0.pool.read().unwrap() {
+            Some(ref pool) => pool.spawn(self.0.monitor.track(job)),
+            None => warn!("Job discarded because the thread pool is shut down."),
+        }
+    }
+
+    fn shutdown(&self, timeout: Duration) -> Result<()> {
+        // Dropping a rayon thread pool lets its threads exit once all the jobs are done.
+        self.0.pool.write().unwrap().take();
+        self.0.monitor.wait_threads_exited(timeout)
+    }
+
+    fn resize(&self, threads: u32) -> Result<()> {
+        let mut pool = self.0.pool.write().unwrap();
+        if pool.is_none() {
+            return Err(KvsError::ThreadPoolShutdown);
+        }
+        *pool = Some(build_pool(threads, &self.0.monitor)?);
+        Ok(())
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.0.monitor.metrics()
     }
 }
+
+fn build_pool(threads: u32, monitor: &Monitor) -> Result<rayon::ThreadPool> {
+    let exit_monitor = monitor.clone();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads as usize)
+        .exit_handler(move |_| exit_monitor.thread_exited())
+        // Without a panic handler, rayon aborts the process when a job panics.
+        .panic_handler(|_| error!("A job panicked in the thread pool."))
+        .build()
+        .map_err(|e| KvsError::StringError(format!("{}", e)))?;
+    monitor.threads_started(pool.current_num_threads());
+    Ok(pool)
+}
@@ -1,7 +1,11 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 
-use super::ThreadPool;
-use crate::Result;
+use super::monitor::Monitor;
+use super::{Metrics, ThreadPool};
+use crate::{KvsError, Result};
 
 use crossbeam::channel::{self, Receiver, Sender};
 
@@ -13,39 +17,137 @@ use crossbeam::channel::{self, Receiver, Sender};
 /// If a spawned task panics, the old thread will be destroyed and a new one will be
 /// created. It fails silently when any failure to create the thread at the OS level
 /// is captured after the thread pool is created. So, the thread number in the pool
-/// can decrease to zero, then spawning a task to the thread pool will panic.
+/// can decrease to zero, then spawned tasks stay in the queue until the thread pool
+/// is resized.
+///
+/// Threads are stopped by `Message::Exit` messages sent through the same queue, so
+/// they always finish the jobs spawned before them. Jobs spawned while the pool is
+/// shutting down are discarded once the threads have exited.
 #[derive(Clone)]
 pub struct SharedQueueThreadPool {
-    tx: Sender<Box<dyn FnOnce() + Send + 'static>>,
+    tx: Sender<Message>,
+    rx: Receiver<Message>,
+    shared: Arc<Shared>,
+}
+
+enum Message {
+    Run(Box<dyn FnOnce() + Send + 'static>),
+    Exit,
+}
+
+struct Shared {
+    // the number of threads the pool is resized to
+    size: Mutex<u32>,
+    shutdown: AtomicBool,
+    // the number of `spawn` calls between checking `shutdown` and queueing the job
+    spawning: AtomicUsize,
+    monitor: Monitor,
 }
 
 impl ThreadPool for SharedQueueThreadPool {
     fn new(threads: u32) -> Result<Self> {
-        let (tx, rx) = channel::unbounded::<Box<dyn FnOnce() + Send + 'static>>();
-        for _ in 0..threads {
-            let rx = TaskReceiver(rx.clone());
-            thread::Builder::new().spawn(move || run_tasks(rx))?;
-        }
-        Ok(SharedQueueThreadPool { tx })
+        let (tx, rx) = channel::unbounded::<Message>();
+        let pool = SharedQueueThreadPool {
+            tx,
+            rx,
+            shared: Arc::new(Shared {
+                size: Mutex::new(0),
+                shutdown: AtomicBool::new(false),
+                spawning: AtomicUsize::new(0),
+                monitor: Monitor::default(),
+            }),
+        };
+        pool.resize(threads)?;
+        Ok(pool)
     }
 
-    /// Spawns a function into the thread pool.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the thread pool has no thread.
     fn spawn<F>(&self, job: F)
     where
         F: FnOnce() + Send + 'static,
     {
+        // A job spawned while the pool is shutting down may be queued after the
+        // `Exit`s, where `shutdown` discards it.
+        self.shared.spawning.fetch_add(1, Ordering::SeqCst);
+        if self.shared.shutdown.load(Ordering::SeqCst) {
+            self.shared.spawning.fetch_sub(1, Ordering::SeqCst);
+            warn!("Job discarded because the thread pool is shut down.");
+            return;
+        }
+        let job = self.shared.monitor.track(job);
         self.tx
-            .send(Box::new(job))
-            .expect("The thread pool has no thread.");
+            .send(Message::Run(Box::new(job)))
+            .expect("The thread pool is destroyed.");
+        self.shared.spawning.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn shutdown(&self, timeout: Duration) -> Result<()> {
+        {
+            let mut size = self.shared.size.lock().unwrap();
+            self.shared.shutdown.store(true, Ordering::SeqCst);
+            self.stop_threads(*size);
+            *size = 0;
+        }
+        self.shared.monitor.wait_threads_exited(timeout)?;
+        self.discard_late_jobs();
+        Ok(())
+    }
+
+    fn resize(&self, threads: u32) -> Result<()> {
+        let mut size = self.shared.size.lock().unwrap();
+        if self.shared.shutdown.load(Ordering::SeqCst) {
+            return Err(KvsError::ThreadPoolShutdown);
+        }
+        if threads < *size {
+            self.stop_threads(*size - threads);
+            *size = threads;
+        }
+        while *size < threads {
+            let rx = TaskReceiver {
+                rx: self.rx.clone(),
+                monitor: self.shared.monitor.clone(),
+            };
+            self.shared.monitor.threads_started(1);
+            thread::Builder::new().spawn(move || run_tasks(rx))?;
+            *size += 1;
+        }
+        Ok(())
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.shared.monitor.metrics()
+    }
+}
+
+impl SharedQueueThreadPool {
+    /// Drops the jobs queued after the `Exit`s of `shutdown`, once all threads have
+    /// exited.
+    fn discard_late_jobs(&self) {
+        // The spawns that saw the pool running finish queueing their jobs first.
+        while self.shared.spawning.load(Ordering::SeqCst) > 0 {
+            thread::yield_now();
+        }
+        for message in self.rx.try_iter() {
+            if let Message::Run(_) = message {
+                warn!("Job discarded because the thread pool is shut down.");
+                self.shared.monitor.job_discarded();
+            }
+        }
+    }
+
+    fn stop_threads(&self, n: u32) {
+        for _ in 0..n {
+            self.tx
+                .send(Message::Exit)
+                .expect("The thread pool is destroyed.");
+        }
     }
 }
 
 #[derive(Clone)]
-struct TaskReceiver(Receiver<Box<dyn FnOnce() + Send + 'static>>);
+struct TaskReceiver {
+    rx: Receiver<Message>,
+    monitor: Monitor,
+}
 
 impl Drop for TaskReceiver {
     fn drop(&mut self) {
@@ -53,18 +155,28 @@ impl Drop for TaskReceiver {
             let rx = self.clone();
             if let Err(e) = thread::Builder::new().spawn(move || run_tasks(rx)) {
                 error!("Failed to spawn a thread: {}", e);
+                self.monitor.thread_exited();
             }
+        } else {
+            self.monitor.thread_exited();
         }
     }
 }
 
 fn run_tasks(rx: TaskReceiver) {
     loop {
-        match rx.0.recv() {
-            Ok(task) => {
+        match rx.rx.recv() {
+            Ok(Message::Run(task)) => {
                 task();
             }
-            Err(_) => debug!("Thread exits because the thread pool is destroyed."),
+            Ok(Message::Exit) => {
+                debug!("Thread exits because the thread pool is resized or shut down.");
+                return;
+            }
+            Err(_) => {
+                debug!("Thread exits because the thread pool is destroyed.");
+                return;
+            }
         }
     }
 }
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::iter;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use super::monitor::Monitor;
+use super::{Metrics, Priority, ThreadPool};
+use crate::{KvsError, Result};
+
+use crossbeam::deque::{Injector, Steal, Stealer, Worker};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// An idle worker wakes up at least this often to look for work that is only
+// reachable by stealing.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// A thread pool with a deque per worker thread.
+///
+/// Spawned jobs are pushed into a global queue of their priority. An idle worker
+/// moves a batch of normal jobs from the global queue into its own deque, and steals
+/// from the deques of other workers when both are empty. High priority jobs are
+/// always taken before any other job, and low priority jobs only when there is no
+/// other job in the pool.
+///
+/// Like `SharedQueueThreadPool`, a worker thread destroyed by a panicking job is
+/// replaced by a new one which takes over its deque. A worker thread leaving the
+/// pool because of `resize` pushes the jobs left in its deque back to the global
+/// queue.
+#[derive(Clone)]
+pub struct WorkStealingThreadPool {
+    shared: Arc<Shared>,
+    // shuts down the worker threads when the last clone of the pool is dropped
+    _handle: Arc<ShutdownHandle>,
+}
+
+struct Shared {
+    high: Injector<Job>,
+    normal: Injector<Job>,
+    low: Injector<Job>,
+    // stealers of the deques of running workers, keyed by worker id
+    stealers: RwLock<HashMap<usize, Stealer<Job>>>,
+    next_id: AtomicUsize,
+    // the number of threads the pool is resized to
+    size: Mutex<u32>,
+    // the number of workers which should exit to reach `size`
+    retiring: AtomicUsize,
+    idle: Mutex<()>,
+    wakeup: Condvar,
+    shutdown: AtomicBool,
+    monitor: Monitor,
+}
+
+struct ShutdownHandle(Arc<Shared>);
+
+impl Drop for ShutdownHandle {
+    fn drop(&mut self) {
+        self.0.stop();
+    }
+}
+
+impl ThreadPool for WorkStealingThreadPool {
+    fn new(threads: u32) -> Result<Self> {
+        let shared = Arc::new(Shared {
+            high: Injector::new(),
+            normal: Injector::new(),
+            low: Injector::new(),
+            stealers: RwLock::new(HashMap::new()),
+            next_id: AtomicUsize::new(0),
+            size: Mutex::new(0),
+            retiring: AtomicUsize::new(0),
+            idle: Mutex::new(()),
+            wakeup: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+            monitor: Monitor::default(),
+        });
+        let pool = WorkStealingThreadPool {
+            _handle: Arc::new(ShutdownHandle(Arc::clone(&shared))),
+            shared,
+        };
+        pool.resize(threads)?;
+        Ok(pool)
+    }
+
+    fn spawn<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.spawn_with_priority(Priority::Normal, job)
+    }
+
+    fn spawn_with_priority<F>(&self, priority: Priority, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if self.shared.shutdown.load(Ordering::SeqCst) {
+            warn!("Job discarded because the thread pool is shut down.");
+            return;
+        }
+        let queue = match priority {
+            Priority::High => &self.shared.high,
+            Priority::Normal => &self.shared.normal,
+            Priority::Low => &self.shared.low,
+        };
+        queue.push(Box::new(self.shared.monitor.track(job)));
+        let _idle = self.shared.idle.lock().unwrap();
+        self.shared.wakeup.notify_one();
+    }
+
+    fn shutdown(&self, timeout: Duration) -> Result<()> {
+        {
+            let mut size = self.shared.size.lock().unwrap();
+            self.shared.stop();
+            *size = 0;
+        }
+        self.shared.monitor.wait_threads_exited(timeout)
+    }
+
+    fn resize(&self, threads: u32) -> Result<()> {
+        let mut size = self.shared.size.lock().unwrap();
+        if self.shared.shutdown.load(Ordering::SeqCst) {
+            return Err(KvsError::ThreadPoolShutdown);
+        }
+        if threads < *size {
+            let surplus = (*size - threads) as usize;
+            self.shared.retiring.fetch_add(surplus, Ordering::SeqCst);
+            *size = threads;
+            let _idle = self.shared.idle.lock().unwrap();
+            self.shared.wakeup.notify_all();
+        }
+        while *size < threads {
+            spawn_worker(&self.shared)?;
+            *size += 1;
+        }
+        Ok(())
+    }
+
+    fn metrics(&self) -> Metrics {
+        self.shared.monitor.metrics()
+    }
+}
+
+impl Shared {
+    fn has_queued(&self) -> bool {
+        !self.high.is_empty() || !self.normal.is_empty() || !self.low.is_empty()
+    }
+
+    fn stop(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        let _idle = self.idle.lock().unwrap();
+        self.wakeup.notify_all();
+    }
+
+    // Returns true if the calling worker should exit to shrink the pool.
+    fn try_retire(&self) -> bool {
+        let mut retiring = self.retiring.load(Ordering::SeqCst);
+        while retiring > 0 {
+            match self.retiring.compare_exchange(
+                retiring,
+                retiring - 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return true,
+                Err(current) => retiring = current,
+            }
+        }
+        false
+    }
+}
+
+fn spawn_worker(shared: &Arc<Shared>) -> Result<()> {
+    let local = Worker::new_fifo();
+    let id = shared.next_id.fetch_add(1, Ordering::SeqCst);
+    shared.stealers.write().unwrap().insert(id, local.stealer());
+    shared.monitor.threads_started(1);
+    let worker = WorkerThread {
+        id,
+        local: Some(local),
+        shared: Arc::clone(shared),
+    };
+    thread::Builder::new().spawn(move || run_tasks(worker))?;
+    Ok(())
+}
+
+struct WorkerThread {
+    id: usize,
+    // `None` only after the deque is handed over to a replacing thread
+    local: Option<Worker<Job>>,
+    shared: Arc<Shared>,
+}
+
+impl WorkerThread {
+    fn find_task(&self) -> Option<Job> {
+        let local = self.local.as_ref().unwrap();
+        let shared = &*self.shared;
+        steal_from(&shared.high)
+            .or_else(|| local.pop())
+            .or_else(|| {
+                // Retry until the global queue and the deques of other workers are
+                // observed without contention.
+                iter::repeat_with(|| {
+                    shared.normal.steal_batch_and_pop(local).or_else(|| {
+                        let stealers = shared.stealers.read().unwrap();
+                        stealers.values().map(Stealer::steal).collect()
+                    })
+                })
+                .find(|s| !s.is_retry())
+                .and_then(Steal::success)
+            })
+            .or_else(|| steal_from(&shared.low))
+    }
+}
+
+fn steal_from(queue: &Injector<Job>) -> Option<Job> {
+    iter::repeat_with(|| queue.steal())
+        .find(|s| !s.is_retry())
+        .and_then(Steal::success)
+}
+
+impl Drop for WorkerThread {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let worker = WorkerThread {
+                id: self.id,
+                local: self.local.take(),
+                shared: Arc::clone(&self.shared),
+            };
+            if let Err(e) = thread::Builder::new().spawn(move || run_tasks(worker)) {
+                error!("Failed to spawn a thread: {}", e);
+            }
+        } else if let Some(local) = self.local.take() {
+            // The worker leaves the pool. Give its jobs to the remaining workers.
+            self.shared.stealers.write().unwrap().remove(&self.id);
+            while let Some(job) = local.pop() {
+                self.shared.normal.push(job);
+            }
+            self.shared.monitor.thread_exited();
+            let _idle = self.shared.idle.lock().unwrap();
+            self.shared.wakeup.notify_one();
+        }
+    }
+}
+
+fn run_tasks(worker: WorkerThread) {
+    loop {
+        if worker.shared.try_retire() {
+            debug!("Thread exits because the thread pool is resized.");
+            return;
+        }
+        if let Some(task) = worker.find_task() {
+            task();
+            continue;
+        }
+        if worker.shared.shutdown.load(Ordering::SeqCst) {
+            debug!("Thread exits because the thread pool is shut down.");
+            return;
+        }
+        let idle = worker.shared.idle.lock().unwrap();
+        if !worker.shared.has_queued()
+            && !worker.shared.shutdown.load(Ordering::SeqCst)
+            && worker.shared.retiring.load(Ordering::SeqCst) == 0
+        {
+            let _ = worker
+                .shared
+                .wakeup
+                .wait_timeout(idle, IDLE_TIMEOUT)
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Reading and writing key/value pairs in files for importing and exporting.
+
+use crate::{KvsError, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Deserializer;
+use std::io::{Read, Write};
+
+/// File format of key/value pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    /// One JSON object with `key` and `value` fields per line
+    JsonLines,
+    /// CSV with a `key,value` header
+    Csv,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    key: String,
+    value: String,
+}
+
+/// Writes the pairs to `writer` in `format`.
+///
+/// Stops at the first error of `pairs`. Returns the number of pairs written.
+pub fn write_pairs<W, I>(writer: W, format: DataFormat, pairs: I) -> Result<u64>
+where
+    W: Write,
+    I: IntoIterator<Item = Result<(String, String)>>,
+{
+    let mut count = 0;
+    match format {
+        DataFormat::JsonLines => {
+            let mut writer = writer;
+            for pair in pairs {
+                let (key, value) = pair?;
+                serde_json::to_writer(&mut writer, &Record { key, value })?;
+                writer.write_all(b"\n")?;
+                count += 1;
+            }
+            writer.flush()?;
+        }
+        DataFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for pair in pairs {
+                let (key, value) = pair?;
+                writer.serialize(Record { key, value })?;
+                count += 1;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(count)
+}
+
+/// Returns an iterator over the pairs read from `reader` in `format`.
+pub fn read_pairs<'a, R: Read + 'a>(
+    reader: R,
+    format: DataFormat,
+) -> Box<dyn Iterator<Item = Result<(String, String)>> + 'a> {
+    match format {
+        DataFormat::JsonLines => Box::new(
+            Deserializer::from_reader(reader)
+                .into_iter::<Record>()
+                .map(into_pair),
+        ),
+        DataFormat::Csv => Box::new(
+            csv::Reader::from_reader(reader)
+                .into_deserialize::<Record>()
+                .map(into_pair),
+        ),
+    }
+}
+
+/// Returns an iterator over the pairs fetched page by page with `scan`.
+///
+/// `scan` is called with the last key of the previous page, or `None` for the
+/// first page. An empty page ends the iteration.
+pub fn scan_pages<F>(scan: F) -> impl Iterator<Item = Result<(String, String)>>
+where
+    F: FnMut(Option<String>) -> Result<Vec<(String, String)>>,
+{
+    Pages {
+        scan,
+        after: None,
+        page: Vec::new().into_iter(),
+        done: false,
+    }
+}
+
+struct Pages<F> {
+    scan: F,
+    // the last key of the current page
+    after: Option<String>,
+    page: std::vec::IntoIter<(String, String)>,
+    done: bool,
+}
+
+impl<F> Iterator for Pages<F>
+where
+    F: FnMut(Option<String>) -> Result<Vec<(String, String)>>,
+{
+    type Item = Result<(String, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pair) = self.page.next() {
+                return Some(Ok(pair));
+            }
+            if self.done {
+                return None;
+            }
+            match (self.scan)(self.after.take()) {
+                Ok(page) => {
+                    self.after = page.last().map(|(key, _)| key.clone());
+                    self.done = page.is_empty();
+                    self.page = page.into_iter();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+fn into_pair<E>(record: std::result::Result<Record, E>) -> Result<(String, String)>
+where
+    KvsError: From<E>,
+{
+    let record = record?;
+    Ok((record.key, record.value))
+}
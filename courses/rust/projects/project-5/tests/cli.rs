@@ -1,7 +1,8 @@
 use assert_cmd::prelude::*;
 use predicates::str::{contains, is_empty};
 use std::fs::{self, File};
-use std::process::Command;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
@@ -335,3 +336,456 @@ fn cli_access_server_kvs_engine() {
 fn cli_access_server_sled_engine() {
     cli_access_server("sled", "127.0.0.1:4005");
 }
+
+#[test]
+fn cli_watch() {
+    let addr = "127.0.0.1:4006";
+    let temp_dir = TempDir::new().unwrap();
+    let mut server = Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--addr", addr])
+        .current_dir(&temp_dir)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+
+    let set = |key: &str, value: &str| {
+        Command::cargo_bin("kvs-client")
+            .unwrap()
+            .args(&["set", key, value, "--addr", addr])
+            .current_dir(&temp_dir)
+            .assert()
+            .success();
+    };
+    set("key1", "value1");
+
+    let mut watcher = Command::cargo_bin("kvs-client")
+        .unwrap()
+        .args(&["watch", "key", "--from", "1", "--addr", addr])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    set("other", "value2");
+    set("key2", "value3");
+    Command::cargo_bin("kvs-client")
+        .unwrap()
+        .args(&["rm", "key1", "--addr", addr])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    let lines: Vec<String> = BufReader::new(watcher.stdout.take().unwrap())
+        .lines()
+        .take(3)
+        .map(|line| line.unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        vec!["1 set key1 value1", "3 set key2 value3", "4 rm key1"]
+    );
+
+    watcher.kill().expect("watcher exited before killed");
+    server.kill().expect("server exited before killed");
+}
+
+#[test]
+fn cli_namespaces() {
+    let addr = "127.0.0.1:4007";
+    let temp_dir = TempDir::new().unwrap();
+    let mut server = Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--engine", "sled", "--addr", addr])
+        .current_dir(&temp_dir)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+
+    let client = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("kvs-client").unwrap();
+        cmd.args(args)
+            .args(&["--addr", addr])
+            .current_dir(&temp_dir);
+        cmd
+    };
+
+    client(&["create-namespace", "ns1"]).assert().success();
+    client(&["create-namespace", "ns1"])
+        .assert()
+        .failure()
+        .stderr(contains("Namespace already exists"));
+    client(&["set", "key1", "value1", "--namespace", "ns1"])
+        .assert()
+        .success();
+    client(&["get", "key1", "--namespace", "ns1"])
+        .assert()
+        .success()
+        .stdout("value1\n");
+    client(&["get", "key1"])
+        .assert()
+        .success()
+        .stdout(contains("Key not found"));
+    client(&["list-namespaces"])
+        .assert()
+        .success()
+        .stdout("ns1\n");
+    client(&["drop-namespace", "ns1"]).assert().success();
+    client(&["get", "key1", "--namespace", "ns1"])
+        .assert()
+        .failure()
+        .stderr(contains("Namespace not found"));
+
+    server.kill().expect("server exited before killed");
+}
+
+#[test]
+fn cli_bench() {
+    let addr = "127.0.0.1:4008";
+    let temp_dir = TempDir::new().unwrap();
+    let mut server = Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--engine", "kvs", "--addr", addr])
+        .current_dir(&temp_dir)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+
+    Command::cargo_bin("kvs-bench")
+        .unwrap()
+        .args(&[
+            "--addr",
+            addr,
+            "--concurrency",
+            "4",
+            "--requests",
+            "400",
+            "--keys",
+            "50",
+            "--distribution",
+            "zipfian",
+            "--value-size",
+            "10",
+        ])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(contains("Requests: 400"))
+        .stdout(contains("errors: 0"))
+        .stdout(contains("Throughput:"))
+        .stdout(contains("p99"));
+
+    Command::cargo_bin("kvs-bench")
+        .unwrap()
+        .args(&["--addr", addr, "--read-ratio", "2"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure();
+
+    server.kill().expect("server exited before killed");
+}
+
+#[test]
+fn cli_replication() {
+    let primary_addr = "127.0.0.1:4009";
+    let replica_addr = "127.0.0.1:4010";
+    let primary_dir = TempDir::new().unwrap();
+    let replica_dir = TempDir::new().unwrap();
+    let mut primary = Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--engine", "kvs", "--addr", primary_addr])
+        .current_dir(&primary_dir)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+
+    let client = |addr: &str, args: &[&str]| {
+        let mut cmd = Command::cargo_bin("kvs-client").unwrap();
+        cmd.args(args)
+            .args(&["--addr", addr])
+            .current_dir(&primary_dir);
+        cmd
+    };
+
+    client(primary_addr, &["set", "key1", "value1"])
+        .assert()
+        .success();
+    client(primary_addr, &["set", "key2", "value2"])
+        .assert()
+        .success();
+    client(primary_addr, &["create-namespace", "ns1"])
+        .assert()
+        .success();
+    client(
+        primary_addr,
+        &["set", "key1", "value1", "--namespace", "ns1"],
+    )
+    .assert()
+    .success();
+
+    let mut replica = Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&[
+            "--engine",
+            "kvs",
+            "--addr",
+            replica_addr,
+            "--replica-of",
+            primary_addr,
+        ])
+        .current_dir(&replica_dir)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+
+    // The replica starts from a snapshot of the primary
+    client(replica_addr, &["get", "key1"])
+        .assert()
+        .success()
+        .stdout("value1\n");
+    client(replica_addr, &["set", "key3", "value3"])
+        .assert()
+        .failure()
+        .stderr(contains("read-only replica"));
+    client(replica_addr, &["get", "key1", "--namespace", "ns1"])
+        .assert()
+        .success()
+        .stdout("value1\n");
+
+    // and follows the changes after it
+    client(primary_addr, &["rm", "key1"]).assert().success();
+    client(primary_addr, &["set", "key2", "value4"])
+        .assert()
+        .success();
+    thread::sleep(Duration::from_millis(500));
+    client(replica_addr, &["get", "key1"])
+        .assert()
+        .success()
+        .stdout(contains("Key not found"));
+    client(replica_addr, &["get", "key2"])
+        .assert()
+        .success()
+        .stdout("value4\n");
+
+    // including namespaces created on the primary
+    client(primary_addr, &["create-namespace", "ns2"])
+        .assert()
+        .success();
+    client(
+        primary_addr,
+        &["set", "key2", "value2", "--namespace", "ns2"],
+    )
+    .assert()
+    .success();
+    thread::sleep(Duration::from_secs(3));
+    client(replica_addr, &["get", "key2", "--namespace", "ns2"])
+        .assert()
+        .success()
+        .stdout("value2\n");
+
+    // The promoted replica accepts writes and stops following
+    client(replica_addr, &["promote"]).assert().success();
+    client(replica_addr, &["set", "key3", "value3"])
+        .assert()
+        .success();
+    client(primary_addr, &["set", "key2", "value5"])
+        .assert()
+        .success();
+    thread::sleep(Duration::from_millis(500));
+    client(replica_addr, &["get", "key2"])
+        .assert()
+        .success()
+        .stdout("value4\n");
+
+    replica.kill().expect("replica exited before killed");
+    primary.kill().expect("primary exited before killed");
+}
+
+#[test]
+fn cli_batch() {
+    let addr = "127.0.0.1:4011";
+    let temp_dir = TempDir::new().unwrap();
+    let mut server = Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--engine", "kvs", "--addr", addr])
+        .current_dir(&temp_dir)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+
+    let script = temp_dir.path().join("script");
+    fs::write(
+        &script,
+        "# comment\n\
+         set key1 \"value 1\"\n\
+         get key1\n\
+         rm key2\n\
+         \n\
+         create-namespace ns\n\
+         use ns\n\
+         get key1\n\
+         bogus\n",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("kvs-client")
+        .unwrap()
+        .args(&["batch", script.to_str().unwrap(), "--addr", addr])
+        .current_dir(&temp_dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert!(lines[0].starts_with("OK ("));
+    assert!(lines[1].starts_with("value 1 ("));
+    assert!(lines[2].starts_with("Error: Key not found"));
+    assert!(lines[5].starts_with("Key not found ("));
+    assert!(lines[6].starts_with("Error: Unknown command"));
+
+    let script = temp_dir.path().join("script.json");
+    fs::write(&script, "get key1\nlist-namespaces\n").unwrap();
+    let output = Command::cargo_bin("kvs-client")
+        .unwrap()
+        .args(&["batch", script.to_str().unwrap(), "--format", "json"])
+        .args(&["--addr", addr])
+        .current_dir(&temp_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines[0]["command"], "get key1");
+    assert_eq!(lines[0]["value"], "value 1");
+    assert!(lines[0]["elapsed_us"].is_u64());
+    assert_eq!(lines[1]["namespaces"], serde_json::json!(["ns"]));
+
+    server.kill().expect("server exited before killed");
+}
+
+#[test]
+fn server_cli_config() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("kvs-server.toml"),
+        "addr = \"127.0.0.1:4012\"\n\
+         engine = \"sled\"\n\
+         \n\
+         [thread_pool]\n\
+         kind = \"work_stealing\"\n\
+         threads = 3\n\
+         \n\
+         [limits]\n\
+         max_key_size = 8\n",
+    )
+    .unwrap();
+
+    // The environment overrides the file and the command line overrides both
+    Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--print-config", "--threads", "5"])
+        .env("KVS_THREADS", "4")
+        .env("KVS_DURABILITY", "sync")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(contains("addr = \"127.0.0.1:4012\""))
+        .stdout(contains("engine = \"sled\""))
+        .stdout(contains("kind = \"work_stealing\""))
+        .stdout(contains("threads = 5"))
+        .stdout(contains("durability = \"sync\""))
+        .stdout(contains("max_key_size = 8"));
+
+    fs::write(temp_dir.path().join("bad.toml"), "unknown = 1\n").unwrap();
+    Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--print-config", "--config", "bad.toml"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(contains("Invalid config file"));
+
+    // The limits of the file are applied to the server
+    let data_dir = temp_dir.path().join("data");
+    let mut server = Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--data-dir", data_dir.to_str().unwrap()])
+        .current_dir(&temp_dir)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+    Command::cargo_bin("kvs-client")
+        .unwrap()
+        .args(&["set", "key1", "value1", "--addr", "127.0.0.1:4012"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    Command::cargo_bin("kvs-client")
+        .unwrap()
+        .args(&["set", "too_long_key", "value1", "--addr", "127.0.0.1:4012"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(contains("Key is too large"));
+    server.kill().expect("server exited before killed");
+    assert_eq!(fs::read_to_string(data_dir.join("engine")).unwrap(), "sled");
+}
+
+#[test]
+fn cli_import_export() {
+    let addr = "127.0.0.1:4013";
+    let temp_dir = TempDir::new().unwrap();
+    let mut server = Command::cargo_bin("kvs-server")
+        .unwrap()
+        .args(&["--engine", "kvs", "--addr", addr])
+        .current_dir(&temp_dir)
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_secs(1));
+
+    let client = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("kvs-client").unwrap();
+        cmd.args(args)
+            .args(&["--addr", addr])
+            .current_dir(&temp_dir);
+        cmd
+    };
+
+    fs::write(
+        temp_dir.path().join("data.csv"),
+        "key,value\nkey1,value1\nkey2,\"value, 2\"\nkey3,value3\n",
+    )
+    .unwrap();
+    // Pretend that the first pair was imported by an interrupted import
+    fs::write(temp_dir.path().join("data.csv.progress"), "1").unwrap();
+    client(&["import", "data.csv", "--format", "csv", "--resume"])
+        .arg("--batch-size")
+        .arg("1")
+        .assert()
+        .success()
+        .stderr(contains("Resuming after 1 pairs"))
+        .stderr(contains("Imported 3 pairs"));
+    assert!(!temp_dir.path().join("data.csv.progress").exists());
+    client(&["get", "key1"])
+        .assert()
+        .success()
+        .stdout(contains("Key not found"));
+    client(&["get", "key2"])
+        .assert()
+        .success()
+        .stdout("value, 2\n");
+
+    client(&["export", "data.jsonl"])
+        .assert()
+        .success()
+        .stderr(contains("Exported 2 pairs"));
+    let exported = fs::read_to_string(temp_dir.path().join("data.jsonl")).unwrap();
+    assert_eq!(
+        exported,
+        "{\"key\":\"key2\",\"value\":\"value, 2\"}\n{\"key\":\"key3\",\"value\":\"value3\"}\n"
+    );
+
+    server.kill().expect("server exited before killed");
+}
@@ -1,5 +1,6 @@
-use kvs::thread_pool::RayonThreadPool;
-use kvs::{KvStore, KvsEngine, KvsError, Result};
+use kvs::thread_pool::{NaiveThreadPool, RayonThreadPool};
+use kvs::transfer::DataFormat;
+use kvs::{Event, KvStore, KvStoreOptions, KvsEngine, KvsError, Result, SledKvsEngine, Snapshot};
 use tempfile::TempDir;
 use tokio::prelude::*;
 use tokio::runtime::Runtime;
@@ -237,3 +238,461 @@ fn concurrent_get() -> Result<()> {
 
     Ok(())
 }
+
+// Should read with more jobs at once than the store has readers
+#[test]
+fn concurrent_get_beyond_concurrency() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    // `NaiveThreadPool` runs every job on its own thread.
+    let store = KvStore::<NaiveThreadPool>::open(temp_dir.path(), 1)?;
+    for i in 0..10 {
+        store
+            .set(format!("key{}", i), format!("value{}", i))
+            .wait()?;
+    }
+
+    let gets = (0..100).map(|i| store.get(format!("key{}", i % 10)));
+    let values = future::join_all(gets).wait()?;
+    for (i, value) in values.into_iter().enumerate() {
+        assert_eq!(value, Some(format!("value{}", i % 10)));
+    }
+    let pairs = future::join_all((0..20).map(|_| store.scan(None, 10))).wait()?;
+    assert!(pairs.iter().all(|pairs| pairs.len() == 10));
+
+    Ok(())
+}
+
+// Should replay and deliver changes of watched keys in sequence order
+#[test]
+fn watch_changes() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 2)?;
+    store.set("key1".to_owned(), "value1".to_owned()).wait()?;
+    store.set("other".to_owned(), "value2".to_owned()).wait()?;
+    store.set("key2".to_owned(), "value3".to_owned()).wait()?;
+    store.remove("key1".to_owned()).wait()?;
+
+    let events = store
+        .watch("key".to_owned(), Some(0))
+        .take(3)
+        .collect()
+        .wait()?;
+    assert_eq!(
+        events,
+        vec![
+            Event::Set {
+                seq: 1,
+                key: "key1".to_owned(),
+                value: "value1".to_owned()
+            },
+            Event::Set {
+                seq: 3,
+                key: "key2".to_owned(),
+                value: "value3".to_owned()
+            },
+            Event::Remove {
+                seq: 4,
+                key: "key1".to_owned()
+            },
+        ]
+    );
+
+    // Resume from the next sequence number and receive a live change
+    let events = store.watch("key".to_owned(), Some(5));
+    store.set("key3".to_owned(), "value4".to_owned()).wait()?;
+    let events = events.take(1).collect().wait()?;
+    assert_eq!(
+        events,
+        vec![Event::Set {
+            seq: 5,
+            key: "key3".to_owned(),
+            value: "value4".to_owned()
+        }]
+    );
+
+    // Changes before a future sequence number are skipped
+    let events = store.watch("key".to_owned(), Some(7));
+    store.set("key3".to_owned(), "value5".to_owned()).wait()?;
+    store.set("key3".to_owned(), "value6".to_owned()).wait()?;
+    let events = events.take(1).collect().wait()?;
+    assert_eq!(
+        events,
+        vec![Event::Set {
+            seq: 7,
+            key: "key3".to_owned(),
+            value: "value6".to_owned()
+        }]
+    );
+
+    // Sequence numbers continue after reopening
+    drop(store);
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 2)?;
+    store.remove("key3".to_owned()).wait()?;
+    let events = store
+        .watch("key3".to_owned(), Some(0))
+        .take(4)
+        .collect()
+        .wait()?;
+    assert_eq!(
+        events[3],
+        Event::Remove {
+            seq: 8,
+            key: "key3".to_owned()
+        }
+    );
+
+    Ok(())
+}
+
+// Should refuse to resume a watch from compacted history
+#[test]
+fn watch_compacted() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?;
+    for iter in 0..40000 {
+        store.set("key".to_owned(), format!("{}", iter)).wait()?;
+    }
+    store.set("key".to_owned(), "last".to_owned()).wait()?;
+
+    let check = |store: &KvStore<RayonThreadPool>| -> Result<()> {
+        match store.watch("key".to_owned(), Some(1)).into_future().wait() {
+            Err((KvsError::Compacted(1), _)) => {}
+            _ => panic!("Compacted history should not be replayed"),
+        }
+        let events = store
+            .watch("key".to_owned(), Some(40001))
+            .take(1)
+            .collect()
+            .wait()?;
+        assert_eq!(
+            events,
+            vec![Event::Set {
+                seq: 40001,
+                key: "key".to_owned(),
+                value: "last".to_owned()
+            }]
+        );
+        Ok(())
+    };
+    check(&store)?;
+
+    // reopen and check again
+    drop(store);
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?;
+    check(&store)
+}
+
+// Keys in different namespaces should be independent
+#[test]
+fn namespaces() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?;
+    store.create_namespace("ns1".to_owned()).wait()?;
+    store.create_namespace("ns2".to_owned()).wait()?;
+    assert_eq!(
+        store.list_namespaces().wait()?,
+        vec!["ns1".to_owned(), "ns2".to_owned()]
+    );
+
+    let ns1 = store.namespace("ns1")?;
+    store.set("key1".to_owned(), "value0".to_owned()).wait()?;
+    ns1.set("key1".to_owned(), "value1".to_owned()).wait()?;
+    assert_eq!(
+        store.get("key1".to_owned()).wait()?,
+        Some("value0".to_owned())
+    );
+    assert_eq!(
+        ns1.get("key1".to_owned()).wait()?,
+        Some("value1".to_owned())
+    );
+    assert_eq!(store.namespace("ns2")?.get("key1".to_owned()).wait()?, None);
+
+    // Open from disk again and check persistent data
+    drop(ns1);
+    drop(store);
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?;
+    assert_eq!(
+        store.namespace("ns1")?.get("key1".to_owned()).wait()?,
+        Some("value1".to_owned())
+    );
+
+    store.drop_namespace("ns1".to_owned()).wait()?;
+    assert_eq!(store.list_namespaces().wait()?, vec!["ns2".to_owned()]);
+    match store.namespace("ns1") {
+        Err(KvsError::NamespaceNotFound(_)) => {}
+        _ => panic!("Dropped namespace should not be found"),
+    }
+    assert_eq!(
+        store.get("key1".to_owned()).wait()?,
+        Some("value0".to_owned())
+    );
+
+    Ok(())
+}
+
+// Should fail operations through a handle of a dropped namespace
+#[test]
+fn use_dropped_namespace() -> Result<()> {
+    fn check<E: KvsEngine>(store: E) -> Result<()> {
+        store.create_namespace("ns".to_owned()).wait()?;
+        let ns = store.namespace("ns")?;
+        ns.set("key".to_owned(), "value".to_owned()).wait()?;
+        store.drop_namespace("ns".to_owned()).wait()?;
+
+        match ns.set("key".to_owned(), "value".to_owned()).wait() {
+            Err(KvsError::NamespaceNotFound(_)) => {}
+            _ => panic!("Dropped namespace should not be written"),
+        }
+        match ns.get("key".to_owned()).wait() {
+            Err(KvsError::NamespaceNotFound(_)) => {}
+            _ => panic!("Dropped namespace should not be read"),
+        }
+        match ns.remove("key".to_owned()).wait() {
+            Err(KvsError::NamespaceNotFound(_)) => {}
+            _ => panic!("Dropped namespace should not be written"),
+        }
+        Ok(())
+    }
+
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    check(KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?)?;
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let db = sled::Db::start_default(temp_dir.path())?;
+    check(SledKvsEngine::<RayonThreadPool>::new(db, 1)?)
+}
+
+#[test]
+fn create_invalid_namespace() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?;
+    store.create_namespace("ns".to_owned()).wait()?;
+    match store.create_namespace("ns".to_owned()).wait() {
+        Err(KvsError::NamespaceExists(_)) => {}
+        _ => panic!("Namespace should not be created twice"),
+    }
+    for name in &["", "../ns", "_ns", "n s"] {
+        match store.create_namespace(name.to_string()).wait() {
+            Err(KvsError::InvalidNamespace(_)) => {}
+            _ => panic!("Invalid namespace {:?} should be rejected", name),
+        }
+    }
+    Ok(())
+}
+
+// A snapshot followed by its changes should be enough to rebuild the key space
+#[test]
+fn snapshot_and_restore() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 2)?;
+    store.set("key1".to_owned(), "value1".to_owned()).wait()?;
+    store.set("key2".to_owned(), "value2".to_owned()).wait()?;
+    store.remove("key1".to_owned()).wait()?;
+
+    let (snapshot, events) = store.snapshot().wait()?;
+    assert_eq!(
+        snapshot,
+        Snapshot {
+            seq: 3,
+            pairs: vec![("key2".to_owned(), "value2".to_owned())],
+        }
+    );
+    store.set("key3".to_owned(), "value3".to_owned()).wait()?;
+    let events = events.take(1).collect().wait()?;
+    assert_eq!(
+        events,
+        vec![Event::Set {
+            seq: 4,
+            key: "key3".to_owned(),
+            value: "value3".to_owned()
+        }]
+    );
+
+    let replica_dir = TempDir::new().expect("unable to create temporary working directory");
+    let replica = KvStore::<RayonThreadPool>::open(replica_dir.path(), 2)?;
+    replica.set("key2".to_owned(), "stale".to_owned()).wait()?;
+    replica.set("key4".to_owned(), "stale".to_owned()).wait()?;
+    replica.restore(snapshot).wait()?;
+    assert_eq!(
+        replica.get("key2".to_owned()).wait()?,
+        Some("value2".to_owned())
+    );
+    assert_eq!(replica.get("key4".to_owned()).wait()?, None);
+
+    // Restoring the same snapshot again changes nothing
+    let events = replica.watch(String::new(), None);
+    let (snapshot, _) = store.snapshot().wait()?;
+    replica.restore(snapshot).wait()?;
+    replica.set("key5".to_owned(), "value5".to_owned()).wait()?;
+    let events = events.take(2).collect().wait()?;
+    assert_eq!(events[0].key(), "key3");
+    assert_eq!(events[1].key(), "key5");
+
+    Ok(())
+}
+
+// Should scan the pairs set in one batch page by page in key order
+#[test]
+fn set_many_and_scan() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 2)?;
+    let pairs: Vec<(String, String)> = (0..5)
+        .rev()
+        .map(|i| (format!("key{}", i), format!("value{}", i)))
+        .collect();
+    store.set_many(pairs).wait()?;
+
+    let page = store.scan(None, 2).wait()?;
+    assert_eq!(
+        page,
+        vec![
+            ("key0".to_owned(), "value0".to_owned()),
+            ("key1".to_owned(), "value1".to_owned()),
+        ]
+    );
+    let page = store.scan(Some("key1".to_owned()), 10).wait()?;
+    let keys: Vec<&str> = page.iter().map(|(key, _)| &key[..]).collect();
+    assert_eq!(keys, vec!["key2", "key3", "key4"]);
+    assert!(store.scan(Some("key4".to_owned()), 10).wait()?.is_empty());
+
+    Ok(())
+}
+
+// Exported pairs should be imported into another store in both formats
+#[test]
+fn export_import() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 2)?;
+    store.set("key1".to_owned(), "value1".to_owned()).wait()?;
+    store
+        .set("key2".to_owned(), "value, \"2\"\n".to_owned())
+        .wait()?;
+    store.set("key3".to_owned(), "value3".to_owned()).wait()?;
+
+    for &format in &[DataFormat::JsonLines, DataFormat::Csv] {
+        let mut data = Vec::new();
+        assert_eq!(store.export(&mut data, format)?, 3);
+
+        let target_dir = TempDir::new().expect("unable to create temporary working directory");
+        let target = KvStore::<RayonThreadPool>::open(target_dir.path(), 2)?;
+        let mut reports = Vec::new();
+        let count = target.import(&data[..], format, 1, |count| reports.push(count))?;
+        assert_eq!(count, 3);
+        assert_eq!(reports, vec![3]);
+        assert_eq!(target.get("key1".to_owned()).wait()?, None);
+        assert_eq!(
+            target.get("key2".to_owned()).wait()?,
+            Some("value, \"2\"\n".to_owned())
+        );
+        assert_eq!(
+            target.get("key3".to_owned()).wait()?,
+            Some("value3".to_owned())
+        );
+    }
+
+    Ok(())
+}
+
+// Only one store should write to a directory at a time
+#[test]
+fn lock_data_dir() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?;
+    match KvStore::<RayonThreadPool>::open(temp_dir.path(), 1) {
+        Err(KvsError::Locked(_)) => {}
+        _ => panic!("The directory should be locked"),
+    }
+
+    // The lock is held until the last handle is dropped
+    let ns = store.clone();
+    drop(store);
+    assert!(KvStore::<RayonThreadPool>::open(temp_dir.path(), 1).is_err());
+    drop(ns);
+    KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?;
+
+    Ok(())
+}
+
+// A read-only store should be opened besides a writer and reject writes
+#[test]
+fn read_only() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::<RayonThreadPool>::open(temp_dir.path(), 1)?;
+    store.set("key1".to_owned(), "value1".to_owned()).wait()?;
+    store.create_namespace("ns".to_owned()).wait()?;
+
+    let options = KvStoreOptions {
+        read_only: true,
+        ..KvStoreOptions::default()
+    };
+    let reader = KvStore::<RayonThreadPool>::open_with_options(temp_dir.path(), 1, options)?;
+    assert_eq!(
+        reader.get("key1".to_owned()).wait()?,
+        Some("value1".to_owned())
+    );
+    assert_eq!(reader.list_namespaces().wait()?, vec!["ns".to_owned()]);
+    match reader.set("key1".to_owned(), "value2".to_owned()).wait() {
+        Err(KvsError::ReadOnly) => {}
+        _ => panic!("A read-only store should not be written"),
+    }
+    match reader.remove("key1".to_owned()).wait() {
+        Err(KvsError::ReadOnly) => {}
+        _ => panic!("A read-only store should not be written"),
+    }
+    match reader.drop_namespace("ns".to_owned()).wait() {
+        Err(KvsError::ReadOnly) => {}
+        _ => panic!("A read-only store should not be written"),
+    }
+
+    // The writer is not affected
+    store.set("key1".to_owned(), "value2".to_owned()).wait()?;
+    assert_eq!(
+        store.get("key1".to_owned()).wait()?,
+        Some("value2".to_owned())
+    );
+    Ok(())
+}
+
+// A read-only store should read the values a writer has moved by a compaction
+#[test]
+fn read_only_compaction() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let options = KvStoreOptions {
+        compaction_threshold: 4096,
+        ..KvStoreOptions::default()
+    };
+    let store = KvStore::<RayonThreadPool>::open_with_options(temp_dir.path(), 1, options)?;
+    store.set("key0".to_owned(), "value0".to_owned()).wait()?;
+
+    let options = KvStoreOptions {
+        read_only: true,
+        ..KvStoreOptions::default()
+    };
+    let reader = KvStore::<RayonThreadPool>::open_with_options(temp_dir.path(), 4, options)?;
+    assert_eq!(
+        reader.get("key0".to_owned()).wait()?,
+        Some("value0".to_owned())
+    );
+
+    let first_log = temp_dir.path().join("1.log");
+    let mut iter = 0;
+    while first_log.exists() {
+        iter += 1;
+        assert!(iter < 1000, "No compaction detected");
+        let pairs = (0..100)
+            .map(|key_id| (format!("key{}", key_id), format!("value{}", iter)))
+            .collect();
+        store.set_many(pairs).wait()?;
+    }
+
+    for key_id in 0..100 {
+        assert_eq!(
+            reader.get(format!("key{}", key_id)).wait()?,
+            Some(format!("value{}", iter))
+        );
+    }
+    let pairs = reader.scan(None, 1000).wait()?;
+    assert_eq!(pairs.len(), 100);
+    let expected = format!("value{}", iter);
+    assert!(pairs.iter().all(|(_, value)| *value == expected));
+    Ok(())
+}
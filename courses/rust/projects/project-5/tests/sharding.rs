@@ -0,0 +1,79 @@
+use kvs::thread_pool::RayonThreadPool;
+use kvs::{KvStore, KvsClient, KvsServer, Result, ShardedKvsClient};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::prelude::*;
+
+// Runs a server in the background and returns its address.
+fn start_server(port: u16, temp_dir: &TempDir) -> Result<SocketAddr> {
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+    let engine = KvStore::<RayonThreadPool>::open(temp_dir.path(), 2)?;
+    thread::spawn(move || KvsServer::new(engine).run(addr).unwrap());
+    thread::sleep(Duration::from_millis(500));
+    Ok(addr)
+}
+
+fn key_count(addr: SocketAddr, keys: &[String]) -> Result<usize> {
+    let mut client = KvsClient::connect(addr).wait()?;
+    let mut count = 0;
+    for key in keys {
+        let (value, c) = client.get(key.clone()).wait()?;
+        client = c;
+        if value.is_some() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+// Keys should be spread over the servers and moved when a server is added
+#[test]
+fn sharded_client() -> Result<()> {
+    let temp_dirs: Vec<TempDir> = (0..3).map(|_| TempDir::new().unwrap()).collect();
+    let addr1 = start_server(4101, &temp_dirs[0])?;
+    let addr2 = start_server(4102, &temp_dirs[1])?;
+    let addr3 = start_server(4103, &temp_dirs[2])?;
+
+    let keys: Vec<String> = (0..100).map(|i| format!("key{}", i)).collect();
+    let pairs = keys
+        .iter()
+        .map(|key| (key.clone(), format!("value-{}", key)))
+        .collect();
+    let client = ShardedKvsClient::connect(vec![addr1, addr2]).wait()?;
+    let client = client.set_many(pairs).wait()?;
+    let client = client.remove("key0".to_owned()).wait()?;
+
+    let (values, client) = client.get_many(keys.clone()).wait()?;
+    assert_eq!(values[0], None);
+    for (key, value) in keys.iter().zip(values).skip(1) {
+        assert_eq!(value, Some(format!("value-{}", key)));
+    }
+    let on_server1 = key_count(addr1, &keys)?;
+    let on_server2 = key_count(addr2, &keys)?;
+    assert_eq!(on_server1 + on_server2, 99);
+    assert!(on_server1 > 0 && on_server2 > 0);
+
+    let (moved, client) = client.add_server(addr3).wait()?;
+    assert!(moved > 0);
+    assert_eq!(key_count(addr3, &keys)? as u64, moved);
+    assert_eq!(
+        key_count(addr1, &keys)? + key_count(addr2, &keys)? + moved as usize,
+        99
+    );
+    for key in &keys[1..] {
+        let server = client.server_of(key);
+        let (value, _) = KvsClient::connect(server)
+            .and_then(|c| c.get(key.clone()))
+            .wait()?;
+        assert_eq!(value, Some(format!("value-{}", key)));
+    }
+
+    // A new client of the three servers finds every key
+    let client = ShardedKvsClient::connect(vec![addr1, addr2, addr3]).wait()?;
+    let (value, _) = client.get("key42".to_owned()).wait()?;
+    assert_eq!(value, Some("value-key42".to_owned()));
+
+    Ok(())
+}
@@ -1,10 +1,13 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use kvs::thread_pool::*;
-use kvs::Result;
+use kvs::{KvsError, Result};
 
 use crossbeam_utils::sync::WaitGroup;
+use tokio::prelude::*;
 
 fn spawn_counter<P: ThreadPool>(pool: P) -> Result<()> {
     const TASK_NUM: usize = 20;
@@ -46,6 +49,161 @@ fn spawn_panic_task<P: ThreadPool>() -> Result<()> {
     spawn_counter(pool)
 }
 
+fn shutdown_finishes_jobs<P: ThreadPool>() -> Result<()> {
+    const TASK_NUM: usize = 100;
+
+    let pool = P::new(4)?;
+    let counter = Arc::new(AtomicUsize::new(0));
+    for _ in 0..TASK_NUM {
+        let counter = Arc::clone(&counter);
+        pool.spawn(move || {
+            thread::sleep(Duration::from_millis(1));
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+    pool.shutdown(Duration::from_secs(10))?;
+    assert_eq!(counter.load(Ordering::SeqCst), TASK_NUM);
+
+    // jobs spawned after shutdown are discarded
+    let counter_clone = Arc::clone(&counter);
+    pool.spawn(move || {
+        counter_clone.fetch_add(1, Ordering::SeqCst);
+    });
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(counter.load(Ordering::SeqCst), TASK_NUM);
+    assert!(pool.resize(4).is_err());
+    Ok(())
+}
+
+fn shutdown_timeout<P: ThreadPool>() -> Result<()> {
+    let pool = P::new(1)?;
+    let (tx, rx) = mpsc::channel::<()>();
+    pool.spawn(move || {
+        rx.recv().unwrap();
+    });
+    match pool.shutdown(Duration::from_millis(100)) {
+        Err(KvsError::ShutdownTimeout) => {}
+        res => panic!("unexpected shutdown result: {:?}", res),
+    }
+    tx.send(()).unwrap();
+    pool.shutdown(Duration::from_secs(10))
+}
+
+fn metrics<P: ThreadPool>() -> Result<()> {
+    const TASK_NUM: u64 = 20;
+
+    let pool = P::new(2)?;
+    let (tx, rx) = mpsc::channel::<()>();
+    let rx = Arc::new(Mutex::new(rx));
+    for _ in 0..TASK_NUM {
+        let rx = Arc::clone(&rx);
+        pool.spawn(move || {
+            rx.lock().unwrap().recv().unwrap();
+        });
+        pool.spawn(move || {
+            panic_control::disable_hook_in_current_thread();
+            panic!();
+        });
+    }
+    wait_until(|| pool.metrics().active > 0);
+    // no job can return before the blocking jobs are released
+    assert_eq!(pool.metrics().completed, 0);
+
+    for _ in 0..TASK_NUM {
+        tx.send(()).unwrap();
+    }
+    pool.shutdown(Duration::from_secs(10))?;
+    let metrics = pool.metrics();
+    assert_eq!(metrics.threads, 0);
+    assert_eq!(metrics.queued, 0);
+    assert_eq!(metrics.active, 0);
+    assert_eq!(metrics.completed, TASK_NUM);
+    assert_eq!(metrics.panicked, TASK_NUM);
+    Ok(())
+}
+
+fn resize<P: ThreadPool>() -> Result<()> {
+    let pool = P::new(1)?;
+    assert_eq!(pool.metrics().threads, 1);
+    pool.resize(4)?;
+    wait_until(|| pool.metrics().threads == 4);
+    pool.resize(2)?;
+    wait_until(|| pool.metrics().threads == 2);
+    spawn_counter(pool.clone())?;
+    pool.shutdown(Duration::from_secs(10))?;
+    assert_eq!(pool.metrics().threads, 0);
+    Ok(())
+}
+
+fn spawn_with_handle<P: ThreadPool>() -> Result<()> {
+    let pool = P::new(2)?;
+    let handles: Vec<JoinHandle<usize>> = (0..10)
+        .map(|i| pool.spawn_with_handle(move || i * i))
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.join()?, i * i);
+    }
+
+    let handle = pool.spawn_with_handle(|| {
+        panic_control::disable_hook_in_current_thread();
+        panic!();
+    });
+    match handle.join() {
+        Err(KvsError::JobPanicked) => {}
+        res => panic!("unexpected result of a panicking job: {:?}", res),
+    }
+    // the handle is also a future
+    assert_eq!(pool.spawn_with_handle(|| "ok").wait()?, "ok");
+
+    pool.shutdown(Duration::from_secs(10))?;
+    match pool.spawn_with_handle(|| ()).join() {
+        Err(KvsError::JobCanceled) => {}
+        res => panic!(
+            "unexpected result of a job spawned after shutdown: {:?}",
+            res
+        ),
+    }
+    Ok(())
+}
+
+fn spawn_during_shutdown<P: ThreadPool>() -> Result<()> {
+    for _ in 0..20 {
+        let pool = P::new(4)?;
+        let spawner = {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                (0..200)
+                    .map(|i| pool.spawn_with_handle(move || i))
+                    .collect::<Vec<_>>()
+            })
+        };
+        pool.shutdown(Duration::from_secs(10))?;
+        // every job either runs or is canceled, none is left behind in the queue
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (i, handle) in spawner.join().unwrap().into_iter().enumerate() {
+                match handle.join() {
+                    Ok(value) => assert_eq!(value, i),
+                    Err(KvsError::JobCanceled) => {}
+                    Err(e) => panic!("unexpected result of job {}: {:?}", i, e),
+                }
+            }
+            tx.send(()).unwrap();
+        });
+        rx.recv_timeout(Duration::from_secs(10))
+            .expect("a job handle never resolved");
+    }
+    Ok(())
+}
+
+fn wait_until<F: Fn() -> bool>(cond: F) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !cond() {
+        assert!(Instant::now() < deadline, "condition not met in time");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
 #[test]
 fn naive_thread_pool_spawn_counter() -> Result<()> {
     let pool = NaiveThreadPool::new(4)?;
@@ -68,3 +226,121 @@ fn rayon_thread_pool_spawn_counter() -> Result<()> {
 fn shared_queue_thread_pool_panic_task() -> Result<()> {
     spawn_panic_task::<SharedQueueThreadPool>()
 }
+
+#[test]
+fn work_stealing_thread_pool_spawn_counter() -> Result<()> {
+    let pool = WorkStealingThreadPool::new(4)?;
+    spawn_counter(pool)
+}
+
+#[test]
+fn work_stealing_thread_pool_panic_task() -> Result<()> {
+    spawn_panic_task::<WorkStealingThreadPool>()
+}
+
+#[test]
+fn work_stealing_thread_pool_priority() -> Result<()> {
+    let pool = WorkStealingThreadPool::new(1)?;
+    let (block_tx, block_rx) = mpsc::channel::<()>();
+    let (order_tx, order_rx) = mpsc::channel();
+
+    // keep the only thread busy until all jobs are queued
+    pool.spawn(move || {
+        block_rx.recv().unwrap();
+    });
+    for &priority in &[Priority::Low, Priority::Normal, Priority::High] {
+        let order_tx = order_tx.clone();
+        pool.spawn_with_priority(priority, move || {
+            order_tx.send(priority).unwrap();
+        });
+    }
+    block_tx.send(()).unwrap();
+
+    let order: Vec<Priority> = order_rx.iter().take(3).collect();
+    assert_eq!(order, vec![Priority::High, Priority::Normal, Priority::Low]);
+    Ok(())
+}
+
+#[test]
+fn naive_thread_pool_shutdown() -> Result<()> {
+    shutdown_finishes_jobs::<NaiveThreadPool>()?;
+    shutdown_timeout::<NaiveThreadPool>()
+}
+
+#[test]
+fn shared_queue_thread_pool_shutdown() -> Result<()> {
+    shutdown_finishes_jobs::<SharedQueueThreadPool>()?;
+    shutdown_timeout::<SharedQueueThreadPool>()
+}
+
+#[test]
+fn rayon_thread_pool_shutdown() -> Result<()> {
+    shutdown_finishes_jobs::<RayonThreadPool>()?;
+    shutdown_timeout::<RayonThreadPool>()
+}
+
+#[test]
+fn work_stealing_thread_pool_shutdown() -> Result<()> {
+    shutdown_finishes_jobs::<WorkStealingThreadPool>()?;
+    shutdown_timeout::<WorkStealingThreadPool>()
+}
+
+#[test]
+fn naive_thread_pool_metrics() -> Result<()> {
+    metrics::<NaiveThreadPool>()
+}
+
+#[test]
+fn shared_queue_thread_pool_metrics() -> Result<()> {
+    metrics::<SharedQueueThreadPool>()
+}
+
+#[test]
+fn rayon_thread_pool_metrics() -> Result<()> {
+    metrics::<RayonThreadPool>()
+}
+
+#[test]
+fn work_stealing_thread_pool_metrics() -> Result<()> {
+    metrics::<WorkStealingThreadPool>()
+}
+
+#[test]
+fn shared_queue_thread_pool_resize() -> Result<()> {
+    resize::<SharedQueueThreadPool>()
+}
+
+#[test]
+fn rayon_thread_pool_resize() -> Result<()> {
+    resize::<RayonThreadPool>()
+}
+
+#[test]
+fn work_stealing_thread_pool_resize() -> Result<()> {
+    resize::<WorkStealingThreadPool>()
+}
+
+#[test]
+fn naive_thread_pool_spawn_with_handle() -> Result<()> {
+    spawn_with_handle::<NaiveThreadPool>()
+}
+
+#[test]
+fn shared_queue_thread_pool_spawn_with_handle() -> Result<()> {
+    spawn_with_handle::<SharedQueueThreadPool>()
+}
+
+#[test]
+fn rayon_thread_pool_spawn_with_handle() -> Result<()> {
+    spawn_with_handle::<RayonThreadPool>()
+}
+
+#[test]
+fn work_stealing_thread_pool_spawn_with_handle() -> Result<()> {
+    spawn_with_handle::<WorkStealingThreadPool>()
+}
+
+#[test]
+fn shared_queue_thread_pool_spawn_during_shutdown() -> Result<()> {
+    spawn_during_shutdown::<SharedQueueThreadPool>()
+}
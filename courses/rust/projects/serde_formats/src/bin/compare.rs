@@ -0,0 +1,37 @@
+//! Writes `Move` values in every format and prints how each one did.
+//!
+//! Usage: `compare [COUNT] [DIR]`, with 1000 values in the temporary directory by
+//! default.
+
+use serde_formats::{compare, Format, Move};
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let count: usize = match args.next() {
+        Some(count) => count.parse()?,
+        None => 1000,
+    };
+    let dir = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("serde_formats"));
+    std::fs::create_dir_all(&dir)?;
+
+    let moves: Vec<Move> = (0..count)
+        .map(|i| {
+            let steps = (i % 256) as u8;
+            match i % 4 {
+                0 => Move::Left { steps },
+                1 => Move::Right { steps },
+                2 => Move::Up { steps },
+                _ => Move::Down { steps },
+            }
+        })
+        .collect();
+    println!("{} moves in {}", count, dir.display());
+    for report in compare(&dir, &moves, &Format::ALL)? {
+        println!("{}", report);
+    }
+    Ok(())
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// A move of a game character any number of squares in one direction, as in the
+/// serde exercises.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Move {
+    Left { steps: u8 },
+    Right { steps: u8 },
+    Up { steps: u8 },
+    Down { steps: u8 },
+}
@@ -0,0 +1,42 @@
+use crate::format::Format;
+use std::fmt::{self, Display};
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the stream failed
+    Io(io::Error),
+    /// A value could not be encoded or decoded in the format
+    Format { format: Format, msg: String },
+    /// A value read back differs from the one written
+    Mismatch { format: Format, index: usize },
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Format { format, msg } => write!(f, "{} error: {}", format, msg),
+            Error::Mismatch { format, index } => {
+                write!(f, "{} value {} did not round-trip", format, index)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,231 @@
+use crate::error::{Error, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A serialization format, and how values follow each other in a stream of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// One JSON value per line
+    JsonLines,
+    /// One RON value per line, which the compact RON output never breaks
+    Ron,
+    /// One document per value, holding it in its `value` field, since the top level
+    /// of BSON must be a document
+    Bson,
+    /// Values back to back, as bincode knows where each one ends
+    Bincode,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [
+        Format::JsonLines,
+        Format::Ron,
+        Format::Bson,
+        Format::Bincode,
+    ];
+
+    /// Returns the usual extension of the files of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::JsonLines => "jsonl",
+            Format::Ron => "ron",
+            Format::Bson => "bson",
+            Format::Bincode => "bin",
+        }
+    }
+
+    fn error(self, e: impl Display) -> Error {
+        Error::Format {
+            format: self,
+            msg: e.to_string(),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::JsonLines => "JSON Lines",
+            Format::Ron => "RON",
+            Format::Bson => "BSON",
+            Format::Bincode => "bincode",
+        })
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" | "jsonl" => Ok(Format::JsonLines),
+            "ron" => Ok(Format::Ron),
+            "bson" => Ok(Format::Bson),
+            "bincode" | "bin" => Ok(Format::Bincode),
+            _ => Err(format!(
+                "unknown format '{}', expected jsonl, ron, bson or bincode",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BsonValue<'a, T: ?Sized> {
+    value: &'a T,
+}
+
+#[derive(Deserialize)]
+struct OwnedBsonValue<T> {
+    value: T,
+}
+
+/// Writes values one after the other in a format.
+#[derive(Debug)]
+pub struct ValueWriter<W: Write> {
+    format: Format,
+    writer: W,
+}
+
+impl<W: Write> ValueWriter<W> {
+    pub fn new(format: Format, writer: W) -> Self {
+        ValueWriter { format, writer }
+    }
+
+    pub fn write<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let format = self.format;
+        match format {
+            Format::JsonLines => {
+                serde_json::to_writer(&mut self.writer, value).map_err(|e| format.error(e))?;
+                self.writer.write_all(b"\n")?;
+            }
+            Format::Ron => {
+                let line = ron::to_string(value).map_err(|e| format.error(e))?;
+                writeln!(self.writer, "{}", line)?;
+            }
+            Format::Bson => {
+                let document =
+                    bson::to_document(&BsonValue { value }).map_err(|e| format.error(e))?;
+                document
+                    .to_writer(&mut self.writer)
+                    .map_err(|e| format.error(e))?;
+            }
+            Format::Bincode => {
+                bincode::serialize_into(&mut self.writer, value).map_err(|e| format.error(e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes the written values and returns the writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the values of a stream in a format, until its end or the first error.
+#[derive(Debug)]
+pub struct ValueReader<R, T> {
+    format: Format,
+    reader: R,
+    line: String,
+    failed: bool,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<R: BufRead, T: DeserializeOwned> ValueReader<R, T> {
+    pub fn new(format: Format, reader: R) -> Self {
+        ValueReader {
+            format,
+            reader,
+            line: String::new(),
+            failed: false,
+            value: PhantomData,
+        }
+    }
+
+    fn read_value(&mut self) -> Result<Option<T>> {
+        let format = self.format;
+        match format {
+            Format::JsonLines => self
+                .read_line()?
+                .map(|line| serde_json::from_str(line).map_err(|e| format.error(e)))
+                .transpose(),
+            Format::Ron => self
+                .read_line()?
+                .map(|line| ron::from_str(line).map_err(|e| format.error(e)))
+                .transpose(),
+            Format::Bson => {
+                if self.reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                let document =
+                    bson::Document::from_reader(&mut self.reader).map_err(|e| format.error(e))?;
+                let wrapped: OwnedBsonValue<T> =
+                    bson::from_document(document).map_err(|e| format.error(e))?;
+                Ok(Some(wrapped.value))
+            }
+            Format::Bincode => {
+                if self.reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                let value =
+                    bincode::deserialize_from(&mut self.reader).map_err(|e| format.error(e))?;
+                Ok(Some(value))
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<&str>> {
+        self.line.clear();
+        if self.reader.read_line(&mut self.line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.line.trim_end_matches(&['\r', '\n'][..])))
+    }
+}
+
+impl<R: BufRead, T: DeserializeOwned> Iterator for ValueReader<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let value = self.read_value().transpose();
+        self.failed = matches!(value, Some(Err(_)));
+        value
+    }
+}
+
+/// Writes `values` to a new file at `path`.
+pub fn write_file<'a, T, I>(path: impl AsRef<Path>, format: Format, values: I) -> Result<()>
+where
+    T: 'a + Serialize,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut writer = ValueWriter::new(format, BufWriter::new(File::create(path)?));
+    for value in values {
+        writer.write(value)?;
+    }
+    writer.finish()?.into_inner().map_err(|e| e.into_error())?;
+    Ok(())
+}
+
+/// Reads all the values of the file at `path`.
+pub fn read_file<T>(path: impl AsRef<Path>, format: Format) -> Result<Vec<T>>
+where
+    T: DeserializeOwned,
+{
+    ValueReader::new(format, BufReader::new(File::open(path)?)).collect()
+}
@@ -0,0 +1,31 @@
+//! Streams of serde values in files of several formats.
+//!
+//! Any `Serialize + DeserializeOwned` type can be written value after value with a
+//! [`ValueWriter`] and read back with a [`ValueReader`], in JSON Lines, RON, BSON
+//! or bincode, and [`compare`] reports how big and fast each format is.
+//!
+//! ```
+//! use serde_formats::{Format, Move, ValueReader, ValueWriter};
+//!
+//! let moves = vec![Move::Left { steps: 7 }, Move::Up { steps: 1 }];
+//! let mut writer = ValueWriter::new(Format::Ron, Vec::new());
+//! for m in &moves {
+//!     writer.write(m).unwrap();
+//! }
+//! let buffer = writer.finish().unwrap();
+//! assert_eq!(buffer, b"Left(steps:7)\nUp(steps:1)\n");
+//!
+//! let read: Vec<Move> = ValueReader::new(Format::Ron, &buffer[..])
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! assert_eq!(read, moves);
+//! ```
+
+mod data;
+mod error;
+mod format;
+mod report;
+pub use crate::data::Move;
+pub use crate::error::{Error, Result};
+pub use crate::format::{read_file, write_file, Format, ValueReader, ValueWriter};
+pub use crate::report::{compare, Report};
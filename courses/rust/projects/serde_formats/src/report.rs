@@ -0,0 +1,68 @@
+use crate::error::{Error, Result};
+use crate::format::{read_file, write_file, Format};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How one format did at writing values to a file and reading them back.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub format: Format,
+    pub values: usize,
+    /// The size of the file
+    pub bytes: u64,
+    pub write_time: Duration,
+    pub read_time: Duration,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_value = self.bytes as f64 / self.values.max(1) as f64;
+        write!(
+            f,
+            "{:<10} {:>10} bytes {:>8.1} bytes/value  write {:>9.3} ms  read {:>9.3} ms",
+            self.format.to_string(),
+            self.bytes,
+            per_value,
+            self.write_time.as_secs_f64() * 1000.0,
+            self.read_time.as_secs_f64() * 1000.0,
+        )
+    }
+}
+
+/// Writes `values` to a file per format in `dir`, reads them back and checks they
+/// are unchanged, measuring the size of the files and the time spent.
+pub fn compare<T>(dir: impl AsRef<Path>, values: &[T], formats: &[Format]) -> Result<Vec<Report>>
+where
+    T: Serialize + DeserializeOwned + PartialEq,
+{
+    let mut reports = Vec::with_capacity(formats.len());
+    for &format in formats {
+        let path = dir.as_ref().join(format!("values.{}", format.extension()));
+
+        let start = Instant::now();
+        write_file(&path, format, values)?;
+        let write_time = start.elapsed();
+
+        let start = Instant::now();
+        let read: Vec<T> = read_file(&path, format)?;
+        let read_time = start.elapsed();
+
+        if let Some(index) =
+            (0..values.len().max(read.len())).find(|&i| values.get(i) != read.get(i))
+        {
+            return Err(Error::Mismatch { format, index });
+        }
+        reports.push(Report {
+            format,
+            values: values.len(),
+            bytes: fs::metadata(&path)?.len(),
+            write_time,
+            read_time,
+        });
+    }
+    Ok(reports)
+}
@@ -0,0 +1,99 @@
+use proptest::prelude::*;
+use serde_formats::{
+    compare, read_file, write_file, Error, Format, Move, ValueReader, ValueWriter,
+};
+use std::collections::BTreeMap;
+
+fn any_move() -> impl Strategy<Value = Move> {
+    prop_oneof![
+        any::<u8>().prop_map(|steps| Move::Left { steps }),
+        any::<u8>().prop_map(|steps| Move::Right { steps }),
+        any::<u8>().prop_map(|steps| Move::Up { steps }),
+        any::<u8>().prop_map(|steps| Move::Down { steps }),
+    ]
+}
+
+fn any_format() -> impl Strategy<Value = Format> {
+    prop::sample::select(Format::ALL.to_vec())
+}
+
+fn round_trip<T>(format: Format, values: &[T]) -> Vec<T>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let mut writer = ValueWriter::new(format, Vec::new());
+    for value in values {
+        writer.write(value).unwrap();
+    }
+    let buffer = writer.finish().unwrap();
+    ValueReader::new(format, &buffer[..])
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+proptest! {
+    // Should read back every stream of moves in every format
+    #[test]
+    fn moves_round_trip(format in any_format(), moves in prop::collection::vec(any_move(), 0..100)) {
+        prop_assert_eq!(round_trip(format, &moves), moves);
+    }
+
+    // Should read back values with strings of any content
+    #[test]
+    fn strings_round_trip(
+        format in any_format(),
+        values in prop::collection::vec((any::<String>(), any::<i32>()), 0..20),
+    ) {
+        prop_assert_eq!(round_trip(format, &values), values);
+    }
+}
+
+// Should stream values to files and report on every format
+#[test]
+fn files_and_reports() {
+    let dir = tempfile::tempdir().unwrap();
+    let moves: Vec<Move> = (0..1000u32)
+        .map(|i| Move::Right {
+            steps: (i % 256) as u8,
+        })
+        .collect();
+    let reports = compare(dir.path(), &moves, &Format::ALL).unwrap();
+    assert_eq!(reports.len(), 4);
+    for report in &reports {
+        assert_eq!(report.values, 1000);
+        assert!(report.bytes > 0);
+    }
+
+    let path = dir.path().join("maps.bson");
+    let maps: Vec<BTreeMap<String, Move>> = vec![
+        BTreeMap::new(),
+        [("first".to_owned(), Move::Down { steps: 3 })].into(),
+    ];
+    write_file(&path, Format::Bson, &maps).unwrap();
+    assert_eq!(
+        read_file::<BTreeMap<String, Move>>(&path, Format::Bson).unwrap(),
+        maps
+    );
+}
+
+// Should stop at the first value that does not decode
+#[test]
+fn invalid_values() {
+    let input = b"{\"Left\":{\"steps\":1}}\n{\"Left\":{\"steps\":300}}\n{\"Up\":{\"steps\":1}}\n";
+    let mut reader = ValueReader::<_, Move>::new(Format::JsonLines, &input[..]);
+    assert_eq!(reader.next().unwrap().unwrap(), Move::Left { steps: 1 });
+    assert!(matches!(
+        reader.next(),
+        Some(Err(Error::Format {
+            format: Format::JsonLines,
+            ..
+        }))
+    ));
+    assert!(reader.next().is_none());
+
+    let truncated = &bincode::serialize(&Move::Up { steps: 1 }).unwrap()[..3];
+    let values: Result<Vec<Move>, _> = ValueReader::new(Format::Bincode, truncated).collect();
+    assert!(values.is_err());
+    assert_eq!("jsonl".parse::<Format>(), Ok(Format::JsonLines));
+    assert!("xml".parse::<Format>().is_err());
+}
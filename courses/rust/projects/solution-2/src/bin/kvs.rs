@@ -48,7 +48,7 @@ fn main() -> Result<()> {
         }
         ("get", Some(arg_matches)) => {
             let key = arg_matches.value_of("KEY").unwrap();
-            let mut kvs = KvStore::open(cwd)?;
+            let mut kvs = KvStore::open_read_only(cwd)?;
             let res = kvs.get(key.into());
             let res = handle_result(res.map(|val| {
                 if let None = &val {
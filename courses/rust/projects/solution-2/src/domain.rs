@@ -54,7 +54,8 @@ impl<RS: Read + Seek> BufReaderWithPos<RS> {
 
 impl<RS: Read + Seek> Seek for BufReaderWithPos<RS> {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
-        self.inner.seek(pos)
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
     }
 }
 
@@ -100,6 +101,7 @@ impl<WS: Write + Seek> Write for BufWriterWithPos<WS> {
 
 impl<WS: Write + Seek> Seek for BufWriterWithPos<WS> {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
-        self.inner.seek(pos)
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
     }
 }
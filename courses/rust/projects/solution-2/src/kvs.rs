@@ -16,26 +16,31 @@
 //! many cases, the operating system’s filesystem read-ahead cache makes this a much faster operation than would
 //! be otherwise expected
 
+use fs2::FileExt;
 use serde_json::Deserializer;
 
 // use crate::domain::LogPointer;
 use crate::{
     domain::{BufReaderWithPos, BufWriterWithPos, KvsCommand, LogPointer},
-    error::Result,
+    error::{KvsError, Result},
 };
 use std::io::{Seek, SeekFrom, Write};
 use std::{collections::BTreeMap, fs, io::Read};
 use std::{collections::HashMap, fs::File, path::PathBuf};
 
-// Threshold in bytes which needs to be exceeded in order to do a compaction operation.
+// Threshold in bytes of stale commands which needs to be exceeded in order to do a
+// compaction operation.
 static COMPACTION_THRESHOLD: u64 = 1024 * 1024;
 static LOG_FILETYPE_SUFFIX: &'static str = ".log";
 static KEY_NOT_FOUND: &'static str = "Key not found";
+static LOCK_FILE: &str = "LOCK";
+static READ_ONLY: &str = "Store is opened read-only";
 
 /// A new in memory key-value store
 /// Key/value pairs are persisted to disk in log files. Log files are named after
 /// monotonically increasing generation numbers with a `log` extension name.
 /// A `BTreeMap` in memory stores the keys and the value locations for fast query.
+/// The directory is locked while the store is open, so only one process writes to it.
 #[derive(Debug)]
 pub struct KvStore {
     /// A keydir is simply a hash
@@ -46,21 +51,24 @@ pub struct KvStore {
     path: PathBuf,
 
     index: BTreeMap<String, LogPointer>,
-    // writer of the current log.
-    writer: BufWriterWithPos<File>,
+    // writer of the current log, `None` if the store is read-only.
+    writer: Option<BufWriterWithPos<File>>,
     // map generation number to the file reader.
     readers: HashMap<u64, BufReaderWithPos<File>>,
     current_file_idx: u64,
     // the number of bytes representing "stale" commands that could be
     // deleted during a compaction.
     uncompacted: u64,
+    // the locked file of the directory, unlocked when the store is dropped.
+    _lock: Option<File>,
 }
 
 impl KvStore {
     /// Set a new entry to the KvStore
     /// ```rust
     /// # use kvs::KvStore;
-    /// let mut store = KvStore::open("./").unwrap();
+    /// # let dir = tempfile::TempDir::new().unwrap();
+    /// let mut store = KvStore::open(dir.path()).unwrap();
     /// store.set(String::from("key"), String::from("value"));
     /// assert!(store.get(String::from("key")).is_ok());
     /// ```
@@ -69,12 +77,14 @@ impl KvStore {
             key: key.clone(),
             value: value.clone(),
         });
-        let pos = self.writer.pos;
-        serde_json::to_writer(&mut self.writer, &set_cmd)?;
-        self.writer.flush()?;
+        let writer = self.writer()?;
+        let pos = writer.pos;
+        serde_json::to_writer(&mut *writer, &set_cmd)?;
+        writer.flush()?;
+        let new_pos = writer.pos;
         if let Some(old_cmd) = self
             .index
-            .insert(key, (self.current_file_idx, (pos..self.writer.pos)).into())
+            .insert(key, (self.current_file_idx, (pos..new_pos)).into())
         {
             self.uncompacted += old_cmd.value_size;
         }
@@ -90,11 +100,13 @@ impl KvStore {
     /// Returns the Ok(value) or [`None`] if the key does not exist
     /// ```rust
     /// # use kvs::KvStore;
-    /// let mut store = KvStore::open("./").unwrap();
+    /// # let dir = tempfile::TempDir::new().unwrap();
+    /// let mut store = KvStore::open(dir.path()).unwrap();
     /// let result = store.get(String::from("key"));
     /// assert!(result.is_ok());
     /// ```
     pub fn get(&mut self, key: String) -> Result<Option<String>> {
+        self.reload_if_compacted(&key)?;
         match self.index.get(&key) {
             Some(log_pointer) => {
                 let reader = self
@@ -117,19 +129,28 @@ impl KvStore {
     /// Remove a value from the KvStore
     /// ```rust
     /// # use kvs::KvStore;
-    /// let mut store = KvStore::open("./").unwrap();
+    /// # let dir = tempfile::TempDir::new().unwrap();
+    /// let mut store = KvStore::open(dir.path()).unwrap();
     /// store.set(String::from("key"), String::from("value"));
     /// store.remove(String::from("key"));
     /// assert!(store.get(String::from("key")).is_ok());
     /// ```
     pub fn remove(&mut self, key: String) -> Result<()> {
         if self.index.contains_key(&key) {
-            dbg!("inside");
             let rm_cmd = KvsCommand::Rm(crate::domain::Rm { key: key.clone() });
-            serde_json::to_writer(&mut self.writer, &rm_cmd)?;
-            self.writer.flush()?;
+            let writer = self.writer()?;
+            let pos = writer.pos;
+            serde_json::to_writer(&mut *writer, &rm_cmd)?;
+            writer.flush()?;
+            let new_pos = writer.pos;
             let old_cmd = self.index.remove(&key).expect(KEY_NOT_FOUND);
             self.uncompacted += old_cmd.value_size;
+            // the "remove" command itself can be deleted in the next compaction.
+            self.uncompacted += new_pos - pos;
+
+            if self.uncompacted > COMPACTION_THRESHOLD {
+                self.compact()?;
+            }
             Ok(())
         } else {
             Err(KEY_NOT_FOUND.into())
@@ -141,11 +162,35 @@ impl KvStore {
     ///
     /// # Errors
     ///
+    /// It fails if the directory is opened by another `KvStore` which is not read-only.
+    ///
     /// It propagates I/O or deserialization errors during the log replay.
     pub fn open(path: impl Into<PathBuf>) -> Result<KvStore> {
         let path_buf: PathBuf = path.into();
         fs::create_dir_all(&path_buf)?;
+        let lock = lock_dir(&path_buf)?;
+        let mut store = Self::load(path_buf)?;
+        store.current_file_idx += 1;
+        store.writer = Some(store.create_file(store.current_file_idx)?);
+        store._lock = Some(lock);
+        Ok(store)
+    }
+
+    /// Opens a `KvStore` with the given path without writing to it.
+    ///
+    /// Another process may write to the directory meanwhile. Reads see the data as of
+    /// opening until the writer compacts away the log file of a value, then the log
+    /// files are loaded again. Writes fail.
+    ///
+    /// # Errors
+    ///
+    /// It propagates I/O or deserialization errors during the log replay.
+    pub fn open_read_only(path: impl Into<PathBuf>) -> Result<KvStore> {
+        Self::load(path.into())
+    }
 
+    /// Builds the index from the log files in the directory.
+    fn load(path_buf: PathBuf) -> Result<KvStore> {
         let mut readers = HashMap::new();
         let mut index = BTreeMap::new();
 
@@ -153,34 +198,63 @@ impl KvStore {
         let mut uncompacted = 0;
 
         for &file_idx in &file_idx_list {
-            let mut reader = BufReaderWithPos::new(File::open(create_path(&path_buf, file_idx))?)?;
+            // A compaction of another process deletes files only after copying their
+            // entries into a later file, so a deleted file can be skipped.
+            let file = match File::open(create_path(&path_buf, file_idx)) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let mut reader = BufReaderWithPos::new(file)?;
             uncompacted += build_index_from_reader(file_idx, &mut reader, &mut index)?;
             readers.insert(file_idx, reader);
         }
 
-        let current_file_idx = file_idx_list.last().unwrap_or(&0) + 1;
-        let writer = create_file(&path_buf, current_file_idx, &mut readers)?;
+        let current_file_idx = *file_idx_list.last().unwrap_or(&0);
 
         Ok(KvStore {
             path: path_buf,
             readers,
             index,
             current_file_idx,
-            writer,
+            writer: None,
             uncompacted,
+            _lock: None,
         })
     }
 
+    /// Loads the log files again if the store is read-only and the file holding `key`
+    /// has been deleted by a compaction of the writer.
+    fn reload_if_compacted(&mut self, key: &str) -> Result<()> {
+        while let Some(log_pointer) = self.index.get(key) {
+            if self.writer.is_some() || create_path(&self.path, log_pointer.file_id).is_file() {
+                break;
+            }
+            *self = Self::load(self.path.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the writer of the current log, or an error if the store is read-only.
+    fn writer(&mut self) -> Result<&mut BufWriterWithPos<File>> {
+        self.writer.as_mut().ok_or_else(|| READ_ONLY.into())
+    }
+
     /// Clears stale entries in the log.
+    ///
+    /// The live entries are copied into a new compaction file, and new writes go to
+    /// the file after it. The index is only pointed to the compaction file after it is
+    /// flushed, and the readers of the older files are closed before they are deleted.
     pub(crate) fn compact(&mut self) -> crate::Result<()> {
         // increase current gen by 2. current_gen + 1 is for the compaction file.
         let cur_file_idx = self.current_file_idx + 1;
         self.current_file_idx += 2;
-        self.writer = self.create_file(self.current_file_idx)?;
+        self.writer = Some(self.create_file(self.current_file_idx)?);
         let mut compaction_writer = self.create_file(cur_file_idx)?;
 
         let mut new_pos = 0; // pos in the new log file.
-        for log_pointer in &mut self.index.values_mut() {
+        let mut new_pointers = Vec::with_capacity(self.index.len());
+        for (key, log_pointer) in &self.index {
             let reader = self
                 .readers
                 .get_mut(&log_pointer.file_id)
@@ -191,11 +265,15 @@ impl KvStore {
 
             let mut entry_reader = reader.take(log_pointer.value_size);
             let len = std::io::copy(&mut entry_reader, &mut compaction_writer)?;
-            *log_pointer = (cur_file_idx, new_pos..new_pos + len).into();
+            new_pointers.push((key.clone(), new_pos..new_pos + len));
             new_pos += len;
         }
         compaction_writer.flush()?;
 
+        for (key, range) in new_pointers {
+            self.index.insert(key, (cur_file_idx, range).into());
+        }
+
         // remove stale log files.
         let stale_gens: Vec<_> = self
             .readers
@@ -220,6 +298,27 @@ impl KvStore {
     }
 }
 
+/// Locks the directory for the process.
+///
+/// Returns the lock file, which is unlocked when it is closed.
+fn lock_dir(path: &std::path::Path) -> Result<File> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.join(LOCK_FILE))?;
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(file),
+        Err(e) if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() => Err(KvsError {
+            msg: format!(
+                "Data directory is locked by another process: {}",
+                path.display()
+            ),
+        }),
+        Err(e) => Err(e.into()),
+    }
+}
+
 fn create_path(dir: &std::path::Path, suffix: u64) -> PathBuf {
     dir.join(format!("{}{}", suffix, LOG_FILETYPE_SUFFIX))
 }
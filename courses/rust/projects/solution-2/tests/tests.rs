@@ -299,3 +299,115 @@ fn compaction() -> Result<()> {
 
     panic!("No compaction detected");
 }
+
+// Overwriting and removing the same keys repeatedly should not grow the directory
+// beyond the compaction threshold.
+#[test]
+fn compaction_bounds_dir_size() -> Result<()> {
+    // the threshold of stale bytes plus the live entries with some slack.
+    const MAX_DIR_SIZE: u64 = 2 * 1024 * 1024;
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let mut store = KvStore::open(temp_dir.path())?;
+
+    let dir_size = || {
+        let entries = WalkDir::new(temp_dir.path()).into_iter();
+        let len: walkdir::Result<u64> = entries
+            .map(|res| {
+                res.and_then(|entry| entry.metadata())
+                    .map(|metadata| metadata.len())
+            })
+            .sum();
+        len.expect("fail to get directory size")
+    };
+
+    let mut max_size = 0;
+    for iter in 0..200 {
+        for key_id in 0..500 {
+            store.set(format!("key{}", key_id), format!("value{}", iter))?;
+        }
+        for key_id in 0..50 {
+            store.remove(format!("key{}", key_id))?;
+        }
+        max_size = max_size.max(dir_size());
+    }
+    assert!(
+        max_size < MAX_DIR_SIZE,
+        "directory grew to {} bytes",
+        max_size
+    );
+
+    drop(store);
+    let mut store = KvStore::open(temp_dir.path())?;
+    for key_id in 0..50 {
+        assert_eq!(store.get(format!("key{}", key_id))?, None);
+    }
+    for key_id in 50..500 {
+        assert_eq!(
+            store.get(format!("key{}", key_id))?,
+            Some("value199".to_owned())
+        );
+    }
+
+    Ok(())
+}
+
+// Only one store should write to a directory at a time
+#[test]
+fn lock_dir() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::open(temp_dir.path())?;
+    assert!(KvStore::open(temp_dir.path()).is_err());
+
+    drop(store);
+    KvStore::open(temp_dir.path())?;
+
+    Ok(())
+}
+
+// A read-only store should be opened besides a writer and reject writes
+#[test]
+fn read_only() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let mut store = KvStore::open(temp_dir.path())?;
+    store.set("key1".to_owned(), "value1".to_owned())?;
+
+    let mut reader = KvStore::open_read_only(temp_dir.path())?;
+    assert_eq!(reader.get("key1".to_owned())?, Some("value1".to_owned()));
+    assert!(reader.set("key1".to_owned(), "value2".to_owned()).is_err());
+    assert!(reader.remove("key1".to_owned()).is_err());
+
+    store.set("key1".to_owned(), "value2".to_owned())?;
+    assert_eq!(store.get("key1".to_owned())?, Some("value2".to_owned()));
+
+    Ok(())
+}
+
+// A read-only store should read the values a writer has moved by a compaction
+#[test]
+fn read_only_compaction() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let mut store = KvStore::open(temp_dir.path())?;
+    store.set("key0".to_owned(), "value0".to_owned())?;
+
+    let mut reader = KvStore::open_read_only(temp_dir.path())?;
+    assert_eq!(reader.get("key0".to_owned())?, Some("value0".to_owned()));
+
+    let first_log = temp_dir.path().join("1.log");
+    let mut iter = 0;
+    while first_log.exists() {
+        iter += 1;
+        assert!(iter < 1000, "No compaction detected");
+        for key_id in 0..1000 {
+            store.set(format!("key{}", key_id), format!("value{}", iter))?;
+        }
+    }
+
+    for key_id in 0..1000 {
+        assert_eq!(
+            reader.get(format!("key{}", key_id))?,
+            Some(format!("value{}", iter))
+        );
+    }
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
-use clap::{App, AppSettings, Arg, SubCommand};
-use kvs::{KvStore, Result};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use kvs::{KvsClient, Result};
+use std::net::SocketAddr;
 
 #[derive(Debug)]
 struct ParsedEnvVars {
@@ -10,8 +11,16 @@ struct ParsedEnvVars {
 }
 
 fn main() -> Result<()> {
-    let cwd = std::env::current_dir().unwrap();
     let env_vars: ParsedEnvVars = init_env_vars();
+    let addr_arg = Arg::with_name("addr")
+        .long("addr")
+        .help("IP-PORT")
+        .default_value("127.0.0.1:4000")
+        .validator(|s| {
+            s.parse::<SocketAddr>()
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        });
     let matches = App::new(env_vars.app_name)
         .author(env_vars.author)
         .version(env_vars.version)
@@ -19,11 +28,6 @@ fn main() -> Result<()> {
         .setting(AppSettings::DisableHelpSubcommand)
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .setting(AppSettings::VersionlessSubcommands)
-        .arg(Arg::with_name("addr")
-        .long("addr")
-        .help("IP-PORT")
-        .default_value("127.0.0.1:4000")
-        .required(true))
         .subcommands(vec![
             SubCommand::with_name("set")
                 .about("Set the value of a string key to a string")
@@ -32,13 +36,16 @@ fn main() -> Result<()> {
                     Arg::with_name("VALUE")
                         .help("A string value")
                         .required(true),
-                ),
+                )
+                .arg(addr_arg.clone()),
             SubCommand::with_name("get")
                 .about("Get the string value of a given string key")
-                .arg(Arg::with_name("KEY").help("A string key").required(true)),
+                .arg(Arg::with_name("KEY").help("A string key").required(true))
+                .arg(addr_arg.clone()),
             SubCommand::with_name("rm")
                 .about("Remove a given key")
-                .arg(Arg::with_name("KEY").help("A string key").required(true)),
+                .arg(Arg::with_name("KEY").help("A string key").required(true))
+                .arg(addr_arg),
         ])
         .get_matches();
 
@@ -46,48 +53,39 @@ fn main() -> Result<()> {
         ("set", Some(arg_matches)) => {
             let key = arg_matches.value_of("KEY").unwrap();
             let value = arg_matches.value_of("VALUE").unwrap();
-            let mut kvs = KvStore::open(cwd)?;
-            let res = kvs.set(key.into(), value.into());
-            let res = handle_result(res);
-            res
+            let res =
+                connect(arg_matches).and_then(|mut client| client.set(key.into(), value.into()));
+            handle_result(res)
         }
         ("get", Some(arg_matches)) => {
             let key = arg_matches.value_of("KEY").unwrap();
-            let mut kvs = KvStore::open(cwd)?;
-            let res = kvs.get(key.into());
-            let res = handle_result(res.map(|val| {
-                if let None = &val {
-                    println!("Key not found")
-                } else {
-                    println!("{}", val.unwrap())
-                }
-            }));
-            res
+            let res = connect(arg_matches).and_then(|mut client| client.get(key.into()));
+            handle_result(res.map(|val| match val {
+                Some(val) => println!("{}", val),
+                None => println!("Key not found"),
+            }))
         }
         ("rm", Some(arg_matches)) => {
             let key = arg_matches.value_of("KEY").unwrap();
-            let mut kvs = KvStore::open(cwd)?;
-            let res = kvs.remove(key.into());
-            let res = handle_result(res);
-
-            if let Err(_) = res {
-                std::process::exit(1);
-            }
-            res
+            let res = connect(arg_matches).and_then(|mut client| client.remove(key.into()));
+            handle_result(res)
         }
         _ => unreachable!(),
     }
 }
 
+fn connect(arg_matches: &ArgMatches) -> Result<KvsClient> {
+    KvsClient::connect(arg_matches.value_of("addr").unwrap())
+}
+
+/// Prints the error and exits with a non-zero code if the command failed.
 fn handle_result(res: Result<()>) -> Result<()> {
-    if res.is_err() {
-        let error = res.unwrap_err();
+    if let Err(error) = res {
         let error_msg = &error.msg;
-        println!("{error_msg}");
-        Err(error)
-    } else {
-        res
+        eprintln!("{error_msg}");
+        std::process::exit(1);
     }
+    Ok(())
 }
 
 fn init_env_vars() -> ParsedEnvVars {
@@ -1,5 +1,14 @@
 use clap::{App, Arg};
+use kvs::thread_pool::{SharedQueueThreadPool, ThreadPool};
+use kvs::{KvStore, KvsEngine, KvsError, KvsServer, Result, SledKvsEngine};
+use log::{error, info, warn, LevelFilter};
+use std::fs;
+use std::path::Path;
+use std::process::exit;
 
+static ENGINE_FILE: &str = "engine";
+// The number of clients served at once, as each connection holds a thread of the pool.
+const MAX_CLIENTS: u32 = 32;
 
 #[derive(Debug)]
 struct ParsedEnvVars {
@@ -10,37 +19,84 @@ struct ParsedEnvVars {
 }
 
 fn main() {
-    println!("Hello from kvs-server!");
+    env_logger::builder().filter_level(LevelFilter::Info).init();
     let env_vars = init_env_vars();
     let matches = App::new(env_vars.app_name)
         .author(env_vars.author)
         .version(env_vars.version)
         .about(env_vars.description)
-        .arg(Arg::with_name("addr")
+        .arg(
+            Arg::with_name("addr")
                 .long("addr")
                 .help("IP-PORT")
                 .default_value("127.0.0.1:4000")
-                .required(true))
-        .arg(Arg::with_name("engine")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("engine")
                 .long("engine")
-                .default_value("kvs")
                 .help("ENGINE-NAME")
-                .validator(|s| {
-                    if !s.eq("kvs") && !s.eq("sled") {
-                        Err(String::from("Engine name not part of possible engine names."))
-                    } else  {
-                        Ok(())
-                    }
-                })
-                .required(true)
-            )
+                .possible_values(&["kvs", "sled"])
+                .takes_value(true),
+        )
         .get_matches();
     let addr = matches.value_of("addr").unwrap();
-    let engine = matches.value_of("engine").unwrap();
-    dbg!(addr);
-    dbg!(engine);
+    let engine = matches.value_of("engine");
+
+    if let Err(e) = run(env_vars.version, addr, engine) {
+        error!("{}", e.msg);
+        exit(1);
+    }
+}
+
+fn run(version: &str, addr: &str, engine: Option<&str>) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+    let current_engine = current_engine(&cwd)?;
+    let engine = match (engine, current_engine.as_deref()) {
+        (Some(engine), Some(current)) if engine != current => {
+            return Err(KvsError {
+                msg: format!("Wrong engine: the data directory uses {}", current),
+            });
+        }
+        (Some(engine), _) | (None, Some(engine)) => engine.to_owned(),
+        (None, None) => "kvs".to_owned(),
+    };
+    info!("kvs-server {}", version);
+    info!("Storage engine: {}", engine);
+    info!("Listening on {}", addr);
+
+    let pool = SharedQueueThreadPool::new(MAX_CLIENTS)?;
+    // The engine file is written once the engine holds the data directory, so a
+    // server failing to open it leaves the directory as it was.
+    if engine == "sled" {
+        let store = SledKvsEngine::new(sled::open(&cwd)?);
+        fs::write(cwd.join(ENGINE_FILE), &engine)?;
+        run_with(store, pool, addr)
+    } else {
+        let store = KvStore::open(cwd.clone())?;
+        fs::write(cwd.join(ENGINE_FILE), &engine)?;
+        run_with(store, pool, addr)
+    }
 }
 
+fn run_with<E: KvsEngine, P: ThreadPool>(engine: E, pool: P, addr: &str) -> Result<()> {
+    KvsServer::new(engine, pool).run(addr)
+}
+
+/// Returns the engine the data in the directory is stored with.
+fn current_engine(dir: &Path) -> Result<Option<String>> {
+    let path = dir.join(ENGINE_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let engine = fs::read_to_string(path)?;
+    if engine == "kvs" || engine == "sled" {
+        Ok(Some(engine))
+    } else {
+        warn!("The content of engine file is invalid: {}", engine);
+        Ok(None)
+    }
+}
 
 const fn init_env_vars() -> ParsedEnvVars {
     let author = env!("CARGO_PKG_AUTHORS");
@@ -53,4 +109,4 @@ const fn init_env_vars() -> ParsedEnvVars {
         version,
         description,
     }
-}
\ No newline at end of file
+}
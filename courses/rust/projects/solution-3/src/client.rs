@@ -0,0 +1,64 @@
+use crate::common::{Request, Response};
+use crate::error::{KvsError, Result};
+use serde::Deserialize;
+use serde_json::de::{Deserializer, IoRead};
+use std::io::{BufReader, BufWriter, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+/// A client of a `KvsServer`.
+pub struct KvsClient {
+    reader: Deserializer<IoRead<BufReader<TcpStream>>>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl KvsClient {
+    /// Connects to the server at `addr`.
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let tcp_reader = TcpStream::connect(addr)?;
+        let tcp_writer = tcp_reader.try_clone()?;
+        Ok(KvsClient {
+            reader: Deserializer::from_reader(BufReader::new(tcp_reader)),
+            writer: BufWriter::new(tcp_writer),
+        })
+    }
+
+    /// Gets the value of a given key from the server.
+    pub fn get(&mut self, key: String) -> Result<Option<String>> {
+        match self.request(Request::Get { key })? {
+            Response::Value(value) => Ok(value),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    /// Sets the value of a string key in the server.
+    pub fn set(&mut self, key: String, value: String) -> Result<()> {
+        match self.request(Request::Set { key, value })? {
+            Response::Done => Ok(()),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    /// Removes a string key in the server.
+    pub fn remove(&mut self, key: String) -> Result<()> {
+        match self.request(Request::Remove { key })? {
+            Response::Done => Ok(()),
+            resp => Err(unexpected(resp)),
+        }
+    }
+
+    /// Sends the request and returns the response, or the error sent by the server.
+    fn request(&mut self, req: Request) -> Result<Response> {
+        serde_json::to_writer(&mut self.writer, &req)?;
+        self.writer.flush()?;
+        match Response::deserialize(&mut self.reader)? {
+            Response::Err(msg) => Err(KvsError { msg }),
+            resp => Ok(resp),
+        }
+    }
+}
+
+fn unexpected(resp: Response) -> KvsError<String> {
+    KvsError {
+        msg: format!("Unexpected response: {:?}", resp),
+    }
+}
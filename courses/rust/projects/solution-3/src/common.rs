@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Request {
+    Get { key: String },
+    Set { key: String, value: String },
+    Remove { key: String },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Response {
+    Value(Option<String>),
+    Done,
+    Err(String),
+}
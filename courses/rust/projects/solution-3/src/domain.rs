@@ -19,7 +19,7 @@ pub(crate) struct Rm {
     pub(crate) key: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub(crate) struct LogPointer {
     pub(crate) file_id: u64,
     pub(crate) value_size: u64,
@@ -21,6 +21,22 @@ impl From<serde_json::Error> for KvsError<String> {
     }
 }
 
+impl From<sled::Error> for KvsError<String> {
+    fn from(err: sled::Error) -> Self {
+        KvsError {
+            msg: err.to_string(),
+        }
+    }
+}
+
+impl From<std::string::FromUtf8Error> for KvsError<String> {
+    fn from(err: std::string::FromUtf8Error) -> Self {
+        KvsError {
+            msg: err.to_string(),
+        }
+    }
+}
+
 impl From<&str> for KvsError<String> {
     fn from(input: &str) -> Self {
         KvsError { msg: input.into() }
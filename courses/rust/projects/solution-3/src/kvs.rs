@@ -16,207 +16,419 @@
 //! many cases, the operating system’s filesystem read-ahead cache makes this a much faster operation than would
 //! be otherwise expected
 
+use crossbeam_skiplist::SkipMap;
+use fs2::FileExt;
 use serde_json::Deserializer;
 
-// use crate::domain::LogPointer;
 use crate::{
     domain::{BufReaderWithPos, BufWriterWithPos, KvsCommand, LogPointer},
-    error::Result,
+    error::{KvsError, Result},
 };
-use std::io::{Seek, SeekFrom, Write};
-use std::{collections::BTreeMap, fs, io::Read};
-use std::{collections::HashMap, fs::File, path::PathBuf};
+use std::cell::RefCell;
+use std::collections::{btree_map, BTreeMap};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{fs::File, path::PathBuf};
 
 // Threshold in bytes which needs to be exceeded in order to do a compaction operation.
 static COMPACTION_THRESHOLD: u64 = 1024 * 1024;
 static LOG_FILETYPE_SUFFIX: &'static str = ".log";
 static KEY_NOT_FOUND: &'static str = "Key not found";
+static LOCK_FILE: &str = "LOCK";
+static READ_ONLY: &str = "Store is opened read-only";
 
 /// A new in memory key-value store
 /// Key/value pairs are persisted to disk in log files. Log files are named after
 /// monotonically increasing generation numbers with a `log` extension name.
-/// A `BTreeMap` in memory stores the keys and the value locations for fast query.
-#[derive(Debug)]
+/// A lock-free `SkipMap` in memory stores the keys and the value locations for fast query.
+/// Opening a writable store locks the directory until the last clone is dropped.
+///
+/// The store can be cloned and sent to other threads. Clones share the index and the
+/// writer, while each clone reads the log files through its own file handles, so reads
+/// never wait for other reads or writes.
+#[derive(Debug, Clone)]
 pub struct KvStore {
     /// A keydir is simply a hash
     /// table that maps every key in a Bitcask to a fixed-size structure giving the file, offset, and size of the most recently
     /// written entry for that key
-
-    /// Directory for the log and other data
-    path: PathBuf,
-
-    index: BTreeMap<String, LogPointer>,
-    // writer of the current log.
-    writer: BufWriterWithPos<File>,
-    // map generation number to the file reader.
-    readers: HashMap<u64, BufReaderWithPos<File>>,
-    current_file_idx: u64,
-    // the number of bytes representing "stale" commands that could be
-    // deleted during a compaction.
-    uncompacted: u64,
+    index: Arc<SkipMap<String, LogPointer>>,
+    reader: KvStoreReader,
+    writer: Arc<Mutex<KvStoreWriter>>,
+    read_only: bool,
 }
 
 impl KvStore {
-    /// Set a new entry to the KvStore
-    /// ```rust
-    /// # use kvs::KvStore;
-    /// let mut store = KvStore::open("./").unwrap();
-    /// store.set(String::from("key"), String::from("value"));
-    /// assert!(store.get(String::from("key")).is_ok());
-    /// ```
-    pub fn set(&mut self, key: String, value: String) -> Result<()> {
-        let set_cmd = KvsCommand::Set(crate::domain::Set {
-            key: key.clone(),
-            value: value.clone(),
-        });
-        let pos = self.writer.pos;
-        serde_json::to_writer(&mut self.writer, &set_cmd)?;
-        self.writer.flush()?;
-        if let Some(old_cmd) = self
-            .index
-            .insert(key, (self.current_file_idx, (pos..self.writer.pos)).into())
+    /// Opens a `KvStore` with the given path.
+    ///
+    /// This will create a new directory if the given one does not exist.
+    ///
+    /// # Errors
+    ///
+    /// It fails if the lock of the directory is held, e.g. by another server process.
+    ///
+    /// It propagates I/O or deserialization errors during the log replay.
+    pub fn open(path: impl Into<PathBuf>) -> Result<KvStore> {
+        let path_buf: PathBuf = path.into();
+        fs::create_dir_all(&path_buf)?;
+        let lock = lock_dir(&path_buf)?;
+        let store = Self::load(path_buf)?;
         {
-            self.uncompacted += old_cmd.value_size;
+            let mut writer = store.writer.lock().unwrap();
+            writer.current_file_idx += 1;
+            writer.writer = Some(create_file(&writer.path, writer.current_file_idx)?);
+            writer._lock = Some(lock);
         }
+        Ok(KvStore {
+            read_only: false,
+            ..store
+        })
+    }
 
-        if self.uncompacted > COMPACTION_THRESHOLD {
-            self.compact()?;
+    /// Opens a `KvStore` on a directory which another process may be writing to.
+    ///
+    /// No lock is taken and `set` and `remove` fail. The index is built from the log
+    /// files when opening, and is rebuilt for all clones when a read finds that the
+    /// writer has compacted away the file of its value.
+    ///
+    /// # Errors
+    ///
+    /// It propagates I/O or deserialization errors during the log replay.
+    pub fn open_read_only(path: impl Into<PathBuf>) -> Result<KvStore> {
+        Self::load(path.into())
+    }
+
+    /// Builds the index from the log files in the directory.
+    fn load(path_buf: PathBuf) -> Result<KvStore> {
+        let path = Arc::new(path_buf);
+        let mut readers = BTreeMap::new();
+        let index = Arc::new(SkipMap::new());
+
+        let file_idx_list = sorted_file_idx_list(&path)?;
+        let mut uncompacted = 0;
+
+        for &file_idx in &file_idx_list {
+            if let Some(mut reader) = open_log(&path, file_idx)? {
+                uncompacted += build_index_from_reader(file_idx, &mut reader, &index)?;
+                readers.insert(file_idx, reader);
+            }
+        }
+
+        let current_file_idx = *file_idx_list.last().unwrap_or(&0);
+        let reader = KvStoreReader {
+            path: Arc::clone(&path),
+            safe_point: Arc::new(AtomicU64::new(0)),
+            readers: RefCell::new(readers),
+        };
+        let writer = KvStoreWriter {
+            reader: reader.clone(),
+            writer: None,
+            current_file_idx,
+            uncompacted,
+            path,
+            index: Arc::clone(&index),
+            _lock: None,
+        };
+
+        Ok(KvStore {
+            index,
+            reader,
+            writer: Arc::new(Mutex::new(writer)),
+            read_only: true,
+        })
+    }
+
+    /// Returns whether the store is read-only and the log file has been deleted by a
+    /// compaction of the writer.
+    fn is_compacted(&self, file_id: u64) -> bool {
+        self.read_only && !create_path(&self.reader.path, file_id).is_file()
+    }
+
+    /// Rebuilds the index of a read-only store from the log files in the directory.
+    fn reload(&self) -> Result<()> {
+        // one reload at a time
+        let _writer = self.writer.lock().unwrap();
+        let index = SkipMap::new();
+        let mut first_file_idx = None;
+        for file_idx in sorted_file_idx_list(&self.reader.path)? {
+            if let Some(mut reader) = open_log(&self.reader.path, file_idx)? {
+                first_file_idx.get_or_insert(file_idx);
+                build_index_from_reader(file_idx, &mut reader, &index)?;
+            }
         }
 
+        for entry in self.index.iter() {
+            if !index.contains_key(entry.key()) {
+                self.index.remove(entry.key());
+            }
+        }
+        for entry in index.iter() {
+            self.index.insert(entry.key().clone(), *entry.value());
+        }
+        // The readers close their handles of the deleted files.
+        self.reader
+            .safe_point
+            .store(first_file_idx.unwrap_or(0), Ordering::SeqCst);
         Ok(())
     }
+}
+
+impl KvsEngine for KvStore {
+    /// Set a new entry to the KvStore
+    /// ```rust
+    /// # use kvs::{KvStore, KvsEngine};
+    /// # let dir = tempfile::TempDir::new().unwrap();
+    /// let store = KvStore::open(dir.path()).unwrap();
+    /// store.set(String::from("key"), String::from("value"));
+    /// assert!(store.get(String::from("key")).is_ok());
+    /// ```
+    fn set(&self, key: String, value: String) -> Result<()> {
+        self.writer.lock().unwrap().set(key, value)
+    }
 
     /// Get a value from the KvStore by specifying the key
     /// Returns the Ok(value) or [`None`] if the key does not exist
+    ///
+    /// It does not take any lock. If a compaction deletes the file the value was in
+    /// while it is read, the value is looked up again in the compaction file.
     /// ```rust
-    /// # use kvs::KvStore;
-    /// let mut store = KvStore::open("./").unwrap();
+    /// # use kvs::{KvStore, KvsEngine};
+    /// # let dir = tempfile::TempDir::new().unwrap();
+    /// let store = KvStore::open(dir.path()).unwrap();
     /// let result = store.get(String::from("key"));
     /// assert!(result.is_ok());
     /// ```
-    pub fn get(&mut self, key: String) -> Result<Option<String>> {
-        match self.index.get(&key) {
-            Some(log_pointer) => {
-                let reader = self
-                    .readers
-                    .get_mut(&log_pointer.file_id)
-                    .expect("Cannot find log reader");
-                reader.seek(SeekFrom::Start(log_pointer.value_pos))?;
-                let cmd_reader = reader.take(log_pointer.value_size);
-                if let KvsCommand::Set(crate::domain::Set { value, .. }) =
-                    serde_json::from_reader(cmd_reader)?
-                {
-                    return Ok(Some(value));
-                } else {
-                    return Err("Unexpected deserializes command".into());
-                }
+    fn get(&self, key: String) -> Result<Option<String>> {
+        loop {
+            let log_pointer = match self.index.get(&key) {
+                Some(entry) => *entry.value(),
+                None => return Ok(None),
+            };
+            // A read-only store is not told about the compactions of the writer.
+            if self.is_compacted(log_pointer.file_id) {
+                self.reload()?;
+                continue;
+            }
+            match self.reader.read_command(log_pointer) {
+                Ok(KvsCommand::Set(crate::domain::Set { value, .. })) => return Ok(Some(value)),
+                Ok(_) => return Err("Unexpected deserializes command".into()),
+                // The index points into the compaction file before the stale files are
+                // deleted, so the next lookup finds the value there.
+                Err(_) if self.reader.is_stale(log_pointer.file_id) => continue,
+                Err(_) if self.is_compacted(log_pointer.file_id) => self.reload()?,
+                Err(e) => return Err(e),
             }
-            None => Ok(None),
         }
     }
+
     /// Remove a value from the KvStore
     /// ```rust
-    /// # use kvs::KvStore;
-    /// let mut store = KvStore::open("./").unwrap();
+    /// # use kvs::{KvStore, KvsEngine};
+    /// # let dir = tempfile::TempDir::new().unwrap();
+    /// let store = KvStore::open(dir.path()).unwrap();
     /// store.set(String::from("key"), String::from("value"));
     /// store.remove(String::from("key"));
     /// assert!(store.get(String::from("key")).is_ok());
     /// ```
-    pub fn remove(&mut self, key: String) -> Result<()> {
+    fn remove(&self, key: String) -> Result<()> {
+        self.writer.lock().unwrap().remove(key)
+    }
+}
+
+/// A single thread reader of the log files.
+///
+/// Each `KvStore` clone has its own `KvStoreReader` with its own file handles,
+/// so the clones can read concurrently in different threads.
+#[derive(Debug)]
+struct KvStoreReader {
+    path: Arc<PathBuf>,
+    // index of the latest compaction file, files before it are stale.
+    safe_point: Arc<AtomicU64>,
+    // map generation number to the file reader.
+    readers: RefCell<BTreeMap<u64, BufReaderWithPos<File>>>,
+}
+
+impl KvStoreReader {
+    /// Closes the handles of files before the latest compaction file.
+    ///
+    /// The index has no pointers into those files after the compaction, so they can
+    /// be deleted.
+    fn close_stale_handles(&self) {
+        let mut readers = self.readers.borrow_mut();
+        let safe_point = self.safe_point.load(Ordering::SeqCst);
+        *readers = readers.split_off(&safe_point);
+    }
+
+    /// Returns whether the file has been replaced by a compaction and may be deleted.
+    fn is_stale(&self, file_id: u64) -> bool {
+        file_id < self.safe_point.load(Ordering::SeqCst)
+    }
+
+    /// Reads the entry at the given `LogPointer` with `f`.
+    fn read_and<F, R>(&self, log_pointer: LogPointer, f: F) -> Result<R>
+    where
+        F: FnOnce(io::Take<&mut BufReaderWithPos<File>>) -> Result<R>,
+    {
+        self.close_stale_handles();
+
+        let mut readers = self.readers.borrow_mut();
+        let reader = match readers.entry(log_pointer.file_id) {
+            btree_map::Entry::Occupied(entry) => entry.into_mut(),
+            btree_map::Entry::Vacant(entry) => entry.insert(BufReaderWithPos::new(File::open(
+                create_path(&self.path, log_pointer.file_id),
+            )?)?),
+        };
+        reader.seek(SeekFrom::Start(log_pointer.value_pos))?;
+        f(reader.take(log_pointer.value_size))
+    }
+
+    /// Reads the command at the given `LogPointer`.
+    fn read_command(&self, log_pointer: LogPointer) -> Result<KvsCommand> {
+        self.read_and(log_pointer, |cmd_reader| {
+            Ok(serde_json::from_reader(cmd_reader)?)
+        })
+    }
+}
+
+impl Clone for KvStoreReader {
+    fn clone(&self) -> KvStoreReader {
+        KvStoreReader {
+            path: Arc::clone(&self.path),
+            safe_point: Arc::clone(&self.safe_point),
+            // don't share the file handles with other readers
+            readers: RefCell::new(BTreeMap::new()),
+        }
+    }
+}
+
+/// The single writer of the log, shared by all clones of a `KvStore`.
+#[derive(Debug)]
+struct KvStoreWriter {
+    reader: KvStoreReader,
+    // writer of the current log, `None` if the store is read-only.
+    writer: Option<BufWriterWithPos<File>>,
+    current_file_idx: u64,
+    // the number of bytes representing "stale" commands that could be
+    // deleted during a compaction.
+    uncompacted: u64,
+    /// Directory for the log and other data
+    path: Arc<PathBuf>,
+    index: Arc<SkipMap<String, LogPointer>>,
+    // the locked file of the directory, unlocked when the store is dropped.
+    _lock: Option<File>,
+}
+
+impl KvStoreWriter {
+    fn set(&mut self, key: String, value: String) -> Result<()> {
+        let set_cmd = KvsCommand::Set(crate::domain::Set {
+            key: key.clone(),
+            value,
+        });
+        let writer = self.writer()?;
+        let pos = writer.pos;
+        serde_json::to_writer(&mut *writer, &set_cmd)?;
+        writer.flush()?;
+        let new_pos = writer.pos;
+        if let Some(old_cmd) = self.index.get(&key) {
+            self.uncompacted += old_cmd.value().value_size;
+        }
+        self.index
+            .insert(key, (self.current_file_idx, (pos..new_pos)).into());
+
+        if self.uncompacted > COMPACTION_THRESHOLD {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, key: String) -> Result<()> {
         if self.index.contains_key(&key) {
-            dbg!("inside");
             let rm_cmd = KvsCommand::Rm(crate::domain::Rm { key: key.clone() });
-            serde_json::to_writer(&mut self.writer, &rm_cmd)?;
-            self.writer.flush()?;
+            let writer = self.writer()?;
+            let pos = writer.pos;
+            serde_json::to_writer(&mut *writer, &rm_cmd)?;
+            writer.flush()?;
+            let new_pos = writer.pos;
             let old_cmd = self.index.remove(&key).expect(KEY_NOT_FOUND);
-            self.uncompacted += old_cmd.value_size;
+            self.uncompacted += old_cmd.value().value_size;
+            // the "remove" command itself can be deleted in the next compaction.
+            self.uncompacted += new_pos - pos;
             Ok(())
         } else {
             Err(KEY_NOT_FOUND.into())
         }
     }
-    /// Opens a `KvStore` with the given path.
-    ///
-    /// This will create a new directory if the given one does not exist.
-    ///
-    /// # Errors
-    ///
-    /// It propagates I/O or deserialization errors during the log replay.
-    pub fn open(path: impl Into<PathBuf>) -> Result<KvStore> {
-        let path_buf: PathBuf = path.into();
-        fs::create_dir_all(&path_buf)?;
-
-        let mut readers = HashMap::new();
-        let mut index = BTreeMap::new();
-
-        let file_idx_list = sorted_file_idx_list(&path_buf)?;
-        let mut uncompacted = 0;
-
-        for &file_idx in &file_idx_list {
-            let mut reader = BufReaderWithPos::new(File::open(create_path(&path_buf, file_idx))?)?;
-            uncompacted += build_index_from_reader(file_idx, &mut reader, &mut index)?;
-            readers.insert(file_idx, reader);
-        }
-
-        let current_file_idx = file_idx_list.last().unwrap_or(&0) + 1;
-        let writer = create_file(&path_buf, current_file_idx, &mut readers)?;
 
-        Ok(KvStore {
-            path: path_buf,
-            readers,
-            index,
-            current_file_idx,
-            writer,
-            uncompacted,
-        })
+    /// Returns the writer of the current log, or an error if the store is read-only.
+    fn writer(&mut self) -> Result<&mut BufWriterWithPos<File>> {
+        self.writer.as_mut().ok_or_else(|| READ_ONLY.into())
     }
 
     /// Clears stale entries in the log.
-    pub(crate) fn compact(&mut self) -> crate::Result<()> {
+    fn compact(&mut self) -> Result<()> {
         // increase current gen by 2. current_gen + 1 is for the compaction file.
         let cur_file_idx = self.current_file_idx + 1;
         self.current_file_idx += 2;
-        self.writer = self.create_file(self.current_file_idx)?;
-        let mut compaction_writer = self.create_file(cur_file_idx)?;
+        self.writer = Some(create_file(&self.path, self.current_file_idx)?);
+        let mut compaction_writer = create_file(&self.path, cur_file_idx)?;
 
         let mut new_pos = 0; // pos in the new log file.
-        for log_pointer in &mut self.index.values_mut() {
-            let reader = self
-                .readers
-                .get_mut(&log_pointer.file_id)
-                .expect("Cannot find log reader");
-            if reader.pos != log_pointer.value_pos {
-                reader.seek(SeekFrom::Start(log_pointer.value_pos))?;
-            }
-
-            let mut entry_reader = reader.take(log_pointer.value_size);
-            let len = std::io::copy(&mut entry_reader, &mut compaction_writer)?;
-            *log_pointer = (cur_file_idx, new_pos..new_pos + len).into();
+        let mut new_index = Vec::new();
+        for entry in self.index.iter() {
+            let len = self.reader.read_and(*entry.value(), |mut entry_reader| {
+                Ok(io::copy(&mut entry_reader, &mut compaction_writer)?)
+            })?;
+            new_index.push((entry.key().clone(), new_pos..new_pos + len));
             new_pos += len;
         }
         compaction_writer.flush()?;
 
+        // Concurrent readers must not see the new positions before they are flushed.
+        for (key, range) in new_index {
+            self.index.insert(key, (cur_file_idx, range).into());
+        }
+        self.reader.safe_point.store(cur_file_idx, Ordering::SeqCst);
+        self.reader.close_stale_handles();
+
         // remove stale log files.
-        let stale_gens: Vec<_> = self
-            .readers
-            .keys()
-            .filter(|&&f_idx| f_idx < cur_file_idx)
-            .cloned()
-            .collect();
+        // Other readers may still keep open handles, which are closed the next time
+        // they read. On Windows, the files cannot be deleted until then, so they are
+        // left to the next compaction.
+        let stale_gens = sorted_file_idx_list(&self.path)?
+            .into_iter()
+            .filter(|&f_idx| f_idx < cur_file_idx);
         for stale_gen in stale_gens {
-            self.readers.remove(&stale_gen);
-            fs::remove_file(create_path(&self.path, stale_gen))?;
+            let file_path = create_path(&self.path, stale_gen);
+            if let Err(e) = fs::remove_file(&file_path) {
+                log::error!("{} cannot be deleted: {}", file_path.display(), e);
+            }
         }
         self.uncompacted = 0;
 
         Ok(())
     }
+}
 
-    /// Create a new log file with given generation number and add the reader to the readers map.
-    ///
-    /// Returns the writer to the log.
-    pub(crate) fn create_file(&mut self, file_idx: u64) -> crate::Result<BufWriterWithPos<File>> {
-        create_file(&self.path, file_idx, &mut self.readers)
+/// Locks the directory for the process.
+///
+/// Returns the lock file, which is unlocked when it is closed.
+fn lock_dir(path: &std::path::Path) -> Result<File> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.join(LOCK_FILE))?;
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(file),
+        Err(e) if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() => Err(KvsError {
+            msg: format!(
+                "Data directory is locked by another process: {}",
+                path.display()
+            ),
+        }),
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -224,11 +436,10 @@ fn create_path(dir: &std::path::Path, suffix: u64) -> PathBuf {
     dir.join(format!("{}{}", suffix, LOG_FILETYPE_SUFFIX))
 }
 
-fn create_file(
-    path: &std::path::Path,
-    file_idx: u64,
-    readers: &mut HashMap<u64, BufReaderWithPos<File>>,
-) -> crate::Result<BufWriterWithPos<File>> {
+/// Creates a new log file with given generation number.
+///
+/// Returns the writer to the log.
+fn create_file(path: &std::path::Path, file_idx: u64) -> Result<BufWriterWithPos<File>> {
     let path = create_path(&path, file_idx);
     let writer = BufWriterWithPos::new(
         File::options()
@@ -237,10 +448,21 @@ fn create_file(
             .append(true)
             .open(&path)?,
     )?;
-    readers.insert(file_idx, BufReaderWithPos::new(File::open(&path)?)?);
     Ok(writer)
 }
 
+/// Opens the log file with the given generation number.
+///
+/// Returns `None` if the file has been deleted, which a compaction only does after
+/// copying its entries into a later file.
+fn open_log(path: &std::path::Path, file_idx: u64) -> Result<Option<BufReaderWithPos<File>>> {
+    match File::open(create_path(path, file_idx)) {
+        Ok(file) => Ok(Some(BufReaderWithPos::new(file)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 fn sorted_file_idx_list(path: &std::path::Path) -> crate::Result<Vec<u64>> {
     let mut file_list: Vec<u64> = fs::read_dir(&path)?
         .flat_map(|res| -> Result<_> { Ok(res?.path()) })
@@ -260,7 +482,7 @@ fn sorted_file_idx_list(path: &std::path::Path) -> crate::Result<Vec<u64>> {
 fn build_index_from_reader(
     idx: u64,
     reader: &mut BufReaderWithPos<File>,
-    index: &mut BTreeMap<String, LogPointer>,
+    index: &SkipMap<String, LogPointer>,
 ) -> crate::Result<u64> {
     // Make sure we read from the beginning of the file
     let mut pos = reader.seek(SeekFrom::Start(0))?;
@@ -270,13 +492,14 @@ fn build_index_from_reader(
         let new_pos = stream_cmds.byte_offset() as u64;
         match cmd {
             KvsCommand::Set(crate::domain::Set { key, .. }) => {
-                if let Some(old_cmd) = index.insert(key, (idx, (pos..new_pos)).into()) {
-                    uncompacted += old_cmd.value_size;
+                if let Some(old_cmd) = index.get(&key) {
+                    uncompacted += old_cmd.value().value_size;
                 }
+                index.insert(key, (idx, (pos..new_pos)).into());
             }
             KvsCommand::Rm(crate::domain::Rm { key }) => {
                 if let Some(old_cmd) = index.remove(&key) {
-                    uncompacted += old_cmd.value_size;
+                    uncompacted += old_cmd.value().value_size;
                 }
                 // the "remove" command itself can be deleted in the next compaction.
                 // so we add its length to `uncompacted`.
@@ -288,23 +511,23 @@ fn build_index_from_reader(
     Ok(uncompacted)
 }
 
-
-/// KvsEngine
-pub trait KvsEngine {
-
+/// Trait for a key value storage engine.
+///
+/// Engines are cloned into the threads serving the clients, so all methods take `&self`
+/// and the implementations lock internally where needed.
+pub trait KvsEngine: Clone + Send + 'static {
     /// Set the value of a string key to a string.
-
+    ///
     /// Return an error if the value is not written successfully.
-    fn set(&mut self, key: String, value: String) -> Result<()>;
+    fn set(&self, key: String, value: String) -> Result<()>;
 
     /// Get the string value of a string key. If the key does not exist, return None.
+    ///
+    /// Return an error if the value is not read successfully.
+    fn get(&self, key: String) -> Result<Option<String>>;
 
-    /// Return an error if the value is not read successfully.    
-    fn get(&mut self, key: String) -> Result<Option<String>>;
-    
     /// Remove a given string key.
-
+    ///
     /// Return an error if the key does not exit or value is not read successfully
-    fn remove(&mut self, key: String) -> Result<()>;
-
-}
\ No newline at end of file
+    fn remove(&self, key: String) -> Result<()>;
+}
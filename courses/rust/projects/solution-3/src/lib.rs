@@ -1,7 +1,15 @@
 //! src/lib.rs
 #![deny(missing_docs)]
+mod client;
+mod common;
 mod domain;
 mod error;
 mod kvs;
-pub use crate::error::Result;
+mod server;
+mod sled_engine;
+pub mod thread_pool;
+pub use crate::client::KvsClient;
+pub use crate::error::{KvsError, Result};
 pub use crate::kvs::*;
+pub use crate::server::KvsServer;
+pub use crate::sled_engine::SledKvsEngine;
@@ -0,0 +1,64 @@
+use crate::common::{Request, Response};
+use crate::error::Result;
+use crate::thread_pool::ThreadPool;
+use crate::KvsEngine;
+use serde_json::Deserializer;
+use std::io::{BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/// A server of a key value store.
+///
+/// Every connection is served by a job in the thread pool with its own clone of
+/// the engine, so clients are served concurrently. A job holds its thread until the
+/// client disconnects, so the size of the pool limits the number of clients served at
+/// once. Further clients are accepted but wait until a thread is free.
+pub struct KvsServer<E: KvsEngine, P: ThreadPool> {
+    engine: E,
+    pool: P,
+}
+
+impl<E: KvsEngine, P: ThreadPool> KvsServer<E, P> {
+    /// Creates a `KvsServer` with a given storage engine and thread pool.
+    pub fn new(engine: E, pool: P) -> Self {
+        KvsServer { engine, pool }
+    }
+
+    /// Runs the server listening on the given address.
+    pub fn run(self, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            let engine = self.engine.clone();
+            self.pool.spawn(move || match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve(engine, stream) {
+                        log::error!("Error on serving client: {}", e.msg);
+                    }
+                }
+                Err(e) => log::error!("Connection failed: {}", e),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn serve<E: KvsEngine>(engine: E, tcp: TcpStream) -> Result<()> {
+    let peer_addr = tcp.peer_addr()?;
+    let reader = BufReader::new(&tcp);
+    let mut writer = BufWriter::new(&tcp);
+    let requests = Deserializer::from_reader(reader).into_iter::<Request>();
+
+    for req in requests {
+        let req = req?;
+        log::debug!("Receive request from {}: {:?}", peer_addr, req);
+        let resp = match req {
+            Request::Get { key } => engine.get(key).map(Response::Value),
+            Request::Set { key, value } => engine.set(key, value).map(|()| Response::Done),
+            Request::Remove { key } => engine.remove(key).map(|()| Response::Done),
+        };
+        let resp = resp.unwrap_or_else(|e| Response::Err(e.msg));
+        serde_json::to_writer(&mut writer, &resp)?;
+        writer.flush()?;
+        log::debug!("Response sent to {}: {:?}", peer_addr, resp);
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::{KvsEngine, Result};
+use sled::Db;
+
+/// A `KvsEngine` storing the key/value pairs in a `sled::Db`.
+#[derive(Debug, Clone)]
+pub struct SledKvsEngine {
+    db: Db,
+}
+
+impl SledKvsEngine {
+    /// Creates a `SledKvsEngine` from `sled::Db`.
+    pub fn new(db: Db) -> Self {
+        SledKvsEngine { db }
+    }
+}
+
+impl KvsEngine for SledKvsEngine {
+    fn set(&self, key: String, value: String) -> Result<()> {
+        self.db.insert(key, value.into_bytes())?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn get(&self, key: String) -> Result<Option<String>> {
+        match self.db.get(key)? {
+            Some(value) => Ok(Some(String::from_utf8(value.to_vec())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn remove(&self, key: String) -> Result<()> {
+        if self.db.remove(key)?.is_none() {
+            return Err("Key not found".into());
+        }
+        self.db.flush()?;
+        Ok(())
+    }
+}
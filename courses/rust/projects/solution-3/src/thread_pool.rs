@@ -0,0 +1,86 @@
+//! Thread pools running the jobs of a `KvsServer`.
+
+use crate::Result;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// The trait that all thread pools should implement.
+pub trait ThreadPool {
+    /// Creates a new thread pool, immediately spawning the specified number of
+    /// threads.
+    ///
+    /// Returns an error if any thread fails to spawn.
+    fn new(threads: u32) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Spawns a function into the thread pool.
+    ///
+    /// If the function panics, the thread pool keeps the same number of threads.
+    fn spawn<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static;
+}
+
+/// A thread pool whose threads take the jobs from a shared queue.
+///
+/// A thread running a job that panics is replaced with a new one.
+pub struct SharedQueueThreadPool {
+    tx: Sender<Job>,
+}
+
+impl ThreadPool for SharedQueueThreadPool {
+    fn new(threads: u32) -> Result<Self> {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..threads {
+            let worker = Worker(Arc::clone(&rx));
+            thread::Builder::new().spawn(move || worker.run())?;
+        }
+        Ok(SharedQueueThreadPool { tx })
+    }
+
+    /// Spawns a function into the thread pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread pool has no thread.
+    fn spawn<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.tx
+            .send(Box::new(job))
+            .expect("The thread pool has no thread.");
+    }
+}
+
+struct Worker(Arc<Mutex<Receiver<Job>>>);
+
+impl Worker {
+    fn run(self) {
+        loop {
+            // The lock is released before running the job.
+            let job = self.0.lock().unwrap().recv();
+            match job {
+                Ok(job) => job(),
+                // the thread pool is dropped
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let worker = Worker(Arc::clone(&self.0));
+            if let Err(e) = thread::Builder::new().spawn(move || worker.run()) {
+                log::error!("Failed to spawn a thread: {}", e);
+            }
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use assert_cmd::prelude::*;
+use kvs::thread_pool::{SharedQueueThreadPool, ThreadPool};
+use kvs::{KvStore, KvsClient, KvsServer};
 use predicates::str::{contains, is_empty};
 use std::fs::{self, File};
 use std::process::Command;
@@ -335,3 +337,37 @@ fn cli_access_server_kvs_engine() {
 fn cli_access_server_sled_engine() {
     cli_access_server("sled", "127.0.0.1:4005");
 }
+
+// A server should serve more clients than threads, each waiting until an earlier
+// client disconnects
+#[test]
+fn server_more_clients_than_threads() {
+    let addr = "127.0.0.1:4006";
+    let temp_dir = TempDir::new().unwrap();
+    let store = KvStore::open(temp_dir.path()).unwrap();
+    let pool = SharedQueueThreadPool::new(2).unwrap();
+    thread::spawn(move || KvsServer::new(store, pool).run(addr).unwrap());
+    thread::sleep(Duration::from_secs(1));
+
+    let mut first = KvsClient::connect(addr).unwrap();
+    first.set("key1".to_owned(), "value1".to_owned()).unwrap();
+    let mut second = KvsClient::connect(addr).unwrap();
+    second.set("key2".to_owned(), "value2".to_owned()).unwrap();
+
+    let (sender, receiver) = mpsc::sync_channel(0);
+    thread::spawn(move || {
+        let mut third = KvsClient::connect(addr).unwrap();
+        sender.send(third.get("key1".to_owned()).unwrap()).unwrap();
+    });
+    assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+
+    drop(first);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+        Some("value1".to_owned())
+    );
+    assert_eq!(
+        second.get("key2".to_owned()).unwrap(),
+        Some("value2".to_owned())
+    );
+}
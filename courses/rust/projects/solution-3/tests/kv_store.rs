@@ -1,4 +1,10 @@
+// The tests before `concurrent_set_get` were written when `KvStore` took `&mut self`.
+#![allow(unused_mut)]
+
 use kvs::{KvStore, KvsEngine, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
 use tempfile::TempDir;
 use walkdir::WalkDir;
 
@@ -124,3 +130,136 @@ fn compaction() -> Result<()> {
 
     panic!("No compaction detected");
 }
+
+// Only one store should write to a directory at a time
+#[test]
+fn lock_dir() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::open(temp_dir.path())?;
+    assert!(KvStore::open(temp_dir.path()).is_err());
+
+    drop(store);
+    KvStore::open(temp_dir.path())?;
+
+    Ok(())
+}
+
+// A read-only store should be opened besides a writer and reject writes
+#[test]
+fn read_only() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let mut store = KvStore::open(temp_dir.path())?;
+    store.set("key1".to_owned(), "value1".to_owned())?;
+
+    let mut reader = KvStore::open_read_only(temp_dir.path())?;
+    assert_eq!(reader.get("key1".to_owned())?, Some("value1".to_owned()));
+    assert!(reader.set("key1".to_owned(), "value2".to_owned()).is_err());
+    assert!(reader.remove("key1".to_owned()).is_err());
+
+    store.set("key1".to_owned(), "value2".to_owned())?;
+    assert_eq!(store.get("key1".to_owned())?, Some("value2".to_owned()));
+
+    Ok(())
+}
+
+// A read-only store should read the values a writer has moved by a compaction
+#[test]
+fn read_only_compaction() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::open(temp_dir.path())?;
+    store.set("key0".to_owned(), "value0".to_owned())?;
+
+    let reader = KvStore::open_read_only(temp_dir.path())?;
+    assert_eq!(reader.get("key0".to_owned())?, Some("value0".to_owned()));
+
+    let first_log = temp_dir.path().join("1.log");
+    let mut iter = 0;
+    while first_log.exists() {
+        iter += 1;
+        assert!(iter < 1000, "No compaction detected");
+        for key_id in 0..1000 {
+            store.set(format!("key{}", key_id), format!("value{}", iter))?;
+        }
+    }
+
+    // A clone has its own file handles but shares the rebuilt index.
+    let clone = reader.clone();
+    for key_id in 0..1000 {
+        let expected = Some(format!("value{}", iter));
+        assert_eq!(reader.get(format!("key{}", key_id))?, expected);
+        assert_eq!(clone.get(format!("key{}", key_id))?, expected);
+    }
+
+    Ok(())
+}
+
+// Clones of a store should be written and read from multiple threads at the same time
+#[test]
+fn concurrent_set_get() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::open(temp_dir.path())?;
+    let handles: Vec<_> = (0..8)
+        .map(|thread_id| {
+            let store = store.clone();
+            thread::spawn(move || -> Result<()> {
+                for i in 0..100 {
+                    let key = format!("key{}_{}", thread_id, i);
+                    store.set(key.clone(), format!("value{}", i))?;
+                    assert_eq!(store.get(key)?, Some(format!("value{}", i)));
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+
+    drop(store);
+    let store = KvStore::open(temp_dir.path())?;
+    for thread_id in 0..8 {
+        for i in 0..100 {
+            let key = format!("key{}_{}", thread_id, i);
+            assert_eq!(store.get(key)?, Some(format!("value{}", i)));
+        }
+    }
+    Ok(())
+}
+
+// Reads should not fail while compactions delete the files they read from
+#[test]
+fn get_during_compaction() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create temporary working directory");
+    let store = KvStore::open(temp_dir.path())?;
+    let value = "x".repeat(1000);
+    for key_id in 0..100 {
+        store.set(format!("key{}", key_id), value.clone())?;
+    }
+
+    let done = Arc::new(AtomicBool::new(false));
+    let readers: Vec<_> = (0..8)
+        .map(|_| {
+            let store = store.clone();
+            let done = Arc::clone(&done);
+            thread::spawn(move || -> Result<()> {
+                let mut key_id = 0;
+                while !done.load(Ordering::SeqCst) {
+                    store.get(format!("key{}", key_id))?;
+                    key_id = (key_id + 1) % 100;
+                }
+                Ok(())
+            })
+        })
+        .collect();
+    // several megabytes of overwrites trigger a few compactions
+    for _ in 0..200 {
+        for key_id in 0..100 {
+            store.set(format!("key{}", key_id), value.clone())?;
+        }
+    }
+    done.store(true, Ordering::SeqCst);
+    for reader in readers {
+        reader.join().unwrap()?;
+    }
+    Ok(())
+}